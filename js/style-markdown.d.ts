@@ -0,0 +1,25 @@
+export { default as init } from "../pkg/style_markdown.js";
+
+export interface Diagnostic {
+    /** The name of the rule that found it. */
+    rule: string;
+    /** 1-based line number. */
+    line: number;
+    severity: "note" | "warning";
+    message: string;
+}
+
+export interface Rewritten {
+    text: string;
+    diagnostics: Diagnostic[];
+}
+
+export interface Options {
+    /** The file name the rules see. */
+    path?: string;
+    /** How to end the output, like `--final-newline`: with one newline (the default), as it is, or with none. */
+    finalNewline?: "one" | "keep" | "none";
+}
+
+/** Rewrite Markdown `input` with `rules`, like `["quotes", "dashes --style unicode"]`, in order. */
+export function rewrite(input: string, rules: string[], options?: Options): Rewritten;
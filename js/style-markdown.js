@@ -0,0 +1,23 @@
+// A JavaScript wrapper around the WebAssembly build of style-markdown, in `../pkg`, built with
+//
+//     wasm-pack build --target web
+//
+// It runs the same rules as the `style-markdown` CLI, except ones that need a filesystem.
+
+import init, { rewriteJson } from "../pkg/style_markdown.js";
+
+export { init };
+
+/**
+ * Rewrite Markdown `input` with `rules`, in order.
+ *
+ * @param {string} input The Markdown text.
+ * @param {string[]} rules Commands with their arguments, like `["quotes", "dashes --style unicode"]`.
+ * @param {{path?: string, finalNewline?: "one" | "keep" | "none"}} [options]
+ *     `path` is the file name the rules see,
+ *     and `finalNewline` is how to end the output, like `--final-newline`.
+ * @returns {{text: string, diagnostics: {rule: string, line: number, severity: string, message: string}[]}}
+ */
+export function rewrite(input, rules, options = {}) {
+    return JSON.parse(rewriteJson(input, rules, JSON.stringify(options)));
+}
@@ -0,0 +1,252 @@
+//! Admonitions, i.e. notes and warnings set apart from the rest of the text,
+//! which each platform writes differently.
+
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use regex::Regex;
+
+use crate::markdown;
+use crate::markdown::Line;
+use crate::markdown::LineKind;
+
+/// How to write admonitions.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdmonitionStyle {
+    /// GitHub alerts: `> [!NOTE]`.
+    Github,
+
+    /// MkDocs-Material admonitions: `!!! note "Title"`, with the content indented.
+    Mkdocs,
+
+    /// Obsidian callouts: `> [!note]- Title`.
+    Obsidian,
+}
+
+/// An admonition in any style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Admonition<'a> {
+    /// Lowercase, like `note`.
+    kind: String,
+
+    title: Option<&'a str>,
+
+    /// `-` for collapsed or `+` for expanded if it's collapsible.
+    fold: Option<char>,
+
+    /// The lines of content without any `>` or indentation.
+    content: Vec<&'a str>,
+}
+
+impl<'a> Admonition<'a> {
+    /// Parse an admonition starting at the first of `lines`, returning it and how many lines it is.
+    fn parse(lines: &[Line<'a>]) -> Option<(Self, usize)> {
+        static CALLOUT: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^ {0,3}>[ \t]?\[!(?<kind>[A-Za-z]+)\](?<fold>[-+])?(?:[ \t]+(?<title>.*?))?[ \t]*$").unwrap()
+        });
+        static MKDOCS: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r#"^(?<marker>!!!|\?\?\?\+?)[ \t]+(?<kind>[A-Za-z]+)(?:[ \t]+"(?<title>[^"]*)")?[ \t]*$"#).unwrap()
+        });
+        let first = lines.first().filter(|line| line.kind == LineKind::Text)?;
+        let rest = lines[1..]
+            .iter()
+            .take_while(|line| line.kind == LineKind::Text);
+        if let Some(captures) = CALLOUT.captures(first.text) {
+            static QUOTE: LazyLock<Regex> =
+                LazyLock::new(|| Regex::new(r"^ {0,3}>[ \t]?").unwrap());
+            let content = rest
+                .map_while(|line| QUOTE.find(line.text).map(|m| &line.text[m.end()..]))
+                .collect::<Vec<_>>();
+            let len = 1 + content.len();
+            let admonition = Self {
+                kind: captures["kind"].to_lowercase(),
+                title: captures.name("title").map(|title| title.as_str()),
+                fold: captures
+                    .name("fold")
+                    .and_then(|fold| fold.as_str().chars().next()),
+                content,
+            };
+            return Some((admonition, len));
+        }
+        let captures = MKDOCS.captures(first.text)?;
+        let mut content = rest
+            .map_while(|line| {
+                let text = line.text;
+                match text.trim().is_empty() {
+                    true => Some(""),
+                    false => text
+                        .strip_prefix("    ")
+                        .or_else(|| text.strip_prefix('\t')),
+                }
+            })
+            .collect::<Vec<_>>();
+        while content.last() == Some(&"") {
+            content.pop();
+        }
+        let len = 1 + content.len();
+        let fold = match &captures["marker"] {
+            "???" => Some('-'),
+            "???+" => Some('+'),
+            _ => None,
+        };
+        let admonition = Self {
+            kind: captures["kind"].to_lowercase(),
+            title: captures.name("title").map(|title| title.as_str()),
+            fold,
+            content,
+        };
+        Some((admonition, len))
+    }
+
+    /// The closest of GitHub's five alert kinds.
+    fn github_kind(&self) -> &'static str {
+        match self.kind.as_str() {
+            "tip" | "hint" | "success" | "check" | "done" => "TIP",
+            "important" => "IMPORTANT",
+            "warning" | "attention" => "WARNING",
+            "caution" | "danger" | "error" | "failure" | "fail" | "missing" | "bug" => "CAUTION",
+            _ => "NOTE",
+        }
+    }
+
+    fn lines(&self, style: AdmonitionStyle) -> Vec<String> {
+        let kind = &self.kind;
+        let quoted = |line: &str| match line.is_empty() {
+            true => ">".to_owned(),
+            false => format!("> {line}"),
+        };
+        let content = self.content.iter().copied();
+        match style {
+            AdmonitionStyle::Github => {
+                // GitHub alerts can't have titles or be collapsed.
+                let title = self.title.map(|title| format!("**{title}**"));
+                [format!("> [!{}]", self.github_kind())]
+                    .into_iter()
+                    .chain(title.as_deref().map(quoted))
+                    .chain(content.map(quoted))
+                    .collect()
+            }
+            AdmonitionStyle::Mkdocs => {
+                let marker = match self.fold {
+                    None => "!!!",
+                    Some('+') => "???+",
+                    Some(_) => "???",
+                };
+                let title = self
+                    .title
+                    .map(|title| format!(" \"{}\"", title.replace('"', "'")))
+                    .unwrap_or_default();
+                [format!("{marker} {kind}{title}")]
+                    .into_iter()
+                    .chain(content.map(|line| match line.is_empty() {
+                        true => String::new(),
+                        false => format!("    {line}"),
+                    }))
+                    .collect()
+            }
+            AdmonitionStyle::Obsidian => {
+                let fold = self.fold.map(String::from).unwrap_or_default();
+                let title = self
+                    .title
+                    .map(|title| format!(" {title}"))
+                    .unwrap_or_default();
+                [format!("> [!{kind}]{fold}{title}")]
+                    .into_iter()
+                    .chain(content.map(quoted))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Convert GitHub alerts, MkDocs admonitions, and Obsidian callouts to `style`.
+///
+/// Converting to GitHub alerts loses collapsibility and maps other kinds to GitHub's five,
+/// and titles are kept as a bold first line.
+pub fn convert_admonitions(before: String, style: AdmonitionStyle) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let mut after = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let is_block_start = i == 0 || lines[i - 1].text.trim().is_empty();
+        match Admonition::parse(&lines[i..]).filter(|_| is_block_start) {
+            Some((admonition, len)) => {
+                after.extend(admonition.lines(style));
+                i += len;
+            }
+            None => {
+                after.push(lines[i].text.to_owned());
+                i += 1;
+            }
+        }
+    }
+    after.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::admonitions::convert_admonitions;
+    use crate::admonitions::AdmonitionStyle;
+
+    #[test]
+    fn test_convert_admonitions() {
+        let github = "> [!WARNING]
+> Mind the gap.
+>
+> Really.
+
+Text.";
+        let mkdocs = "!!! warning
+    Mind the gap.
+
+    Really.
+
+Text.";
+        let obsidian = "> [!warning]
+> Mind the gap.
+>
+> Really.
+
+Text.";
+        for before in [github, mkdocs, obsidian] {
+            assert_eq!(
+                convert_admonitions(before.into(), AdmonitionStyle::Github),
+                github
+            );
+            assert_eq!(
+                convert_admonitions(before.into(), AdmonitionStyle::Mkdocs),
+                mkdocs
+            );
+            assert_eq!(
+                convert_admonitions(before.into(), AdmonitionStyle::Obsidian),
+                obsidian
+            );
+        }
+
+        let before = "??? danger \"Third rail\"
+    Don't touch.
+
+> [!info]+ Schedule
+> Trains run hourly.";
+        let after = "> [!CAUTION]
+> **Third rail**
+> Don't touch.
+
+> [!NOTE]
+> **Schedule**
+> Trains run hourly.";
+        assert_eq!(
+            convert_admonitions(before.into(), AdmonitionStyle::Github),
+            after
+        );
+        let after = "> [!danger]- Third rail
+> Don't touch.
+
+> [!info]+ Schedule
+> Trains run hourly.";
+        assert_eq!(
+            convert_admonitions(before.into(), AdmonitionStyle::Obsidian),
+            after
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Timing each rule over a corpus of documents,
+//! to see which rules dominate the runtime and catch slow regexes.
+
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use color_eyre::eyre;
+use serde_json::json;
+
+use crate::markdown;
+use crate::markdown::Dialect;
+use crate::walk;
+use crate::walk::Walk;
+use crate::Command;
+
+/// The Markdown files in `dir` and its subdirectories that aren't ignored, sorted by path.
+pub fn corpus(dir: &Path) -> eyre::Result<Vec<(PathBuf, String)>> {
+    walk::markdown_files(dir, Walk::default())?
+        .into_iter()
+        .map(|path| {
+            let text = fs_err::read_to_string(&path)?;
+            Ok((path, text))
+        })
+        .collect()
+}
+
+/// Every rule that works with its default options and only reads the file it rewrites.
+pub fn default_rules() -> Vec<Command> {
+    Command::rules()
+        .iter()
+        .filter_map(|rule| Command::from_rule(&rule.name).ok())
+        .filter(|command| !command.needs_filesystem())
+        .collect()
+}
+
+/// How long a rule took over a whole corpus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timing {
+    pub rule: String,
+    pub duration: Duration,
+
+    /// The size of the corpus.
+    pub bytes: usize,
+}
+
+impl Timing {
+    /// Throughput in MB/s.
+    pub fn throughput(&self) -> f64 {
+        self.bytes as f64 / 1e6 / self.duration.as_secs_f64()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "rule": self.rule,
+            "seconds": self.duration.as_secs_f64(),
+            "bytes": self.bytes,
+            "mb_per_second": self.throughput(),
+        })
+    }
+}
+
+/// Time each of `commands` on its own over each of `files`, slowest first.
+pub fn bench(files: &[(PathBuf, String)], commands: &[Command]) -> Vec<Timing> {
+    let bytes = files.iter().map(|(_, text)| text.len()).sum();
+    let mut timings = commands
+        .iter()
+        .map(|command| {
+            // Warm up first, like compiling the rule's regexes.
+            if let Some((path, text)) = files.first() {
+                command.rewrite(path, text.clone(), &mut Vec::new());
+            }
+            let mut duration = Duration::ZERO;
+            for (path, text) in files {
+                let dialect = Dialect::from_path(path).unwrap_or_default();
+                let text = text.clone();
+                let mut diagnostics = Vec::new();
+                let start = Instant::now();
+                markdown::with_dialect(dialect, || command.rewrite(path, text, &mut diagnostics));
+                duration += start.elapsed();
+            }
+            Timing {
+                rule: command.name(),
+                duration,
+                bytes,
+            }
+        })
+        .collect::<Vec<_>>();
+    timings.sort_by_key(|timing| std::cmp::Reverse(timing.duration));
+    timings
+}
+
+/// A table of how long each rule took, slowest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Benchmark(pub Vec<Timing>);
+
+impl Benchmark {
+    pub fn to_json(&self) -> serde_json::Value {
+        self.0.iter().map(Timing::to_json).collect()
+    }
+}
+
+impl Display for Benchmark {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let rule_width = self
+            .0
+            .iter()
+            .map(|timing| timing.rule.len())
+            .chain(["rule".len()])
+            .max()
+            .unwrap();
+        write!(f, "{:rule_width$}  {:>10}  {:>10}", "rule", "ms", "MB/s")?;
+        for timing in &self.0 {
+            write!(
+                f,
+                "\n{:rule_width$}  {:>10.1}  {:>10.1}",
+                timing.rule,
+                timing.duration.as_secs_f64() * 1e3,
+                timing.throughput()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::bench::bench;
+    use crate::bench::default_rules;
+    use crate::bench::Benchmark;
+    use crate::bench::Timing;
+    use crate::Command;
+
+    #[test]
+    fn test_default_rules() {
+        let names = default_rules()
+            .iter()
+            .map(Command::name)
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"quotes".into()));
+        assert!(names.contains(&"semantic-line-breaks".into()));
+        assert!(!names.contains(&"file-links".into()));
+    }
+
+    #[test]
+    fn test_bench() {
+        let files = [(PathBuf::from("a.md"), "“a”\n".to_owned())];
+        let timings = bench(
+            &files,
+            &[Command::from_rule("quotes").unwrap(), Command::Escapes],
+        );
+        let rules = timings
+            .iter()
+            .map(|timing| timing.rule.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(rules.len(), 2);
+        assert!(rules.contains(&"quotes"));
+        assert!(timings.iter().all(|timing| timing.bytes == 8));
+    }
+
+    #[test]
+    fn test_benchmark() {
+        let benchmark = Benchmark(vec![Timing {
+            rule: "quotes".into(),
+            duration: Duration::from_millis(20),
+            bytes: 2_000_000,
+        }]);
+        assert_eq!(
+            benchmark.to_string(),
+            "rule            ms        MB/s
+quotes        20.0       100.0"
+        );
+    }
+}
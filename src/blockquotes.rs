@@ -0,0 +1,114 @@
+//! Blockquotes, i.e. `> quote`.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::lists;
+use crate::markdown;
+use crate::markdown::Fence;
+use crate::markdown::LineKind;
+
+/// Split a line into its blockquote depth and the content after the `>` markers.
+pub fn parse_markers(line: &str) -> (usize, &str) {
+    static MARKERS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^ {0,3}(?:>[ \t]?)+").unwrap());
+    match MARKERS.find(line) {
+        None => (0, line),
+        Some(m) => (m.as_str().matches('>').count(), &line[m.end()..]),
+    }
+}
+
+/// Whether `content` starts a block other than a paragraph,
+/// so it can't be a lazy continuation line.
+fn starts_other_block(content: &str) -> bool {
+    let trimmed = content.trim();
+    trimmed.is_empty()
+        || markdown::is_heading(content)
+        || markdown::is_thematic_break(trimmed)
+        || lists::parse_item(trimmed).is_some()
+        || Fence::parse_opening(content).is_some()
+}
+
+/// Write the `>` markers of every line in a blockquote, including lazy continuation lines,
+/// as `> ` for each level of nesting.
+pub fn normalize_blockquotes(before: String) -> String {
+    let mut after = Vec::new();
+    // The depth of the previous line, and whether it can be continued lazily.
+    let mut previous = (0, false);
+    let mut fence = None::<Fence>;
+    for line in markdown::lines(&before) {
+        if line.kind != LineKind::Text {
+            after.push(line.text.to_owned());
+            previous = (0, false);
+            continue;
+        }
+        let (mut depth, content) = parse_markers(line.text);
+        let (previous_depth, is_paragraph) = previous;
+        if depth < previous_depth && is_paragraph && !starts_other_block(content) {
+            depth = previous_depth;
+        }
+        if depth == 0 {
+            after.push(line.text.to_owned());
+            previous = (0, false);
+            fence = None;
+            continue;
+        }
+        let is_code = match fence {
+            Some(opening) => {
+                if opening.is_closed_by(content) {
+                    fence = None;
+                }
+                true
+            }
+            None => {
+                fence = Fence::parse_opening(content);
+                fence.is_some()
+            }
+        };
+        let markers = "> ".repeat(depth);
+        let quoted = format!("{markers}{content}");
+        after.push(quoted.trim_end().to_owned());
+        previous = (depth, !is_code && !starts_other_block(content));
+    }
+    after.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blockquotes::normalize_blockquotes;
+
+    #[test]
+    fn test_normalize_blockquotes() {
+        let before = ">A quote
+that continues lazily.
+>
+> > Nested
+>>and more
+lazy.
+>
+> ```
+> code
+> ```
+after code
+
+Not quoted.
+> - item
+- not lazy";
+        let after = "> A quote
+> that continues lazily.
+>
+> > Nested
+> > and more
+> > lazy.
+>
+> ```
+> code
+> ```
+after code
+
+Not quoted.
+> - item
+- not lazy";
+        assert_eq!(normalize_blockquotes(before.into()), after);
+    }
+}
@@ -0,0 +1,207 @@
+//! A cache of files that are already styled, so that repeated runs can skip them,
+//! like formatters do.
+
+use std::collections::BTreeMap;
+use std::hash::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::path::Path;
+use std::path::PathBuf;
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use serde_json::Value;
+
+use crate::journal;
+use crate::markdown::Dialect;
+use crate::Command;
+use crate::FinalNewline;
+
+/// The name of the cache in [`journal::DIR`].
+pub const FILE_NAME: &str = "cache.json";
+
+/// The hash of a file's styled `text`, and everything else that affects how it's styled.
+///
+/// `std`'s hasher isn't stable across Rust versions, but that only means a stale cache is missed.
+pub fn key(
+    commands: &[Command],
+    dialect: Dialect,
+    final_newline: FinalNewline,
+    text: &str,
+) -> String {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    // The `Debug` output includes all of a command's options,
+    // which are sorted, so that it's the same every run.
+    format!("{commands:?}").hash(&mut hasher);
+    format!("{dialect:?}").hash(&mut hasher);
+    format!("{final_newline:?}").hash(&mut hasher);
+    text.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Whether files styled with `commands` can be cached,
+/// which they can't if a command reads other files or the network,
+/// since those can change without the file changing.
+pub fn is_cacheable(commands: &[Command]) -> bool {
+    !commands.iter().any(Command::needs_filesystem)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cache {
+    /// The [`key`] of each file when it was last styled, by path.
+    pub keys: BTreeMap<String, String>,
+}
+
+impl Cache {
+    pub fn parse(json: &str) -> eyre::Result<Self> {
+        let json = serde_json::from_str::<Value>(json)?;
+        let keys = json
+            .as_object()
+            .ok_or_else(|| eyre!("the cache must be an object"))?
+            .iter()
+            .filter_map(|(path, key)| Some((path.clone(), key.as_str()?.to_owned())))
+            .collect();
+        Ok(Self { keys })
+    }
+
+    pub fn to_json(&self) -> Value {
+        self.keys
+            .iter()
+            .map(|(path, key)| (path.clone(), Value::from(key.as_str())))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+
+    /// Read the cache in [`journal::DIR`] in `dir`,
+    /// starting over if there isn't one or it's unreadable.
+    pub fn load(dir: &Path) -> Self {
+        fs_err::read_to_string(dir.join(journal::DIR).join(FILE_NAME))
+            .ok()
+            .and_then(|json| Self::parse(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the cache in [`journal::DIR`] in `dir`, next to the journal.
+    pub fn save(&self, dir: &Path) -> eyre::Result<()> {
+        let dir = journal::create_dir(dir)?;
+        let json = serde_json::to_string_pretty(&self.to_json())?;
+        fs_err::write(dir.join(FILE_NAME), json)?;
+        Ok(())
+    }
+
+    /// Whether the file at `path` is unchanged since it was styled the same way.
+    pub fn is_styled(&self, path: &Path, key: &str) -> bool {
+        self.keys
+            .get(&path_key(path))
+            .is_some_and(|styled| styled == key)
+    }
+
+    /// Record that the file at `path` is styled.
+    pub fn insert(&mut self, path: &Path, key: String) {
+        self.keys.insert(path_key(path), key);
+    }
+}
+
+fn path_key(path: &Path) -> String {
+    let path = fs_err::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    path.display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::Path;
+
+    use crate::cache::is_cacheable;
+    use crate::cache::key;
+    use crate::cache::Cache;
+    use crate::markdown::Dialect;
+    use crate::Command;
+    use crate::FinalNewline;
+
+    #[test]
+    fn test_cache() {
+        let commands = [Command::from_rule("quotes").unwrap()];
+        let styled = key(&commands, Dialect::Gfm, FinalNewline::One, "'a'\n");
+        assert_eq!(
+            styled,
+            key(&commands, Dialect::Gfm, FinalNewline::One, "'a'\n")
+        );
+        assert_ne!(
+            styled,
+            key(&commands, Dialect::Gfm, FinalNewline::One, "‘a’\n")
+        );
+        assert_ne!(
+            styled,
+            key(&commands, Dialect::Mdx, FinalNewline::One, "'a'\n")
+        );
+        assert_ne!(
+            styled,
+            key(
+                &[Command::Escapes],
+                Dialect::Gfm,
+                FinalNewline::One,
+                "'a'\n"
+            )
+        );
+        assert_ne!(
+            styled,
+            key(&commands, Dialect::Gfm, FinalNewline::Keep, "'a'\n")
+        );
+
+        let path = Path::new("a.md");
+        let mut cache = Cache::default();
+        assert!(!cache.is_styled(path, &styled));
+        cache.insert(path, styled.clone());
+        assert!(cache.is_styled(path, &styled));
+        let cache = Cache::parse(&cache.to_json().to_string()).unwrap();
+        assert!(cache.is_styled(path, &styled));
+        assert!(Cache::parse("[]").is_err());
+
+        let dir = env::temp_dir().join("style-markdown-test-cache");
+        cache.save(&dir).unwrap();
+        assert_eq!(Cache::load(&dir), cache);
+        assert_eq!(
+            fs_err::read_to_string(dir.join(".style-markdown/.gitignore")).unwrap(),
+            "*\n"
+        );
+    }
+
+    #[test]
+    fn test_cache_options() {
+        let alt_text = |mapping: &[(&str, &str)]| Command::AltText {
+            fix: true,
+            mapping: Some(
+                mapping
+                    .iter()
+                    .map(|&(src, alt)| (src.to_owned(), alt.to_owned()))
+                    .collect(),
+            ),
+            from_file_name: false,
+        };
+        let pairs = [
+            ("a.png", "A"),
+            ("b.png", "B"),
+            ("c.png", "C"),
+            ("d.png", "D"),
+        ];
+        let mut reversed = pairs;
+        reversed.reverse();
+        assert_eq!(
+            key(&[alt_text(&pairs)], Dialect::Gfm, FinalNewline::One, "a\n"),
+            key(
+                &[alt_text(&reversed)],
+                Dialect::Gfm,
+                FinalNewline::One,
+                "a\n"
+            )
+        );
+
+        assert!(is_cacheable(&[alt_text(&pairs), Command::Escapes]));
+        assert!(!is_cacheable(&[
+            Command::Escapes,
+            Command::from_rule("file-links").unwrap()
+        ]));
+    }
+}
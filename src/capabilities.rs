@@ -0,0 +1,117 @@
+//! What the CLI can do, as JSON, so that tools wrapping it, like editor plugins and CI actions,
+//! can check for features instead of parsing its `--help`.
+
+use clap::ArgAction;
+use clap::ValueEnum;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::markdown::Dialect;
+use crate::Command;
+
+/// The formats that output, like changes, diagnostics, and reports, can be printed in.
+pub const FORMATS: &[&str] = &["text", "json"];
+
+/// The options of `command`, besides `--help` and `--version`.
+fn options(command: &clap::Command) -> Value {
+    command
+        .get_arguments()
+        .filter(|arg| !matches!(arg.get_id().as_str(), "help" | "version"))
+        .map(|arg| {
+            let name = match arg.get_long() {
+                Some(long) => format!("--{long}"),
+                None => arg.get_id().to_string(),
+            };
+            json!({
+                "name": name,
+                "help": arg.get_help().map(ToString::to_string),
+                "takes_value": arg.get_action().takes_values(),
+                "repeatable": matches!(arg.get_action(), ArgAction::Append | ArgAction::Count),
+                "default": arg
+                    .get_default_values()
+                    .iter()
+                    .map(|value| value.to_string_lossy())
+                    .collect::<Vec<_>>(),
+                "values": arg
+                    .get_possible_values()
+                    .iter()
+                    .map(|value| value.get_name())
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+/// `command`'s name, aliases, description, and options.
+fn subcommand(command: &clap::Command) -> Value {
+    json!({
+        "name": command.get_name(),
+        "aliases": command.get_all_aliases().collect::<Vec<_>>(),
+        "about": command.get_about().map(ToString::to_string),
+        "options": options(command),
+    })
+}
+
+/// The capabilities of the CLI `cli`, whose subcommands include every [`Command`] rule,
+/// and the reports named `reports`.
+pub fn capabilities(cli: &clap::Command, reports: &[&str]) -> Value {
+    let mut cli = cli.clone();
+    // Fill in what's derived, like which options take values.
+    cli.build();
+    let rules = Command::rules();
+    let is_rule =
+        |command: &&clap::Command| rules.iter().any(|rule| rule.name == command.get_name());
+    let is_report = |command: &&clap::Command| reports.contains(&command.get_name());
+    let dialects = Dialect::value_variants()
+        .iter()
+        .filter_map(|dialect| dialect.to_possible_value())
+        .map(|dialect| {
+            json!({
+                "name": dialect.get_name(),
+                "help": dialect.get_help().map(ToString::to_string),
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "version": cli.get_version(),
+        "options": options(&cli),
+        "rules": cli.get_subcommands().filter(is_rule).map(subcommand).collect::<Vec<_>>(),
+        "reports": cli.get_subcommands().filter(is_report).map(subcommand).collect::<Vec<_>>(),
+        "dialects": dialects,
+        "formats": FORMATS,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Arg;
+    use clap::ArgAction;
+    use clap::Subcommand;
+
+    use crate::capabilities::capabilities;
+    use crate::Command;
+
+    #[test]
+    fn test_capabilities() {
+        let report = clap::Command::new("stats").about("Print stats");
+        let cli = Command::augment_subcommands(clap::Command::new("style-markdown"))
+            .version("1.0.0")
+            .arg(Arg::new("json").long("json").action(ArgAction::SetTrue))
+            .subcommand(report);
+        let capabilities = capabilities(&cli, &["stats"]);
+        assert_eq!(capabilities["version"], "1.0.0");
+        assert_eq!(capabilities["options"][0]["name"], "--json");
+        assert_eq!(capabilities["options"][0]["takes_value"], false);
+        let quotes = &capabilities["rules"][0];
+        assert_eq!(quotes["name"], "quotes");
+        assert_eq!(quotes["aliases"][0], "dequote");
+        let locale = &quotes["options"][1];
+        assert_eq!(locale["name"], "--locale");
+        assert_eq!(locale["default"][0], "en");
+        assert_eq!(locale["values"][2], "fr");
+        assert_eq!(capabilities["reports"][0]["name"], "stats");
+        assert_eq!(capabilities["reports"].as_array().unwrap().len(), 1);
+        assert_eq!(capabilities["dialects"][0]["name"], "commonmark");
+        assert_eq!(capabilities["formats"][1], "json");
+    }
+}
@@ -0,0 +1,318 @@
+//! Summarizing what rules changed.
+
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use color_eyre::eyre;
+use color_eyre::eyre::ensure;
+use color_eyre::eyre::eyre;
+use serde_json::json;
+use similar::ChangeTag;
+use similar::DiffTag;
+use similar::TextDiff;
+
+/// The number of edits from `before` to `after`,
+/// i.e. runs of consecutive changed lines.
+pub fn count_edits(before: &str, after: &str) -> usize {
+    TextDiff::from_lines(before, after)
+        .ops()
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .count()
+}
+
+/// An edit replacing whole lines of the original text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEdit {
+    /// The 0-based range of the replaced lines, which is empty for an insertion.
+    pub lines: Range<usize>,
+
+    /// The new lines, including their `\n`s.
+    pub text: String,
+}
+
+impl LineEdit {
+    /// Whether the edit only changes `lines`.
+    pub fn is_within(&self, lines: &Range<usize>) -> bool {
+        lines.start <= self.lines.start && self.lines.end <= lines.end
+    }
+}
+
+/// The edits from `before` to `after`, in order.
+///
+/// Unlike in [`count_edits`], runs of lines that are each rewritten are separate edits,
+/// so that they can be applied separately.
+pub fn line_edits(before: &str, after: &str) -> Vec<LineEdit> {
+    let new_lines = after.split_inclusive('\n').collect::<Vec<_>>();
+    let mut edits = Vec::new();
+    for op in TextDiff::from_lines(before, after).ops() {
+        let (old, new) = (op.old_range(), op.new_range());
+        match op.tag() {
+            DiffTag::Equal => {}
+            DiffTag::Replace if old.len() == new.len() => {
+                edits.extend(old.zip(new).map(|(old, new)| LineEdit {
+                    lines: old..old + 1,
+                    text: new_lines[new].to_owned(),
+                }));
+            }
+            _ => edits.push(LineEdit {
+                lines: old,
+                text: new_lines[new].concat(),
+            }),
+        }
+    }
+    edits
+}
+
+/// Apply `edits` from [`line_edits`] to `before`.
+pub fn apply_line_edits(before: &str, edits: &[LineEdit]) -> String {
+    let lines = before.split_inclusive('\n').collect::<Vec<_>>();
+    let mut after = String::new();
+    let mut last = 0;
+    for edit in edits {
+        after.push_str(&lines[last..edit.lines.start].concat());
+        after.push_str(&edit.text);
+        last = edit.lines.end;
+    }
+    after.push_str(&lines[last..].concat());
+    after
+}
+
+/// The changed lines from `before` to `after`, with the 1-based line each starts at in `before`,
+/// marked up with the words that changed, since most edits are a few words in a long line of prose.
+///
+/// With `color`, deleted words are red and struck through, and inserted words are green,
+/// and otherwise they're marked like `[-old-]{+new+}`, like `git diff --word-diff`.
+pub fn word_diff(before: &str, after: &str, color: bool) -> Vec<(usize, String)> {
+    let (deleted, inserted) = match color {
+        true => (("\x1b[9;31m", "\x1b[0m"), ("\x1b[32m", "\x1b[0m")),
+        false => (("[-", "-]"), ("{+", "+}")),
+    };
+    let old_lines = before.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = after.split_inclusive('\n').collect::<Vec<_>>();
+    TextDiff::from_lines(before, after)
+        .ops()
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| {
+            let old = old_lines[op.old_range()].concat();
+            let new = new_lines[op.new_range()].concat();
+            let mut marked = String::new();
+            for change in TextDiff::from_words(&old, &new).iter_all_changes() {
+                let (start, end) = match change.tag() {
+                    ChangeTag::Equal => ("", ""),
+                    ChangeTag::Delete => deleted,
+                    ChangeTag::Insert => inserted,
+                };
+                marked.push_str(start);
+                marked.push_str(change.value());
+                marked.push_str(end);
+            }
+            let marked = marked.strip_suffix('\n').unwrap_or(&marked).to_owned();
+            (op.old_range().start + 1, marked)
+        })
+        .collect()
+}
+
+/// What `--range` counts.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeUnit {
+    /// 1-based lines, including the end.
+    Lines,
+
+    /// 0-based byte offsets, excluding the end.
+    Bytes,
+}
+
+/// Parse a range like `10:20`.
+pub fn parse_range(range: &str) -> eyre::Result<(usize, usize)> {
+    let (start, end) = range
+        .split_once(':')
+        .ok_or_else(|| eyre!("expected `START:END`"))?;
+    let (start, end) = (start.parse()?, end.parse()?);
+    ensure!(start <= end, "the start of `{range}` is after its end");
+    Ok((start, end))
+}
+
+/// The 0-based range of lines of `text` that a `--range` of `unit`s covers.
+pub fn covered_lines(text: &str, (start, end): (usize, usize), unit: RangeUnit) -> Range<usize> {
+    match unit {
+        RangeUnit::Lines => start.saturating_sub(1)..end,
+        RangeUnit::Bytes => {
+            let line_of = |offset: usize| {
+                let offset = offset.min(text.len());
+                text.as_bytes()[..offset]
+                    .iter()
+                    .filter(|&&b| b == b'\n')
+                    .count()
+            };
+            line_of(start)..line_of(end.max(start + 1) - 1) + 1
+        }
+    }
+}
+
+/// How many edits a rule made to a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub path: PathBuf,
+    pub rule: String,
+    pub edits: usize,
+}
+
+impl Change {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "path": self.path.display().to_string(),
+            "rule": self.rule,
+            "edits": self.edits,
+        })
+    }
+}
+
+/// A table of the edits each rule made in each file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary(pub Vec<Change>);
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let paths = self
+            .0
+            .iter()
+            .map(|change| change.path.display().to_string())
+            .collect::<Vec<_>>();
+        let path_width = paths
+            .iter()
+            .map(String::len)
+            .chain(["file".len()])
+            .max()
+            .unwrap();
+        let rule_width = self
+            .0
+            .iter()
+            .map(|change| change.rule.len())
+            .chain(["rule".len()])
+            .max()
+            .unwrap();
+        write!(f, "{:path_width$}  {:rule_width$}  edits", "file", "rule")?;
+        for (change, path) in self.0.iter().zip(&paths) {
+            write!(
+                f,
+                "\n{path:path_width$}  {:rule_width$}  {}",
+                change.rule, change.edits
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::changes::apply_line_edits;
+    use crate::changes::count_edits;
+    use crate::changes::covered_lines;
+    use crate::changes::line_edits;
+    use crate::changes::parse_range;
+    use crate::changes::word_diff;
+    use crate::changes::Change;
+    use crate::changes::LineEdit;
+    use crate::changes::RangeUnit;
+    use crate::changes::Summary;
+
+    #[test]
+    fn test_count_edits() {
+        assert_eq!(count_edits("a\nb\nc\nd\n", "a\nb\nc\nd\n"), 0);
+        assert_eq!(count_edits("a\nb\nc\nd\n", "A\nB\nc\nD\n"), 2);
+        assert_eq!(count_edits("a\nb\n", "a\nx\nb\n"), 1);
+    }
+
+    #[test]
+    fn test_line_edits() {
+        assert_eq!(
+            line_edits("a\nb\nc\nd\n", "A\nB\nc\nd\ne\n"),
+            [
+                LineEdit {
+                    lines: 0..1,
+                    text: "A\n".into(),
+                },
+                LineEdit {
+                    lines: 1..2,
+                    text: "B\n".into(),
+                },
+                LineEdit {
+                    lines: 4..4,
+                    text: "e\n".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_line_edits() {
+        let (before, after) = ("a\nb\nc\nd\n", "A\nb\nc\nD\ne\n");
+        let edits = line_edits(before, after);
+        assert_eq!(apply_line_edits(before, &edits), after);
+        let within = edits
+            .into_iter()
+            .filter(|edit| edit.is_within(&(0..2)))
+            .collect::<Vec<_>>();
+        assert_eq!(apply_line_edits(before, &within), "A\nb\nc\nd\n");
+    }
+
+    #[test]
+    fn test_word_diff() {
+        let before = "A long line with “quotes” in it.\nUnchanged.\nA -- dash.\n";
+        let after = "A long line with \"quotes\" in it.\nUnchanged.\nA — dash.\n";
+        assert_eq!(
+            word_diff(before, after, false),
+            [
+                (
+                    1,
+                    "A long line with [-“quotes”-]{+\"quotes\"+} in it.".into()
+                ),
+                (3, "A [----]{+—+} dash.".into()),
+            ]
+        );
+        assert_eq!(
+            word_diff("a b\n", "a c\n", true),
+            [(1, "a \x1b[9;31mb\x1b[0m\x1b[32mc\x1b[0m".into())]
+        );
+    }
+
+    #[test]
+    fn test_covered_lines() {
+        assert_eq!(parse_range("2:3").unwrap(), (2, 3));
+        assert!(parse_range("3:2").is_err());
+        assert!(parse_range("3").is_err());
+        let text = "ab\ncd\nef\n";
+        assert_eq!(covered_lines(text, (2, 3), RangeUnit::Lines), 1..3);
+        assert_eq!(covered_lines(text, (1, 4), RangeUnit::Bytes), 0..2);
+        assert_eq!(covered_lines(text, (3, 6), RangeUnit::Bytes), 1..2);
+        assert_eq!(covered_lines(text, (4, 4), RangeUnit::Bytes), 1..2);
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary(vec![
+            Change {
+                path: "posts/a.md".into(),
+                rule: "quotes".into(),
+                edits: 3,
+            },
+            Change {
+                path: "b.md".into(),
+                rule: "quotes".into(),
+                edits: 0,
+            },
+        ]);
+        assert_eq!(
+            summary.to_string(),
+            "file        rule    edits
+posts/a.md  quotes  3
+b.md        quotes  0"
+        );
+    }
+}
@@ -0,0 +1,493 @@
+//! Fenced and indented code blocks.
+
+use clap::ValueEnum;
+use itertools::Itertools;
+
+use crate::diagnostic::Diagnostic;
+use crate::footnotes;
+use crate::lists;
+use crate::markdown;
+use crate::markdown::Fence;
+use crate::markdown::Line;
+use crate::markdown::LineKind;
+
+/// A fenced code block, split into its parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock<'a> {
+    /// The opening fence line.
+    pub opening: &'a str,
+
+    /// The fence to write, which is the opening fence unless changed.
+    pub fence: Fence,
+
+    /// The info string after the opening fence, like `rust`.
+    pub info: &'a str,
+
+    pub content: Vec<&'a str>,
+
+    /// The closing fence line, if the code block is closed.
+    pub closing: Option<&'a str>,
+}
+
+impl<'a> CodeBlock<'a> {
+    fn parse(opening: &'a str, content: Vec<&'a str>, closing: Option<&'a str>) -> Self {
+        let fence = Fence::parse_opening(opening).unwrap();
+        let info = opening.trim_start_matches(' ')[fence.len..].trim();
+        Self {
+            opening,
+            fence,
+            info,
+            content,
+            closing,
+        }
+    }
+
+    /// The language of the code block, i.e. the first word of the info string.
+    pub fn language(&self) -> Option<&'a str> {
+        self.info.split_whitespace().next()
+    }
+
+    /// Guess the language of simple cases: shell sessions, shell scripts, JSON, and diffs.
+    pub fn infer_language(&self) -> Option<&'static str> {
+        let lines = self
+            .content
+            .iter()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let first = *lines.first()?;
+        let last = *lines.last()?;
+        if first.starts_with("$ ") {
+            Some("console")
+        } else if first.starts_with("#!")
+            && ["sh", "bash", "zsh"].iter().any(|sh| first.ends_with(sh))
+        {
+            Some("shell")
+        } else if first.starts_with("diff --git")
+            || (first.starts_with("--- ")
+                && lines.get(1).is_some_and(|line| line.starts_with("+++ ")))
+            || first.starts_with("@@ ")
+        {
+            Some("diff")
+        } else if (first.starts_with('{') && last.ends_with('}'))
+            || (first.starts_with('[')
+                && last.ends_with(']')
+                && lines.iter().any(|line| line.contains('"')))
+        {
+            Some("json")
+        } else {
+            None
+        }
+    }
+
+    /// The shortest fence of `char` that no content line would close.
+    pub fn min_fence_len(&self, char: char) -> usize {
+        min_fence_len(&self.content, char)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let Self {
+            opening,
+            fence,
+            info,
+            ..
+        } = *self;
+        let original = Self::parse(opening, Vec::new(), None);
+        // Keep the indentation and whatever's after the fence.
+        let split_fence = |line: &'a str| {
+            let unindented = line.trim_start_matches(' ');
+            let indent = &line[..line.len() - unindented.len()];
+            (indent, unindented.trim_start_matches(['`', '~']))
+        };
+        let (indent, after_fence) = split_fence(opening);
+        let opening = if original.info != info {
+            format!("{indent}{fence}{info}")
+        } else if original.fence != fence {
+            format!("{indent}{fence}{after_fence}")
+        } else {
+            opening.to_owned()
+        };
+        let closing = self.closing.map(|closing| {
+            if original.fence == fence {
+                return closing.to_owned();
+            }
+            let (indent, after_fence) = split_fence(closing);
+            format!("{indent}{fence}{after_fence}")
+        });
+        [opening]
+            .into_iter()
+            .chain(self.content.iter().map(|line| line.to_string()))
+            .chain(closing)
+            .collect()
+    }
+}
+
+/// The shortest fence of `char` that no line of `content` would close.
+fn min_fence_len(content: &[impl AsRef<str>], char: char) -> usize {
+    content
+        .iter()
+        .map(|line| line.as_ref())
+        .filter(|line| line.len() - line.trim_start_matches(' ').len() <= 3)
+        .map(|line| line.trim_start_matches(' '))
+        .filter(|line| line.trim_start_matches(char).trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(char).len() + 1)
+        .chain([3])
+        .max()
+        .unwrap()
+}
+
+/// A block of a document: either a line outside of a fenced code block or a whole code block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block<'a> {
+    Line(Line<'a>),
+    Code(CodeBlock<'a>),
+}
+
+/// Split `text` into fenced code blocks and the lines in between.
+pub fn blocks(text: &str) -> Vec<Block<'_>> {
+    let mut blocks = Vec::new();
+    let mut lines = markdown::lines(text).peekable();
+    while let Some(line) = lines.next() {
+        if line.kind != LineKind::Fence {
+            blocks.push(Block::Line(line));
+            continue;
+        }
+        let content = lines
+            .peeking_take_while(|line| line.kind == LineKind::Code)
+            .map(|line| line.text)
+            .collect();
+        let closing = lines.next_if(|line| line.kind == LineKind::Fence);
+        blocks.push(Block::Code(CodeBlock::parse(
+            line.text,
+            content,
+            closing.map(|line| line.text),
+        )));
+    }
+    blocks
+}
+
+pub fn join_blocks(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .flat_map(|block| match block {
+            Block::Line(line) => vec![line.text.to_owned()],
+            Block::Code(code) => code.lines(),
+        })
+        .join("\n")
+}
+
+/// Which character to write code fences with.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FenceStyle {
+    /// ```` ``` ````
+    Backticks,
+
+    /// `~~~`
+    Tildes,
+}
+
+/// Convert code fences to use `style` and be as short as possible.
+///
+/// Unclosed code blocks are left alone,
+/// as are ones with a backtick in their info string when converting to backticks.
+pub fn normalize_code_fences(before: String, style: FenceStyle) -> String {
+    let char = match style {
+        FenceStyle::Backticks => '`',
+        FenceStyle::Tildes => '~',
+    };
+    let mut blocks = blocks(&before);
+    for block in &mut blocks {
+        let Block::Code(code) = block else {
+            continue;
+        };
+        if code.closing.is_none() || (char == '`' && code.info.contains('`')) {
+            continue;
+        }
+        code.fence = Fence {
+            char,
+            len: code.min_fence_len(char),
+        };
+    }
+    join_blocks(&blocks)
+}
+
+/// Report code blocks without a language.
+///
+/// If `fix`, set their language to one inferred from the code, or else to `default`.
+pub fn check_code_languages(
+    before: String,
+    fix: bool,
+    default: Option<&str>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let mut blocks = blocks(&before);
+    let mut changed = false;
+    let mut line = 1;
+    for block in &mut blocks {
+        let code = match block {
+            Block::Line(_) => {
+                line += 1;
+                continue;
+            }
+            Block::Code(code) => code,
+        };
+        let lines = 1 + code.content.len() + code.closing.iter().len();
+        if code.language().is_none() {
+            match code.infer_language().or(default).filter(|_| fix) {
+                Some(language) => {
+                    code.info = language;
+                    changed = true;
+                }
+                None => diagnostics.push(Diagnostic::new(line, "code block has no language")),
+            }
+        }
+        line += lines;
+    }
+    match changed {
+        true => join_blocks(&blocks),
+        false => before,
+    }
+}
+
+/// Remove `columns` columns of indentation from `line`, keeping any further indentation as is.
+fn remove_indent(line: &str, columns: usize) -> String {
+    let mut column = 0;
+    for (i, c) in line.char_indices() {
+        if column >= columns {
+            // A tab that was only partially removed leaves some spaces.
+            return format!("{}{}", " ".repeat(column - columns), &line[i..]);
+        }
+        match c {
+            ' ' => column += 1,
+            '\t' => column += 4 - column % 4,
+            _ => return line[i..].to_owned(),
+        }
+    }
+    " ".repeat(column.saturating_sub(columns))
+}
+
+/// Convert indented code blocks to fenced code blocks, with an optional `language`.
+///
+/// Indented lines in list items and footnotes are continuation lines, not code, so they're left alone.
+pub fn fence_indented_code(before: String, language: Option<&str>) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let is_blank = |i: usize| lines[i].text.trim().is_empty();
+    let is_indented = |i: usize| {
+        lines[i].kind == LineKind::Text
+            && !is_blank(i)
+            && markdown::expand_indent(lines[i].text).0 >= 4
+    };
+    let mut after = Vec::new();
+    let mut in_container = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let (indent, rest) = markdown::expand_indent(line.text);
+        // Indented code can't interrupt a paragraph.
+        let can_start = i == 0 || is_blank(i - 1);
+        if !(is_indented(i) && can_start && !in_container) {
+            if line.kind == LineKind::Text && !rest.is_empty() && indent < 4 {
+                in_container = lists::parse_item(rest).is_some()
+                    || footnotes::definition_label(line.text).is_some()
+                    || (in_container && indent > 0);
+            }
+            after.push(line.text.to_owned());
+            i += 1;
+            continue;
+        }
+        let mut end = i;
+        let mut j = i;
+        while j < lines.len()
+            && (is_indented(j) || (lines[j].kind == LineKind::Text && is_blank(j)))
+        {
+            if is_indented(j) {
+                end = j + 1;
+            }
+            j += 1;
+        }
+        let content = lines[i..end]
+            .iter()
+            .map(|line| remove_indent(line.text, 4))
+            .collect::<Vec<_>>();
+        let fence = Fence {
+            char: '`',
+            len: min_fence_len(&content, '`'),
+        };
+        after.push(format!("{fence}{}", language.unwrap_or_default()));
+        after.extend(content);
+        after.push(fence.to_string());
+        i = end;
+    }
+    after.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::code_blocks::check_code_languages;
+    use crate::code_blocks::fence_indented_code;
+    use crate::code_blocks::normalize_code_fences;
+    use crate::code_blocks::FenceStyle;
+    use crate::diagnostic::Diagnostic;
+
+    #[test]
+    fn test_normalize_code_fences() {
+        let before = "~~~~~ rust
+a
+~~~~~~
+
+`````markdown
+```rust
+b
+```
+`````
+
+  ~~~ `c`
+~~~
+
+~~~
+unclosed";
+        let after = "``` rust
+a
+```
+
+````markdown
+```rust
+b
+```
+````
+
+  ~~~ `c`
+~~~
+
+~~~
+unclosed";
+        assert_eq!(
+            normalize_code_fences(before.into(), FenceStyle::Backticks),
+            after
+        );
+        let after = "~~~ rust
+a
+~~~
+
+~~~markdown
+```rust
+b
+```
+~~~
+
+  ~~~ `c`
+~~~
+
+~~~
+unclosed";
+        assert_eq!(
+            normalize_code_fences(before.into(), FenceStyle::Tildes),
+            after
+        );
+    }
+
+    #[test]
+    fn test_check_code_languages() {
+        let before = "```
+$ cargo build
+```
+
+```rust
+fn main() {}
+```
+
+```
+{\"a\": 1}
+```
+
+~~~
+--- a.txt
++++ b.txt
+~~~
+
+```
+???
+```";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_code_languages(before.into(), false, None, &mut diagnostics),
+            before
+        );
+        let lines = [1, 9, 13, 18];
+        assert_eq!(
+            diagnostics,
+            lines.map(|line| Diagnostic::new(line, "code block has no language"))
+        );
+        let after = "```console
+$ cargo build
+```
+
+```rust
+fn main() {}
+```
+
+```json
+{\"a\": 1}
+```
+
+~~~diff
+--- a.txt
++++ b.txt
+~~~
+
+```text
+???
+```";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_code_languages(before.into(), true, Some("text"), &mut diagnostics),
+            after
+        );
+        assert_eq!(diagnostics, []);
+    }
+
+    #[test]
+    fn test_fence_indented_code() {
+        let before = "a
+
+    fn main() {
+    \tprintln!();
+
+    }
+
+b
+    not code
+
+- c
+
+      not code
+
+[^1]: d
+
+    not code
+
+e";
+        let after = "a
+
+```rust
+fn main() {
+\tprintln!();
+
+}
+```
+
+b
+    not code
+
+- c
+
+      not code
+
+[^1]: d
+
+    not code
+
+e";
+        assert_eq!(fence_indented_code(before.into(), Some("rust")), after);
+    }
+}
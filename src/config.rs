@@ -0,0 +1,197 @@
+//! The `style-markdown.toml` config, which defines presets of rules, like:
+//!
+//! ```toml
+//! [preset.blog-post]
+//! rules = ["quotes", "dashes --style unicode", "line-length --max 80"]
+//! ```
+//!
+//! For CI, where flags can be awkward to pass through, the config can be found with [`PATH_VAR`],
+//! and the rules given with [`RULES_VAR`].
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+
+use color_eyre::eyre;
+use color_eyre::eyre::bail;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use itertools::Itertools;
+
+use crate::error::Error;
+use crate::presets;
+use crate::Command;
+
+/// The name of the config file, which is looked for in the current directory.
+pub const FILE_NAME: &str = "style-markdown.toml";
+
+/// The environment variable with the path of the config to use, instead of looking for one.
+pub const PATH_VAR: &str = "STYLE_MARKDOWN_CONFIG";
+
+/// The environment variable with the rules to run without a command or a preset,
+/// as parsed by [`presets::parse_rule_list`].
+pub const RULES_VAR: &str = "STYLE_MARKDOWN_RULES";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// The rules of each user-defined preset, by name, which take precedence over built-in ones.
+    pub presets: BTreeMap<String, Vec<String>>,
+}
+
+impl Config {
+    pub fn parse(toml: &str) -> eyre::Result<Self> {
+        let config = toml.parse::<toml::Table>()?;
+        let mut presets = BTreeMap::new();
+        let Some(preset_tables) = config.get("preset") else {
+            return Ok(Self { presets });
+        };
+        let preset_tables = preset_tables
+            .as_table()
+            .ok_or_else(|| eyre!("`preset` must be a table"))?;
+        for (name, preset) in preset_tables {
+            let rules = preset
+                .get("rules")
+                .ok_or_else(|| eyre!("preset `{name}` must be a table with `rules`"))?
+                .as_array()
+                .ok_or_else(|| eyre!("`preset.{name}.rules` must be a list"))?
+                .iter()
+                .map(|rule| {
+                    rule.as_str()
+                        .map(str::to_owned)
+                        .ok_or_else(|| eyre!("`preset.{name}.rules` must be a list of strings"))
+                })
+                .collect::<eyre::Result<_>>()?;
+            presets.insert(name.clone(), rules);
+        }
+        Ok(Self { presets })
+    }
+
+    /// Read the config at [`PATH_VAR`] if it's set, or else in `dir`,
+    /// or the default config if there isn't one.
+    pub fn find(dir: &Path) -> eyre::Result<Self> {
+        if let Some(path) = env::var_os(PATH_VAR).filter(|path| !path.is_empty()) {
+            return Self::read(Path::new(&path))
+                .wrap_err_with(|| format!("couldn't read `{PATH_VAR}`"));
+        }
+        let path = dir.join(FILE_NAME);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        Self::read(&path)
+    }
+
+    /// Read the config at `path`, which must exist.
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let toml = fs_err::read_to_string(path)?;
+        let config =
+            Self::parse(&toml).map_err(|error| match error.downcast_ref::<toml::de::Error>() {
+                Some(toml_error) => {
+                    let message = eyre!("{}", toml_error.message());
+                    Error::invalid_config(path, &toml, toml_error.span(), &message)
+                }
+                None => Error::invalid_config(path, &toml, None, &error),
+            })?;
+        // Check the rules now, pointing to the invalid one, instead of once its preset is used.
+        for rule in config.presets.values().flatten() {
+            if let Err(error) = Command::from_rule(rule) {
+                let quoted = format!("\"{rule}\"");
+                let span = toml.find(&quoted).map(|start| start..start + quoted.len());
+                return Err(Error::invalid_config(path, &toml, span, &error).into());
+            }
+        }
+        Ok(config)
+    }
+
+    /// The rules of the preset `name`, either user-defined or built-in.
+    pub fn preset(&self, name: &str) -> eyre::Result<Vec<Command>> {
+        if let Some(rules) = self.presets.get(name) {
+            return presets::parse_rules(rules)
+                .wrap_err_with(|| format!("invalid preset `{name}` in `{FILE_NAME}`"));
+        }
+        if presets::BUILTIN.iter().any(|(builtin, _)| *builtin == name) {
+            return presets::builtin(name);
+        }
+        let names = self
+            .presets
+            .keys()
+            .map(String::as_str)
+            .chain(presets::BUILTIN.iter().map(|(name, _)| *name))
+            .unique()
+            .map(|name| format!("`{name}`"))
+            .join(", ");
+        bail!("unknown preset `{name}`; expected one of {names}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::config::Config;
+    use crate::config::FILE_NAME;
+    use crate::error::Error;
+    use crate::Command;
+
+    #[test]
+    fn test_config() {
+        let toml = r#"
+[preset.blog-post]
+rules = ["quotes", "dashes --style unicode"]
+
+[preset.gdocs-cleanup]
+rules = ["embedded-images"]
+"#;
+        let config = Config::parse(toml).unwrap();
+        let names = |preset: &str| {
+            config
+                .preset(preset)
+                .unwrap()
+                .iter()
+                .map(Command::name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("blog-post"), ["quotes", "dashes"]);
+        assert_eq!(names("gdocs-cleanup"), ["embedded-images"]);
+        assert_eq!(
+            config.preset("docs").unwrap_err().to_string(),
+            "unknown preset `docs`; expected one of `blog-post`, `gdocs-cleanup`"
+        );
+        assert!(Config::parse("[preset.a]\nrules = [1]").is_err());
+        assert!(Config::parse("[preset]\na = 1").is_err());
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_find() {
+        let dir = env::temp_dir().join("style-markdown-test-config");
+        fs_err::create_dir_all(&dir).unwrap();
+        let path = dir.join(FILE_NAME);
+        fs_err::write(
+            &path,
+            "[preset.a]\nrules = [\"quotes\", \"dashes --bad\"]\n",
+        )
+        .unwrap();
+        let error = Config::find(&dir).unwrap_err();
+        let Some(Error::InvalidConfig { span, .. }) = error.downcast_ref() else {
+            panic!("{error:?}");
+        };
+        assert_eq!(span.map(|span| (span.offset(), span.len())), Some((30, 14)));
+        fs_err::write(&path, "[preset.a\n").unwrap();
+        let error = Config::find(&dir).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(Error::InvalidConfig { span: Some(_), .. })
+        ));
+        fs_err::write(&path, "[preset.a]\nfoo = 1\n").unwrap();
+        let error = Config::find(&dir).unwrap_err();
+        let Some(Error::InvalidConfig { message, .. }) = error.downcast_ref() else {
+            panic!("{error:?}");
+        };
+        assert_eq!(message, "preset `a` must be a table with `rules`");
+        fs_err::write(&path, "[preset.a]\nrules = [\"quotes\"]\n").unwrap();
+        assert_eq!(Config::read(&path).unwrap().presets["a"], ["quotes"]);
+        fs_err::remove_file(&path).unwrap();
+        assert_eq!(Config::find(&dir).unwrap(), Config::default());
+        assert!(Config::read(&path).is_err());
+    }
+}
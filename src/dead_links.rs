@@ -0,0 +1,252 @@
+//! Finding dead links and replacing them with archived snapshots from the Wayback Machine.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use color_eyre::eyre;
+use itertools::Itertools;
+use regex::Captures;
+use ureq::Agent;
+
+use crate::diagnostic::Diagnostic;
+use crate::links;
+use crate::markdown;
+use crate::markdown::LineKind;
+
+/// Whether a URL is alive, dead, or couldn't be checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Alive,
+    Dead(String),
+    Unknown(String),
+}
+
+fn agent() -> Agent {
+    Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(20)))
+        .build()
+        .into()
+}
+
+/// Request `url`, counting it as dead only if it's definitely gone (404, 410, or no such host),
+/// since servers often reject bots with other errors.
+fn check_url(agent: &Agent, url: &str) -> Status {
+    let mut response = agent.head(url).call();
+    // Some servers don't support `HEAD`.
+    if let Err(ureq::Error::StatusCode(405 | 403 | 501)) = response {
+        response = agent.get(url).call();
+    }
+    match response {
+        Ok(_) => Status::Alive,
+        Err(ureq::Error::StatusCode(status @ (404 | 410))) => {
+            Status::Dead(format!("HTTP status {status}"))
+        }
+        Err(ureq::Error::HostNotFound) => Status::Dead("host not found".into()),
+        Err(e) => Status::Unknown(e.to_string()),
+    }
+}
+
+/// Parse the closest snapshot's URL from a response of the Wayback Machine availability API.
+fn parse_availability(json: &str) -> Option<String> {
+    let json = serde_json::from_str::<serde_json::Value>(json).ok()?;
+    let closest = &json["archived_snapshots"]["closest"];
+    if closest["available"] != true {
+        return None;
+    }
+    let url = closest["url"].as_str()?;
+    let url = match url.strip_prefix("http://") {
+        Some(rest) => format!("https://{rest}"),
+        None => url.to_owned(),
+    };
+    Some(url)
+}
+
+/// Find the closest archived snapshot of `url` in the Wayback Machine.
+fn closest_snapshot(agent: &Agent, url: &str) -> eyre::Result<Option<String>> {
+    let json = agent
+        .get("https://archive.org/wayback/available")
+        .query("url", url)
+        .call()?
+        .body_mut()
+        .read_to_string()?;
+    Ok(parse_availability(&json))
+}
+
+/// Report links to URLs that are dead.
+///
+/// If `wayback`, replace them with their closest snapshot in the Wayback Machine,
+/// and if `keep_original`, add a footnote with the original URL.
+pub fn check_dead_links(
+    before: String,
+    wayback: bool,
+    keep_original: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let agent = agent();
+    rewrite_dead_links(
+        before,
+        keep_original,
+        |url| check_url(&agent, url),
+        |url| match wayback {
+            true => closest_snapshot(&agent, url),
+            false => Ok(None),
+        },
+        diagnostics,
+    )
+}
+
+/// [`check_dead_links`] with the network requests abstracted out.
+fn rewrite_dead_links(
+    before: String,
+    keep_original: bool,
+    mut check: impl FnMut(&str) -> Status,
+    mut snapshot: impl FnMut(&str) -> eyre::Result<Option<String>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let link = links::url_destination_regex();
+    let mut statuses = HashMap::new();
+    let mut originals = Vec::new();
+    let footnote_label = |n: usize| format!("archived-{n}");
+    let mut next_footnote = 1;
+    let after = markdown::lines(&before)
+        .enumerate()
+        .map(|(i, line)| {
+            if line.kind != LineKind::Text {
+                return line.text.to_owned();
+            }
+            markdown::rewrite_outside_code(line.text, |text| {
+                let rewritten = link.replace_all(text, |captures: &Captures| {
+                    let unchanged = captures[0].to_owned();
+                    let url = &captures["url"];
+                    let status = statuses
+                        .entry(url.to_owned())
+                        .or_insert_with(|| check(url))
+                        .clone();
+                    let reason = match status {
+                        Status::Alive => return unchanged,
+                        Status::Dead(reason) => reason,
+                        Status::Unknown(error) => {
+                            diagnostics.push(Diagnostic::note(
+                                i + 1,
+                                format!("couldn't check `{url}`: {error}"),
+                            ));
+                            return unchanged;
+                        }
+                    };
+                    let archived = match snapshot(url) {
+                        Ok(Some(archived)) => archived,
+                        Ok(None) => {
+                            diagnostics.push(Diagnostic::new(
+                                i + 1,
+                                format!("`{url}` is dead ({reason})"),
+                            ));
+                            return unchanged;
+                        }
+                        Err(e) => {
+                            diagnostics.push(Diagnostic::new(
+                                i + 1,
+                                format!("`{url}` is dead ({reason}) and couldn't be archived: {e}"),
+                            ));
+                            return unchanged;
+                        }
+                    };
+                    diagnostics.push(Diagnostic::note(
+                        i + 1,
+                        format!("replaced dead `{url}` with `{archived}`"),
+                    ));
+                    let prefix = &captures["prefix"];
+                    let suffix = &captures["suffix"];
+                    let mut rewritten = format!("{prefix}{archived}{suffix}");
+                    // Footnotes can't go on reference definitions.
+                    if keep_original && prefix.starts_with([']', '<']) {
+                        while before.contains(&format!("[^{}]", footnote_label(next_footnote))) {
+                            next_footnote += 1;
+                        }
+                        let label = footnote_label(next_footnote);
+                        next_footnote += 1;
+                        rewritten.push_str(&format!("[^{label}]"));
+                        originals.push(format!(
+                            "[^{label}]: Archived from the original, <{url}>, which is now dead."
+                        ));
+                    }
+                    rewritten
+                });
+                rewritten.into_owned()
+            })
+        })
+        .join("\n");
+    if originals.is_empty() {
+        return after;
+    }
+    format!("{}\n\n{}\n", after.trim_end(), originals.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dead_links::parse_availability;
+    use crate::dead_links::rewrite_dead_links;
+    use crate::dead_links::Status;
+    use crate::diagnostic::Diagnostic;
+
+    #[test]
+    fn test_parse_availability() {
+        let json = r#"{"url": "example.com/a.pdf", "archived_snapshots": {"closest": {
+            "status": "200", "available": true, "timestamp": "20130919044612",
+            "url": "http://web.archive.org/web/20130919044612/http://example.com/a.pdf"}}}"#;
+        assert_eq!(
+            parse_availability(json).as_deref(),
+            Some("https://web.archive.org/web/20130919044612/http://example.com/a.pdf")
+        );
+        let json = r#"{"url": "example.com/b.pdf", "archived_snapshots": {}}"#;
+        assert_eq!(parse_availability(json), None);
+    }
+
+    #[test]
+    fn test_rewrite_dead_links() {
+        let before = "See [the schedule](https://transit.example/old.pdf \"Schedule\"),
+<https://transit.example/old.pdf>, [the map](https://transit.example/map.pdf),
+and [the home page](https://transit.example/).
+
+[schedule]: https://transit.example/old.pdf
+";
+        let check = |url: &str| match url {
+            "https://transit.example/" => Status::Alive,
+            _ => Status::Dead("HTTP status 404".into()),
+        };
+        let snapshot = |url: &str| {
+            Ok(match url {
+                "https://transit.example/old.pdf" => {
+                    Some(format!("https://web.archive.org/web/2015/{url}"))
+                }
+                _ => None,
+            })
+        };
+        let mut diagnostics = Vec::new();
+        let after = rewrite_dead_links(before.into(), true, check, snapshot, &mut diagnostics);
+        assert_eq!(
+            after,
+            "See [the schedule](https://web.archive.org/web/2015/https://transit.example/old.pdf \"Schedule\")[^archived-1],
+<https://web.archive.org/web/2015/https://transit.example/old.pdf>[^archived-2], [the map](https://transit.example/map.pdf),
+and [the home page](https://transit.example/).
+
+[schedule]: https://web.archive.org/web/2015/https://transit.example/old.pdf
+
+[^archived-1]: Archived from the original, <https://transit.example/old.pdf>, which is now dead.
+[^archived-2]: Archived from the original, <https://transit.example/old.pdf>, which is now dead.
+"
+        );
+        let replaced = "replaced dead `https://transit.example/old.pdf` with `https://web.archive.org/web/2015/https://transit.example/old.pdf`";
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::note(1, replaced),
+                Diagnostic::note(2, replaced),
+                Diagnostic::new(
+                    2,
+                    "`https://transit.example/map.pdf` is dead (HTTP status 404)"
+                ),
+                Diagnostic::note(5, replaced),
+            ]
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Problems rules find but can't (or weren't asked to) fix.
+
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Just information, like what was changed.
+    Note,
+
+    /// A problem that fails the run.
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let severity = match self {
+            Self::Note => "note",
+            Self::Warning => "warning",
+        };
+        f.write_str(severity)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based line number.
+    pub line: usize,
+
+    pub severity: Severity,
+
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+
+    pub fn note(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Note,
+            ..Self::new(line, message)
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "line": self.line,
+            "severity": self.severity.to_string(),
+            "message": self.message,
+        })
+    }
+}
+
+/// Formatted as `{line}: {severity}: {message}`, to be prefixed with the path.
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self {
+            line,
+            severity,
+            message,
+        } = self;
+        write!(f, "{line}: {severity}: {message}")
+    }
+}
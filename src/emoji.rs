@@ -0,0 +1,93 @@
+//! Emoji, written either as Unicode or as GitHub shortcodes, like `:bullettrain_side:`.
+
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use regex::Captures;
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::markdown;
+
+/// How to write emoji.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmojiStyle {
+    /// Unicode emoji, like `🚄`.
+    Unicode,
+
+    /// GitHub shortcodes, like `:bullettrain_side:`.
+    Shortcode,
+}
+
+/// Whether `emoji` is displayed as an emoji by default,
+/// unlike symbols such as `©` that are only emoji with a variation selector.
+fn is_emoji_presentation(emoji: &str) -> bool {
+    let mut chars = emoji.chars();
+    let first = chars.next().unwrap_or_default();
+    chars.next().is_some() || first >= '\u{1F000}'
+}
+
+/// Replace Unicode emoji with their shortcodes, keeping emoji that don't have one.
+///
+/// Each grapheme is replaced whole, so an emoji with a skin tone that doesn't have a shortcode
+/// is kept as is, instead of its base emoji being replaced and the skin tone left dangling.
+fn to_shortcodes(text: &str) -> String {
+    text.graphemes(true)
+        .map(
+            |grapheme| match emojis::get(grapheme).and_then(|emoji| emoji.shortcode()) {
+                Some(shortcode) if is_emoji_presentation(grapheme) => {
+                    format!(":{shortcode}:").into()
+                }
+                _ => Cow::Borrowed(grapheme),
+            },
+        )
+        .collect()
+}
+
+/// Convert emoji shortcodes, like `:bullettrain_side:`, to Unicode emoji, or the reverse.
+///
+/// Unknown shortcodes and emoji without a shortcode are left alone.
+pub fn convert_emoji(before: String, style: EmojiStyle) -> String {
+    static SHORTCODE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r":(?<name>[a-z0-9_+-]+):").unwrap());
+    let after = markdown::rewrite_prose_in_parallel(&before, |text| match style {
+        EmojiStyle::Unicode => SHORTCODE
+            .replace_all(text, |captures: &Captures| {
+                match emojis::get_by_shortcode(&captures["name"]) {
+                    Some(emoji) => emoji.as_str().to_owned(),
+                    None => captures[0].to_owned(),
+                }
+            })
+            .into_owned(),
+        EmojiStyle::Shortcode => to_shortcodes(text),
+    });
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emoji::convert_emoji;
+    use crate::emoji::EmojiStyle;
+
+    #[test]
+    fn test_convert_emoji() {
+        let shortcodes = "Take the :bullettrain_side: :+1:, not the :not_an_emoji: at 10:30:45.
+:woman_technologist: `:rocket:` © [:train:](https://example.com/:train:)";
+        let unicode = "Take the 🚄 👍, not the :not_an_emoji: at 10:30:45.
+👩‍💻 `:rocket:` © [🚋](https://example.com/:train:)";
+        assert_eq!(
+            convert_emoji(shortcodes.into(), EmojiStyle::Unicode),
+            unicode
+        );
+        assert_eq!(
+            convert_emoji(unicode.into(), EmojiStyle::Shortcode),
+            shortcodes
+        );
+        // There's no shortcode for a skin tone, so it's kept whole.
+        assert_eq!(
+            convert_emoji("👍🏽 🇫🇷".into(), EmojiStyle::Shortcode),
+            "👍🏽 :fr:"
+        );
+    }
+}
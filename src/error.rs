@@ -0,0 +1,177 @@
+//! Errors that point into a file, which the CLI prints with an annotated snippet of it.
+
+use std::ops::Range;
+use std::path::Path;
+use std::str::Utf8Error;
+
+use color_eyre::eyre;
+use miette::Diagnostic;
+use miette::NamedSource;
+use miette::SourceSpan;
+use thiserror::Error;
+
+use crate::markdown;
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum Error {
+    #[error("`{path}` isn't UTF-8")]
+    #[diagnostic(
+        code(style_markdown::not_utf8),
+        help("convert it to UTF-8 first, like with `iconv -t UTF-8`")
+    )]
+    NotUtf8 {
+        path: String,
+
+        #[source_code]
+        source_code: NamedSource<String>,
+
+        #[label("not UTF-8")]
+        span: SourceSpan,
+    },
+
+    #[error("invalid config `{path}`")]
+    #[diagnostic(code(style_markdown::invalid_config))]
+    InvalidConfig {
+        path: String,
+
+        #[source_code]
+        source_code: NamedSource<String>,
+
+        #[label("{message}")]
+        span: Option<SourceSpan>,
+
+        /// Why it's invalid, which is also the help if there's nothing to point to.
+        message: String,
+
+        #[help]
+        help: Option<String>,
+    },
+
+    #[error("`{rule}` broke `{path}`")]
+    #[diagnostic(
+        code(style_markdown::invalid_output),
+        help("this is a bug in `{rule}`, so the file was left unchanged")
+    )]
+    InvalidOutput {
+        rule: String,
+
+        path: String,
+
+        /// The broken output.
+        #[source_code]
+        source_code: NamedSource<String>,
+
+        #[label("{message}")]
+        span: SourceSpan,
+
+        message: String,
+    },
+}
+
+impl Error {
+    /// The file at `path` with `bytes` isn't UTF-8, as found by `error`.
+    pub fn not_utf8(path: &Path, bytes: &[u8], error: Utf8Error) -> Self {
+        let start = error.valid_up_to();
+        // Only show up to the end of the line with the invalid bytes.
+        let end = bytes[start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |i| start + i);
+        let text = String::from_utf8_lossy(&bytes[..end]).into_owned();
+        let path = path.display().to_string();
+        Self::NotUtf8 {
+            // The invalid bytes are replaced by a `U+FFFD`.
+            span: (start, '\u{FFFD}'.len_utf8()).into(),
+            source_code: NamedSource::new(&path, text),
+            path,
+        }
+    }
+
+    /// The config at `path` with `text` is invalid because of `error`,
+    /// pointing to `span` of it if known.
+    pub fn invalid_config(
+        path: &Path,
+        text: &str,
+        span: Option<Range<usize>>,
+        error: &eyre::Report,
+    ) -> Self {
+        // Errors from `clap` continue with its usage, which isn't about the config.
+        let message = error.to_string();
+        let message = message.lines().next().unwrap_or_default();
+        let message = message.trim_start_matches("error: ").to_owned();
+        let path = path.display().to_string();
+        Self::InvalidConfig {
+            help: span.is_none().then(|| message.clone()),
+            span: span.map(SourceSpan::from),
+            message,
+            source_code: NamedSource::new(&path, text.to_owned()),
+            path,
+        }
+    }
+
+    /// Check that `after`, what `rule` rewrote `before` in the file at `path` to,
+    /// isn't broken in a way that `before` wasn't,
+    /// like a code fence that's never closed, which would turn the rest of it into code.
+    pub fn check_output(path: &Path, rule: &str, before: &str, after: &str) -> eyre::Result<()> {
+        let Some(fence) = markdown::unclosed_fence(after) else {
+            return Ok(());
+        };
+        if markdown::unclosed_fence(before).is_some() {
+            return Ok(());
+        }
+        let path = path.display().to_string();
+        Err(Self::InvalidOutput {
+            rule: rule.to_owned(),
+            span: fence.into(),
+            message: "this code fence is never closed".to_owned(),
+            source_code: NamedSource::new(&path, after.to_owned()),
+            path,
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use color_eyre::eyre::eyre;
+    use miette::Diagnostic;
+
+    use crate::error::Error;
+
+    #[test]
+    fn test_not_utf8() {
+        let bytes = b"# Title\n\nCaf\xe9 au lait\nMore.\n".to_vec();
+        let error = str::from_utf8(&bytes).unwrap_err();
+        let error = Error::not_utf8(Path::new("a.md"), &bytes, error);
+        assert_eq!(error.to_string(), "`a.md` isn't UTF-8");
+        let Error::NotUtf8 {
+            source_code, span, ..
+        } = &error
+        else {
+            panic!("{error:?}");
+        };
+        assert_eq!(source_code.inner(), "# Title\n\nCaf\u{FFFD} au lait");
+        assert_eq!((span.offset(), span.len()), (12, 3));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let error = eyre!("error: unexpected argument '--bad' found\n\nUsage: dashes");
+        let error = Error::invalid_config(Path::new("a.toml"), "", Some(1..2), &error);
+        let labels = error.labels().unwrap().collect::<Vec<_>>();
+        assert_eq!(labels[0].label(), Some("unexpected argument '--bad' found"));
+        assert!(error.help().is_none());
+    }
+
+    #[test]
+    fn test_check_output() {
+        let path = Path::new("a.md");
+        let check = |before, after| Error::check_output(path, "rule", before, after);
+        assert!(check("a\n", "a\n```\n```\n").is_ok());
+        assert!(check("```\n", "```\na\n").is_ok());
+        let error = check("a\n", "a\n```\n").unwrap_err();
+        assert_eq!(error.to_string(), "`rule` broke `a.md`");
+    }
+}
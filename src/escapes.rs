@@ -0,0 +1,137 @@
+//! Backslash escapes, i.e. `\_`.
+
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use regex::Captures;
+use regex::Regex;
+
+use crate::graphemes;
+use crate::markdown;
+use crate::markdown::LineKind;
+use crate::references;
+
+/// Whether the escape of `c` is needed, given the text before and after it,
+/// and whether that text before is the start of the line.
+fn is_escape_needed(c: char, before: &str, after: &str, is_line_start: bool) -> bool {
+    let previous = before.chars().next_back();
+    let next = after.chars().next();
+    // Graphemes, so that a letter with a combining accent is still a letter.
+    let is_alphanumeric = |grapheme: Option<&str>| grapheme.is_some_and(graphemes::is_alphanumeric);
+    let is_at_line_start = is_line_start && before.trim().is_empty();
+    match c {
+        // Intraword `_` can't start or end emphasis.
+        '_' => {
+            !(is_alphanumeric(graphemes::last(before)) && is_alphanumeric(graphemes::first(after)))
+        }
+        '-' | '+' | '=' | '>' => is_at_line_start,
+        '.' | ')' => {
+            let number = before.trim_start();
+            is_line_start && !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+        }
+        '#' => is_at_line_start || previous.is_some_and(char::is_whitespace),
+        '!' => next == Some('['),
+        '^' | '(' | ':' => matches!(previous, Some('[' | ']')),
+        '~' => previous == Some('~') || matches!(next, Some('~' | '\\')),
+        '&' => next.is_some_and(|c| c == '#' || c.is_ascii_alphanumeric()),
+        '"' | '\'' | ',' | ';' | '/' | '?' | '@' | '%' | '{' | '}' => false,
+        // `*`, `` ` ``, `\`, `<`, `[`, `]`, `|`, `$`, and anything else.
+        _ => true,
+    }
+}
+
+/// Remove backslash escapes that aren't needed, like the `\_` in `URL\_2`,
+/// which Google Docs adds everywhere.
+///
+/// Escaped brackets are unescaped if they can't form a link or footnote.
+pub fn remove_unneeded_escapes(before: String) -> String {
+    let labels = references::definitions(&before)
+        .into_iter()
+        .map(|(_, definition)| references::normalize_label(definition.label))
+        .collect::<HashSet<_>>();
+    static BRACKETS: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\\\[(?<label>[^\[\]\\]*)\\\]").unwrap());
+    static ESCAPE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\\(?<char>[[:punct:]])").unwrap());
+    let line_definitions = references::line_definitions(&before);
+    let after = markdown::lines(&before)
+        .zip(line_definitions)
+        .map(|(line, definition)| {
+            if line.kind != LineKind::Text || definition.is_some() {
+                return line.text.to_owned();
+            }
+            let mut is_line_start = true;
+            markdown::rewrite_prose(line.text, |text| {
+                let text = BRACKETS.replace_all(text, |captures: &Captures| {
+                    let whole = captures.get(0).unwrap();
+                    let label = &captures["label"];
+                    let next = text[whole.end()..].chars().next();
+                    let can_link = matches!(next, Some('(' | '[' | ':'))
+                        || label.starts_with('^')
+                        || labels.contains(&references::normalize_label(label));
+                    match can_link {
+                        true => whole.as_str().to_owned(),
+                        false => format!("[{label}]"),
+                    }
+                });
+                let mut rewritten = String::new();
+                let mut last = 0;
+                for captures in ESCAPE.captures_iter(&text) {
+                    let whole = captures.get(0).unwrap();
+                    // An escaped backslash isn't an escape.
+                    let backslashes = text[..whole.start()].len()
+                        - text[..whole.start()].trim_end_matches('\\').len();
+                    let c = captures["char"].chars().next().unwrap();
+                    if backslashes % 2 == 1
+                        || whole.start() < last
+                        || is_escape_needed(
+                            c,
+                            &text[..whole.start()],
+                            &text[whole.end()..],
+                            is_line_start,
+                        )
+                    {
+                        continue;
+                    }
+                    rewritten.push_str(&text[last..whole.start()]);
+                    rewritten.push(c);
+                    last = whole.end();
+                }
+                rewritten.push_str(&text[last..]);
+                is_line_start = false;
+                rewritten
+            })
+        })
+        .join("\n");
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::escapes::remove_unneeded_escapes;
+
+    #[test]
+    fn test_remove_unneeded_escapes() {
+        let before = r"[URL\_2](URL_2) see \_emphasis\_ and snake\_case, 5\-10 \(or so\)\, and C\#.
+\- not a list, 1\. not a list, \# not a heading, and \*not emphasis\*.
+\[1\] and \[see below\], but not \[^1\], \[a\](b), or \[def\].
+Keep `a\_b`, \\\_, \!\[image\], and \&amp;.
+2\. not a list either.
+
+[def]: https://example.com";
+        let after = r"[URL_2](URL_2) see \_emphasis\_ and snake_case, 5-10 (or so), and C#.
+\- not a list, 1. not a list, \# not a heading, and \*not emphasis\*.
+[1] and [see below], but not \[^1\], \[a\](b), or \[def\].
+Keep `a\_b`, \\\_, \![image], and \&amp;.
+2\. not a list either.
+
+[def]: https://example.com";
+        assert_eq!(remove_unneeded_escapes(before.into()), after);
+        // A combining accent is part of the letter before it.
+        assert_eq!(
+            remove_unneeded_escapes("cafe\u{301}\\_noir".into()),
+            "cafe\u{301}_noir"
+        );
+    }
+}
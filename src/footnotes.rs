@@ -0,0 +1,880 @@
+//! Footnotes, i.e. `[^label]` references and `[^label]: ...` definitions.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use regex::Captures;
+use regex::Regex;
+
+use crate::diagnostic::Diagnostic;
+use crate::markdown;
+use crate::markdown::LineKind;
+
+fn label_regex() -> &'static Regex {
+    static LABEL: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\[\^(?<label>[^\]\s]+)\]").unwrap());
+    &LABEL
+}
+
+/// The footnote labels in `line`, in references and definitions, but not in code spans.
+fn labels(line: &str) -> impl Iterator<Item = &str> {
+    let code_spans = markdown::code_spans(line);
+    label_regex()
+        .captures_iter(line)
+        .filter(move |captures| {
+            let start = captures.get(0).unwrap().start();
+            !code_spans.iter().any(|span| span.contains(&start))
+        })
+        .map(|captures| captures.name("label").unwrap().as_str())
+}
+
+/// Footnote labels match case-insensitively.
+fn normalize_label(label: &str) -> String {
+    label.to_lowercase()
+}
+
+/// The label of the footnote definition starting on `line`, if any.
+///
+/// Footnotes are a GFM extension, so there are none in CommonMark.
+pub fn definition_label(line: &str) -> Option<&str> {
+    if !markdown::dialect().has_gfm_extensions() {
+        return None;
+    }
+    let unindented = line.trim_start_matches(' ');
+    if line.len() - unindented.len() > 3 {
+        return None;
+    }
+    let label = unindented.strip_prefix("[^")?;
+    let (label, _) = label.split_once("]:")?;
+    if label.is_empty() || label.contains(|c: char| c == ']' || c.is_whitespace()) {
+        return None;
+    }
+    Some(label)
+}
+
+/// All footnote labels in `text`, with the references in the order they're first used,
+/// followed by any labels that are only defined.
+pub fn labels_by_first_use(text: &str) -> Vec<&str> {
+    if !markdown::dialect().has_gfm_extensions() {
+        return Vec::new();
+    }
+    let mut references = Vec::new();
+    let mut definitions = Vec::new();
+    for line in markdown::lines(text) {
+        if line.kind != LineKind::Text {
+            continue;
+        }
+        let definition = definition_label(line.text);
+        for (i, label) in labels(line.text).enumerate() {
+            if i == 0 && definition == Some(label) {
+                definitions.push(label);
+            } else {
+                references.push(label);
+            }
+        }
+    }
+    references
+        .into_iter()
+        .chain(definitions)
+        .unique_by(|label| normalize_label(label))
+        .collect()
+}
+
+/// Rename every footnote label, in both references and definitions, but not in code spans.
+fn rename_labels(before: &str, renames: &HashMap<String, String>) -> String {
+    let regex = label_regex();
+    let after = markdown::lines(before)
+        .map(|line| {
+            if line.kind != LineKind::Text {
+                return line.text.into();
+            }
+            let code_spans = markdown::code_spans(line.text);
+            regex.replace_all(line.text, |captures: &Captures| {
+                let whole = captures.get(0).unwrap();
+                if code_spans.iter().any(|span| span.contains(&whole.start())) {
+                    return whole.as_str().to_owned();
+                }
+                let label = &captures["label"];
+                let label = renames
+                    .get(&normalize_label(label))
+                    .map(String::as_str)
+                    .unwrap_or(label);
+                format!("[^{label}]")
+            })
+        })
+        .join("\n");
+    after
+}
+
+/// What to renumber footnote labels to.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FootnoteLabels {
+    /// Number all footnotes sequentially.
+    Numbers,
+
+    /// Number only numbered footnotes sequentially,
+    /// and normalize named footnotes to lowercase `kebab-case`.
+    Names,
+}
+
+/// Renumber footnotes sequentially by the order they're first used.
+pub fn renumber_footnotes(before: String, labels: FootnoteLabels) -> String {
+    let mut number = 0;
+    let mut next_number = || {
+        number += 1;
+        number.to_string()
+    };
+    let mut renames = HashMap::new();
+    for label in labels_by_first_use(&before) {
+        let is_number = label.chars().all(|c| c.is_ascii_digit());
+        let mut new_label = match labels {
+            FootnoteLabels::Numbers => next_number(),
+            FootnoteLabels::Names if is_number => next_number(),
+            FootnoteLabels::Names => label
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .join("-")
+                .to_lowercase(),
+        };
+        // Don't merge different footnotes that normalize to the same name.
+        if renames.values().contains(&new_label) {
+            new_label = (2..)
+                .map(|i| format!("{new_label}-{i}"))
+                .find(|new_label| !renames.values().contains(new_label))
+                .unwrap();
+        }
+        renames.insert(normalize_label(label), new_label);
+    }
+    rename_labels(&before, &renames)
+}
+
+/// A footnote definition, including any continuation lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition<'a> {
+    pub label: &'a str,
+    pub lines: Vec<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block<'a> {
+    Line(&'a str),
+    Definition(Definition<'a>),
+}
+
+impl<'a> Block<'a> {
+    fn lines(&self) -> &[&'a str] {
+        match self {
+            Self::Line(line) => std::slice::from_ref(line),
+            Self::Definition(definition) => &definition.lines,
+        }
+    }
+}
+
+/// Split `text` into footnote definitions and the lines in between.
+///
+/// A definition continues with indented lines (possibly separated by blank lines),
+/// and with lazy continuation lines directly after a non-blank line.
+pub fn blocks(text: &str) -> Vec<Block<'_>> {
+    let lines = markdown::lines(text).collect::<Vec<_>>();
+    let is_indented = |line: &str| line.starts_with("    ") || line.starts_with('\t');
+    let is_blank = |line: &str| line.trim().is_empty();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        let label = match line.kind {
+            LineKind::Text => definition_label(line.text),
+            _ => None,
+        };
+        let Some(label) = label else {
+            blocks.push(Block::Line(line.text));
+            continue;
+        };
+        let mut definition = Definition {
+            label,
+            lines: vec![line.text],
+        };
+        while let Some(next) = lines.get(i) {
+            let continues = if next.kind != LineKind::Text {
+                false
+            } else if is_indented(next.text) {
+                true
+            } else if is_blank(next.text) {
+                // Blank lines only continue a definition if an indented line follows them.
+                lines[i..]
+                    .iter()
+                    .find(|line| !is_blank(line.text))
+                    .is_some_and(|line| line.kind == LineKind::Text && is_indented(line.text))
+            } else {
+                // Lazy continuation.
+                !is_blank(definition.lines.last().unwrap())
+                    && definition_label(next.text).is_none()
+                    && !markdown::is_heading(next.text)
+            };
+            if !continues {
+                break;
+            }
+            definition.lines.push(next.text);
+            i += 1;
+        }
+        blocks.push(Block::Definition(definition));
+    }
+    blocks
+}
+
+fn join_blocks(blocks: &[Block]) -> String {
+    blocks.iter().flat_map(Block::lines).join("\n")
+}
+
+/// Reorder footnote definitions to match the order they're first used,
+/// keeping them where the definitions already are.
+pub fn sort_footnote_definitions(before: String) -> String {
+    let order = labels_by_first_use(&before)
+        .into_iter()
+        .enumerate()
+        .map(|(i, label)| (normalize_label(label), i))
+        .collect::<HashMap<_, _>>();
+    let mut blocks = blocks(&before);
+    let sorted = blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Definition(definition) => Some(definition.clone()),
+            Block::Line(_) => None,
+        })
+        .sorted_by_key(|definition| order[&normalize_label(definition.label)])
+        .collect::<Vec<_>>();
+    let slots = blocks
+        .iter_mut()
+        .filter(|block| matches!(block, Block::Definition(_)));
+    for (slot, definition) in slots.zip(sorted) {
+        *slot = Block::Definition(definition);
+    }
+    join_blocks(&blocks)
+}
+
+/// Remove the footnote definitions matching `remove` from `blocks`,
+/// returning the remaining lines and the removed definitions.
+fn remove_definitions<'a>(
+    blocks: Vec<Block<'a>>,
+    mut remove: impl FnMut(&Definition) -> bool,
+) -> (Vec<&'a str>, Vec<Definition<'a>>) {
+    let mut lines = Vec::new();
+    let mut definitions = Vec::new();
+    let mut removed_definition = false;
+    for block in blocks {
+        match block {
+            Block::Definition(definition) if remove(&definition) => {
+                definitions.push(definition);
+                removed_definition = true;
+            }
+            block => {
+                for &line in block.lines() {
+                    // Don't leave a double blank line where definitions were removed.
+                    let is_blank = line.trim().is_empty();
+                    let last_is_blank = lines
+                        .last()
+                        .is_none_or(|line: &&str| line.trim().is_empty());
+                    if !(removed_definition && is_blank && last_is_blank) {
+                        lines.push(line);
+                    }
+                    removed_definition = false;
+                }
+            }
+        }
+    }
+    (lines, definitions)
+}
+
+/// Move all footnote definitions to the end of the document,
+/// under `heading` if given and it's not already there.
+pub fn move_footnote_definitions_to_end(before: String, heading: Option<&str>) -> String {
+    let (mut lines, definitions) = remove_definitions(blocks(&before), |_| true);
+    if definitions.is_empty() {
+        return before;
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    if let Some(heading) = heading.filter(|heading| !lines.contains(heading)) {
+        if !lines.is_empty() {
+            lines.push("");
+        }
+        lines.push(heading);
+    }
+    // Separate definitions with blank lines if any of them have multiple lines.
+    let separator = match definitions
+        .iter()
+        .any(|definition| definition.lines.len() > 1)
+    {
+        true => "\n\n",
+        false => "\n",
+    };
+    let definitions = definitions
+        .iter()
+        .map(|definition| definition.lines.join("\n"))
+        .join(separator);
+    let mut after = lines.join("\n");
+    if !after.is_empty() {
+        after.push_str("\n\n");
+    }
+    after.push_str(&definitions);
+    if before.ends_with('\n') {
+        after.push('\n');
+    }
+    after
+}
+
+/// How to write footnotes.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FootnoteStyle {
+    /// `[^1]` references with `[^1]: ...` definitions, like in GFM.
+    #[default]
+    Reference,
+
+    /// Pandoc's inline footnotes, like `^[a note]`.
+    Inline,
+}
+
+/// The end of the inline footnote whose text starts at byte `i` of `text`, after its `^[`,
+/// skipping over `skipped` ranges, like code spans, and stopping at `end`.
+fn inline_footnote_end(
+    text: &str,
+    mut i: usize,
+    end: usize,
+    skipped: &[Range<usize>],
+) -> Option<usize> {
+    let mut depth = 1;
+    while i < end {
+        if let Some(range) = skipped.iter().find(|range| range.contains(&i)) {
+            i = range.end;
+            continue;
+        }
+        let c = text[i..].chars().next()?;
+        match c {
+            '\\' => {
+                i += 1 + text[i + 1..].chars().next().map_or(0, char::len_utf8);
+                continue;
+            }
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+        i += c.len_utf8();
+    }
+    None
+}
+
+/// The byte ranges of Pandoc's inline footnotes in `text`, like `^[a note]`, including the `^[` and `]`.
+///
+/// The brackets in a note are balanced, like around a link in it,
+/// and a note can span lines, but not paragraphs.
+fn inline_footnotes(text: &str) -> Vec<Range<usize>> {
+    let mut skipped = markdown::opaque_ranges(text);
+    let mut paragraphs = Vec::new();
+    let mut paragraph = None::<Range<usize>>;
+    let mut start = 0;
+    for line in markdown::lines(text) {
+        let end = start + line.text.len();
+        if line.kind == LineKind::Text && !line.text.trim().is_empty() {
+            paragraph.get_or_insert(start..end).end = end;
+            skipped.extend(
+                markdown::code_spans(line.text)
+                    .into_iter()
+                    .map(|span| start + span.start..start + span.end),
+            );
+        } else {
+            paragraphs.extend(paragraph.take());
+        }
+        start = end + 1;
+    }
+    paragraphs.extend(paragraph);
+
+    let mut notes = Vec::new();
+    for paragraph in paragraphs {
+        let mut i = paragraph.start;
+        while let Some(start) = text[i..paragraph.end].find("^[").map(|start| i + start) {
+            i = start + 1;
+            if let Some(range) = skipped.iter().find(|range| range.contains(&start)) {
+                i = i.max(range.end);
+                continue;
+            }
+            if text[..start].ends_with('\\') {
+                continue;
+            }
+            if let Some(end) = inline_footnote_end(text, start + 2, paragraph.end, &skipped) {
+                notes.push(start..end);
+                i = end;
+            }
+        }
+    }
+    notes
+}
+
+/// Convert Pandoc's inline footnotes to `[^n]` references,
+/// with their definitions added after the last footnote definition, or else at the end.
+///
+/// New labels are the lowest numbers not already used, which `renumber-footnotes` can renumber.
+fn inline_footnotes_to_references(before: String) -> String {
+    let used = labels_by_first_use(&before)
+        .into_iter()
+        .map(normalize_label)
+        .collect::<HashSet<_>>();
+    let mut labels = (1..)
+        .map(|n: usize| n.to_string())
+        .filter(|label| !used.contains(label));
+    let mut text = String::new();
+    let mut definitions = Vec::new();
+    let mut last = 0;
+    for note in inline_footnotes(&before) {
+        let content = before[note.start + 2..note.end - 1]
+            .split('\n')
+            .map(str::trim)
+            .join("\n    ");
+        if content.is_empty() {
+            continue;
+        }
+        let label = labels.next().unwrap();
+        text.push_str(&before[last..note.start]);
+        text.push_str(&format!("[^{label}]"));
+        definitions.push(format!("[^{label}]: {content}"));
+        last = note.end;
+    }
+    if definitions.is_empty() {
+        return before;
+    }
+    text.push_str(&before[last..]);
+
+    let blocks = blocks(&text);
+    // Separate definitions with blank lines if any of them have multiple lines.
+    let is_multiline = blocks.iter().any(|block| match block {
+        Block::Definition(definition) => definition.lines.len() > 1,
+        Block::Line(_) => false,
+    }) || definitions
+        .iter()
+        .any(|definition| definition.contains('\n'));
+    let separator = match is_multiline {
+        true => "\n\n",
+        false => "\n",
+    };
+    let definitions = definitions.join(separator);
+    let last_definition = blocks
+        .iter()
+        .rposition(|block| matches!(block, Block::Definition(_)));
+    let Some(i) = last_definition else {
+        let mut after = text.trim_end_matches('\n').to_owned();
+        after.push_str("\n\n");
+        after.push_str(&definitions);
+        if text.ends_with('\n') {
+            after.push('\n');
+        }
+        return after;
+    };
+    let (head, tail) = blocks.split_at(i + 1);
+    let mut after = join_blocks(head);
+    after.push_str(separator);
+    after.push_str(&definitions);
+    if !tail.is_empty() {
+        after.push('\n');
+        after.push_str(&join_blocks(tail));
+    }
+    after
+}
+
+/// Whether the brackets in `text` are balanced, so it can be put in an inline footnote.
+fn is_balanced(text: &str) -> bool {
+    let mut depth = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => depth += 1,
+            ']' if depth == 0 => return false,
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+/// Convert footnotes to Pandoc's inline footnotes,
+/// if they're used once and are a single paragraph, which is all an inline footnote can be.
+fn references_to_inline_footnotes(before: String) -> String {
+    let regex = label_regex();
+    let mut uses = HashMap::<String, usize>::new();
+    markdown::rewrite_outside_code(&before, |part| {
+        let definition = definition_label(part);
+        for (i, captures) in regex.captures_iter(part).enumerate() {
+            let label = captures.name("label").unwrap().as_str();
+            if !(i == 0 && definition == Some(label)) {
+                *uses.entry(normalize_label(label)).or_default() += 1;
+            }
+        }
+        part.to_owned()
+    });
+    let content = |definition: &Definition| {
+        let (_, first) = definition.lines[0].split_once("]:").unwrap();
+        [first]
+            .into_iter()
+            .chain(definition.lines[1..].iter().copied())
+            .map(str::trim)
+            .join(" ")
+    };
+    let is_inlinable = |definition: &Definition| {
+        let content = content(definition);
+        uses.get(&normalize_label(definition.label)) == Some(&1)
+            && !definition.lines.iter().any(|line| line.trim().is_empty())
+            && !content.is_empty()
+            && is_balanced(&content)
+    };
+    let (lines, removed) = remove_definitions(blocks(&before), is_inlinable);
+    if removed.is_empty() {
+        return before;
+    }
+    let notes = removed
+        .iter()
+        .map(|definition| (normalize_label(definition.label), content(definition)))
+        .collect::<HashMap<_, _>>();
+    markdown::rewrite_outside_code(&lines.join("\n"), |part| {
+        let definition = definition_label(part);
+        let after = regex.replace_all(part, |captures: &Captures| {
+            let label = &captures["label"];
+            match notes.get(&normalize_label(label)) {
+                Some(note) if definition != Some(label) => format!("^[{note}]"),
+                _ => captures[0].to_owned(),
+            }
+        });
+        after.into_owned()
+    })
+}
+
+/// Convert Pandoc's inline footnotes, like `^[a note]`, to `[^1]` references with definitions,
+/// or the reverse, for renderers that only support one or the other.
+///
+/// Footnotes are a GFM extension, so there are none to convert in CommonMark.
+pub fn convert_inline_footnotes(before: String, to: FootnoteStyle) -> String {
+    if !markdown::dialect().has_gfm_extensions() {
+        return before;
+    }
+    match to {
+        FootnoteStyle::Reference => inline_footnotes_to_references(before),
+        FootnoteStyle::Inline => references_to_inline_footnotes(before),
+    }
+}
+
+/// Report footnotes that are used but never defined, or defined but never used.
+///
+/// If `fix`, delete the definitions that are never used instead of reporting them.
+pub fn check_footnotes(before: String, fix: bool, diagnostics: &mut Vec<Diagnostic>) -> String {
+    if !markdown::dialect().has_gfm_extensions() {
+        return before;
+    }
+    let mut references = Vec::new();
+    let mut definitions = HashSet::new();
+    for (i, line) in markdown::lines(&before).enumerate() {
+        if line.kind != LineKind::Text {
+            continue;
+        }
+        let definition = definition_label(line.text);
+        if let Some(label) = definition {
+            definitions.insert(normalize_label(label));
+        }
+        for (j, label) in labels(line.text).enumerate() {
+            if !(j == 0 && definition == Some(label)) {
+                references.push((i + 1, label));
+            }
+        }
+    }
+    let referenced = references
+        .iter()
+        .map(|(_, label)| normalize_label(label))
+        .collect::<HashSet<_>>();
+    for &(line, label) in &references {
+        if !definitions.contains(&normalize_label(label)) {
+            diagnostics.push(Diagnostic::new(
+                line,
+                format!("footnote `[^{label}]` is never defined"),
+            ));
+        }
+    }
+
+    let blocks = blocks(&before);
+    let is_unused =
+        |definition: &Definition| !referenced.contains(&normalize_label(definition.label));
+    if fix {
+        let (lines, removed) = remove_definitions(blocks, is_unused);
+        if removed.is_empty() {
+            return before;
+        }
+        return lines.join("\n");
+    }
+    let mut line = 1;
+    for block in &blocks {
+        if let Block::Definition(definition) = block {
+            if is_unused(definition) {
+                diagnostics.push(Diagnostic::new(
+                    line,
+                    format!("footnote `[^{}]` is never used", definition.label),
+                ));
+            }
+        }
+        line += block.lines().len();
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    before
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::footnotes::blocks;
+    use crate::footnotes::check_footnotes;
+    use crate::footnotes::convert_inline_footnotes;
+    use crate::footnotes::labels_by_first_use;
+    use crate::footnotes::move_footnote_definitions_to_end;
+    use crate::footnotes::renumber_footnotes;
+    use crate::footnotes::sort_footnote_definitions;
+    use crate::footnotes::Block;
+    use crate::footnotes::Definition;
+    use crate::footnotes::FootnoteLabels;
+    use crate::footnotes::FootnoteStyle;
+    use crate::markdown;
+    use crate::markdown::Dialect;
+
+    #[test]
+    fn test_labels_by_first_use() {
+        let text = "a[^5] `[^code]` b[^2] c[^5]
+
+[^2]: two
+[^5]: five
+[^unused]: unused";
+        assert_eq!(labels_by_first_use(text), ["5", "2", "unused"]);
+    }
+
+    #[test]
+    fn test_renumber_footnotes() {
+        let before = "a[^5] b[^M8 citation] c[^M8_Citation] d[^2] e[^5] `code[^z]`
+
+```
+[^2]
+```
+
+[^2]: two
+[^5]: five
+[^M8_Citation]: M8";
+        let after = "a[^1] b[^M8 citation] c[^m8-citation] d[^2] e[^1] `code[^z]`
+
+```
+[^2]
+```
+
+[^2]: two
+[^1]: five
+[^m8-citation]: M8";
+        assert_eq!(
+            renumber_footnotes(before.into(), FootnoteLabels::Names),
+            after
+        );
+        let after = "a[^1] b[^M8 citation] c[^2] d[^3] e[^1] `code[^z]`
+
+```
+[^2]
+```
+
+[^3]: two
+[^1]: five
+[^2]: M8";
+        assert_eq!(
+            renumber_footnotes(before.into(), FootnoteLabels::Numbers),
+            after
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let text = "a[^1]
+
+[^1]: one
+lazy
+
+    more
+
+b
+[^2]: two
+
+c";
+        assert_eq!(
+            blocks(text),
+            [
+                Block::Line("a[^1]"),
+                Block::Line(""),
+                Block::Definition(Definition {
+                    label: "1",
+                    lines: vec!["[^1]: one", "lazy", "", "    more"],
+                }),
+                Block::Line(""),
+                Block::Line("b"),
+                Block::Definition(Definition {
+                    label: "2",
+                    lines: vec!["[^2]: two"],
+                }),
+                Block::Line(""),
+                Block::Line("c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_footnote_definitions() {
+        let before = "a[^b] b[^a]
+
+[^a]: a
+
+    more a
+[^b]: b
+";
+        let after = "a[^b] b[^a]
+
+[^b]: b
+[^a]: a
+
+    more a
+";
+        assert_eq!(sort_footnote_definitions(before.into()), after);
+    }
+
+    #[test]
+    fn test_move_footnote_definitions_to_end() {
+        let before = "a[^1]
+
+[^1]: one
+
+b[^2]
+
+[^2]: two
+    more two
+
+c
+";
+        let after = "a[^1]
+
+b[^2]
+
+c
+
+## Notes
+
+[^1]: one
+
+[^2]: two
+    more two
+";
+        assert_eq!(
+            move_footnote_definitions_to_end(before.into(), Some("## Notes")),
+            after
+        );
+    }
+
+    #[test]
+    fn test_check_footnotes() {
+        let before = "a[^1] b[^2] `[^z]`
+
+[^1]: one
+[^3]: three
+    more three
+
+c
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_footnotes(before.into(), false, &mut diagnostics),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::new(1, "footnote `[^2]` is never defined"),
+                Diagnostic::new(4, "footnote `[^3]` is never used"),
+            ]
+        );
+        let after = "a[^1] b[^2] `[^z]`
+
+[^1]: one
+
+c
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_footnotes(before.into(), true, &mut diagnostics),
+            after
+        );
+        assert_eq!(
+            diagnostics,
+            [Diagnostic::new(1, "footnote `[^2]` is never defined")]
+        );
+        let mut diagnostics = Vec::new();
+        let commonmark = markdown::with_dialect(Dialect::CommonMark, || {
+            check_footnotes(before.into(), true, &mut diagnostics)
+        });
+        assert_eq!(commonmark, before);
+        assert_eq!(diagnostics, []);
+    }
+
+    #[test]
+    fn test_convert_inline_footnotes() {
+        let before = "a^[One [link](b).] `c^[d]`
+e^[Two
+  lines.] f\\^[g]
+
+[^1]: existing
+
+h[^1] i[^1]
+";
+        let after = "a[^2] `c^[d]`
+e[^3] f\\^[g]
+
+[^1]: existing
+
+[^2]: One [link](b).
+
+[^3]: Two
+    lines.
+
+h[^1] i[^1]
+";
+        let to_reference =
+            |text: &str| convert_inline_footnotes(text.into(), FootnoteStyle::Reference);
+        let to_inline = |text: &str| convert_inline_footnotes(text.into(), FootnoteStyle::Inline);
+        assert_eq!(to_reference(before), after);
+        assert_eq!(to_reference("a^[b]\n"), "a[^1]\n\n[^1]: b\n");
+        assert_eq!(to_reference("a^[b\n\nc]\n"), "a^[b\n\nc]\n");
+        let inline = "a^[One [link](b).] `c^[d]`
+e^[Two lines.] f\\^[g]
+
+[^1]: existing
+
+h[^1] i[^1]
+";
+        assert_eq!(to_inline(after), inline);
+        let before = "a[^1] b[^2] c[^2] `[^3]`
+
+[^1]: one [
+[^2]: two
+[^3]: three
+";
+        assert_eq!(to_inline(before), before);
+        let commonmark = markdown::with_dialect(Dialect::CommonMark, || to_reference("a^[b]"));
+        assert_eq!(commonmark, "a^[b]");
+    }
+}
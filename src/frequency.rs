@@ -0,0 +1,195 @@
+//! The most used words and pairs of words per section, to notice overused ones.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use regex::Regex;
+use serde_json::json;
+
+use crate::stats;
+
+/// Common words that are used a lot in any text, so their frequency doesn't matter.
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "been",
+    "before", "but", "by", "can", "could", "did", "do", "does", "each", "for", "from", "had",
+    "has", "have", "he", "her", "his", "how", "i", "if", "in", "into", "is", "it", "it's", "its",
+    "just", "more", "most", "my", "no", "not", "of", "on", "one", "only", "or", "other", "our",
+    "out", "over", "same", "she", "should", "so", "some", "such", "than", "that", "the", "their",
+    "them", "then", "there", "these", "they", "this", "those", "through", "to", "up", "us", "very",
+    "was", "we", "were", "what", "when", "where", "which", "while", "who", "will", "with", "would",
+    "you", "your",
+];
+
+/// A word or pair of words and how many times it's used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frequency {
+    pub term: String,
+    pub count: usize,
+}
+
+impl Display for Frequency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.term, self.count)
+    }
+}
+
+/// The most frequent `top` terms used at least twice, most frequent first.
+fn most_frequent(counts: HashMap<String, usize>, top: usize) -> Vec<Frequency> {
+    let mut frequencies = counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(term, count)| Frequency { term, count })
+        .collect::<Vec<_>>();
+    frequencies.sort_by_cached_key(|frequency| (Reverse(frequency.count), frequency.term.clone()));
+    frequencies.truncate(top);
+    frequencies
+}
+
+/// The most frequent words and bigrams in a section, i.e. the text under a heading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionFrequency {
+    /// The 1-based line number of the heading, if the section has one.
+    pub line: Option<usize>,
+
+    pub heading: Option<String>,
+
+    pub words: Vec<Frequency>,
+
+    /// Pairs of adjacent words in a sentence, like `third rail`.
+    pub bigrams: Vec<Frequency>,
+}
+
+impl SectionFrequency {
+    pub fn to_json(&self) -> serde_json::Value {
+        let frequencies = |frequencies: &[Frequency]| {
+            frequencies
+                .iter()
+                .map(|Frequency { term, count }| json!({"term": term, "count": count}))
+                .collect::<Vec<_>>()
+        };
+        json!({
+            "line": self.line,
+            "heading": self.heading,
+            "words": frequencies(&self.words),
+            "bigrams": frequencies(&self.bigrams),
+        })
+    }
+}
+
+impl Display for SectionFrequency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.heading, self.line) {
+            (Some(heading), Some(line)) => write!(f, "{line}: {heading}:")?,
+            _ => write!(f, "(before any heading):")?,
+        }
+        write!(f, "\n  words: {}", self.words.iter().join(", "))?;
+        write!(f, "\n  bigrams: {}", self.bigrams.iter().join(", "))
+    }
+}
+
+/// The most frequent words and bigrams of each section, ignoring [`STOPWORDS`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordFrequency(pub Vec<SectionFrequency>);
+
+impl WordFrequency {
+    /// The `top` most frequent words and bigrams of each section of `text`.
+    pub fn of(text: &str, top: usize) -> Self {
+        static WORD: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"\p{L}[\p{L}\p{N}'’-]*").unwrap());
+        // The heading and its word and bigram counts for each section.
+        let mut sections = Vec::<(Option<(usize, String)>, HashMap<_, _>, HashMap<_, _>)>::new();
+        for block in stats::prose_blocks(text) {
+            if block.heading_level.is_some() || sections.is_empty() {
+                let heading = block
+                    .heading_level
+                    .map(|_| (block.line + 1, block.text.clone()));
+                sections.push((heading, HashMap::new(), HashMap::new()));
+            }
+            if block.heading_level.is_some() {
+                continue;
+            }
+            let (_, words, bigrams) = sections.last_mut().unwrap();
+            for sentence in stats::sentences(&block.text) {
+                let sentence_words = WORD
+                    .find_iter(sentence)
+                    .map(|word| {
+                        word.as_str()
+                            .trim_end_matches(['\'', '’', '-'])
+                            .to_lowercase()
+                    })
+                    .map(|word| (!STOPWORDS.contains(&word.as_str())).then_some(word))
+                    .collect::<Vec<_>>();
+                for word in sentence_words.iter().flatten() {
+                    *words.entry(word.clone()).or_insert(0) += 1;
+                }
+                for (a, b) in sentence_words.iter().tuple_windows() {
+                    if let (Some(a), Some(b)) = (a, b) {
+                        *bigrams.entry(format!("{a} {b}")).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        let sections = sections
+            .into_iter()
+            .filter(|(heading, words, _)| heading.is_some() || !words.is_empty())
+            .map(|(heading, words, bigrams)| {
+                let (line, heading) = heading.unzip();
+                SectionFrequency {
+                    line,
+                    heading,
+                    words: most_frequent(words, top),
+                    bigrams: most_frequent(bigrams, top),
+                }
+            })
+            .collect();
+        Self(sections)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        self.0.iter().map(SectionFrequency::to_json).collect()
+    }
+}
+
+impl Display for WordFrequency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.iter().join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frequency::WordFrequency;
+
+    #[test]
+    fn test_word_frequency() {
+        let text = "Intro about trains.
+
+## Electrification
+
+Electrification of the third rail. Third rail electrification
+is cheap, but electrification with catenary is faster. Trains, trains!
+
+## Empty
+";
+        let frequency = WordFrequency::of(text, 2)
+            .0
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frequency,
+            [
+                "(before any heading):\n  words: \n  bigrams: ",
+                "3: Electrification:
+  words: electrification (3), rail (2)
+  bigrams: third rail (2)",
+                "8: Empty:\n  words: \n  bigrams: ",
+            ]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! YAML front matter, i.e. metadata between `---` lines at the start of a document.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use yaml_rust2::yaml::Hash;
+use yaml_rust2::Yaml;
+use yaml_rust2::YamlEmitter;
+use yaml_rust2::YamlLoader;
+
+use crate::diagnostic::Diagnostic;
+use crate::markdown;
+use crate::markdown::LineKind;
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Normalize a date, like `Jan 5, 2024` or `2024/1/5`, to `2024-01-05`.
+///
+/// Dates like `1/5/2024` are only normalized if it's clear which number is the day.
+fn normalize_date(date: &str) -> Option<String> {
+    static NUMERIC: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<year>\d{4})[-/.](?<month>\d{1,2})[-/.](?<day>\d{1,2})$").unwrap()
+    });
+    static SLASHES: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(?<a>\d{1,2})/(?<b>\d{1,2})/(?<year>\d{4})$").unwrap());
+    static MONTH_FIRST: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<month>[A-Za-z]{3,9})\.? (?<day>\d{1,2})(?:st|nd|rd|th)?,? (?<year>\d{4})$")
+            .unwrap()
+    });
+    static DAY_FIRST: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<day>\d{1,2})(?:st|nd|rd|th)? (?<month>[A-Za-z]{3,9})\.?,? (?<year>\d{4})$")
+            .unwrap()
+    });
+    let month_number = |month: &str| {
+        let month = month.to_lowercase();
+        MONTHS
+            .iter()
+            .position(|prefix| month.starts_with(prefix))
+            .map(|i| (i + 1) as u32)
+    };
+    let (year, month, day) = if let Some(captures) = NUMERIC.captures(date) {
+        (
+            captures["year"].parse().ok()?,
+            captures["month"].parse().ok()?,
+            captures["day"].parse().ok()?,
+        )
+    } else if let Some(captures) = SLASHES.captures(date) {
+        let a = captures["a"].parse::<u32>().ok()?;
+        let b = captures["b"].parse::<u32>().ok()?;
+        let year = captures["year"].parse().ok()?;
+        match (a > 12, b > 12) {
+            (true, false) => (year, b, a),
+            (false, true) => (year, a, b),
+            _ => return None,
+        }
+    } else if let Some(captures) = MONTH_FIRST
+        .captures(date)
+        .or_else(|| DAY_FIRST.captures(date))
+    {
+        (
+            captures["year"].parse::<u32>().ok()?,
+            month_number(&captures["month"])?,
+            captures["day"].parse().ok()?,
+        )
+    } else {
+        return None;
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+/// Normalize dates in string values and sort keys of mappings.
+fn normalize(value: Yaml, key_order: &[String]) -> Yaml {
+    match value {
+        Yaml::String(string) => match normalize_date(&string) {
+            Some(date) => Yaml::String(date),
+            None => Yaml::String(string),
+        },
+        Yaml::Array(array) => Yaml::Array(
+            array
+                .into_iter()
+                .map(|value| normalize(value, key_order))
+                .collect(),
+        ),
+        Yaml::Hash(hash) => {
+            let mut entries = hash.into_iter().collect::<Vec<_>>();
+            let rank = |key: &Yaml| {
+                let key = key.as_str().unwrap_or_default();
+                let position = key_order.iter().position(|ordered| ordered == key);
+                (position.unwrap_or(key_order.len()), key.to_owned())
+            };
+            entries.sort_by_cached_key(|(key, _)| rank(key));
+            let hash = entries
+                .into_iter()
+                .map(|(key, value)| (key, normalize(value, key_order)))
+                .collect::<Hash>();
+            Yaml::Hash(hash)
+        }
+        value => value,
+    }
+}
+
+/// Rewrite YAML front matter deterministically:
+/// with consistent quoting, keys sorted by `key_order` and then alphabetically,
+/// and dates as `YYYY-MM-DD`.
+///
+/// Front matter with comments is left alone, since they would be lost.
+pub fn format_front_matter(
+    before: String,
+    key_order: &[String],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let len = lines
+        .iter()
+        .take_while(|line| line.kind == LineKind::FrontMatter)
+        .count();
+    if len == 0 || lines[0].text.trim_end() != "---" {
+        return before;
+    }
+    let yaml = lines[1..len - 1]
+        .iter()
+        .map(|line| line.text)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if yaml
+        .lines()
+        .any(|line| line.trim_start().starts_with('#') || line.contains(" #"))
+    {
+        diagnostics.push(Diagnostic::note(
+            1,
+            "not formatting front matter with comments",
+        ));
+        return before;
+    }
+    let documents = match YamlLoader::load_from_str(&yaml) {
+        Ok(documents) => documents,
+        Err(e) => {
+            let line = 1 + 1 + e.marker().line();
+            diagnostics.push(Diagnostic::new(
+                line,
+                format!("invalid front matter: {}", e.info()),
+            ));
+            return before;
+        }
+    };
+    let Some(document) = documents.into_iter().next() else {
+        return before;
+    };
+    let mut formatted = String::new();
+    YamlEmitter::new(&mut formatted)
+        .dump(&normalize(document, key_order))
+        .unwrap();
+    let formatted = formatted.strip_prefix("---\n").unwrap_or(&formatted);
+    let rest = lines[len - 1..]
+        .iter()
+        .map(|line| line.text)
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("---\n{formatted}\n{rest}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::front_matter::format_front_matter;
+
+    #[test]
+    fn test_format_front_matter() {
+        let before = r#"---
+tags: ['rail', "transit"]
+date: Jan 5, 2024
+title: 'Through-running: a primer'
+updated: 2024/3/15
+draft: "false"
+author:
+  name: "A"
+  email: a@example.com
+---
+# Text
+"#;
+        let after = r#"---
+title: "Through-running: a primer"
+date: 2024-01-05
+author:
+  email: a@example.com
+  name: A
+draft: "false"
+tags:
+  - rail
+  - transit
+updated: 2024-03-15
+---
+# Text
+"#;
+        let mut diagnostics = Vec::new();
+        let order = ["title".into(), "date".into()];
+        assert_eq!(
+            format_front_matter(before.into(), &order, &mut diagnostics),
+            after
+        );
+        assert_eq!(
+            format_front_matter(after.into(), &order, &mut diagnostics),
+            after
+        );
+        assert_eq!(diagnostics, []);
+
+        let before = "---\ntitle: a # comment\n---\n";
+        assert_eq!(
+            format_front_matter(before.into(), &order, &mut diagnostics),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [Diagnostic::note(
+                1,
+                "not formatting front matter with comments"
+            )]
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! Text as grapheme clusters, what a reader sees as one character,
+//! so that emoji, combining accents, and flags are never split or miscounted.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The number of graphemes in `text`, like `1` for `é` even if it's decomposed.
+pub fn count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+pub fn first(text: &str) -> Option<&str> {
+    text.graphemes(true).next()
+}
+
+pub fn last(text: &str) -> Option<&str> {
+    text.graphemes(true).next_back()
+}
+
+/// Whether `grapheme` is a letter or digit, including any accents on it.
+pub fn is_alphanumeric(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphemes::count;
+    use crate::graphemes::first;
+    use crate::graphemes::is_alphanumeric;
+    use crate::graphemes::last;
+
+    #[test]
+    fn test_graphemes() {
+        let text = "Cafe\u{301} 🇫🇷 👩‍💻";
+        assert_eq!(count(text), 8);
+        assert_eq!(first(text), Some("C"));
+        assert_eq!(last(text), Some("👩‍💻"));
+        assert_eq!(last("Cafe\u{301}"), Some("e\u{301}"));
+        assert_eq!(last(""), None);
+        assert!(is_alphanumeric("e\u{301}"));
+        assert!(!is_alphanumeric("\u{301}"));
+        assert!(!is_alphanumeric("🇫🇷"));
+    }
+}
@@ -0,0 +1,955 @@
+//! Headings, their anchors, and tables of contents.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use regex::Regex;
+
+use crate::diagnostic::Diagnostic;
+use crate::links::BALANCED_DESTINATION;
+use crate::markdown;
+use crate::markdown::LineKind;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading<'a> {
+    /// 0-based index of the heading's line (the text line for setext headings).
+    pub line: usize,
+
+    /// 1 to 6.
+    pub level: usize,
+
+    /// The raw Markdown text of the heading.
+    pub text: &'a str,
+}
+
+/// Parse an ATX heading, i.e. `## Heading ##`, into its level and text.
+pub fn parse_atx_heading(line: &str) -> Option<(usize, &str)> {
+    if !markdown::is_heading(line) {
+        return None;
+    }
+    let unindented = line.trim_start_matches(' ');
+    let level = unindented.len() - unindented.trim_start_matches('#').len();
+    let text = unindented[level..].trim();
+    // An optional closing sequence of `#`s.
+    let without_closing = text.trim_end_matches('#');
+    let text = match without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        true => without_closing.trim_end(),
+        false => text,
+    };
+    Some((level, text))
+}
+
+/// All ATX and setext headings in `text`.
+pub fn headings(text: &str) -> Vec<Heading<'_>> {
+    let lines = markdown::lines(text).collect::<Vec<_>>();
+    static SETEXT_UNDERLINE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^ {0,3}(?:=+|-+)[ \t]*$").unwrap());
+    let mut headings = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.kind != LineKind::Text {
+            continue;
+        }
+        if let Some((level, text)) = parse_atx_heading(line.text) {
+            headings.push(Heading {
+                line: i,
+                level,
+                text,
+            });
+            continue;
+        }
+        let Some(next) = lines.get(i + 1) else {
+            continue;
+        };
+        let is_paragraph_start = i == 0 || {
+            let previous = lines[i - 1];
+            previous.kind != LineKind::Text
+                || previous.text.trim().is_empty()
+                || parse_atx_heading(previous.text).is_some()
+                || markdown::is_thematic_break(previous.text.trim())
+        };
+        let text = line.text.trim();
+        if next.kind == LineKind::Text
+            && SETEXT_UNDERLINE.is_match(next.text)
+            && is_paragraph_start
+            && !text.is_empty()
+            && !markdown::is_thematic_break(text)
+            && markdown::expand_indent(line.text).0 < 4
+        {
+            let level = match next.text.trim().starts_with('=') {
+                true => 1,
+                false => 2,
+            };
+            headings.push(Heading {
+                line: i,
+                level,
+                text,
+            });
+        }
+    }
+    headings
+}
+
+/// Convert setext headings, underlined with `===` or `---`,
+/// and closed ATX headings, like `## Heading ##`, to plain ATX headings, like `## Heading`.
+pub fn convert_to_atx_headings(before: String) -> String {
+    let mut lines = before.split('\n').map(str::to_owned).collect::<Vec<_>>();
+    let mut underlines = HashSet::new();
+    for heading in headings(&before) {
+        let is_setext = parse_atx_heading(&lines[heading.line]).is_none();
+        if is_setext {
+            underlines.insert(heading.line + 1);
+        }
+        lines[heading.line] = format!("{} {}", "#".repeat(heading.level), heading.text);
+    }
+    let after = lines
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !underlines.contains(i))
+        .map(|(_, line)| line)
+        .join("\n");
+    after
+}
+
+/// Put exactly one space between the `#`s of an ATX heading and its text,
+/// including in lines like `#Heading` at the start of a paragraph, which aren't headings without it,
+/// unless they're `hashtags`.
+fn fix_heading_spaces(before: &str, hashtags: bool) -> String {
+    static MARKS: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<indent> {0,3})(?<marks>#{1,6})(?<space>[ \t]*)(?<text>[^#\s].*)?$")
+            .unwrap()
+    });
+    let mut previous_is_blank = true;
+    let after = markdown::lines(before)
+        .map(|line| {
+            let is_paragraph_start = previous_is_blank;
+            previous_is_blank = line.kind != LineKind::Text || line.text.trim().is_empty();
+            if line.kind != LineKind::Text {
+                return Cow::Borrowed(line.text);
+            }
+            let Some(captures) = MARKS.captures(line.text) else {
+                return Cow::Borrowed(line.text);
+            };
+            let Some(text) = captures.name("text") else {
+                return Cow::Borrowed(line.text);
+            };
+            let space = &captures["space"];
+            let is_missing = space.is_empty()
+                && is_paragraph_start
+                && !hashtags
+                && text.as_str().starts_with(char::is_alphabetic);
+            if space == " " || !(is_missing || markdown::is_heading(line.text)) {
+                return Cow::Borrowed(line.text);
+            }
+            Cow::Owned(format!(
+                "{}{} {}",
+                &captures["indent"],
+                &captures["marks"],
+                text.as_str()
+            ))
+        })
+        .join("\n");
+    after
+}
+
+/// Put `blank_before` blank lines before each heading and `blank_after` after it,
+/// except at the start and end of the document,
+/// and exactly one space after its `#`s (see [`fix_heading_spaces`]).
+pub fn normalize_heading_spacing(
+    before: String,
+    blank_before: usize,
+    blank_after: usize,
+    hashtags: bool,
+) -> String {
+    let text = fix_heading_spaces(&before, hashtags);
+    let lines = markdown::lines(&text).collect::<Vec<_>>();
+    let is_blank = |i: usize| lines[i].kind == LineKind::Text && lines[i].text.trim().is_empty();
+    // The lines of each heading, including a setext heading's underline.
+    let heading_lines = headings(&text)
+        .into_iter()
+        .map(|heading| {
+            let is_setext = parse_atx_heading(lines[heading.line].text).is_none();
+            (heading.line, heading.line + 1 + usize::from(is_setext))
+        })
+        .collect::<HashMap<_, _>>();
+    let mut after = Vec::<&str>::new();
+    // The blank lines after the last heading, if nothing else has come since.
+    let mut pending_blank = 0;
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(&end) = heading_lines.get(&i) else {
+            if !is_blank(i) {
+                pending_blank = 0;
+            }
+            after.push(lines[i].text);
+            i += 1;
+            continue;
+        };
+        // Keep `\r\n` line endings.
+        let blank = match lines[i].text.ends_with('\r') {
+            true => "\r",
+            false => "",
+        };
+        let start = after.len()
+            - after
+                .iter()
+                .rev()
+                .take_while(|line| line.trim().is_empty())
+                .count();
+        if start > 0 {
+            after.truncate(start);
+            after.extend(std::iter::repeat_n(blank, blank_before.max(pending_blank)));
+        }
+        after.extend(lines[i..end].iter().map(|line| line.text));
+        i = end;
+        let next = (i..lines.len()).find(|&i| !is_blank(i));
+        if let Some(next) = next {
+            after.extend(std::iter::repeat_n(blank, blank_after));
+            pending_blank = blank_after;
+            i = next;
+        }
+    }
+    after.join("\n")
+}
+
+/// Strip inline Markdown from heading text, leaving the text as it would be rendered.
+pub fn plain_text(text: &str) -> String {
+    static LINK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r"!?\[(?<text>[^\]]*)\](?:\((?:<[^>]*>|{BALANCED_DESTINATION})[^)]*\)|\[[^\]]*\])"
+        ))
+        .unwrap()
+    });
+    static HTML: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+    static EMPHASIS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\*+|\b_+|_+\b|~~").unwrap());
+    let text = LINK.replace_all(text, "$text");
+    let text = HTML.replace_all(&text, "");
+    let text = EMPHASIS.replace_all(&text, "");
+    text.replace('`', "")
+}
+
+/// The anchor GitHub generates for a heading, without the `#`.
+///
+/// Lowercase the text, remove punctuation other than `-` and `_`, and replace spaces with `-`.
+pub fn slugify(text: &str) -> String {
+    plain_text(text)
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// The anchors of `headings`, with duplicates suffixed by `-1`, `-2`, etc. like GitHub does.
+pub fn anchors(headings: &[Heading]) -> Vec<String> {
+    let mut counts = HashMap::<String, usize>::new();
+    headings
+        .iter()
+        .map(|heading| {
+            let slug = slugify(heading.text);
+            let count = counts.entry(slug.clone()).or_default();
+            let anchor = match *count {
+                0 => slug,
+                n => format!("{slug}-{n}"),
+            };
+            *count += 1;
+            anchor
+        })
+        .collect()
+}
+
+/// How similar two anchors are, from 0 to 1, by the words they share.
+fn similarity(a: &str, b: &str) -> f64 {
+    let words = |anchor: &'_ str| {
+        anchor
+            .split('-')
+            .filter(|word| !word.is_empty())
+            .map(str::to_owned)
+            .collect::<HashSet<_>>()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// The anchor that a broken `anchor` most likely meant, if there's a single good match.
+fn resolve_anchor<'a>(anchor: &str, anchors: &'a HashSet<String>) -> Option<&'a str> {
+    if let Some(anchor) = anchors.get(&slugify(&anchor.replace('-', " "))) {
+        return Some(anchor);
+    }
+    let mut scores = anchors
+        .iter()
+        .map(|candidate| (similarity(anchor, candidate), candidate))
+        .filter(|&(score, _)| score >= 0.5)
+        .collect::<Vec<_>>();
+    scores.sort_by(|a, b| b.0.total_cmp(&a.0));
+    match scores.as_slice() {
+        [] => {}
+        [(best, _), (second, _), ..] if best == second => return None,
+        [(_, candidate), ..] => return Some(candidate),
+    }
+    // Renames within words, like `rolling-stocks` to `rolling-stock`, share few whole words,
+    // so fall back to a few character edits, like for mistyped rule names.
+    let max_distance = (anchor.chars().count() / 4).max(2);
+    let mut distances = anchors
+        .iter()
+        .map(|candidate| (strsim::damerau_levenshtein(anchor, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect::<Vec<_>>();
+    distances.sort_by_key(|&(distance, _)| distance);
+    match distances.as_slice() {
+        [] => None,
+        [(best, _), (second, _), ..] if best == second => None,
+        [(_, candidate), ..] => Some(candidate),
+    }
+}
+
+/// Report links to `#anchors` in the same document that don't match any heading,
+/// suggesting the heading they most likely meant (e.g. if it was renamed).
+///
+/// If `fix`, update the links that can be resolved instead of reporting them.
+pub fn check_anchor_links(before: String, fix: bool, diagnostics: &mut Vec<Diagnostic>) -> String {
+    let anchors = anchors(&headings(&before))
+        .into_iter()
+        .collect::<HashSet<_>>();
+    static LINK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?<prefix>\]\(#|^ {0,3}\[[^\]]+\]:[ \t]*<?#)(?<anchor>[^\s)>]*)").unwrap()
+    });
+    let after = markdown::lines(&before)
+        .enumerate()
+        .map(|(i, line)| {
+            if line.kind != LineKind::Text {
+                return line.text.to_owned();
+            }
+            markdown::rewrite_outside_code(line.text, |text| {
+                let rewritten = LINK.replace_all(text, |captures: &regex::Captures| {
+                    let prefix = &captures["prefix"];
+                    let anchor = &captures["anchor"];
+                    if anchors.contains(anchor) {
+                        return captures[0].to_owned();
+                    }
+                    match resolve_anchor(anchor, &anchors) {
+                        Some(resolved) if fix => format!("{prefix}{resolved}"),
+                        Some(resolved) => {
+                            diagnostics.push(Diagnostic::new(
+                                i + 1,
+                                format!("no heading for `#{anchor}`; did you mean `#{resolved}`?"),
+                            ));
+                            captures[0].to_owned()
+                        }
+                        None => {
+                            diagnostics.push(Diagnostic::new(
+                                i + 1,
+                                format!("no heading for `#{anchor}`"),
+                            ));
+                            captures[0].to_owned()
+                        }
+                    }
+                });
+                rewritten.into_owned()
+            })
+        })
+        .join("\n");
+    after
+}
+
+/// Rename `old_headings` in `before` to `renamed`,
+/// updating links to their anchors,
+/// including the links' text if it was the heading's text, like in a table of contents.
+fn rename_headings(before: &str, old_headings: &[Heading], renamed: &[String]) -> String {
+    let mut lines = markdown::lines(before)
+        .map(|line| line.text.to_owned())
+        .collect::<Vec<_>>();
+    for (heading, text) in old_headings.iter().zip(renamed) {
+        let line = &mut lines[heading.line];
+        if let Some(start) = line.find(heading.text) {
+            line.replace_range(start..start + heading.text.len(), text);
+        }
+    }
+    let renamed_text = lines.join("\n");
+
+    let new_headings = headings(&renamed_text);
+    let renames = anchors(old_headings)
+        .into_iter()
+        .zip(anchors(&new_headings))
+        .zip(old_headings.iter().zip(&new_headings))
+        .filter(|((old, new), _)| old != new)
+        .map(|((old, new), (old_heading, new_heading))| {
+            (old, (new, old_heading.text, new_heading.text))
+        })
+        .collect::<HashMap<_, _>>();
+    static LINK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"(?<text>\[[^\]]*\])?(?<prefix>\]?\(#|^ {0,3}\[[^\]]+\]:[ \t]*<?#)(?<anchor>[^\s)>]*)",
+        )
+        .unwrap()
+    });
+    let after = markdown::lines(&renamed_text)
+        .map(|line| {
+            if line.kind != LineKind::Text {
+                return line.text.to_owned();
+            }
+            markdown::rewrite_outside_code(line.text, |text| {
+                let rewritten = LINK.replace_all(text, |captures: &regex::Captures| {
+                    let Some((anchor, old_text, new_text)) = renames.get(&captures["anchor"])
+                    else {
+                        return captures[0].to_owned();
+                    };
+                    let text = match captures.name("text") {
+                        Some(text) if text.as_str() == format!("[{old_text}]") => {
+                            format!("[{new_text}]")
+                        }
+                        text => text
+                            .map(|text| text.as_str())
+                            .unwrap_or_default()
+                            .to_owned(),
+                    };
+                    format!("{text}{}{anchor}", &captures["prefix"])
+                });
+                rewritten.into_owned()
+            })
+        })
+        .join("\n");
+    after
+}
+
+/// A section number at the start of heading text, like `2.3 ` or `1. `.
+///
+/// A number without a `.`, like in `2024 Plans`, isn't a section number.
+fn section_number() -> &'static Regex {
+    static SECTION_NUMBER: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^\d+\.(?:\d+\.?)*[ \t]+").unwrap());
+    &SECTION_NUMBER
+}
+
+/// Number headings with levels in `levels` hierarchically, like `## 2.3 Rolling Stock`,
+/// replacing any existing numbers, or just remove the numbers if `strip`.
+///
+/// Links to the renumbered headings' anchors are updated,
+/// including their text if it was the heading's text, like in a table of contents.
+pub fn number_headings(before: String, levels: (usize, usize), strip: bool) -> String {
+    let section_number = section_number();
+    let (min_level, max_level) = levels;
+    let old_headings = headings(&before);
+    let mut counters = [0; 6];
+    let renamed = old_headings
+        .iter()
+        .map(|heading| {
+            counters[heading.level - 1] += 1;
+            counters[heading.level..].fill(0);
+            if !(min_level..=max_level).contains(&heading.level) {
+                return heading.text.to_owned();
+            }
+            let text = section_number.replace(heading.text, "");
+            if strip {
+                return text.into_owned();
+            }
+            let number = match &counters[min_level - 1..heading.level] {
+                [number] => format!("{number}."),
+                numbers => numbers.iter().join("."),
+            };
+            format!("{number} {text}")
+        })
+        .collect::<Vec<_>>();
+
+    rename_headings(&before, &old_headings, &renamed)
+}
+
+/// Report headings with the same anchor as an earlier heading,
+/// since GitHub suffixes their anchors with `-1`, `-2`, etc.,
+/// which silently change when headings are added or removed.
+///
+/// The suggested new text adds the parent heading, like `Setup (Linux)`, or else a number.
+/// If `fix`, rename the headings instead, updating links to them.
+pub fn check_duplicate_headings(
+    before: String,
+    fix: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let old_headings = headings(&before);
+    let mut slugs = old_headings
+        .iter()
+        .map(|heading| slugify(heading.text))
+        .collect::<HashSet<_>>();
+    let mut first_lines = HashMap::<String, usize>::new();
+    let mut renamed = Vec::new();
+    for (i, heading) in old_headings.iter().enumerate() {
+        let slug = slugify(heading.text);
+        let Some(&first_line) = first_lines.get(&slug) else {
+            first_lines.insert(slug, heading.line);
+            renamed.push(heading.text.to_owned());
+            continue;
+        };
+        let parent = old_headings[..i]
+            .iter()
+            .rev()
+            .find(|parent| parent.level < heading.level)
+            .map(|parent| format!("{} ({})", heading.text, plain_text(parent.text)));
+        let numbered = (2..).map(|n| format!("{} {n}", heading.text));
+        let suggestion = parent
+            .into_iter()
+            .chain(numbered)
+            .find(|text| !slugs.contains(&slugify(text)))
+            .unwrap();
+        slugs.insert(slugify(&suggestion));
+        let (line, text) = (heading.line + 1, heading.text);
+        let diagnostic = match fix {
+            true => Diagnostic::note(line, format!("renamed heading `{text}` to `{suggestion}`")),
+            false => Diagnostic::new(
+                line,
+                format!(
+                    "heading `{text}` has the same anchor as line {}; rename it, e.g. to `{suggestion}`",
+                    first_line + 1
+                ),
+            ),
+        };
+        diagnostics.push(diagnostic);
+        renamed.push(match fix {
+            true => suggestion,
+            false => heading.text.to_owned(),
+        });
+    }
+    if !fix {
+        return before;
+    }
+    rename_headings(&before, &old_headings, &renamed)
+}
+
+pub const TOC_START: &str = "<!-- toc -->";
+pub const TOC_END: &str = "<!-- /toc -->";
+
+/// Insert or update a table of contents between `<!-- toc -->` and `<!-- /toc -->` markers,
+/// adding the end marker if there's only a start marker.
+///
+/// Only headings with levels in `levels` and not in `excluded` are included.
+pub fn update_toc(before: String, levels: (usize, usize), excluded: &[String]) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let is_marker =
+        |i: usize, marker: &str| lines[i].kind == LineKind::Text && lines[i].text.trim() == marker;
+    let Some(start) = (0..lines.len()).find(|&i| is_marker(i, TOC_START)) else {
+        return before;
+    };
+    let end = (start + 1..lines.len()).find(|&i| is_marker(i, TOC_END));
+
+    let headings = headings(&before);
+    let anchors = anchors(&headings);
+    let (min_level, max_level) = levels;
+    let included = headings
+        .iter()
+        .zip(&anchors)
+        .filter(|(heading, _)| (min_level..=max_level).contains(&heading.level))
+        .filter(|(heading, _)| {
+            !excluded
+                .iter()
+                .any(|excluded| plain_text(heading.text) == *excluded)
+        })
+        .collect::<Vec<_>>();
+    let top_level = included
+        .iter()
+        .map(|(heading, _)| heading.level)
+        .min()
+        .unwrap_or(1);
+    static LINK_TEXT: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r"!?\[(?<text>[^\]]*)\](?:\((?:<[^>]*>|{BALANCED_DESTINATION})[^)]*\)|\[[^\]]*\])"
+        ))
+        .unwrap()
+    });
+    let toc = included.iter().map(|(heading, anchor)| {
+        let indent = "  ".repeat(heading.level - top_level);
+        let text = LINK_TEXT.replace_all(heading.text, "$text");
+        format!("{indent}- [{text}](#{anchor})")
+    });
+
+    let before_toc = lines[..=start].iter().map(|line| line.text.to_owned());
+    let after_toc = match end {
+        Some(end) => &lines[end..],
+        None => &lines[start + 1..],
+    };
+    let after_toc = after_toc.iter().map(|line| line.text.to_owned());
+    let end_marker = match end {
+        Some(_) => None,
+        None => Some(TOC_END.to_owned()),
+    };
+    let after = before_toc
+        .chain([String::new()])
+        .chain(toc)
+        .chain([String::new()])
+        .chain(end_marker)
+        .chain(after_toc)
+        .join("\n");
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::headings::anchors;
+    use crate::headings::check_anchor_links;
+    use crate::headings::check_duplicate_headings;
+    use crate::headings::convert_to_atx_headings;
+    use crate::headings::headings;
+    use crate::headings::normalize_heading_spacing;
+    use crate::headings::number_headings;
+    use crate::headings::slugify;
+    use crate::headings::update_toc;
+    use crate::headings::Heading;
+
+    #[test]
+    fn test_headings() {
+        let text = "# A #
+Setext
+------
+
+not a heading
+
+---
+
+```
+# code
+```";
+        assert_eq!(
+            headings(text),
+            [
+                Heading {
+                    line: 0,
+                    level: 1,
+                    text: "A"
+                },
+                Heading {
+                    line: 1,
+                    level: 2,
+                    text: "Setext"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_convert_to_atx_headings() {
+        let before = "Title
+=====
+
+## Closed ##
+
+Setext *two*
+---
+
+#hashtag
+- item
+---";
+        let after = "# Title
+
+## Closed
+
+## Setext *two*
+
+#hashtag
+- item
+---";
+        assert_eq!(convert_to_atx_headings(before.into()), after);
+    }
+
+    #[test]
+    fn test_normalize_heading_spacing() {
+        let before = "#Title
+Text
+##   Setup
+Setext
+---
+
+
+### Steps ###
+```
+# code
+```
+";
+        let after = "# Title
+
+Text
+
+## Setup
+
+Setext
+---
+
+### Steps ###
+
+```
+# code
+```
+";
+        assert_eq!(normalize_heading_spacing(before.into(), 1, 1, false), after);
+        assert_eq!(
+            normalize_heading_spacing(before.into(), 1, 1, true)
+                .lines()
+                .next(),
+            Some("#Title")
+        );
+        let before = "Text\r\n# A\r\n## B\r\nText\r\n";
+        let after = "Text\r\n\r\n\r\n# A\r\n\r\n\r\n## B\r\n\r\nText\r\n";
+        assert_eq!(normalize_heading_spacing(before.into(), 2, 1, false), after);
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(
+            slugify("Part 2: The Future is *Electric*!"),
+            "part-2-the-future-is-electric"
+        );
+        assert_eq!(slugify("[`snake_case`](url) & Co."), "snake_case--co");
+    }
+
+    #[test]
+    fn test_anchors() {
+        let text = "# A\n# A\n# A";
+        assert_eq!(anchors(&headings(text)), ["a", "a-1", "a-2"]);
+    }
+
+    #[test]
+    fn test_update_toc() {
+        let before = "# Title
+
+<!-- toc -->
+
+## Introduction
+
+### [Background](url)
+
+## Introduction
+
+#### Too Deep
+
+## Skip Me
+";
+        let after = "# Title
+
+<!-- toc -->
+
+- [Introduction](#introduction)
+  - [Background](#background)
+- [Introduction](#introduction-1)
+
+<!-- /toc -->
+
+## Introduction
+
+### [Background](url)
+
+## Introduction
+
+#### Too Deep
+
+## Skip Me
+";
+        assert_eq!(
+            update_toc(before.into(), (2, 3), &["Skip Me".into()]),
+            after
+        );
+        assert_eq!(update_toc(after.into(), (2, 3), &["Skip Me".into()]), after);
+    }
+
+    #[test]
+    fn test_check_anchor_links() {
+        let before = "# Getting Started
+
+## Installing on Linux
+
+See [installing](#install-on-linux), [`getting started`](#Getting-Started),
+[start](#getting-started), and [nowhere](#nowhere).
+`[code](#code)`
+
+[linux]: #installing-linux
+
+## Rolling Stock
+
+See [trains](#rolling-stocks).
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_anchor_links(before.into(), false, &mut diagnostics),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::new(
+                    5,
+                    "no heading for `#install-on-linux`; did you mean `#installing-on-linux`?"
+                ),
+                Diagnostic::new(
+                    5,
+                    "no heading for `#Getting-Started`; did you mean `#getting-started`?"
+                ),
+                Diagnostic::new(6, "no heading for `#nowhere`"),
+                Diagnostic::new(
+                    9,
+                    "no heading for `#installing-linux`; did you mean `#installing-on-linux`?"
+                ),
+                Diagnostic::new(
+                    13,
+                    "no heading for `#rolling-stocks`; did you mean `#rolling-stock`?"
+                ),
+            ]
+        );
+        let after = "# Getting Started
+
+## Installing on Linux
+
+See [installing](#installing-on-linux), [`getting started`](#getting-started),
+[start](#getting-started), and [nowhere](#nowhere).
+`[code](#code)`
+
+[linux]: #installing-on-linux
+
+## Rolling Stock
+
+See [trains](#rolling-stock).
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_anchor_links(before.into(), true, &mut diagnostics),
+            after
+        );
+        assert_eq!(
+            diagnostics,
+            [Diagnostic::new(6, "no heading for `#nowhere`")]
+        );
+    }
+
+    #[test]
+    fn test_number_headings() {
+        let before = "# Title
+
+- [Introduction](#introduction)
+  - [Background](#background)
+- [9.9 Rolling Stock](#99-rolling-stock)
+
+## Introduction
+
+### Background
+
+## 9.9 Rolling Stock
+
+### 2024 Orders
+
+See [the orders](#2024-orders) and [stock](#99-rolling-stock).
+
+```
+## Not a heading
+```";
+        let after = "# Title
+
+- [1. Introduction](#1-introduction)
+  - [1.1 Background](#11-background)
+- [2. Rolling Stock](#2-rolling-stock)
+
+## 1. Introduction
+
+### 1.1 Background
+
+## 2. Rolling Stock
+
+### 2.1 2024 Orders
+
+See [the orders](#21-2024-orders) and [stock](#2-rolling-stock).
+
+```
+## Not a heading
+```";
+        assert_eq!(number_headings(before.into(), (2, 3), false), after);
+        assert_eq!(number_headings(after.into(), (2, 3), false), after);
+
+        let stripped = "# Title
+
+- [Introduction](#introduction)
+  - [Background](#background)
+- [Rolling Stock](#rolling-stock)
+
+## Introduction
+
+### Background
+
+## Rolling Stock
+
+### 2024 Orders
+
+See [the orders](#2024-orders) and [stock](#rolling-stock).
+
+```
+## Not a heading
+```";
+        assert_eq!(number_headings(after.into(), (2, 3), true), stripped);
+    }
+
+    #[test]
+    fn test_check_duplicate_headings() {
+        let before = "# Linux
+
+## Setup
+
+# macOS
+
+## Setup
+
+See [Linux](#setup) and [macOS](#setup-1).
+
+# Setup (macOS)
+
+# Windows
+
+## Setup
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_duplicate_headings(before.into(), false, &mut diagnostics),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::new(
+                    7,
+                    "heading `Setup` has the same anchor as line 3; rename it, e.g. to `Setup 2`"
+                ),
+                Diagnostic::new(
+                    15,
+                    "heading `Setup` has the same anchor as line 3; rename it, e.g. to `Setup (Windows)`"
+                ),
+            ]
+        );
+        let after = "# Linux
+
+## Setup
+
+# macOS
+
+## Setup 2
+
+See [Linux](#setup) and [macOS](#setup-2).
+
+# Setup (macOS)
+
+# Windows
+
+## Setup (Windows)
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_duplicate_headings(before.into(), true, &mut diagnostics),
+            after
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::note(7, "renamed heading `Setup` to `Setup 2`"),
+                Diagnostic::note(15, "renamed heading `Setup` to `Setup (Windows)`"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,337 @@
+//! Inline HTML: converting it to Markdown where it's lossless, and stripping comments.
+
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use regex::Captures;
+use regex::Regex;
+
+use crate::markdown;
+use crate::markdown::LineKind;
+
+/// Whether `content` can be wrapped in `*` emphasis delimiters without changing its meaning.
+///
+/// Already emphasized content, like `*a*`, is allowed for nested tags.
+fn can_emphasize(content: &str) -> bool {
+    let leading = content.len() - content.trim_start_matches('*').len();
+    let trailing = content.len() - content.trim_end_matches('*').len();
+    let inner = content.trim_matches('*');
+    leading == trailing
+        && !inner.is_empty()
+        && inner.trim() == inner
+        && !inner.contains(['*', '\\'])
+}
+
+/// Rewrite `<b>`, `<strong>`, `<i>`, `<em>`, `<code>`, and trailing `<br>` tags,
+/// and in GFM, `<s>`, `<del>`, and `<strike>` tags,
+/// to their Markdown equivalents, when that doesn't change the meaning.
+pub fn convert_html_formatting(before: String) -> String {
+    static EMPHASIS: LazyLock<[(Regex, &str); 3]> = LazyLock::new(|| {
+        [
+            (
+                Regex::new(r"(?i)<(?:b|strong)>([^<>\n]*)</(?:b|strong)>").unwrap(),
+                "**",
+            ),
+            (
+                Regex::new(r"(?i)<(?:i|em)>([^<>\n]*)</(?:i|em)>").unwrap(),
+                "*",
+            ),
+            (
+                Regex::new(r"(?i)<(?:s|del|strike)>([^<>\n]*)</(?:s|del|strike)>").unwrap(),
+                "~~",
+            ),
+        ]
+    });
+    // Strikethrough is a GFM extension.
+    let emphasis = match markdown::dialect().has_gfm_extensions() {
+        true => &EMPHASIS[..],
+        false => &EMPHASIS[..2],
+    };
+    static CODE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?i)<code>([^<>\n]*)</code>").unwrap());
+    static LINE_BREAK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<br\s*/?>$").unwrap());
+    let after = markdown::rewrite_outside_code(&before, |text| {
+        let mut text = text.to_owned();
+        // Repeat to handle nested tags, like `<b><i>a</i></b>`.
+        loop {
+            let mut changed = false;
+            for (regex, delimiter) in emphasis {
+                let rewritten = regex.replace_all(&text, |captures: &Captures| {
+                    let content = &captures[1];
+                    if can_emphasize(content) {
+                        changed = true;
+                        format!("{delimiter}{content}{delimiter}")
+                    } else {
+                        captures[0].to_owned()
+                    }
+                });
+                text = rewritten.into_owned();
+            }
+            if !changed {
+                break;
+            }
+        }
+        let text = CODE.replace_all(&text, |captures: &Captures| {
+            let content = &captures[1];
+            if content.is_empty() || content.contains(['`', '&']) {
+                captures[0].to_owned()
+            } else {
+                format!("`{content}`")
+            }
+        });
+        text.into_owned()
+    });
+    // A `<br>` at the end of a line is a hard line break,
+    // but a `\` only is if the paragraph continues on the next line.
+    let lines = markdown::lines(&after).collect::<Vec<_>>();
+    let after = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let continues = lines
+                .get(i + 1)
+                .is_some_and(|next| next.kind == LineKind::Text && !next.text.trim().is_empty());
+            match line.kind == LineKind::Text && continues {
+                true => LINE_BREAK.replace(line.text, "\\"),
+                false => Cow::Borrowed(line.text),
+            }
+        })
+        .join("\n");
+    after
+}
+
+/// Rewrite `<img>` tags to `![alt](src "title")`
+/// if they don't have any other attributes (like `width`, `style`, or `class`).
+///
+/// If `keep_dropped_attributes`, other attributes are instead dropped
+/// and kept in an HTML comment after the image.
+pub fn convert_html_images(before: String, keep_dropped_attributes: bool) -> String {
+    static IMG: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?i)<img\s+(?<attributes>[^>]*?)\s*/?>").unwrap());
+    static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"(?<name>[A-Za-z_:][-\w:.]*)(?:\s*=\s*(?:"(?<double>[^"]*)"|'(?<single>[^']*)'|(?<unquoted>[^\s"'=<>`]+)))?"#).unwrap()
+    });
+    let after = markdown::rewrite_outside_code(&before, |text| {
+        IMG.replace_all(text, |captures: &Captures| {
+            let attributes = &captures["attributes"];
+            let mut src = None;
+            let mut alt = "";
+            let mut title = None;
+            let mut dropped = Vec::new();
+            let mut is_parsed = true;
+            let mut last = 0;
+            for captures in ATTRIBUTE.captures_iter(attributes) {
+                let whole = captures.get(0).unwrap();
+                is_parsed &= attributes[last..whole.start()].trim().is_empty();
+                last = whole.end();
+                let value = ["double", "single", "unquoted"]
+                    .into_iter()
+                    .find_map(|name| captures.name(name))
+                    .map(|value| value.as_str());
+                match (captures["name"].to_lowercase().as_str(), value) {
+                    ("src", Some(value)) => src = Some(value),
+                    ("alt", value) => alt = value.unwrap_or_default(),
+                    ("title", Some(value)) => title = Some(value),
+                    _ => dropped.push(whole.as_str()),
+                }
+            }
+            // Leave anything we couldn't fully parse alone.
+            is_parsed &= attributes[last..].trim().is_empty();
+            let Some(src) = src.filter(|_| is_parsed) else {
+                return captures[0].to_owned();
+            };
+            if !dropped.is_empty() && !keep_dropped_attributes {
+                return captures[0].to_owned();
+            }
+            let alt = alt.replace('[', r"\[").replace(']', r"\]");
+            let src = match src.contains([' ', '(', ')']) {
+                true => format!("<{src}>"),
+                false => src.to_owned(),
+            };
+            let title = match title {
+                Some(title) => format!(" \"{}\"", title.replace('"', r#"\""#)),
+                None => String::new(),
+            };
+            let comment = match dropped.is_empty() {
+                true => String::new(),
+                false => format!("<!-- {} -->", dropped.join(" ")),
+            };
+            format!("![{alt}]({src}{title}){comment}")
+        })
+        .into_owned()
+    });
+    after
+}
+
+/// Comments that mean something to tools, and so are kept,
+/// matched against the trimmed text of the comment.
+pub const PROTECTED_COMMENTS: &[&str] = &[
+    // Table of contents markers.
+    r"^/?toc$",
+    // Directives for us and other Markdown tools.
+    r"^style-markdown\b",
+    r"^markdownlint-",
+    r"^prettier-ignore",
+    // Excerpt separators for blogs.
+    r"^more$",
+    // License headers.
+    r"(?i)\b(?:copyright|license|spdx-license-identifier)\b",
+];
+
+/// The byte ranges of `text` that are code, i.e. code blocks and code spans.
+fn code_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for line in markdown::lines(text) {
+        match line.kind {
+            LineKind::Text => ranges.extend(
+                markdown::code_spans(line.text)
+                    .into_iter()
+                    .map(|span| start + span.start..start + span.end),
+            ),
+            _ => ranges.push(start..start + line.text.len()),
+        }
+        start += line.text.len() + 1;
+    }
+    ranges
+}
+
+/// Remove HTML comments, like leftover review notes, unless they match [`PROTECTED_COMMENTS`] or `keep`.
+///
+/// Lines left empty are removed, along with a blank line if that would leave two in a row.
+pub fn strip_html_comments(before: String, keep: &[Regex]) -> String {
+    static COMMENT: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?s)<!--(?<text>.*?)-->").unwrap());
+    static PROTECTED: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+        PROTECTED_COMMENTS
+            .iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .collect()
+    });
+    let protected = PROTECTED.iter().chain(keep).collect::<Vec<_>>();
+    let code = code_ranges(&before);
+    let is_blank_line = |line: &str| line.trim().is_empty();
+    let mut after = String::new();
+    let mut last = 0;
+    for captures in COMMENT.captures_iter(&before) {
+        let whole = captures.get(0).unwrap();
+        let text = captures["text"].trim();
+        if whole.start() < last
+            || code.iter().any(|range| range.contains(&whole.start()))
+            || protected.iter().any(|regex| regex.is_match(text))
+        {
+            continue;
+        }
+        let line_start = before[..whole.start()].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = before[whole.end()..]
+            .find('\n')
+            .map_or(before.len(), |i| whole.end() + i);
+        let (mut start, mut end) = (whole.start(), whole.end());
+        if is_blank_line(&before[line_start..start]) && is_blank_line(&before[end..line_end]) {
+            // Remove the whole line, and a following blank line if the previous line is also blank.
+            start = line_start;
+            end = (line_end + 1).min(before.len());
+            let previous = before[..line_start.saturating_sub(1)].rsplit('\n').next();
+            let next = before[end..].split('\n').next();
+            if previous.is_none_or(is_blank_line)
+                && next.is_some_and(is_blank_line)
+                && end < before.len()
+            {
+                end = before[end..]
+                    .find('\n')
+                    .map_or(before.len(), |i| end + i + 1);
+            }
+        } else if before[..start].ends_with([' ', '\t'])
+            && before[end..line_end].starts_with([' ', '\t'])
+        {
+            // Don't leave a double space.
+            start = before[..start].trim_end_matches([' ', '\t']).len();
+        }
+        after.push_str(&before[last..start.max(last)]);
+        last = end;
+    }
+    after.push_str(&before[last..]);
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html::convert_html_formatting;
+    use crate::html::convert_html_images;
+    use crate::html::strip_html_comments;
+    use crate::markdown;
+    use crate::markdown::Dialect;
+
+    #[test]
+    fn test_convert_html_formatting() {
+        let before = "<b>a</b> <STRONG>b</STRONG> <i>c</i> <em>d</em> <b><i>e</i></b>
+<b> f</b> <i>a*b</i> <b class=\"x\">g</b> <code>h</code> <code>&lt;</code>
+i<br>
+j<br/> k
+`<b>l</b>`<br>
+<i>&amp;</i><br>";
+        let after = "**a** **b** *c* *d* ***e***
+<b> f</b> <i>a*b</i> <b class=\"x\">g</b> `h` <code>&lt;</code>
+i\\
+j<br/> k
+`<b>l</b>`\\
+*&amp;*<br>";
+        assert_eq!(convert_html_formatting(before.into()), after);
+        let before = "<del>a</del> <s>b</s> <strike> c</strike>";
+        let after = "~~a~~ ~~b~~ <strike> c</strike>";
+        assert_eq!(convert_html_formatting(before.into()), after);
+        let commonmark = markdown::with_dialect(Dialect::CommonMark, || {
+            convert_html_formatting(before.into())
+        });
+        assert_eq!(commonmark, before);
+    }
+
+    #[test]
+    fn test_convert_html_images() {
+        let before = r#"<img src="a.png" alt="A [b]"> <IMG SRC='c d.png' title="e"/>
+<img src="f.png" width=100 alt=""> <img alt="no src"> `<img src="g.png">`"#;
+        let after = r#"![A \[b\]](a.png) ![](<c d.png> "e")
+<img src="f.png" width=100 alt=""> <img alt="no src"> `<img src="g.png">`"#;
+        assert_eq!(convert_html_images(before.into(), false), after);
+        let after = r#"![A \[b\]](a.png) ![](<c d.png> "e")
+![](f.png)<!-- width=100 --> <img alt="no src"> `<img src="g.png">`"#;
+        assert_eq!(convert_html_images(before.into(), true), after);
+    }
+
+    #[test]
+    fn test_strip_html_comments() {
+        let before = "<!-- Copyright 2024 Example -->
+# Title
+
+<!-- TODO: check this -->
+
+<!-- toc -->
+Text <!-- reviewer note --> more.<!--a-->
+<!--
+multi-line
+-->
+`<!-- code -->`
+<!-- style-markdown-disable -->
+
+```
+<!-- code -->
+```
+<!-- keep me -->";
+        let after = "<!-- Copyright 2024 Example -->
+# Title
+
+<!-- toc -->
+Text more.
+`<!-- code -->`
+<!-- style-markdown-disable -->
+
+```
+<!-- code -->
+```
+<!-- keep me -->";
+        let keep = [regex::Regex::new("^keep").unwrap()];
+        assert_eq!(strip_html_comments(before.into(), &keep), after);
+    }
+}
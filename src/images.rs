@@ -0,0 +1,627 @@
+//! Images, i.e. `![alt](src)` and `![alt][label]`.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use color_eyre::eyre;
+use color_eyre::eyre::Context;
+use itertools::Itertools;
+use regex::Captures;
+use regex::Regex;
+use serde_json::json;
+
+use crate::diagnostic::Diagnostic;
+use crate::links::BALANCED_DESTINATION;
+use crate::links::TITLE;
+use crate::markdown;
+use crate::markdown::LineKind;
+use crate::references;
+
+/// Alt text that doesn't describe anything.
+const PLACEHOLDER_ALT_TEXTS: &[&str] = &[
+    "image",
+    "img",
+    "alt",
+    "alt text",
+    "picture",
+    "photo",
+    "screenshot",
+    "figure",
+    "todo",
+    "untitled",
+    "placeholder",
+];
+
+/// Parse a JSON file mapping image sources to their alt text.
+pub fn parse_alt_text_mapping(path: &str) -> eyre::Result<BTreeMap<String, String>> {
+    let json = fs_err::read_to_string(path)?;
+    serde_json::from_str(&json)
+        .wrap_err_with(|| format!("`{path}` is not a JSON object of strings"))
+}
+
+/// The file name of `src` without its extension.
+fn file_stem(src: &str) -> Option<&str> {
+    let path = src.split(['?', '#']).next().unwrap();
+    Path::new(path).file_stem()?.to_str()
+}
+
+/// Whether `alt` is empty or a placeholder, including just the image's file name.
+fn is_placeholder(alt: &str, src: Option<&str>) -> bool {
+    let alt = alt.trim();
+    alt.is_empty()
+        || PLACEHOLDER_ALT_TEXTS.contains(&alt.to_lowercase().as_str())
+        || src.is_some_and(|src| src.rsplit('/').next() == Some(alt) || file_stem(src) == Some(alt))
+}
+
+/// Alt text from a descriptive file name like `bus-map_2019.png` (`Bus map 2019`),
+/// but not from ones like `IMG_1234.jpg`.
+fn alt_text_from_file_name(src: &str) -> Option<String> {
+    static GENERATED: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)^(?:img|dsc|dscn|pxl|screenshot|image)?[-_ ]?\d+").unwrap()
+    });
+    let stem = file_stem(src)?;
+    if GENERATED.is_match(stem) || !stem.contains(char::is_alphabetic) {
+        return None;
+    }
+    let words = stem
+        .split(['-', '_', ' ', '.'])
+        .filter(|word| !word.is_empty())
+        .join(" ");
+    let mut chars = words.chars();
+    let first = chars.next()?;
+    Some(first.to_uppercase().chain(chars).collect())
+}
+
+/// Report images with empty or placeholder alt text.
+///
+/// If `fix`, replace it with the alt text for the image's source in `mapping`,
+/// or if `from_file_name`, with alt text from a descriptive file name.
+pub fn check_alt_text(
+    before: String,
+    fix: bool,
+    mapping: Option<&BTreeMap<String, String>>,
+    from_file_name: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    static IMAGE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(r"!\[(?<alt>[^\]]*)\](?:\((?<src><[^>]*>|{BALANCED_DESTINATION})(?<rest>[^)]*)\)|\[(?<label>[^\]]*)\])")).unwrap()
+    });
+    let definitions = references::definitions(&before)
+        .into_iter()
+        .map(|(_, definition)| definition)
+        .map(|definition| {
+            (
+                references::normalize_label(definition.label),
+                definition.url(),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let after = markdown::lines(&before)
+        .enumerate()
+        .map(|(i, line)| {
+            if line.kind != LineKind::Text {
+                return line.text.to_owned();
+            }
+            markdown::rewrite_outside_code(line.text, |text| {
+                let rewritten = IMAGE.replace_all(text, |captures: &Captures| {
+                    let whole = captures.get(0).unwrap();
+                    let alt = &captures["alt"];
+                    let src = match (captures.name("src"), captures.name("label")) {
+                        (Some(src), _) => Some(src.as_str().trim_matches(['<', '>'])),
+                        (None, Some(label)) => definitions
+                            .get(&references::normalize_label(label.as_str()))
+                            .copied(),
+                        (None, None) => None,
+                    };
+                    if !is_placeholder(alt, src) {
+                        return whole.as_str().to_owned();
+                    }
+                    let new_alt = src.filter(|_| fix).and_then(|src| {
+                        mapping
+                            .and_then(|mapping| mapping.get(src).cloned())
+                            .or_else(|| alt_text_from_file_name(src).filter(|_| from_file_name))
+                    });
+                    let Some(new_alt) = new_alt else {
+                        let message = match alt.trim().is_empty() {
+                            true => format!("image `{}` has no alt text", src.unwrap_or_default()),
+                            false => format!(
+                                "image `{}` has placeholder alt text `{alt}`",
+                                src.unwrap_or_default()
+                            ),
+                        };
+                        diagnostics.push(Diagnostic::new(i + 1, message));
+                        return whole.as_str().to_owned();
+                    };
+                    let new_alt = new_alt.replace('[', r"\[").replace(']', r"\]");
+                    let alt_range = captures.name("alt").unwrap().range();
+                    let start = whole.start();
+                    format!(
+                        "{}{new_alt}{}",
+                        &text[start..alt_range.start],
+                        &text[alt_range.end..whole.end()]
+                    )
+                });
+                rewritten.into_owned()
+            })
+        })
+        .join("\n");
+    after
+}
+
+/// How to write images.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageStyle {
+    /// `![alt](src "title")`
+    #[default]
+    Inline,
+
+    /// `![alt][label]`, with a `[label]: src "title"` definition.
+    Reference,
+}
+
+/// Matches inline images, capturing the `alt`, `destination`, and `title`,
+/// and reference images, capturing the `alt` and `label` (if it's a full or collapsed reference).
+fn image_regex() -> &'static Regex {
+    static IMAGE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(r"!\[(?<alt>[^\]]*)\](?:\((?<destination><[^>]*>|{BALANCED_DESTINATION})(?:[ \t]+(?<title>{TITLE}))?[ \t]*\)|\[(?<label>[^\]]*)\])?")).unwrap()
+    });
+    &IMAGE
+}
+
+/// Rewrite the images outside of code in `text` with `rewrite`,
+/// which gets the image's captures and returns its replacement, if any.
+fn rewrite_images(text: &str, mut rewrite: impl FnMut(&Captures) -> Option<String>) -> String {
+    let line_definitions = references::line_definitions(text);
+    let after = markdown::lines(text)
+        .zip(line_definitions)
+        .map(|(line, definition)| {
+            if line.kind != LineKind::Text || definition.is_some() {
+                return line.text.to_owned();
+            }
+            markdown::rewrite_outside_code(line.text, |text| {
+                let rewritten = image_regex().replace_all(text, |captures: &Captures| {
+                    rewrite(captures).unwrap_or_else(|| captures[0].to_owned())
+                });
+                rewritten.into_owned()
+            })
+        })
+        .join("\n");
+    after
+}
+
+/// Convert reference images to inline images,
+/// deleting the definitions that only they used.
+fn reference_images_to_inline(before: String) -> String {
+    let definitions = references::definitions(&before)
+        .into_iter()
+        .map(|(_, definition)| definition)
+        .map(|definition| (references::normalize_label(definition.label), definition))
+        .collect::<HashMap<_, _>>();
+    let mut inlined = HashSet::new();
+    let after = rewrite_images(&before, |captures| {
+        if captures.name("destination").is_some() {
+            return None;
+        }
+        let alt = &captures["alt"];
+        // `![alt][]` and `![alt]` use the alt text as the label.
+        let label = match captures.name("label") {
+            Some(label) if !label.is_empty() => label.as_str(),
+            _ => alt,
+        };
+        let label = references::normalize_label(label);
+        let definition = definitions.get(&label)?;
+        let mut destination = definition.destination.to_owned();
+        let is_balanced = destination.matches('(').count() == destination.matches(')').count();
+        if !destination.starts_with('<') && !is_balanced {
+            destination = format!("<{destination}>");
+        }
+        let title = definition
+            .title
+            .map(|title| format!(" {title}"))
+            .unwrap_or_default();
+        inlined.insert(label);
+        Some(format!("![{alt}]({destination}{title})"))
+    });
+    if inlined.is_empty() {
+        return before;
+    }
+
+    // Anything that looks like it could still be a usage counts,
+    // so that we never delete a definition that's actually used.
+    static LABEL: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\[(?<label>[^\[\]]+)\]").unwrap());
+    let lines = markdown::lines(&after)
+        .map(|line| line.text)
+        .zip(references::line_definitions(&after))
+        .collect::<Vec<_>>();
+    let used = lines
+        .iter()
+        .filter(|(_, definition)| definition.is_none())
+        .flat_map(|(line, _)| {
+            LABEL
+                .captures_iter(line)
+                // Not the text of an inline link or image.
+                .filter(|captures| !line[captures.get(0).unwrap().end()..].starts_with('('))
+        })
+        .map(|captures| references::normalize_label(&captures["label"]))
+        .collect::<HashSet<_>>();
+    let mut kept = Vec::new();
+    let mut removed_definition = false;
+    for &(line, definition) in &lines {
+        let is_unused = definition.is_some_and(|definition| {
+            let label = references::normalize_label(definition.label);
+            inlined.contains(&label) && !used.contains(&label)
+        });
+        if is_unused {
+            removed_definition = true;
+            continue;
+        }
+        // Don't leave a double blank line where definitions were removed.
+        let is_blank = line.trim().is_empty();
+        let last_is_blank = kept.last().is_none_or(|line: &&str| line.trim().is_empty());
+        if !(removed_definition && is_blank && last_is_blank) {
+            kept.push(line);
+        }
+        removed_definition = false;
+    }
+    kept.join("\n")
+}
+
+/// A reference label for an image of `src`, like `bus-map` for `maps/bus-map.png`,
+/// so that it doesn't depend on the image having alt text.
+fn image_label(src: &str) -> String {
+    let label = file_stem(src)
+        .unwrap_or_default()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .join("-")
+        .to_lowercase();
+    match label.is_empty() {
+        true => "image".into(),
+        false => label,
+    }
+}
+
+/// Convert inline images to reference images,
+/// reusing existing definitions of the same source and title,
+/// and adding new ones at the end of the document.
+fn inline_images_to_reference(before: String) -> String {
+    let definitions = references::definitions(&before)
+        .into_iter()
+        .map(|(_, definition)| definition)
+        .collect::<Vec<_>>();
+    let mut labels = definitions
+        .iter()
+        .map(|definition| references::normalize_label(definition.label))
+        .collect::<HashSet<_>>();
+    // The first definition of a source and title wins.
+    let mut by_target = definitions
+        .iter()
+        .rev()
+        .map(|definition| {
+            let title = definition.title.map(str::to_owned);
+            (
+                (definition.url().to_owned(), title),
+                definition.label.to_owned(),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let mut new_definitions = Vec::new();
+    let after = rewrite_images(&before, |captures| {
+        let destination = captures.name("destination")?.as_str();
+        let src = destination.trim_matches(['<', '>']);
+        // Embedded images don't have a useful label, and are removed by `embedded-images` anyway.
+        if src.is_empty() || src.starts_with("data:") {
+            return None;
+        }
+        let title = captures
+            .name("title")
+            .map(|title| title.as_str().to_owned());
+        let label = by_target
+            .entry((src.to_owned(), title.clone()))
+            .or_insert_with(|| {
+                let label = image_label(src);
+                let label = [label.clone()]
+                    .into_iter()
+                    .chain((2..).map(|i| format!("{label}-{i}")))
+                    .find(|label| !labels.contains(label))
+                    .unwrap();
+                labels.insert(label.clone());
+                let title = title.map(|title| format!(" {title}")).unwrap_or_default();
+                new_definitions.push(format!("[{label}]: {destination}{title}"));
+                label
+            });
+        Some(format!("![{}][{label}]", &captures["alt"]))
+    });
+    if new_definitions.is_empty() {
+        return after;
+    }
+    // Add the definitions after any already at the end.
+    let body = after.trim_end_matches('\n');
+    let mut text = body.to_owned();
+    if !body.is_empty() {
+        text.push_str(match references::line_definitions(body).last() {
+            Some(Some(_)) => "\n",
+            _ => "\n\n",
+        });
+    }
+    text.push_str(&new_definitions.join("\n"));
+    if before.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// Convert reference images (`![alt][label]`) to inline images (`![alt](src "title")`), or the reverse,
+/// deleting definitions that are no longer used, and reusing or adding ones that are.
+pub fn convert_reference_images(before: String, to: ImageStyle) -> String {
+    match to {
+        ImageStyle::Inline => reference_images_to_inline(before),
+        ImageStyle::Reference => inline_images_to_reference(before),
+    }
+}
+
+/// An image embedded in the document as a `data:` URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedImage {
+    /// 1-based line number.
+    pub line: usize,
+
+    /// Like `image/png`.
+    pub mime: String,
+
+    /// The length of the whole `data:` URI in the document.
+    pub encoded_bytes: usize,
+
+    /// The size of the image file once decoded.
+    pub decoded_bytes: usize,
+}
+
+/// The size of `data` once decoded from base64 or percent-encoding.
+fn decoded_len(data: &str, is_base64: bool) -> usize {
+    match is_base64 {
+        true => {
+            let chars = data.chars().filter(|c| !c.is_whitespace() && *c != '=');
+            chars.count() * 3 / 4
+        }
+        false => data.len() - 2 * data.matches('%').count(),
+    }
+}
+
+/// Format a number of bytes like `1.5 KiB`.
+fn format_size(bytes: usize) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB"] {
+        if size < 1024.0 || unit == "MiB" {
+            return match unit {
+                "B" => format!("{bytes} B"),
+                _ => format!("{size:.1} {unit}"),
+            };
+        }
+        size /= 1024.0;
+    }
+    unreachable!()
+}
+
+/// The images embedded as `data:` URIs in a document, and how much of it they are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedImages {
+    pub images: Vec<EmbeddedImage>,
+
+    /// The size of the whole document.
+    pub file_bytes: usize,
+}
+
+impl EmbeddedImages {
+    pub fn of(text: &str) -> Self {
+        static DATA_URI: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r#"data:(?<mime>image/[A-Za-z0-9.+-]+)(?:;[^;,\s)>"']*)*?(?<base64>;base64)?,(?<data>[^\s)>"']*)"#).unwrap()
+        });
+        let images = markdown::lines(text)
+            .enumerate()
+            .filter(|(_, line)| line.kind == LineKind::Text)
+            .flat_map(|(i, line)| {
+                DATA_URI
+                    .captures_iter(line.text)
+                    .map(move |captures| EmbeddedImage {
+                        line: i + 1,
+                        mime: captures["mime"].to_lowercase(),
+                        encoded_bytes: captures[0].len(),
+                        decoded_bytes: decoded_len(
+                            &captures["data"],
+                            captures.name("base64").is_some(),
+                        ),
+                    })
+            })
+            .collect();
+        Self {
+            images,
+            file_bytes: text.len(),
+        }
+    }
+
+    pub fn encoded_bytes(&self) -> usize {
+        self.images.iter().map(|image| image.encoded_bytes).sum()
+    }
+
+    pub fn decoded_bytes(&self) -> usize {
+        self.images.iter().map(|image| image.decoded_bytes).sum()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let images = self
+            .images
+            .iter()
+            .map(|image| {
+                json!({
+                    "line": image.line,
+                    "mime": image.mime,
+                    "encoded_bytes": image.encoded_bytes,
+                    "decoded_bytes": image.decoded_bytes,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "images": images,
+            "encoded_bytes": self.encoded_bytes(),
+            "decoded_bytes": self.decoded_bytes(),
+            "file_bytes": self.file_bytes,
+        })
+    }
+}
+
+impl Display for EmbeddedImages {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for image in &self.images {
+            writeln!(
+                f,
+                "{}: {}: {} ({} in the document)",
+                image.line,
+                image.mime,
+                format_size(image.decoded_bytes),
+                format_size(image.encoded_bytes),
+            )?;
+        }
+        let percent = match self.file_bytes {
+            0 => 0.0,
+            file_bytes => 100.0 * self.encoded_bytes() as f64 / file_bytes as f64,
+        };
+        write!(
+            f,
+            "total: {} images, {} decoded, {} of the document's {} ({percent:.0}%)",
+            self.images.len(),
+            format_size(self.decoded_bytes()),
+            format_size(self.encoded_bytes()),
+            format_size(self.file_bytes),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::diagnostic::Diagnostic;
+    use crate::images::check_alt_text;
+    use crate::images::convert_reference_images;
+    use crate::images::format_size;
+    use crate::images::EmbeddedImages;
+    use crate::images::ImageStyle;
+
+    #[test]
+    fn test_check_alt_text() {
+        let before = "![](maps/bus-map_2019.png) ![image](photos/IMG_1234.jpg)
+![A train at the station](train.jpg) ![station.png](station.png \"Station\")
+![][diagram] `![](code.png)`
+
+[diagram]: diagrams/track_layout.svg
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_alt_text(before.into(), false, None, false, &mut diagnostics),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::new(1, "image `maps/bus-map_2019.png` has no alt text"),
+                Diagnostic::new(
+                    1,
+                    "image `photos/IMG_1234.jpg` has placeholder alt text `image`"
+                ),
+                Diagnostic::new(
+                    2,
+                    "image `station.png` has placeholder alt text `station.png`"
+                ),
+                Diagnostic::new(3, "image `diagrams/track_layout.svg` has no alt text"),
+            ]
+        );
+        let mapping = BTreeMap::from([("station.png".into(), "The [new] station".into())]);
+        let after = "![Bus map 2019](maps/bus-map_2019.png) ![image](photos/IMG_1234.jpg)
+![A train at the station](train.jpg) ![The \\[new\\] station](station.png \"Station\")
+![Track layout][diagram] `![](code.png)`
+
+[diagram]: diagrams/track_layout.svg
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_alt_text(before.into(), true, Some(&mapping), true, &mut diagnostics),
+            after
+        );
+        assert_eq!(
+            diagnostics,
+            [Diagnostic::new(
+                1,
+                "image `photos/IMG_1234.jpg` has placeholder alt text `image`"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_convert_reference_images() {
+        let before = "![Bus map][map] ![][diagram] ![Track][] `![a][map]`
+![The map](maps/bus-map.png \"Buses\") ![](<a b/c.png>) [Map][map]
+
+[map]: maps/bus-map.png \"Buses\"
+[diagram]: diagrams/track_layout.svg
+[track]: track.png
+";
+        let inline = "![Bus map](maps/bus-map.png \"Buses\") ![](diagrams/track_layout.svg) ![Track](track.png) `![a][map]`
+![The map](maps/bus-map.png \"Buses\") ![](<a b/c.png>) [Map][map]
+
+[map]: maps/bus-map.png \"Buses\"
+";
+        assert_eq!(
+            convert_reference_images(before.into(), ImageStyle::Inline),
+            inline
+        );
+        let reference = "![Bus map][map] ![][track-layout] ![Track][track] `![a][map]`
+![The map][map] ![][c] [Map][map]
+
+[map]: maps/bus-map.png \"Buses\"
+[track-layout]: diagrams/track_layout.svg
+[track]: track.png
+[c]: <a b/c.png>
+";
+        assert_eq!(
+            convert_reference_images(inline.into(), ImageStyle::Reference),
+            reference
+        );
+        let before = "![A](a.png) ![B](b/a.png)";
+        assert_eq!(
+            convert_reference_images(before.into(), ImageStyle::Reference),
+            "![A][a] ![B][a-2]\n\n[a]: a.png\n[a-2]: b/a.png"
+        );
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_embedded_images() {
+        let text = r#"![](data:image/png;base64,iVBORw0KGgo=)
+
+[image2]: <data:image/jpeg;base64,/9j/4AAQSkZJRgABAQ>
+<img src="data:image/svg+xml;charset=utf-8,%3Csvg%3E%3C/svg%3E">
+"#;
+        let images = EmbeddedImages::of(text);
+        assert_eq!(
+            images.to_string(),
+            "1: image/png: 8 B (34 B in the document)
+3: image/jpeg: 13 B (41 B in the document)
+4: image/svg+xml: 11 B (52 B in the document)
+total: 3 images, 32 B decoded, 127 B of the document's 160 B (79%)"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Reading documents to style, without copying large files into memory before they're rewritten.
+
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use color_eyre::eyre;
+use color_eyre::eyre::WrapErr;
+use memmap2::Mmap;
+
+use crate::error::Error;
+
+/// A document's text, either read or memory mapped from a file.
+pub enum Input {
+    Text(String),
+    Mapped { path: PathBuf, map: Mmap },
+}
+
+impl Input {
+    /// Read all of stdin, which is called `name` in errors.
+    pub fn stdin(name: &Path) -> eyre::Result<Self> {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        let text = String::from_utf8(bytes)
+            .map_err(|error| Error::not_utf8(name, error.as_bytes(), error.utf8_error()))?;
+        Ok(Self::Text(text))
+    }
+
+    /// Memory map the file at `path`.
+    ///
+    /// Exports with embedded images can be hundreds of MB,
+    /// and rules like `embedded-images` rewrite them from the mapping
+    /// to something much smaller without ever copying all of them.
+    pub fn map(path: &Path) -> eyre::Result<Self> {
+        let file = fs_err::File::open(path)?;
+        // SAFETY: The file could be modified while it's mapped,
+        // but that's the same race as with a file modified while it's being read,
+        // and the text is only used after checking that it's UTF-8.
+        let map = unsafe { Mmap::map(file.file()) }
+            .wrap_err_with(|| format!("failed to map `{}`", path.display()))?;
+        Ok(Self::Mapped {
+            path: path.to_owned(),
+            map,
+        })
+    }
+
+    pub fn as_str(&self) -> eyre::Result<&str> {
+        match self {
+            Self::Text(text) => Ok(text),
+            Self::Mapped { path, map } => {
+                let text =
+                    str::from_utf8(map).map_err(|error| Error::not_utf8(path, map, error))?;
+                Ok(text)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::error::Error;
+    use crate::input::Input;
+
+    #[test]
+    fn test_map() {
+        let dir = env::temp_dir().join("style-markdown-test-map");
+        fs_err::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.md");
+        fs_err::write(&path, "# Title\n").unwrap();
+        assert_eq!(Input::map(&path).unwrap().as_str().unwrap(), "# Title\n");
+        fs_err::write(&path, "").unwrap();
+        assert_eq!(Input::map(&path).unwrap().as_str().unwrap(), "");
+        fs_err::write(&path, b"\xff").unwrap();
+        let error = Input::map(&path).unwrap().as_str().unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(Error::NotUtf8 { .. })));
+    }
+}
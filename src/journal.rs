@@ -0,0 +1,274 @@
+//! A journal of the edits each run made, so that the last run can be undone,
+//! even in files that aren't under version control.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use color_eyre::eyre;
+use color_eyre::eyre::ensure;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::OptionExt;
+use color_eyre::eyre::WrapErr;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::changes;
+use crate::changes::LineEdit;
+
+/// The directory of the journal, which is kept in the current directory.
+pub const DIR: &str = ".style-markdown";
+
+/// The name of the journal in [`DIR`].
+pub const FILE_NAME: &str = "journal.json";
+
+/// Create [`DIR`] in `dir`, ignoring itself,
+/// so that what's kept in it is never committed or makes the worktree dirty.
+pub fn create_dir(dir: &Path) -> eyre::Result<PathBuf> {
+    let dir = dir.join(DIR);
+    fs_err::create_dir_all(&dir)?;
+    fs_err::write(dir.join(".gitignore"), "*\n")?;
+    Ok(dir)
+}
+
+/// The most runs kept, dropping the oldest ones, since only the last one is undone at a time.
+const MAX_RUNS: usize = 20;
+
+/// The 64-bit FNV-1a hash of a file's `text`, to check that it's unchanged since a run.
+///
+/// It's saved, so unlike `std`'s hasher, it can't change between Rust versions.
+fn hash(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// A file that a run changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalFile {
+    pub path: PathBuf,
+
+    /// The [`hash`] of the file after the run.
+    pub hash: String,
+
+    /// The edits that undo the run's, from after it to before it.
+    pub undo: Vec<LineEdit>,
+}
+
+impl JournalFile {
+    fn to_json(&self) -> Value {
+        json!({
+            "path": self.path.display().to_string(),
+            "hash": self.hash,
+            "undo": self.undo.iter().map(|edit| json!({
+                "start": edit.lines.start,
+                "end": edit.lines.end,
+                "text": edit.text,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    fn parse(json: &Value) -> eyre::Result<Self> {
+        let field = |key| json.get(key).ok_or_else(|| eyre!("missing `{key}`"));
+        let undo = field("undo")?
+            .as_array()
+            .ok_or_eyre("`undo` must be a list")?
+            .iter()
+            .map(|edit| {
+                let line = |key| edit.get(key).and_then(Value::as_u64).map(|n| n as usize);
+                Some(LineEdit {
+                    lines: line("start")?..line("end")?,
+                    text: edit.get("text")?.as_str()?.to_owned(),
+                })
+            })
+            .collect::<Option<_>>()
+            .ok_or_eyre("`undo` must be a list of edits")?;
+        Ok(Self {
+            path: field("path")?
+                .as_str()
+                .ok_or_eyre("`path` must be a string")?
+                .into(),
+            hash: field("hash")?
+                .as_str()
+                .ok_or_eyre("`hash` must be a string")?
+                .to_owned(),
+            undo,
+        })
+    }
+}
+
+/// The files that one run changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Run {
+    /// The command line of the run.
+    pub command: String,
+
+    pub files: Vec<JournalFile>,
+}
+
+impl Run {
+    pub fn new(command: String) -> Self {
+        Self {
+            command,
+            files: Vec::new(),
+        }
+    }
+
+    /// Record that the run is changing the file at `path` from `before` to `after`.
+    pub fn record(&mut self, path: &Path, before: &str, after: &str) -> eyre::Result<()> {
+        self.files.push(JournalFile {
+            // Not resolving symlinks, in case the run replaced one with a regular file.
+            path: std::path::absolute(path)?,
+            hash: hash(after),
+            undo: changes::line_edits(after, before),
+        });
+        Ok(())
+    }
+
+    /// Revert the files the run changed, if none of them have changed since.
+    pub fn undo(&self) -> eyre::Result<()> {
+        let texts = self
+            .files
+            .iter()
+            .map(|file| {
+                let text = fs_err::read_to_string(&file.path)?;
+                ensure!(
+                    hash(&text) == file.hash,
+                    "`{}` changed since `{}`, so it can't be undone",
+                    file.path.display(),
+                    self.command
+                );
+                Ok(text)
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        for (file, text) in self.files.iter().zip(texts) {
+            fs_err::write(&file.path, changes::apply_line_edits(&text, &file.undo))?;
+        }
+        Ok(())
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "command": self.command,
+            "files": self.files.iter().map(JournalFile::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn parse(json: &Value) -> eyre::Result<Self> {
+        let command = json
+            .get("command")
+            .and_then(Value::as_str)
+            .ok_or_eyre("`command` must be a string")?;
+        let files = json
+            .get("files")
+            .and_then(Value::as_array)
+            .ok_or_eyre("`files` must be a list")?
+            .iter()
+            .map(JournalFile::parse)
+            .collect::<eyre::Result<_>>()?;
+        Ok(Self {
+            command: command.to_owned(),
+            files,
+        })
+    }
+}
+
+/// The runs that changed files, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Journal {
+    pub runs: Vec<Run>,
+}
+
+impl Journal {
+    pub fn parse(json: &str) -> eyre::Result<Self> {
+        let runs = serde_json::from_str::<Value>(json)?
+            .as_array()
+            .ok_or_eyre("the journal must be a list")?
+            .iter()
+            .map(Run::parse)
+            .collect::<eyre::Result<_>>()?;
+        Ok(Self { runs })
+    }
+
+    pub fn to_json(&self) -> Value {
+        self.runs.iter().map(Run::to_json).collect()
+    }
+
+    /// Read the journal in `dir`, which is empty if there isn't one.
+    pub fn load(dir: &Path) -> eyre::Result<Self> {
+        let path = dir.join(DIR).join(FILE_NAME);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let json = fs_err::read_to_string(&path)?;
+        Self::parse(&json).wrap_err_with(|| format!("invalid journal `{}`", path.display()))
+    }
+
+    /// Write the journal in [`DIR`] in `dir`.
+    pub fn save(&self, dir: &Path) -> eyre::Result<()> {
+        let dir = create_dir(dir)?;
+        let json = serde_json::to_string_pretty(&self.to_json())?;
+        fs_err::write(dir.join(FILE_NAME), json)?;
+        Ok(())
+    }
+
+    /// Add a run, if it changed anything.
+    pub fn push(&mut self, run: Run) {
+        if run.files.is_empty() {
+            return;
+        }
+        self.runs.push(run);
+        let extra = self.runs.len().saturating_sub(MAX_RUNS);
+        self.runs.drain(..extra);
+    }
+
+    /// Undo the last run, and forget it.
+    pub fn undo(&mut self) -> eyre::Result<Run> {
+        let run = self.runs.last().ok_or_eyre("there's no run to undo")?;
+        run.undo()?;
+        Ok(self.runs.pop().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::journal::hash;
+    use crate::journal::Journal;
+    use crate::journal::Run;
+
+    #[test]
+    fn test_undo() {
+        let dir = env::temp_dir().join("style-markdown-test-journal");
+        fs_err::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.md");
+        fs_err::write(&path, "“a”\nb\n").unwrap();
+
+        let mut run = Run::new("style-markdown a.md quotes".into());
+        run.record(&path, "“a”\nb\n", "\"a\"\nb\n").unwrap();
+        fs_err::write(&path, "\"a\"\nb\n").unwrap();
+        let mut journal = Journal::default();
+        journal.push(run);
+        journal.push(Run::new("unchanged".into()));
+        assert_eq!(journal.runs.len(), 1);
+        journal.save(&dir).unwrap();
+
+        let mut journal = Journal::load(&dir).unwrap();
+        let run = journal.runs[0].clone();
+        fs_err::write(&path, "\"a\"\nb\nc\n").unwrap();
+        assert!(journal.undo().is_err());
+        fs_err::write(&path, "\"a\"\nb\n").unwrap();
+        assert_eq!(journal.undo().unwrap(), run);
+        assert_eq!(fs_err::read_to_string(&path).unwrap(), "“a”\nb\n");
+        assert!(journal.undo().is_err());
+        assert!(Journal::parse("{}").is_err());
+        assert_eq!(
+            fs_err::read_to_string(dir.join(".style-markdown/.gitignore")).unwrap(),
+            "*\n"
+        );
+        // The hash is saved, so it has to be the same in every version.
+        assert_eq!(hash(""), "cbf29ce484222325");
+        assert_eq!(hash("a"), "af63dc4c8601ec8c");
+    }
+}
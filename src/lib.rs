@@ -0,0 +1,2090 @@
+//! Rules that rewrite Markdown files consistently, and reports about them.
+
+#![allow(clippy::let_and_return)]
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use aho_corasick::AhoCorasick;
+use aho_corasick::MatchKind;
+use clap::Parser;
+use clap::Subcommand;
+use clap::ValueEnum;
+use color_eyre::eyre;
+use color_eyre::eyre::ensure;
+use color_eyre::eyre::eyre;
+use itertools::Itertools;
+use regex::Captures;
+use regex::Regex;
+
+use crate::admonitions::AdmonitionStyle;
+use crate::code_blocks::FenceStyle;
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Severity;
+use crate::emoji::EmojiStyle;
+use crate::error::Error;
+use crate::footnotes::FootnoteLabels;
+use crate::footnotes::FootnoteStyle;
+use crate::images::ImageStyle;
+use crate::line_length::Included;
+use crate::links::BareUrlStyle;
+use crate::links::BALANCED_DESTINATION;
+use crate::lists::BulletMarker;
+use crate::markdown::Dialect;
+use crate::markdown::LineKind;
+use crate::prose_lint::ProseRule;
+use crate::references::DefinitionOrder;
+use crate::spell::SpellDictionary;
+use crate::triggers::Trigger;
+use crate::triggers::Triggers;
+use crate::typography::DashStyle;
+use crate::typography::EllipsisStyle;
+use crate::typography::QuoteLocale;
+use crate::typography::QuoteStyle;
+use crate::typography::ThousandsSeparator;
+use crate::wiki_links::LinkKind;
+use crate::wiki_links::SlugStyle;
+
+pub mod admonitions;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bench;
+pub mod blockquotes;
+pub mod cache;
+pub mod capabilities;
+pub mod changes;
+pub mod code_blocks;
+pub mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dead_links;
+pub mod diagnostic;
+pub mod emoji;
+pub mod error;
+pub mod escapes;
+pub mod footnotes;
+pub mod frequency;
+pub mod front_matter;
+pub mod graphemes;
+pub mod headings;
+pub mod html;
+pub mod images;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod input;
+pub mod journal;
+pub mod line_length;
+pub mod links;
+pub mod lists;
+pub mod markdown;
+pub mod markdownlint;
+pub mod mdbook;
+pub mod ordering;
+pub mod pandoc;
+pub mod presets;
+pub mod prose;
+pub mod prose_lint;
+#[cfg(feature = "python")]
+mod python;
+pub mod readability;
+pub mod references;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod remote;
+pub mod review;
+pub mod spell;
+pub mod stats;
+pub mod stream;
+pub mod terms;
+pub mod triggers;
+pub mod typography;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod walk;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+pub mod wiki_links;
+
+/// Commands that rewrite files.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Replace fancy (`‘’`, `“”`) quotes with simple (`'`, `"`) quotes, or the reverse.
+    #[command(visible_alias = "dequote")]
+    Quotes {
+        /// How to write quotes.
+        #[arg(long, value_enum, default_value_t)]
+        to: QuoteStyle,
+
+        /// Whose fancy quotes to use, like guillemets (`« »`) in French.
+        #[arg(long, value_enum, default_value_t)]
+        locale: QuoteLocale,
+    },
+
+    /// Delete large embedded images (i.e. `<data:image/[^>]*>` HTML elements),
+    /// and the `data:image/` destinations of reference definitions.
+    EmbeddedImages,
+
+    /// Delete extra spaces after the `]:` of reference and footnote definitions.
+    ExtraRefSpaces,
+
+    /// Simplify `[URL](URL)`s as `<URL>`.
+    SimplifyUrls,
+
+    /// Add semantic line breaks as best as possible.
+    #[command(visible_alias = "sembr")]
+    SemanticLineBreaks,
+
+    /// Canonicalize "through-running" words, always hyphenating and always putting "through" before "run".
+    ThroughRunning,
+
+    /// Move footnotes to always after punctuation.
+    FootnotesAfterPunctuation,
+
+    /// Collapse runs of consecutive blank lines, except in fenced code blocks.
+    BlankLines {
+        /// Maximum number of consecutive blank lines to keep.
+        #[arg(long, default_value_t = 1)]
+        max: usize,
+    },
+
+    /// Separate code blocks, tables, lists, and blockquotes from the paragraphs around them with blank lines.
+    BlockSpacing,
+
+    /// Strip trailing spaces and tabs, except for hard line breaks.
+    TrailingWhitespace {
+        /// How to write hard line breaks.
+        #[arg(long, value_enum, default_value_t = HardBreak::Spaces)]
+        hard_breaks: HardBreak,
+    },
+
+    /// Convert hard line breaks between trailing spaces, backslashes, and `<br>`.
+    HardBreaks {
+        /// How to write hard line breaks.
+        #[arg(long, value_enum)]
+        to: HardBreak,
+    },
+
+    /// Merge link reference definitions with the same destination, and delete unused ones.
+    RefDefinitions {
+        /// Don't delete definitions that are never used.
+        #[arg(long)]
+        keep_unused: bool,
+    },
+
+    /// Move link reference definitions to the end of the document and sort them.
+    SortRefDefinitions {
+        /// How to sort the definitions.
+        #[arg(long, value_enum, default_value_t = DefinitionOrder::Label)]
+        by: DefinitionOrder,
+
+        /// Move definitions to the end of each heading's section instead.
+        #[arg(long)]
+        per_section: bool,
+    },
+
+    /// Renumber footnotes sequentially by the order they're first used.
+    RenumberFootnotes {
+        /// What to renumber the footnote labels to.
+        #[arg(long, value_enum, default_value_t = FootnoteLabels::Numbers)]
+        labels: FootnoteLabels,
+    },
+
+    /// Reorder footnote definitions to match the order they're first used.
+    SortFootnoteDefinitions,
+
+    /// Move all footnote definitions to the end of the document.
+    FootnotesToEnd {
+        /// A heading to put the footnote definitions under, like `## Notes`.
+        #[arg(long)]
+        heading: Option<String>,
+    },
+
+    /// Report footnotes that are used but never defined, or defined but never used.
+    FootnoteCheck {
+        /// Delete footnote definitions that are never used.
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Convert Pandoc's inline footnotes (`^[a note]`) to `[^1]` references with definitions, or the reverse.
+    InlineFootnotes {
+        /// How to write footnotes.
+        #[arg(long, value_enum, default_value_t)]
+        to: FootnoteStyle,
+    },
+
+    /// Convert simple HTML formatting tags (`<b>`, `<i>`, `<code>`, `<br>`, etc.) to Markdown.
+    HtmlFormatting,
+
+    /// Convert HTML `<img>` tags to `![alt](src)` Markdown images.
+    HtmlImages {
+        /// Convert images with other attributes too (like `width`),
+        /// keeping them in an HTML comment after the image.
+        #[arg(long)]
+        keep_dropped_attributes: bool,
+    },
+
+    /// Remove HTML comments, except for ones that tools need, like `<!-- toc -->`.
+    HtmlComments {
+        /// A regex for comments to keep, matched against their trimmed text.
+        #[arg(long)]
+        keep: Vec<Regex>,
+    },
+
+    /// Normalize thematic breaks (horizontal rules) to a single style.
+    #[command(visible_alias = "hr")]
+    ThematicBreaks {
+        /// The thematic break to use, like `---`, `***`, or `___`.
+        #[arg(long, default_value = "---", value_parser = parse_thematic_break)]
+        style: String,
+    },
+
+    /// Link bare URLs in prose.
+    BareUrls {
+        /// How to link the URLs.
+        #[arg(long, value_enum, default_value_t = BareUrlStyle::Autolink)]
+        style: BareUrlStyle,
+    },
+
+    /// Convert ` - `, `--`, and `---` to en and em dashes, or the reverse.
+    Dashes {
+        /// Whether to write dashes as Unicode or ASCII.
+        #[arg(long, value_enum, default_value_t = DashStyle::Unicode)]
+        style: DashStyle,
+
+        /// Put spaces around em dashes.
+        #[arg(long)]
+        spaced_em_dashes: bool,
+    },
+
+    /// Convert `...` and `. . .` to `…`, or the reverse.
+    Ellipses {
+        /// Whether to write ellipses as Unicode or ASCII.
+        #[arg(long, value_enum, default_value_t = EllipsisStyle::Unicode)]
+        style: EllipsisStyle,
+    },
+
+    /// Use non-breaking spaces between numbers and units and around abbreviations like `No.`.
+    #[command(visible_alias = "nbsp")]
+    NonBreakingSpaces {
+        /// Another unit to keep with the number before it.
+        #[arg(long = "unit")]
+        units: Vec<String>,
+
+        /// Replace non-breaking spaces anywhere else with normal spaces.
+        #[arg(long)]
+        strip_other: bool,
+    },
+
+    /// Normalize Unicode to NFC (composed characters), noting what changed.
+    Nfc,
+
+    /// Remove zero-width spaces, soft hyphens, directional marks, and other invisible characters.
+    InvisibleChars {
+        /// A character (or `U+XXXX` code point) to keep.
+        #[arg(long = "allow", value_parser = typography::parse_char)]
+        allowed: Vec<char>,
+    },
+
+    /// Convert code fences to a single style, as short as possible.
+    CodeFences {
+        /// Which character to write code fences with.
+        #[arg(long, value_enum, default_value_t = FenceStyle::Backticks)]
+        style: FenceStyle,
+    },
+
+    /// Report code blocks without a language.
+    CodeLanguages {
+        /// Set the language of code blocks without one, inferring it for simple cases.
+        #[arg(long)]
+        fix: bool,
+
+        /// The language to use with `--fix` when it can't be inferred.
+        #[arg(long)]
+        default: Option<String>,
+    },
+
+    /// Indent nested lists consistently, converting tabs to spaces.
+    ListIndentation {
+        /// Spaces per level of nesting (more if needed after wide markers like `10.`).
+        #[arg(long, default_value_t = 2)]
+        width: usize,
+    },
+
+    /// Write bullet list markers with a single character.
+    ListMarkers {
+        /// Which character to write bullet list markers with.
+        #[arg(long, value_enum, default_value_t = BulletMarker::Dash)]
+        marker: BulletMarker,
+    },
+
+    /// Convert indented code blocks to fenced code blocks.
+    FenceIndentedCode {
+        /// The language to give the fenced code blocks.
+        #[arg(long)]
+        language: Option<String>,
+    },
+
+    /// Insert or update a table of contents between `<!-- toc -->` and `<!-- /toc -->` markers.
+    Toc {
+        /// The shallowest heading level to include.
+        #[arg(long, default_value_t = 2)]
+        min_depth: usize,
+
+        /// The deepest heading level to include.
+        #[arg(long, default_value_t = 3)]
+        max_depth: usize,
+
+        /// Headings to leave out, by their text.
+        #[arg(long = "exclude")]
+        excluded: Vec<String>,
+    },
+
+    /// Number headings hierarchically, like `## 2.3 Rolling Stock`, updating links to them.
+    NumberHeadings {
+        /// The shallowest heading level to number.
+        #[arg(long, default_value_t = 2)]
+        min_depth: usize,
+
+        /// The deepest heading level to number.
+        #[arg(long, default_value_t = 3)]
+        max_depth: usize,
+
+        /// Remove section numbers instead of adding them.
+        #[arg(long)]
+        strip: bool,
+    },
+
+    /// Convert setext (underlined) and closed ATX (`## Heading ##`) headings to `## Heading`.
+    AtxHeadings,
+
+    /// Put blank lines around headings, and a space after their `#`s.
+    HeadingSpacing {
+        /// The number of blank lines before each heading.
+        #[arg(long, default_value_t = 1)]
+        before: usize,
+
+        /// The number of blank lines after each heading.
+        #[arg(long, default_value_t = 1)]
+        after: usize,
+
+        /// Don't add a missing space after `#`s, like in `#Heading`, since they're hashtags.
+        #[arg(long)]
+        hashtags: bool,
+    },
+
+    /// Report headings with the same anchor as an earlier heading.
+    DuplicateHeadings {
+        /// Rename the headings, updating links to them.
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Report links to `#anchors` that don't match any heading.
+    AnchorLinks {
+        /// Update links to headings that were likely renamed.
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Report links to relative files that don't exist.
+    FileLinks {
+        /// A file that was renamed from `old` to `new`, as `old=new`.
+        #[arg(long = "rename", value_parser = links::parse_rename)]
+        renames: Vec<(PathBuf, PathBuf)>,
+
+        /// Update links to renamed files and ones whose case doesn't match.
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Report links to URLs that are dead.
+    #[cfg(not(target_arch = "wasm32"))]
+    DeadLinks {
+        /// Replace dead links with their closest snapshot in the Wayback Machine.
+        #[arg(long)]
+        wayback: bool,
+
+        /// With `--wayback`, add a footnote with the original URL.
+        #[arg(long, requires = "wayback")]
+        keep_original: bool,
+    },
+
+    /// Canonicalize link URLs: strip tracking parameters, remove default ports,
+    /// and lowercase schemes and hosts.
+    CanonicalUrls {
+        /// Another query parameter to strip, where a trailing `*` matches any suffix.
+        #[arg(long = "param")]
+        params: Vec<String>,
+    },
+
+    /// Report images with empty or placeholder alt text.
+    AltText {
+        /// Replace missing alt text from `--mapping` or `--from-file-name`.
+        #[arg(long)]
+        fix: bool,
+
+        /// A JSON file mapping image sources to their alt text.
+        #[arg(long, value_parser = images::parse_alt_text_mapping)]
+        mapping: Option<BTreeMap<String, String>>,
+
+        /// Generate alt text from descriptive file names, like `bus-map.png`.
+        #[arg(long)]
+        from_file_name: bool,
+    },
+
+    /// Convert reference images (`![alt][label]`) to inline images (`![alt](src)`), or the reverse.
+    ReferenceImages {
+        /// How to write images.
+        #[arg(long, value_enum, default_value_t)]
+        to: ImageStyle,
+    },
+
+    /// Fix the capitalization of known terms, like `GitHub`, in prose.
+    Capitalization {
+        /// Another term to fix the capitalization of.
+        #[arg(long = "term")]
+        terms: Vec<String>,
+    },
+
+    /// Insert thousands separators into large numbers in prose.
+    ThousandsSeparators {
+        /// Which separator to use.
+        #[arg(long, value_enum, default_value_t = ThousandsSeparator::Comma)]
+        separator: ThousandsSeparator,
+
+        /// Only numbers with at least this many digits get separators (4-digit years never do).
+        #[arg(long, default_value_t = 5)]
+        min_digits: usize,
+    },
+
+    /// Canonicalize units after numbers, like `kph` to `km/h`, and space them from the number.
+    Units {
+        /// Another alias of a unit, as `alias=unit`.
+        #[arg(long = "alias", value_parser = typography::parse_unit_alias)]
+        aliases: Vec<(String, String)>,
+
+        /// Use a non-breaking space between the number and the unit.
+        #[arg(long)]
+        non_breaking: bool,
+    },
+
+    /// Remove accidentally doubled words, like `the the`.
+    DoubledWords {
+        /// Another word that can be doubled, like `had had`.
+        #[arg(long = "allow")]
+        allowed: Vec<String>,
+
+        /// Report doubled words instead of removing them.
+        #[arg(long)]
+        report_only: bool,
+    },
+
+    /// Hyphenate compound modifiers before nouns, like `high-speed rail`.
+    CompoundModifiers {
+        /// Another phrase to hyphenate before nouns.
+        #[arg(long = "phrase")]
+        phrases: Vec<String>,
+
+        /// Report compound modifiers instead of hyphenating them.
+        #[arg(long)]
+        report_only: bool,
+    },
+
+    /// Report hedging and filler words and phrases, like `very` and `it is worth noting that`.
+    ///
+    /// Allow them in a paragraph with a `<!-- style-markdown-allow: very, simply -->` comment.
+    WeaselWords {
+        /// Another phrase to report.
+        #[arg(long = "phrase")]
+        phrases: Vec<String>,
+
+        /// A phrase to allow everywhere.
+        #[arg(long = "allow")]
+        allowed: Vec<String>,
+    },
+
+    /// Remove unneeded backslash escapes, like in `URL\_2`.
+    Escapes,
+
+    /// Write `>` markers on every line of blockquotes, including lazy continuation lines.
+    Blockquotes,
+
+    /// Normalize task list items to `- [ ]` and `- [x]`.
+    TaskLists {
+        /// Move completed items after the other items in their list.
+        #[arg(long)]
+        sort: bool,
+    },
+
+    /// Convert admonitions (GitHub alerts, MkDocs admonitions, and Obsidian callouts) to one style.
+    Admonitions {
+        /// Which style to write admonitions in.
+        #[arg(long, value_enum)]
+        to: AdmonitionStyle,
+    },
+
+    /// Convert wiki links, like `[[Page Name]]`, to Markdown links, or the reverse.
+    WikiLinks {
+        /// Which kind of links to convert to.
+        #[arg(long, value_enum, default_value_t = LinkKind::Markdown)]
+        to: LinkKind,
+
+        /// How to turn page names into file names.
+        #[arg(long, value_enum, default_value_t = SlugStyle::Kebab)]
+        slug: SlugStyle,
+
+        /// The extension of pages' files, which may be empty.
+        #[arg(long, default_value = ".md")]
+        extension: String,
+    },
+
+    /// Report misspelled words in prose, with suggestions.
+    Spell {
+        /// The Hunspell dictionary, as the path to its `.aff` and `.dic` files without the extension,
+        /// like `/usr/share/hunspell/en_US`.
+        ///
+        /// It's required, since dictionaries are installed in different places, if at all.
+        #[arg(long, value_parser = spell::parse_dictionary)]
+        dictionary: SpellDictionary,
+
+        /// A file of project words to accept, like names, with a word per line.
+        #[arg(long = "words", value_parser = spell::parse_word_list)]
+        word_lists: Vec<BTreeSet<String>>,
+
+        /// A JSON file mapping misspellings to corrections to apply.
+        #[arg(long, value_parser = spell::parse_corrections)]
+        corrections: Option<BTreeMap<String, String>>,
+
+        /// Ask how to fix each misspelling.
+        #[arg(long)]
+        interactive: bool,
+    },
+
+    /// Report lines longer than a limit, as a softer alternative to wrapping them.
+    LineLength {
+        /// The most characters a line can have.
+        #[arg(long, default_value_t = 100)]
+        max: usize,
+
+        /// Check tables too.
+        #[arg(long)]
+        include_tables: bool,
+
+        /// Check code blocks too.
+        #[arg(long)]
+        include_code: bool,
+
+        /// Check lines that are only too long because of URLs too.
+        #[arg(long)]
+        include_urls: bool,
+    },
+
+    /// Convert emoji shortcodes, like `:bullettrain_side:`, to Unicode emoji, or the reverse.
+    Emoji {
+        /// How to write emoji.
+        #[arg(long, value_enum, default_value_t = EmojiStyle::Unicode)]
+        to: EmojiStyle,
+    },
+
+    /// Format YAML front matter with sorted keys, consistent quoting, and `YYYY-MM-DD` dates.
+    FrontMatter {
+        /// Keys to put first, in this order, before the rest sorted alphabetically.
+        #[arg(long, value_delimiter = ',')]
+        order: Vec<String>,
+    },
+
+    /// Check prose against style rules, like banned phrases, preferred terms, and passive voice.
+    ProseLint {
+        /// A YAML rule file, reported with its severity and named after the file.
+        #[arg(long = "rules", value_parser = prose_lint::parse_rule_file, required = true)]
+        rules: Vec<ProseRule>,
+    },
+}
+
+/// How to end a file.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FinalNewline {
+    /// With exactly one newline, removing any blank lines at the end.
+    #[default]
+    One,
+
+    /// With whatever newlines it already ends with, if any.
+    Keep,
+
+    /// Without a newline.
+    #[value(name = "none")]
+    Strip,
+}
+
+impl FinalNewline {
+    /// What `text` should end with after its last line, or `None` to leave it as it is.
+    ///
+    /// The final newline is `\r\n` if the lines of `text` end with `\r\n`.
+    fn ending(self, text: &str) -> Option<&'static str> {
+        match self {
+            Self::One => Some(match text.split_once('\n') {
+                Some((line, _)) if line.ends_with('\r') => "\r\n",
+                _ => "\n",
+            }),
+            Self::Keep => None,
+            Self::Strip => Some(""),
+        }
+    }
+
+    /// Whether `text` already ends this way.
+    pub fn is_ended(self, text: &str) -> bool {
+        let len = text.trim_end_matches(['\r', '\n']).len();
+        self.ending(text)
+            .is_none_or(|ending| &text[len..] == ending)
+    }
+
+    /// End `text` this way.
+    pub fn end(self, text: &mut String) {
+        if let Some(ending) = self.ending(text) {
+            let len = text.trim_end_matches(['\r', '\n']).len();
+            text.truncate(len);
+            text.push_str(ending);
+        }
+    }
+}
+
+/// The style of a hard line break at the end of a line.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HardBreak {
+    /// Two trailing spaces.
+    Spaces,
+
+    /// A trailing backslash.
+    Backslash,
+
+    /// A trailing `<br>`.
+    Html,
+}
+
+impl HardBreak {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Spaces => "  ",
+            Self::Backslash => "\\",
+            Self::Html => "<br>",
+        }
+    }
+}
+
+/// A rule, from the subcommands of [`Command`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleInfo {
+    pub name: String,
+
+    /// Other names for the rule, like `sembr` for `semantic-line-breaks`.
+    pub aliases: Vec<String>,
+
+    /// What the rule does.
+    pub about: String,
+
+    /// The rules it [runs after](ordering::RUNS_BEFORE), if they're also run.
+    pub runs_after: Vec<String>,
+}
+
+impl RuleInfo {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "aliases": self.aliases,
+            "about": self.about,
+            "runs_after": self.runs_after,
+        })
+    }
+}
+
+/// A [`Command`] on its own, to parse it from its arguments.
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+struct Rule {
+    #[command(subcommand)]
+    command: Command,
+}
+
+impl Command {
+    /// Parse a command from its arguments, like `dashes --style unicode`,
+    /// which are split on whitespace without any quoting.
+    pub fn from_rule(rule: &str) -> eyre::Result<Self> {
+        let name = rule.split_whitespace().next().unwrap_or_default();
+        let is_known = Self::rules()
+            .iter()
+            .any(|rule| rule.name == name || rule.aliases.iter().any(|alias| alias == name));
+        if !is_known && !name.is_empty() && !name.starts_with('-') {
+            return Err(match Self::suggest(name) {
+                Some(suggestion) => eyre!("unknown rule `{name}`; did you mean `{suggestion}`?"),
+                None => eyre!("unknown rule `{name}`"),
+            });
+        }
+        let Rule { command } = Rule::try_parse_from(rule.split_whitespace())?;
+        Ok(command)
+    }
+
+    /// Every rule, in the order they're defined.
+    pub fn rules() -> Vec<RuleInfo> {
+        let rules = Self::augment_subcommands(clap::Command::new("rules"));
+        rules
+            .get_subcommands()
+            .map(|rule| RuleInfo {
+                name: rule.get_name().to_owned(),
+                aliases: rule.get_all_aliases().map(str::to_owned).collect(),
+                about: rule
+                    .get_about()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+                runs_after: ordering::runs_after(rule.get_name())
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// The name or alias of the rule most like `name`, if one is close enough to be a typo of it.
+    pub fn suggest(name: &str) -> Option<String> {
+        let max_distance = (name.chars().count() / 4).max(2);
+        Self::rules()
+            .into_iter()
+            .flat_map(|rule| [rule.name].into_iter().chain(rule.aliases))
+            .map(|candidate| (strsim::damerau_levenshtein(name, &candidate), candidate))
+            .filter(|&(distance, _)| distance <= max_distance)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, candidate)| candidate)
+    }
+
+    /// Whether the command reads other files or the network, not just the file it rewrites.
+    pub fn needs_filesystem(&self) -> bool {
+        match self {
+            Self::FileLinks { .. } => true,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::DeadLinks { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the command only looks within each block of a document,
+    /// so that it can style a huge document one block at a time with `--stream`.
+    ///
+    /// Rules that use the rest of the document, like which references are defined,
+    /// or that change how blocks are separated, aren't.
+    pub fn is_block_local(&self) -> bool {
+        matches!(
+            self,
+            Self::Quotes { .. }
+                | Self::EmbeddedImages
+                | Self::ExtraRefSpaces
+                | Self::SimplifyUrls
+                | Self::SemanticLineBreaks
+                | Self::ThroughRunning
+                | Self::FootnotesAfterPunctuation
+                | Self::TrailingWhitespace { .. }
+                | Self::HardBreaks { .. }
+                | Self::Dashes { .. }
+                | Self::Ellipses { .. }
+                | Self::NonBreakingSpaces { .. }
+                | Self::Nfc
+                | Self::InvisibleChars { .. }
+                | Self::ThousandsSeparators { .. }
+                | Self::Units { .. }
+                | Self::Emoji { .. }
+        )
+    }
+
+    /// The name of the subcommand, like `trailing-whitespace`.
+    pub fn name(&self) -> String {
+        let debug = format!("{self:?}");
+        let variant = debug
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        let mut name = String::new();
+        for (i, c) in variant.char_indices() {
+            if c.is_uppercase() && i > 0 {
+                name.push('-');
+            }
+            name.push(c.to_ascii_lowercase());
+        }
+        name
+    }
+
+    /// What the text the command could change contains, if it can be known ahead of time.
+    pub fn trigger(&self) -> Option<Trigger> {
+        let trigger = match *self {
+            Self::Quotes {
+                to: QuoteStyle::Ascii,
+                ..
+            } => Trigger::CurlyQuote,
+            Self::Quotes {
+                to: QuoteStyle::Smart,
+                ..
+            } => Trigger::StraightQuote,
+            Self::EmbeddedImages => Trigger::DataImage,
+            Self::ExtraRefSpaces => Trigger::RefSpaces,
+            Self::SimplifyUrls => Trigger::InlineLink,
+            Self::ThroughRunning => Trigger::ThroughRun,
+            Self::FootnotesAfterPunctuation => Trigger::FootnoteBeforePunctuation,
+            Self::TrailingWhitespace { .. } => Trigger::TrailingWhitespace,
+            Self::RenumberFootnotes { .. }
+            | Self::SortFootnoteDefinitions
+            | Self::FootnotesToEnd { .. }
+            | Self::FootnoteCheck { .. }
+            | Self::InlineFootnotes {
+                to: FootnoteStyle::Inline,
+            } => Trigger::Footnote,
+            Self::InlineFootnotes {
+                to: FootnoteStyle::Reference,
+            } => Trigger::InlineFootnote,
+            Self::HtmlFormatting => Trigger::Html,
+            Self::HtmlImages { .. } => Trigger::Image,
+            Self::HtmlComments { .. } => Trigger::HtmlComment,
+            Self::BareUrls { .. } | Self::CanonicalUrls { .. } => Trigger::Url,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::DeadLinks { .. } => Trigger::Url,
+            Self::Ellipses { .. } => Trigger::Ellipsis,
+            Self::Nfc | Self::InvisibleChars { .. } => Trigger::NonAscii,
+            Self::CodeFences { .. } | Self::CodeLanguages { .. } => Trigger::Fence,
+            Self::ThousandsSeparators { .. } | Self::Units { .. } => Trigger::Digit,
+            Self::Escapes => Trigger::Backslash,
+            Self::Blockquotes => Trigger::Blockquote,
+            Self::Emoji {
+                to: EmojiStyle::Unicode,
+            } => Trigger::Colon,
+            Self::Emoji {
+                to: EmojiStyle::Shortcode,
+            } => Trigger::NonAscii,
+            _ => return None,
+        };
+        Some(trigger)
+    }
+
+    /// Whether the command can't change text with `triggers`, so it can be skipped.
+    pub fn is_skipped(&self, triggers: &Triggers) -> bool {
+        self.trigger()
+            .is_some_and(|trigger| !triggers.contains(trigger))
+    }
+
+    /// Rewrite `before` with the rules that don't need to own it,
+    /// and so can borrow it back if they don't change anything.
+    fn rewrite_borrowed<'a>(&self, before: &'a str) -> Option<Cow<'a, str>> {
+        let after = match *self {
+            Self::Quotes { to, locale } => typography::convert_quotes(before, to, locale),
+            Self::EmbeddedImages => remove_embedded_images(before),
+            Self::ExtraRefSpaces => remove_extra_ref_spaces(before),
+            Self::SimplifyUrls => simplify_urls(before),
+            Self::ThroughRunning => canonicalize_through_running(before),
+            Self::FootnotesAfterPunctuation => move_footnotes_after_punctuation(before),
+            _ => return None,
+        };
+        Some(after)
+    }
+
+    /// Like [`Command::rewrite`], but without needing to own `before`,
+    /// which is borrowed back if nothing changed,
+    /// so that a clean document isn't copied and whether it changed is already known.
+    pub fn rewrite_str<'a>(
+        &self,
+        path: &Path,
+        before: &'a str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Cow<'a, str> {
+        if let Some(after) = self.rewrite_borrowed(before) {
+            return after;
+        }
+        let after = self.rewrite(path, before.to_owned(), diagnostics);
+        match after == before {
+            true => Cow::Borrowed(before),
+            false => Cow::Owned(after),
+        }
+    }
+
+    pub fn rewrite(
+        &self,
+        path: &Path,
+        before: String,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> String {
+        let is_unchanged = match self.rewrite_borrowed(&before) {
+            Some(Cow::Owned(after)) => return after,
+            Some(Cow::Borrowed(_)) => true,
+            None => false,
+        };
+        if is_unchanged {
+            return before;
+        }
+        match *self {
+            Self::Quotes { .. }
+            | Self::EmbeddedImages
+            | Self::ExtraRefSpaces
+            | Self::SimplifyUrls
+            | Self::ThroughRunning
+            | Self::FootnotesAfterPunctuation => unreachable!("rewritten while borrowed"),
+            Self::SemanticLineBreaks => add_semantic_line_breaks(before),
+            Self::BlankLines { max } => collapse_blank_lines(before, max),
+            Self::BlockSpacing => separate_blocks(before),
+            Self::TrailingWhitespace { hard_breaks } => {
+                remove_trailing_whitespace(before, hard_breaks)
+            }
+            Self::HardBreaks { to } => convert_hard_breaks(before, to),
+            Self::RefDefinitions { keep_unused } => {
+                references::dedup_definitions(before, keep_unused)
+            }
+            Self::SortRefDefinitions { by, per_section } => {
+                references::sort_definitions(before, by, per_section)
+            }
+            Self::RenumberFootnotes { labels } => footnotes::renumber_footnotes(before, labels),
+            Self::SortFootnoteDefinitions => footnotes::sort_footnote_definitions(before),
+            Self::FootnotesToEnd { ref heading } => {
+                footnotes::move_footnote_definitions_to_end(before, heading.as_deref())
+            }
+            Self::FootnoteCheck { fix } => footnotes::check_footnotes(before, fix, diagnostics),
+            Self::InlineFootnotes { to } => footnotes::convert_inline_footnotes(before, to),
+            Self::HtmlFormatting => html::convert_html_formatting(before),
+            Self::HtmlImages {
+                keep_dropped_attributes,
+            } => html::convert_html_images(before, keep_dropped_attributes),
+            Self::HtmlComments { ref keep } => html::strip_html_comments(before, keep),
+            Self::ThematicBreaks { ref style } => normalize_thematic_breaks(before, style),
+            Self::BareUrls { style } => links::link_bare_urls(before, style),
+            Self::Dashes {
+                style,
+                spaced_em_dashes,
+            } => typography::convert_dashes(before, style, spaced_em_dashes),
+            Self::Ellipses { style } => typography::convert_ellipses(before, style),
+            Self::NonBreakingSpaces {
+                ref units,
+                strip_other,
+            } => typography::add_non_breaking_spaces(before, units, strip_other),
+            Self::Nfc => typography::normalize_nfc(before, diagnostics),
+            Self::InvisibleChars { ref allowed } => {
+                typography::remove_invisible_chars(before, allowed)
+            }
+            Self::CodeFences { style } => code_blocks::normalize_code_fences(before, style),
+            Self::CodeLanguages { fix, ref default } => {
+                code_blocks::check_code_languages(before, fix, default.as_deref(), diagnostics)
+            }
+            Self::ListIndentation { width } => lists::normalize_list_indentation(before, width),
+            Self::ListMarkers { marker } => lists::normalize_list_markers(before, marker),
+            Self::FenceIndentedCode { ref language } => {
+                code_blocks::fence_indented_code(before, language.as_deref())
+            }
+            Self::Toc {
+                min_depth,
+                max_depth,
+                ref excluded,
+            } => headings::update_toc(before, (min_depth, max_depth), excluded),
+            Self::NumberHeadings {
+                min_depth,
+                max_depth,
+                strip,
+            } => headings::number_headings(before, (min_depth, max_depth), strip),
+            Self::AtxHeadings => headings::convert_to_atx_headings(before),
+            Self::HeadingSpacing {
+                before: blank_before,
+                after: blank_after,
+                hashtags,
+            } => headings::normalize_heading_spacing(before, blank_before, blank_after, hashtags),
+            Self::DuplicateHeadings { fix } => {
+                headings::check_duplicate_headings(before, fix, diagnostics)
+            }
+            Self::AnchorLinks { fix } => headings::check_anchor_links(before, fix, diagnostics),
+            Self::FileLinks { ref renames, fix } => {
+                links::check_file_links(before, path, renames, fix, diagnostics)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::DeadLinks {
+                wayback,
+                keep_original,
+            } => dead_links::check_dead_links(before, wayback, keep_original, diagnostics),
+            Self::CanonicalUrls { ref params } => links::canonicalize_urls(before, params),
+            Self::AltText {
+                fix,
+                ref mapping,
+                from_file_name,
+            } => images::check_alt_text(before, fix, mapping.as_ref(), from_file_name, diagnostics),
+            Self::ReferenceImages { to } => images::convert_reference_images(before, to),
+            Self::Capitalization { ref terms } => terms::fix_capitalization(before, terms),
+            Self::ThousandsSeparators {
+                separator,
+                min_digits,
+            } => typography::add_thousands_separators(before, separator, min_digits),
+            Self::Units {
+                ref aliases,
+                non_breaking,
+            } => typography::canonicalize_units(before, aliases, non_breaking),
+            Self::DoubledWords {
+                ref allowed,
+                report_only,
+            } => prose::remove_doubled_words(before, allowed, report_only, diagnostics),
+            Self::WeaselWords {
+                ref phrases,
+                ref allowed,
+            } => prose::report_weasel_words(before, phrases, allowed, diagnostics),
+            Self::CompoundModifiers {
+                ref phrases,
+                report_only,
+            } => prose::hyphenate_compound_modifiers(before, phrases, report_only, diagnostics),
+            Self::Escapes => escapes::remove_unneeded_escapes(before),
+            Self::Blockquotes => blockquotes::normalize_blockquotes(before),
+            Self::TaskLists { sort } => lists::normalize_task_lists(before, sort),
+            Self::Admonitions { to } => admonitions::convert_admonitions(before, to),
+            Self::WikiLinks {
+                to,
+                slug,
+                ref extension,
+            } => wiki_links::convert_wiki_links(before, to, slug, extension),
+            Self::Spell {
+                ref dictionary,
+                ref word_lists,
+                ref corrections,
+                interactive,
+            } => {
+                let words = word_lists.iter().flatten().cloned().collect::<Vec<_>>();
+                let mut ask = spell::ask;
+                let choose = match interactive {
+                    true => Some(&mut ask as &mut spell::Choose),
+                    false => None,
+                };
+                spell::check_spelling(
+                    before,
+                    dictionary,
+                    &words,
+                    corrections.as_ref(),
+                    choose,
+                    diagnostics,
+                )
+            }
+            Self::LineLength {
+                max,
+                include_tables,
+                include_code,
+                include_urls,
+            } => {
+                let included = Included {
+                    tables: include_tables,
+                    code: include_code,
+                    urls: include_urls,
+                };
+                line_length::check_line_length(before, max, included, diagnostics)
+            }
+            Self::Emoji { to } => emoji::convert_emoji(before, to),
+            Self::FrontMatter { ref order } => {
+                front_matter::format_front_matter(before, order, diagnostics)
+            }
+            Self::ProseLint { ref rules } => prose_lint::lint_prose(before, rules, diagnostics),
+        }
+    }
+}
+
+/// Text rewritten by multiple commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rewritten {
+    pub text: String,
+
+    /// The diagnostics of each command, with its name.
+    pub diagnostics: Vec<(String, Diagnostic)>,
+}
+
+impl Rewritten {
+    pub fn to_json(&self) -> serde_json::Value {
+        let diagnostics = self
+            .diagnostics
+            .iter()
+            .map(|(rule, diagnostic)| {
+                let mut json = diagnostic.to_json();
+                json["rule"] = rule.as_str().into();
+                json
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "text": self.text,
+            "diagnostics": diagnostics,
+        })
+    }
+
+    /// Whether any diagnostics are warnings.
+    pub fn has_warnings(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|(_, diagnostic)| diagnostic.severity == Severity::Warning)
+    }
+}
+
+/// Rewrite `text` of the file at `path` with each of `commands` in order,
+/// ending it like `final_newline`, like when rewriting files.
+///
+/// Commands that can't change the text are skipped, which is found with [`Triggers`]
+/// for all of them in one pass over the text, repeated only after it changes.
+pub fn rewrite(
+    path: &Path,
+    text: String,
+    commands: &[Command],
+    final_newline: FinalNewline,
+) -> eyre::Result<Rewritten> {
+    let dialect = Dialect::from_path(path).unwrap_or_else(markdown::dialect);
+    rewrite_as(path, text, commands, dialect, final_newline)
+}
+
+/// [`rewrite`] parsing `text` as `dialect`, instead of guessing it from `path`.
+pub fn rewrite_as(
+    path: &Path,
+    text: String,
+    commands: &[Command],
+    dialect: Dialect,
+    final_newline: FinalNewline,
+) -> eyre::Result<Rewritten> {
+    let mut text = text;
+    let mut all_diagnostics = Vec::new();
+    let mut triggers = None;
+    for command in commands {
+        ensure!(
+            cfg!(not(target_arch = "wasm32")) || !command.needs_filesystem(),
+            "`{}` needs a filesystem, which WebAssembly doesn't have",
+            command.name()
+        );
+        if command.is_skipped(triggers.get_or_insert_with(|| Triggers::scan(&text))) {
+            continue;
+        }
+        let mut diagnostics = Vec::new();
+        let rewritten = match markdown::with_dialect(dialect, || {
+            command.rewrite_str(path, &text, &mut diagnostics)
+        }) {
+            Cow::Owned(after) => Some(after),
+            Cow::Borrowed(_) => None,
+        };
+        if let Some(after) = rewritten {
+            Error::check_output(path, &command.name(), &text, &after)?;
+            text = after;
+            // The text changed, so scan it again for the next commands.
+            triggers = None;
+        }
+        let name = command.name();
+        all_diagnostics.extend(
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| (name.clone(), diagnostic)),
+        );
+    }
+    final_newline.end(&mut text);
+    Ok(Rewritten {
+        text,
+        diagnostics: all_diagnostics,
+    })
+}
+
+fn remove_embedded_images(before: &str) -> Cow<'_, str> {
+    static DATA_IMAGE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"<data:image/[^>\n]*>").unwrap());
+    let lines = markdown::lines(before).collect::<Vec<_>>();
+    let definitions = references::definitions(before)
+        .into_iter()
+        .map(|(definition_lines, definition)| {
+            (definition_lines.start, (definition_lines, definition))
+        })
+        .collect::<HashMap<_, _>>();
+    let mut changed = false;
+    let mut after = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        // A definition's destination doesn't need `<>`, and can be on the next line.
+        if let Some((definition_lines, definition)) = definitions.get(&i) {
+            let text = lines[definition_lines.clone()]
+                .iter()
+                .map(|line| line.text)
+                .join("\n");
+            i = definition_lines.end;
+            if !definition.url().starts_with("data:image/") {
+                after.push(Cow::Owned(text));
+                continue;
+            }
+            // Only replace the destination, not a label that looks like it.
+            let (label, rest) = text.split_once("]:").unwrap();
+            let rest = rest.replacen(definition.destination, "TODO", 1);
+            after.push(Cow::Owned(format!("{label}]:{rest}")));
+            changed = true;
+            continue;
+        }
+        i += 1;
+        if line.kind != LineKind::Text || !line.text.contains("<data:image/") {
+            after.push(Cow::Borrowed(line.text));
+            continue;
+        }
+        let rewritten = markdown::rewrite_outside_code(line.text, |text| {
+            DATA_IMAGE.replace_all(text, "TODO").into_owned()
+        });
+        changed = changed || rewritten != line.text;
+        after.push(Cow::Owned(rewritten));
+    }
+    match changed {
+        true => Cow::Owned(after.join("\n")),
+        false => Cow::Borrowed(before),
+    }
+}
+
+fn remove_extra_ref_spaces(before: &str) -> Cow<'_, str> {
+    static REF_WITH_SPACES: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?<label> {0,3}\[[^\]]+\]:)(?<spaces>[ \t]+)(?<rest>.*)$").unwrap()
+    });
+    let definition_starts = references::definitions(before)
+        .into_iter()
+        .map(|(definition_lines, _)| definition_lines.start)
+        .collect::<HashSet<_>>();
+    let mut changed = false;
+    let after = markdown::lines(before)
+        .enumerate()
+        .map(|(i, line)| {
+            let Some(captures) = REF_WITH_SPACES.captures(line.text) else {
+                return Cow::Borrowed(line.text);
+            };
+            let label = &captures["label"];
+            let is_definition =
+                definition_starts.contains(&i) || label.trim_start().starts_with("[^");
+            // A destination on the next line doesn't need any space before it.
+            let rest = &captures["rest"];
+            let space = match rest.trim().is_empty() {
+                true => "",
+                false => " ",
+            };
+            if line.kind != LineKind::Text || !is_definition || &captures["spaces"] == space {
+                return Cow::Borrowed(line.text);
+            }
+            changed = true;
+            Cow::Owned(format!("{label}{space}{rest}"))
+        })
+        .collect::<Vec<_>>();
+    match changed {
+        true => Cow::Owned(after.join("\n")),
+        false => Cow::Borrowed(before),
+    }
+}
+
+fn simplify_urls(before: &str) -> Cow<'_, str> {
+    static LINK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r"\[(?<text>[^\]]*)\]\((?<link>{BALANCED_DESTINATION})\)"
+        ))
+        .unwrap()
+    });
+    let is_url = |captures: &Captures| captures["text"].replace('\\', "") == captures["link"];
+    if !LINK.captures_iter(before).any(|captures| is_url(&captures)) {
+        return Cow::Borrowed(before);
+    }
+    let after = LINK.replace_all(before, |captures: &Captures| match is_url(captures) {
+        true => format!("<{}>", &captures["link"]),
+        false => captures[0].to_string(),
+    });
+    after
+}
+
+fn add_semantic_line_breaks(before: String) -> String {
+    let max_line_length = 100;
+    // Lines shorter than this are always joined with the next line,
+    // even if that makes it too long, so that short fragments aren't left dangling.
+    let min_line_length = 20;
+
+    /// First, split each original line at the given punctuation regex.
+    /// Then rejoin lines before it gets longer than the line length.
+    ///
+    /// `punctuation` should have either a `before` or `after` capture name
+    /// depending on if it should go before or after the line break.
+    fn add_line_breaks<'a>(
+        punctuation: &Regex,
+        line: &'a str,
+        max_line_length: usize,
+        min_line_length: usize,
+    ) -> Cow<'a, str> {
+        // Don't break headings.
+        let is_heading = || line.trim_ascii_start().starts_with('#');
+        // Early optimization.
+        if line.len() < max_line_length || is_heading() {
+            return Cow::Borrowed(line);
+        }
+        let with_all_line_breaks = punctuation
+            // Replace punctuation plus space with punctuation plus newline,
+            // thus adding line breaks at all punctuation.
+            .replace_all(line, |captures: &Captures| {
+                if let Some(before) = captures.name("before") {
+                    format!("{}\n", before.as_str())
+                } else if let Some(after) = captures.name("after") {
+                    format!("\n{}", after.as_str())
+                } else {
+                    panic!("captures supposed to have either `before` xor `after` group, but is {captures:?}");
+                }
+            });
+        // For simplicity, the above is implemented by
+        // replacing the spaces after punctuation with a newline,
+        // so now split again to get the lines.
+        let fully_split_lines = with_all_line_breaks.split('\n');
+        // Newlines are manually added here.
+        let mut rejoined_lines = Vec::new();
+        let mut current_line_length = 0;
+        for line in fully_split_lines {
+            if current_line_length == 0 {
+                // It could be too long, but we can't split it anymore by punctuation.
+                rejoined_lines.push(line);
+                current_line_length = line.len();
+            } else if current_line_length + line.len() < max_line_length
+                || current_line_length < min_line_length
+            {
+                // There's room to join a line (or the current line is too short to stand alone),
+                // so join it with a space.
+                rejoined_lines.push(" ");
+                rejoined_lines.push(line);
+                current_line_length += line.len();
+            } else {
+                // The line is too long, so keep it split.
+                rejoined_lines.push("\n");
+                rejoined_lines.push(line);
+                current_line_length = line.len();
+            }
+        }
+        Cow::Owned(rejoined_lines.concat())
+    }
+
+    static OUTER_SEPARATORS: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?<before>[.!?;:]) +").unwrap());
+    static INNER_SEPARATORS: LazyLock<Regex> = LazyLock::new(|| {
+        // These are chosen somewhat subjectively.
+        // Usually they should be coordinating and subordinating conjunctions.
+        let line_starting_words = ["because", "that", "rather than", "of how", "in order to"];
+        let line_starting_words_regex = line_starting_words
+            .iter()
+            // Sort by more words first, so that they take priority in the regex.
+            .map(|conjunction| conjunction.split(' ').collect::<Vec<_>>())
+            .sorted_by(|a, b| a.len().cmp(&b.len()).reverse())
+            .map(|words| words.join(" "))
+            .join("|");
+        Regex::new(&format!(
+            r"(?<before>[,)\]]) +| +(?<after>\(|\[|{line_starting_words_regex})"
+        ))
+        .unwrap()
+    });
+
+    // Shortcode arguments aren't prose, so don't break them.
+    let after = markdown::rewrite_outside_opaque(&before, |text| {
+        markdown::rewrite_lines_in_parallel(text, |line| {
+            add_line_breaks(&OUTER_SEPARATORS, line, max_line_length, min_line_length)
+                .split_terminator('\n')
+                .map(|line| {
+                    add_line_breaks(&INNER_SEPARATORS, line, max_line_length, min_line_length)
+                })
+                .join("\n")
+        })
+    });
+    after
+}
+
+fn canonicalize_through_running(before: &str) -> Cow<'_, str> {
+    // Longest first, so that `through running` isn't matched as `through run`.
+    static THROUGH_RUNNING: LazyLock<AhoCorasick> = LazyLock::new(|| {
+        AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build([
+                "through running",
+                "running through",
+                "through run",
+                "run through",
+            ])
+            .unwrap()
+    });
+    if !THROUGH_RUNNING.is_match(before) {
+        return Cow::Borrowed(before);
+    }
+    let after = THROUGH_RUNNING.replace_all(
+        before,
+        &[
+            "through-running",
+            "through-running",
+            "through-run",
+            "through-run",
+        ],
+    );
+    Cow::Owned(after)
+}
+
+fn move_footnotes_after_punctuation(before: &str) -> Cow<'_, str> {
+    if !markdown::dialect().has_gfm_extensions() {
+        return Cow::Borrowed(before);
+    }
+    static FOOTNOTE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?<footnote>\[\^[^\]]*\])(?<punctuation>[.!?;,])").unwrap());
+    let after = FOOTNOTE.replace_all(before, |captures: &Captures| {
+        let (_, [footnote, punctuation]) = captures.extract();
+        format!("{punctuation}{footnote}")
+    });
+    after
+}
+
+fn collapse_blank_lines(before: String, max: usize) -> String {
+    let mut blank_lines = 0;
+    let after = markdown::lines(&before)
+        .filter(|line| {
+            if line.kind != LineKind::Text || !line.text.trim().is_empty() {
+                blank_lines = 0;
+                return true;
+            }
+            blank_lines += 1;
+            blank_lines <= max
+        })
+        .map(|line| line.text)
+        .join("\n");
+    after
+}
+
+/// Whether `line` starts a GFM table, i.e. `next` is its delimiter row, like `| --- | :-: |`.
+///
+/// Tables are a GFM extension, so there are none in CommonMark.
+fn is_table_start(line: &str, next: Option<&str>) -> bool {
+    static DELIMITER_ROW: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^ {0,3}\|?[ \t]*:?-+:?[ \t]*(?:\|[ \t]*:?-+:?[ \t]*)*\|?[ \t]*\r?$").unwrap()
+    });
+    markdown::dialect().has_gfm_extensions()
+        && line.contains('|')
+        && next.is_some_and(|next| next.contains('|') && DELIMITER_ROW.is_match(next))
+}
+
+/// Add a blank line between a paragraph and a code block, table, list, or blockquote right after it,
+/// and between a code block and a paragraph right after it,
+/// since not every renderer lets them interrupt a paragraph.
+///
+/// Lines right after a list, blockquote, or table continue it instead,
+/// and an ordered list can only interrupt a paragraph if it starts at 1,
+/// so those are left alone, since a blank line would change what they are.
+fn separate_blocks(before: String) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let line_definitions = references::line_definitions(&before);
+    let mut after = Vec::with_capacity(lines.len());
+    // Whether the previous line is in a paragraph, and not in a list, blockquote, or table.
+    let mut previous_is_paragraph = false;
+    let mut previous_closes_code = false;
+    let mut previous_is_blank = true;
+    let mut in_container = false;
+    let mut in_code = false;
+    for (i, line) in lines.iter().enumerate() {
+        let text = line.text;
+        // Keep `\r\n` line endings.
+        let blank = match text.ends_with('\r') {
+            true => "\r",
+            false => "",
+        };
+        let is_blank = text.trim().is_empty();
+        match line.kind {
+            LineKind::Text if is_blank => {}
+            LineKind::Text => {
+                let (indent, unindented) = markdown::expand_indent(text);
+                if previous_is_blank && indent == 0 {
+                    in_container = false;
+                }
+                let item = match indent < 4 && !markdown::is_thematic_break(unindented.trim()) {
+                    true => lists::parse_item(unindented),
+                    false => None,
+                };
+                let can_interrupt = item.is_some_and(|(marker, spaces)| {
+                    matches!(marker, "-" | "*" | "+" | "1." | "1)")
+                        && !unindented[marker.len() + spaces.len()..].trim().is_empty()
+                });
+                let is_item = item.is_some() && (!previous_is_paragraph || can_interrupt);
+                let is_quote = indent < 4 && unindented.starts_with('>');
+                let next = lines
+                    .get(i + 1)
+                    .filter(|next| next.kind == LineKind::Text)
+                    .map(|next| next.text);
+                let is_table = indent < 4 && is_table_start(text, next);
+                if previous_is_paragraph && (can_interrupt || is_quote || is_table) {
+                    after.push(blank);
+                }
+                in_container = in_container || is_item || is_quote || is_table;
+                let is_paragraph = !in_container
+                    && (previous_is_paragraph || indent < 4)
+                    && !markdown::is_heading(text)
+                    && !markdown::is_thematic_break(text.trim())
+                    && !unindented.starts_with('<')
+                    && line_definitions[i].is_none()
+                    && footnotes::definition_label(text).is_none();
+                if previous_closes_code && is_paragraph {
+                    after.push(blank);
+                }
+                previous_is_paragraph = is_paragraph;
+            }
+            LineKind::Fence if !in_code => {
+                if previous_is_paragraph {
+                    after.push(blank);
+                }
+                in_code = true;
+                previous_is_paragraph = false;
+            }
+            LineKind::Fence => in_code = false,
+            _ => previous_is_paragraph = false,
+        }
+        previous_closes_code = line.kind == LineKind::Fence && !in_code && !in_container;
+        if is_blank || line.kind != LineKind::Text {
+            previous_is_paragraph = false;
+        }
+        previous_is_blank = is_blank;
+        after.push(text);
+    }
+    after.join("\n")
+}
+
+fn remove_trailing_whitespace(before: String, hard_breaks: HardBreak) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let is_text = |i: usize| {
+        lines
+            .get(i)
+            .is_some_and(|line| line.kind == LineKind::Text && !line.text.trim().is_empty())
+    };
+    let after = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if line.kind == LineKind::Code {
+                return Cow::Borrowed(line.text);
+            }
+            // Keep `\r\n` line endings.
+            let (text, cr) = match line.text.strip_suffix('\r') {
+                Some(text) => (text, "\r"),
+                None => (line.text, ""),
+            };
+            let trimmed = text.trim_end_matches([' ', '\t']);
+            // Two or more spaces before a line ending is a hard line break,
+            // but only if the paragraph continues on the next line.
+            let is_hard_break = text.ends_with("  ")
+                && is_text(i)
+                && is_text(i + 1)
+                && !trimmed.trim_start().starts_with('#');
+            if is_hard_break {
+                Cow::Owned(format!("{trimmed}{}{cr}", hard_breaks.as_str()))
+            } else if trimmed.len() == text.len() {
+                Cow::Borrowed(line.text)
+            } else {
+                Cow::Owned(format!("{trimmed}{cr}"))
+            }
+        })
+        .join("\n");
+    after
+}
+
+/// Convert hard line breaks in any style (trailing spaces, a backslash, or `<br>`) to `style`.
+///
+/// Like in [`remove_trailing_whitespace`],
+/// only line ends followed by more of the same paragraph are hard line breaks.
+fn convert_hard_breaks(before: String, style: HardBreak) -> String {
+    static HARD_BREAK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)(?:[ \t]*<br[ \t]*/?>|(?<backslashes>\\+)| {2,})[ \t]*$").unwrap()
+    });
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let is_text = |i: usize| {
+        lines
+            .get(i)
+            .is_some_and(|line| line.kind == LineKind::Text && !line.text.trim().is_empty())
+    };
+    let after = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if !is_text(i) || !is_text(i + 1) || markdown::is_heading(line.text) {
+                return Cow::Borrowed(line.text);
+            }
+            let (text, cr) = match line.text.strip_suffix('\r') {
+                Some(text) => (text, "\r"),
+                None => (line.text, ""),
+            };
+            let Some(captures) = HARD_BREAK.captures(text) else {
+                return Cow::Borrowed(line.text);
+            };
+            // An escaped backslash isn't a hard line break.
+            let mut start = captures.get(0).unwrap().start();
+            if let Some(backslashes) = captures.name("backslashes") {
+                if backslashes.len() % 2 == 0 {
+                    return Cow::Borrowed(line.text);
+                }
+                start = backslashes.end() - 1;
+            }
+            let content = &text[..start];
+            if content.trim().is_empty() {
+                return Cow::Borrowed(line.text);
+            }
+            Cow::Owned(format!("{content}{}{cr}", style.as_str()))
+        })
+        .join("\n");
+    after
+}
+
+fn parse_thematic_break(style: &str) -> Result<String, String> {
+    match markdown::is_thematic_break(style) && style.trim() == style {
+        true => Ok(style.to_owned()),
+        false => Err(format!("`{style}` is not a thematic break")),
+    }
+}
+
+fn normalize_thematic_breaks(before: String, style: &str) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let is_paragraph = |i: usize| {
+        i.checked_sub(1)
+            .and_then(|i| lines.get(i))
+            .is_some_and(|line| {
+                line.kind == LineKind::Text
+                    && !line.text.trim().is_empty()
+                    && !markdown::is_heading(line.text)
+                    && !markdown::is_thematic_break(line.text)
+            })
+    };
+    let after = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if line.kind != LineKind::Text || !markdown::is_thematic_break(line.text) {
+                return Cow::Borrowed(line.text);
+            }
+            // Keep `\r\n` line endings.
+            let cr = match line.text.ends_with('\r') {
+                true => "\r",
+                false => "",
+            };
+            if !is_paragraph(i) {
+                return Cow::Owned(format!("{style}{cr}"));
+            }
+            if line.text.trim_start().starts_with('-') {
+                // A setext heading underline.
+                return Cow::Borrowed(line.text);
+            }
+            if style.starts_with('-') {
+                // Don't turn it into a setext heading underline.
+                return Cow::Owned(format!("{cr}\n{style}{cr}"));
+            }
+            Cow::Owned(format!("{style}{cr}"))
+        })
+        .join("\n");
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::add_semantic_line_breaks;
+    use crate::canonicalize_through_running;
+    use crate::collapse_blank_lines;
+    use crate::convert_hard_breaks;
+    use crate::move_footnotes_after_punctuation;
+    use crate::normalize_thematic_breaks;
+    use crate::remove_embedded_images;
+    use crate::remove_extra_ref_spaces;
+    use crate::remove_trailing_whitespace;
+    use std::path::Path;
+
+    use crate::diagnostic::Diagnostic;
+    use crate::markdown;
+    use crate::markdown::Dialect;
+    use crate::rewrite;
+    use crate::separate_blocks;
+    use crate::simplify_urls;
+    use crate::Command;
+    use crate::FinalNewline;
+    use crate::HardBreak;
+
+    #[test]
+    fn test_command_name() {
+        assert_eq!(Command::from_rule("quotes").unwrap().name(), "quotes");
+        let command = Command::TrailingWhitespace {
+            hard_breaks: HardBreak::Spaces,
+        };
+        assert_eq!(command.name(), "trailing-whitespace");
+    }
+
+    #[test]
+    fn test_from_rule() {
+        let command = Command::from_rule("blank-lines  --max 2").unwrap();
+        assert!(matches!(command, Command::BlankLines { max: 2 }));
+        assert!(Command::from_rule("no-such-rule").is_err());
+        assert!(Command::from_rule("").is_err());
+        let command = Command::from_rule("sembr").unwrap();
+        assert!(matches!(command, Command::SemanticLineBreaks));
+        let error = Command::from_rule("quotse --to smart").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown rule `quotse`; did you mean `quotes`?"
+        );
+        assert_eq!(
+            Command::suggest("semantic-linebreaks").unwrap(),
+            "semantic-line-breaks"
+        );
+        assert_eq!(Command::suggest("nbps").unwrap(), "nbsp");
+        assert_eq!(Command::suggest("unrelated"), None);
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = Command::rules();
+        assert_eq!(rules[0].name, "quotes");
+        assert_eq!(rules[0].aliases, ["dequote"]);
+        assert!(rules[0].about.starts_with("Replace fancy"));
+        assert!(rules.iter().any(|rule| rule.name == "semantic-line-breaks"));
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let commands = [
+            Command::from_rule("quotes").unwrap(),
+            Command::from_rule("doubled-words --report-only").unwrap(),
+        ];
+        let rewritten = rewrite(
+            Path::new("a.md"),
+            "‘Hi’ the the end".into(),
+            &commands,
+            FinalNewline::One,
+        )
+        .unwrap();
+        assert_eq!(rewritten.text, "'Hi' the the end\n");
+        assert_eq!(
+            rewritten.diagnostics,
+            [(
+                "doubled-words".into(),
+                Diagnostic::new(1, "doubled word `the`")
+            )]
+        );
+        assert!(rewritten.has_warnings());
+    }
+
+    #[test]
+    fn test_final_newline() {
+        let end = |final_newline: FinalNewline, text: &str| {
+            let mut text = text.to_owned();
+            final_newline.end(&mut text);
+            text
+        };
+        assert_eq!(end(FinalNewline::One, "a"), "a\n");
+        assert_eq!(end(FinalNewline::One, "a\n\n\n"), "a\n");
+        assert_eq!(end(FinalNewline::One, "a\r\nb\r\n\r\n"), "a\r\nb\r\n");
+        assert_eq!(end(FinalNewline::Keep, "a\n\n"), "a\n\n");
+        assert_eq!(end(FinalNewline::Strip, "a\r\nb\r\n"), "a\r\nb");
+        assert!(FinalNewline::One.is_ended("a\r\n"));
+        assert!(!FinalNewline::One.is_ended("a\r\n\n"));
+        assert!(FinalNewline::Keep.is_ended("a"));
+        assert!(!FinalNewline::Strip.is_ended("a\n"));
+    }
+
+    #[test]
+    fn test_rewrite_str() {
+        let path = Path::new("a.md");
+        for rule in ["quotes", "escapes"] {
+            let command = Command::from_rule(rule).unwrap();
+            let after = command.rewrite_str(path, "Clean.\n", &mut Vec::new());
+            assert!(matches!(after, Cow::Borrowed("Clean.\n")));
+        }
+        let after =
+            Command::from_rule("quotes")
+                .unwrap()
+                .rewrite_str(path, "“a”\n", &mut Vec::new());
+        assert!(matches!(after, Cow::Owned(after) if after == "\"a\"\n"));
+    }
+
+    #[test]
+    fn test_remove_embedded_images() {
+        let before = "[image1]: <data:image/png;base64,iVBORw0KGgoAAAAN>
+
+[image2]: <data:image/png;base64,iVBORw0KGgoAAAANS>";
+        let after = "[image1]: TODO
+
+[image2]: TODO";
+        assert_eq!(remove_embedded_images(before), after);
+        let before = "[data:image/png]:
+  data:image/png;base64,iVBORw0KGgo
+  \"Chart\"
+
+```
+<data:image/png;base64,iVBORw0KGgo>
+```";
+        let after = "[data:image/png]:
+  TODO
+  \"Chart\"
+
+```
+<data:image/png;base64,iVBORw0KGgo>
+```";
+        assert_eq!(remove_embedded_images(before), after);
+    }
+
+    #[test]
+    fn test_remove_extra_ref_spaces() {
+        let before = "[^2]:    hello";
+        let after = "[^2]: hello";
+        assert_eq!(remove_extra_ref_spaces(before), after);
+        let before = "[a]:   /a  'A'\n[b]:  \n  /b\n\n    [c]:   /c\n\nSee [d]:   here.";
+        let after = "[a]: /a  'A'\n[b]:\n  /b\n\n    [c]:   /c\n\nSee [d]:   here.";
+        assert_eq!(remove_extra_ref_spaces(before), after);
+        // Tabs and a space before a destination on the next line are also triggered.
+        let commands = [Command::from_rule("extra-ref-spaces").unwrap()];
+        let rewritten = rewrite(
+            Path::new("a.md"),
+            "[a]:\t/a\n[b]: \n  /b\n".into(),
+            &commands,
+            FinalNewline::One,
+        )
+        .unwrap();
+        assert_eq!(rewritten.text, "[a]: /a\n[b]:\n  /b\n");
+    }
+
+    #[test]
+    fn test_simplify_urls() {
+        let before = r"[URL](URL), [URL\_2](URL_2)";
+        let after = "<URL>, <URL_2>";
+        assert_eq!(simplify_urls(before), after);
+        let before =
+            "[w.org/Mercury_(planet)](w.org/Mercury_(planet)) and [w.org/a_(b](w.org/a_(b)";
+        let after = "<w.org/Mercury_(planet)> and [w.org/a_(b](w.org/a_(b)";
+        assert_eq!(simplify_urls(before), after);
+    }
+
+    #[test]
+    fn test_add_semantic_line_breaks() {
+        let before = "
+# A Not-So-Capital Plan Part 2: The Future is Electric
+
+Metro-North's M8 can run on catenary power (left[^M8-catenary-pantograph-citation]) or on either over- or under-running third rails (shoe seen at right[^M8-third-rail-shoe-citation]).
+
+## Introduction
+
+In major cities all across the globe, electric trains form the backbone of urban transportation. The benefits of electrification are simply too great to ignore. Electric trains accelerate faster, reduce overall journey times, and provide a higher-quality passenger experience than their diesel-powered counterparts, all while being cheaper to run and maintain. Electric trains are also a powerful tool for decarbonization: they can easily run on non-carbon fuel sources and produce no local pollution. It is rare that a single technology can reduce both pollution and costs while also actually improving service, but electric rail can accomplish just that. That is why the future of rail is electric around both the country and the world.
+        ";
+        let after = "
+# A Not-So-Capital Plan Part 2: The Future is Electric
+
+Metro-North's M8 can run on catenary power (left[^M8-catenary-pantograph-citation])
+or on either over- or under-running third rails (shoe seen at right[^M8-third-rail-shoe-citation]).
+
+## Introduction
+
+In major cities all across the globe, electric trains form the backbone of urban transportation.
+The benefits of electrification are simply too great to ignore.
+Electric trains accelerate faster, reduce overall journey times,
+and provide a higher-quality passenger experience than their diesel-powered counterparts,
+all while being cheaper to run and maintain.
+Electric trains are also a powerful tool for decarbonization:
+they can easily run on non-carbon fuel sources and produce no local pollution.
+It is rare that a single technology can reduce both pollution and costs while also actually improving service,
+but electric rail can accomplish just that.
+That is why the future of rail is electric around both the country and the world.
+        ";
+        assert_eq!(add_semantic_line_breaks(before.into()), after);
+    }
+
+    #[test]
+    fn test_semantic_line_breaks_short_fragments() {
+        // Breaking before `that` would leave `It is rare` on its own,
+        // so it's joined with the next line, even though that's over 100 characters.
+        let before = "It is rare that a single technology can reduce both pollution and costs while also improving the service.";
+        assert_eq!(add_semantic_line_breaks(before.into()), before);
+        // A longer first part is broken as usual.
+        let before = "It is quite rare in practice that a single technology can reduce both pollution and costs while also improving service.";
+        let after = "It is quite rare in practice\nthat a single technology can reduce both pollution and costs while also improving service.";
+        assert_eq!(add_semantic_line_breaks(before.into()), after);
+    }
+
+    #[test]
+    fn test_canonicalize_through_running() {
+        let before = "through-running, through running, running through, through-run, through run, run through";
+        let after = "through-running, through-running, through-running, through-run, through-run, through-run";
+        assert_eq!(canonicalize_through_running(before), after);
+    }
+
+    #[test]
+    fn test_move_footnotes_after_punctuation() {
+        let before = "[^1].";
+        let after = ".[^1]";
+        assert_eq!(move_footnotes_after_punctuation(before), after);
+    }
+
+    #[test]
+    fn test_collapse_blank_lines() {
+        let before = "a
+
+
+
+b
+
+```
+
+
+```
+c";
+        let after = "a
+
+b
+
+```
+
+
+```
+c";
+        assert_eq!(collapse_blank_lines(before.into(), 1), after);
+    }
+
+    #[test]
+    fn test_separate_blocks() {
+        let before = "Text
+```
+code
+```
+More
+- item
+lazy
+- item
+
+Para
+> quote
+lazy
+
+Para
+| a | b |
+| - | - |
+| 1 | 2 |
+
+Text
+2019. Not a list
+```
+x
+```
+
+- item
+  ```
+  code
+  ```
+  more
+";
+        let after = "Text
+
+```
+code
+```
+
+More
+
+- item
+lazy
+- item
+
+Para
+
+> quote
+lazy
+
+Para
+
+| a | b |
+| - | - |
+| 1 | 2 |
+
+Text
+2019. Not a list
+
+```
+x
+```
+
+- item
+  ```
+  code
+  ```
+  more
+";
+        assert_eq!(separate_blocks(before.into()), after);
+        assert_eq!(separate_blocks(after.into()), after);
+        let commonmark = markdown::with_dialect(Dialect::CommonMark, || {
+            separate_blocks("a\n| b |\n| - |\n".into())
+        });
+        assert_eq!(commonmark, "a\n| b |\n| - |\n");
+    }
+
+    #[test]
+    fn test_remove_trailing_whitespace() {
+        let before = "a \t
+b   
+c  
+\t
+# d  
+e\t
+```
+f  
+```";
+        let after = "a
+b  
+c
+
+# d
+e
+```
+f  
+```";
+        assert_eq!(
+            remove_trailing_whitespace(before.into(), HardBreak::Spaces),
+            after
+        );
+        let after = after.replacen("b  ", "b\\", 1);
+        assert_eq!(
+            remove_trailing_whitespace(before.into(), HardBreak::Backslash),
+            after
+        );
+    }
+
+    #[test]
+    fn test_convert_hard_breaks() {
+        let before = "a  \nb\\\nc<br>\nd <br />\ne\\\\\nf  \n\n# g  \nh";
+        assert_eq!(
+            convert_hard_breaks(before.into(), HardBreak::Backslash),
+            "a\\\nb\\\nc\\\nd\\\ne\\\\\nf  \n\n# g  \nh"
+        );
+        assert_eq!(
+            convert_hard_breaks(before.into(), HardBreak::Html),
+            "a<br>\nb<br>\nc<br>\nd<br>\ne\\\\\nf  \n\n# g  \nh"
+        );
+        assert_eq!(
+            convert_hard_breaks(before.into(), HardBreak::Spaces),
+            "a  \nb  \nc  \nd  \ne\\\\\nf  \n\n# g  \nh"
+        );
+    }
+
+    #[test]
+    fn test_normalize_thematic_breaks() {
+        let before = "---
+title: a
+---
+
+***
+
+- - -
+a
+---
+b
+___
+```
+***
+```
+_____";
+        let after = "---
+title: a
+---
+
+---
+
+---
+a
+---
+b
+
+---
+```
+***
+```
+---";
+        assert_eq!(normalize_thematic_breaks(before.into(), "---"), after);
+        let before = "a\r\n\r\n***\r\nb\r\n* * *\r\n";
+        let after = "a\r\n\r\n---\r\nb\r\n\r\n---\r\n";
+        assert_eq!(normalize_thematic_breaks(before.into(), "---"), after);
+    }
+}
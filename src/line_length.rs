@@ -0,0 +1,124 @@
+//! Line length, as a softer alternative to wrapping lines.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::diagnostic::Diagnostic;
+use crate::graphemes;
+use crate::markdown;
+use crate::markdown::LineKind;
+use crate::references::Definition;
+
+/// Which lines are checked even though they usually can't be wrapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Included {
+    pub tables: bool,
+    pub code: bool,
+    pub urls: bool,
+}
+
+/// Report lines longer than `max` characters, counting graphemes,
+/// so that an emoji or an accented letter is one character.
+///
+/// Tables, code, and lines that are only too long because of URLs aren't reported,
+/// unless `included`.
+/// Tables are a GFM extension, so in CommonMark they're checked like any other line.
+pub fn check_line_length(
+    before: String,
+    max: usize,
+    included: Included,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    static URL: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?i)\bhttps?://[^\s<>)]+").unwrap());
+    for (i, line) in markdown::lines(&before).enumerate() {
+        let len = graphemes::count(line.text.trim_end());
+        if len <= max {
+            continue;
+        }
+        let is_excluded = match line.kind {
+            LineKind::Code | LineKind::Fence => !included.code,
+            LineKind::FrontMatter | LineKind::Opaque => true,
+            LineKind::Text => {
+                let is_table = markdown::dialect().has_gfm_extensions()
+                    && line.text.trim_start().starts_with('|');
+                let without_urls = match Definition::parse(line.text) {
+                    Some(_) => String::new(),
+                    None => URL.replace_all(line.text, "").into_owned(),
+                };
+                let is_url = graphemes::count(without_urls.trim_end()) <= max;
+                (is_table && !included.tables) || (is_url && !included.urls)
+            }
+        };
+        if !is_excluded {
+            diagnostics.push(Diagnostic::new(
+                i + 1,
+                format!("line is {len} characters long, over the limit of {max}"),
+            ));
+        }
+    }
+    before
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::line_length::check_line_length;
+    use crate::line_length::Included;
+    use crate::markdown;
+    use crate::markdown::Dialect;
+
+    #[test]
+    fn test_check_line_length() {
+        let before = "A short line.
+A line that is much too long for the limit.
+See [the report](https://example.com/a/very/long/path).
+| a table | row that is long |
+
+```
+let code = \"long enough to report\";
+```
+
+[def]: https://example.com/another/long/path";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_line_length(before.into(), 20, Included::default(), &mut diagnostics),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [Diagnostic::new(
+                2,
+                "line is 43 characters long, over the limit of 20"
+            )]
+        );
+        let included = Included {
+            tables: true,
+            code: true,
+            urls: true,
+        };
+        let mut diagnostics = Vec::new();
+        check_line_length(before.into(), 20, included, &mut diagnostics);
+        let lines = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [2, 3, 4, 7, 10]);
+        // Without GFM, there are no tables.
+        let mut diagnostics = Vec::new();
+        markdown::with_dialect(Dialect::CommonMark, || {
+            check_line_length(before.into(), 20, Included::default(), &mut diagnostics)
+        });
+        let lines = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [2, 4]);
+        // Each of these is one character.
+        let before = "Cafe\u{301} 🇫🇷 👩‍💻 👍🏽";
+        let mut diagnostics = Vec::new();
+        check_line_length(before.into(), 10, Included::default(), &mut diagnostics);
+        assert_eq!(diagnostics, []);
+    }
+}
@@ -0,0 +1,718 @@
+//! Finding links, i.e. `[text](destination)`, `<autolinks>`, and bare URLs.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::ops::Range;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use itertools::Itertools;
+use regex::Captures;
+use regex::Regex;
+use serde_json::json;
+
+use crate::diagnostic::Diagnostic;
+use crate::headings;
+use crate::markdown;
+use crate::markdown::LineKind;
+use crate::references;
+use crate::references::Definition;
+
+/// Matches a link destination that isn't in `<>`s, whose parentheses have to be balanced,
+/// like `https://en.wikipedia.org/wiki/Mercury_(planet)`, so that the link doesn't end at the first `)`.
+///
+/// Regexes can't count, so only two levels of nesting are matched, which is plenty for real URLs.
+pub const BALANCED_DESTINATION: &str = r"(?:[^\s()<>]|\((?:[^\s()<>]|\([^\s()<>]*\))*\))*";
+
+/// Matches a link title, in `""`s, `''`s, or `()`s.
+pub const TITLE: &str = r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'|\([^)]*\)"#;
+
+/// The byte ranges in `text` that are already links (or HTML tags),
+/// so URLs in them shouldn't be touched.
+pub fn link_ranges(text: &str) -> Vec<Range<usize>> {
+    if Definition::parse(text).is_some() {
+        let whole = 0..text.len();
+        return vec![whole];
+    }
+    static LINKS: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(r"!?\[[^\]]*\]\((?:<[^>]*>|{BALANCED_DESTINATION})[^)]*\)|\[[^\]]*\]\[[^\]]*\]|<[A-Za-z][A-Za-z0-9+.-]*:[^\s<>]*>|</?[A-Za-z][^>]*>")).unwrap()
+    });
+    LINKS.find_iter(text).map(|m| m.range()).collect()
+}
+
+/// Matches URL link destinations in inline links, autolinks, and reference definitions,
+/// capturing the `url` and what comes before (`prefix`) and after (`suffix`) it.
+pub fn url_destination_regex() -> &'static Regex {
+    static URL_DESTINATION: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r#"(?<prefix>\]\(<?|<|^ {{0,3}}\[[^\]]+\]:[ \t]*<?)(?<url>(?i:https?)://{BALANCED_DESTINATION})(?<suffix>>?(?:[ \t]+(?:"[^"]*"|'[^']*'|\([^)]*\)))?\)?)"#,
+        ))
+        .unwrap()
+    });
+    &URL_DESTINATION
+}
+
+/// Find the bare URLs in `text`, i.e. ones that aren't already in a link.
+///
+/// Like GFM autolinks, trailing punctuation and unbalanced `)`s aren't part of the URL.
+pub fn bare_urls(text: &str) -> Vec<Range<usize>> {
+    static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bhttps?://[^\s<>]+").unwrap());
+    let links = link_ranges(text);
+    URL.find_iter(text)
+        .filter(|m| !links.iter().any(|link| link.contains(&m.start())))
+        .map(|m| {
+            let mut url = m.as_str();
+            loop {
+                let trimmed =
+                    url.trim_end_matches(['.', ',', ':', ';', '!', '?', '"', '\'', '*', '_', '~']);
+                let trimmed = match trimmed.strip_suffix(')') {
+                    Some(stripped)
+                        if trimmed.matches('(').count() < trimmed.matches(')').count() =>
+                    {
+                        stripped
+                    }
+                    _ => trimmed,
+                };
+                if trimmed == url {
+                    break;
+                }
+                url = trimmed;
+            }
+            m.start()..m.start() + url.len()
+        })
+        .collect()
+}
+
+/// How to link bare URLs.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BareUrlStyle {
+    /// `<https://example.com/a>`
+    Autolink,
+
+    /// `[example.com](https://example.com/a)`
+    Host,
+}
+
+/// Query parameters used only for tracking, where a trailing `*` matches any suffix.
+pub const TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "igshid", "_ga", "yclid",
+];
+
+/// Lowercase the scheme and host, remove the default port,
+/// and remove query parameters matching `params` (see [`TRACKING_PARAMS`]).
+pub fn canonicalize_url(url: &str, params: &[impl AsRef<str>]) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_owned();
+    };
+    let scheme = scheme.to_lowercase();
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, rest) = rest.split_at(authority_end);
+    let (user_info, host) = match authority.rsplit_once('@') {
+        Some((user_info, host)) => (Some(user_info), host),
+        None => (None, authority),
+    };
+    let mut host = host.to_lowercase();
+    let default_port = match scheme.as_str() {
+        "http" => ":80",
+        "https" => ":443",
+        _ => "",
+    };
+    if !default_port.is_empty() && host.ends_with(default_port) {
+        host.truncate(host.len() - default_port.len());
+    }
+    let (rest, fragment) = match rest.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (rest, None),
+    };
+    let (path, query) = match rest.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (rest, None),
+    };
+    let is_tracking = |param: &str| {
+        let name = param.split('=').next().unwrap();
+        params
+            .iter()
+            .any(|pattern| match pattern.as_ref().strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern.as_ref(),
+            })
+    };
+    let query = query
+        .map(|query| {
+            query
+                .split('&')
+                .filter(|param| !is_tracking(param))
+                .join("&")
+        })
+        .filter(|query| !query.is_empty());
+
+    let mut canonical = format!("{scheme}://");
+    if let Some(user_info) = user_info {
+        canonical.push_str(&format!("{user_info}@"));
+    }
+    canonical.push_str(&host);
+    canonical.push_str(path);
+    if let Some(query) = query {
+        canonical.push_str(&format!("?{query}"));
+    }
+    if let Some(fragment) = fragment {
+        canonical.push_str(&format!("#{fragment}"));
+    }
+    canonical
+}
+
+/// Canonicalize the URLs of links (see [`canonicalize_url`]),
+/// stripping [`TRACKING_PARAMS`] and `extra_params`.
+pub fn canonicalize_urls(before: String, extra_params: &[String]) -> String {
+    let params = TRACKING_PARAMS
+        .iter()
+        .copied()
+        .chain(extra_params.iter().map(String::as_str))
+        .collect::<Vec<_>>();
+    let destination = url_destination_regex();
+    let after = markdown::rewrite_outside_code(&before, |text| {
+        let rewritten = destination.replace_all(text, |captures: &Captures| {
+            let url = canonicalize_url(&captures["url"], &params);
+            format!("{}{url}{}", &captures["prefix"], &captures["suffix"])
+        });
+        rewritten.into_owned()
+    });
+    after
+}
+
+/// Link bare URLs in prose.
+pub fn link_bare_urls(before: String, style: BareUrlStyle) -> String {
+    let after = markdown::rewrite_outside_code(&before, |text| {
+        let mut rewritten = String::new();
+        let mut last = 0;
+        for range in bare_urls(text) {
+            let url = &text[range.clone()];
+            rewritten.push_str(&text[last..range.start]);
+            match style {
+                BareUrlStyle::Autolink => rewritten.push_str(&format!("<{url}>")),
+                BareUrlStyle::Host => {
+                    let host = url.split_once("://").map_or(url, |(_, rest)| rest);
+                    let host = host.split(['/', '?', '#']).next().unwrap_or(host);
+                    rewritten.push_str(&format!("[{host}]({url})"));
+                }
+            }
+            last = range.end;
+        }
+        rewritten.push_str(&text[last..]);
+        rewritten
+    });
+    after
+}
+
+/// Parse a `--rename old=new` mapping.
+pub fn parse_rename(rename: &str) -> eyre::Result<(PathBuf, PathBuf)> {
+    let (old, new) = rename
+        .split_once('=')
+        .ok_or_else(|| eyre!("expected `old=new`, not `{rename}`"))?;
+    Ok((old.into(), new.into()))
+}
+
+/// Resolve `.` and `..` in `path` without touching the filesystem.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// `path` relative to the directory `dir`, with `/` separators.
+fn relative_path(dir: &Path, path: &Path) -> String {
+    let dir = dir.components().collect::<Vec<_>>();
+    let path = path.components().collect::<Vec<_>>();
+    let common = dir.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    let parents = dir[common..].iter().map(|_| "..".to_owned());
+    let rest = path[common..]
+        .iter()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned());
+    parents.chain(rest).collect::<Vec<_>>().join("/")
+}
+
+/// Find `path` on the filesystem, allowing each component to differ in case.
+fn find_case_insensitive(path: &Path) -> Option<PathBuf> {
+    let mut found = PathBuf::new();
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            found.push(component);
+            continue;
+        };
+        if found.join(name).exists() {
+            found.push(name);
+            continue;
+        }
+        let dir = match found.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => &found,
+        };
+        let name = name.to_string_lossy().to_lowercase();
+        let entry = fs_err::read_dir(dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == name)?;
+        found.push(entry.file_name());
+    }
+    Some(found)
+}
+
+/// Report links to relative files (and their `#sections`) that don't exist,
+/// where `path` is the path of the Markdown file the links are in.
+///
+/// If `fix`, update links to files that were renamed in `renames`
+/// or whose case doesn't match instead of reporting them.
+pub fn check_file_links(
+    before: String,
+    path: &Path,
+    renames: &[(PathBuf, PathBuf)],
+    fix: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let renames = renames
+        .iter()
+        .map(|(old, new)| (normalize_path(old), normalize_path(new)))
+        .collect::<HashMap<_, _>>();
+    static LINK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r"(?<prefix>\]\(<?|^ {{0,3}}\[[^\]]+\]:[ \t]*<?)(?<dest>{BALANCED_DESTINATION})"
+        ))
+        .unwrap()
+    });
+    static SCHEME: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap());
+    let mut anchors = HashMap::new();
+    let after = markdown::lines(&before)
+        .enumerate()
+        .map(|(i, line)| {
+            if line.kind != LineKind::Text {
+                return line.text.to_owned();
+            }
+            markdown::rewrite_outside_code(line.text, |text| {
+                let rewritten = LINK.replace_all(text, |captures: &Captures| {
+                    let unchanged = captures[0].to_owned();
+                    let dest = &captures["dest"];
+                    let (file, section) = match dest.split_once('#') {
+                        Some((file, section)) => (file, Some(section)),
+                        None => (dest, None),
+                    };
+                    let file = file.split('?').next().unwrap();
+                    if file.is_empty() || file.starts_with('/') || SCHEME.is_match(file) {
+                        return unchanged;
+                    }
+                    let target = normalize_path(&dir.join(file.replace("%20", " ")));
+                    let found = match target.exists() {
+                        true => Some(target.clone()),
+                        false => renames
+                            .get(&target)
+                            .cloned()
+                            .filter(|renamed| renamed.exists())
+                            .or_else(|| find_case_insensitive(&target)),
+                    };
+                    let Some(found) = found else {
+                        diagnostics.push(Diagnostic::new(i + 1, format!("`{file}` doesn't exist")));
+                        return unchanged;
+                    };
+                    if let Some(section) =
+                        section.filter(|_| found.extension() == Some(OsStr::new("md")))
+                    {
+                        let anchors = anchors.entry(found.clone()).or_insert_with(|| {
+                            let text = fs_err::read_to_string(&found).unwrap_or_default();
+                            headings::anchors(&headings::headings(&text))
+                        });
+                        if !anchors.iter().any(|anchor| anchor == section) {
+                            diagnostics.push(Diagnostic::new(
+                                i + 1,
+                                format!("`{}` has no heading for `#{section}`", found.display()),
+                            ));
+                        }
+                    }
+                    if found == target {
+                        return unchanged;
+                    }
+                    let mut fixed = relative_path(&normalize_path(dir), &found);
+                    if file.starts_with("./") {
+                        fixed.insert_str(0, "./");
+                    }
+                    if !fix {
+                        diagnostics.push(Diagnostic::new(
+                            i + 1,
+                            format!("`{file}` doesn't exist; did you mean `{fixed}`?"),
+                        ));
+                        return unchanged;
+                    }
+                    let rest = &dest[file.len()..];
+                    format!("{}{fixed}{rest}", &captures["prefix"])
+                });
+                rewritten.into_owned()
+            })
+        })
+        .join("\n");
+    after
+}
+
+/// The syntax of a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkSyntax {
+    /// `[text](destination "title")`
+    Inline,
+
+    /// `[text][label]`, `[label][]`, or `[label]`, with a definition.
+    Reference,
+
+    /// `<https://example.com>`
+    Autolink,
+
+    /// `https://example.com`
+    Bare,
+}
+
+impl LinkSyntax {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Inline => "inline",
+            Self::Reference => "reference",
+            Self::Autolink => "autolink",
+            Self::Bare => "bare",
+        }
+    }
+}
+
+/// A link in a document, with reference links resolved to their definitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// 1-based line number.
+    pub line: usize,
+
+    pub syntax: LinkSyntax,
+
+    pub text: String,
+
+    pub destination: String,
+
+    pub title: Option<String>,
+}
+
+impl Link {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "line": self.line,
+            "kind": self.syntax.as_str(),
+            "text": self.text,
+            "destination": self.destination,
+            "title": self.title,
+        })
+    }
+}
+
+/// Quote a CSV field if needed.
+pub fn csv_field(field: &str) -> Cow<'_, str> {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => Cow::Owned(format!("\"{}\"", field.replace('"', "\"\""))),
+        false => Cow::Borrowed(field),
+    }
+}
+
+/// Remove the quotes or parentheses around a link title, and its backslash escapes.
+fn unquote_title(title: &str) -> String {
+    static ESCAPE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\\(?<char>[[:punct:]])").unwrap());
+    ESCAPE
+        .replace_all(&title[1..title.len() - 1], "$char")
+        .into_owned()
+}
+
+/// Every link in `text` (but not images), in order.
+pub fn links(text: &str) -> Vec<Link> {
+    let line_definitions = references::line_definitions(text);
+    let definitions = references::definitions(text)
+        .into_iter()
+        .map(|(_, definition)| definition)
+        .map(|definition| (references::normalize_label(definition.label), definition))
+        .collect::<HashMap<_, _>>();
+    static LINK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(r"(?<bang>!?)\[(?<text>[^\]]*)\](?:\((?<destination><[^>]*>|{BALANCED_DESTINATION})(?:[ \t]+(?<title>{TITLE}))?[ \t]*\)|\[(?<label>[^\]]*)\])?|<(?<autolink>[A-Za-z][A-Za-z0-9+.-]*:[^\s<>]*)>")).unwrap()
+    });
+    let mut links = Vec::new();
+    for (i, line) in markdown::lines(text).enumerate() {
+        if line.kind != LineKind::Text || line_definitions[i].is_some() {
+            continue;
+        }
+        let mut line_links = Vec::new();
+        markdown::rewrite_outside_code(line.text, |text| {
+            for captures in LINK.captures_iter(text) {
+                let start = captures.get(0).unwrap().start();
+                let link_text = captures.name("text").map_or("", |text| text.as_str());
+                let found = if let Some(url) = captures.name("autolink") {
+                    Some((LinkSyntax::Autolink, url.as_str(), url.as_str(), None))
+                } else if &captures["bang"] == "!" || link_text.starts_with('^') {
+                    None
+                } else if let Some(destination) = captures.name("destination") {
+                    let destination = destination.as_str();
+                    let destination = destination
+                        .strip_prefix('<')
+                        .and_then(|destination| destination.strip_suffix('>'))
+                        .unwrap_or(destination);
+                    let title = captures.name("title").map(|title| title.as_str());
+                    Some((LinkSyntax::Inline, link_text, destination, title))
+                } else {
+                    let label = match captures.name("label") {
+                        Some(label) if !label.is_empty() => label.as_str(),
+                        _ => link_text,
+                    };
+                    let next = text[captures.get(0).unwrap().end()..].chars().next();
+                    definitions
+                        .get(&references::normalize_label(label))
+                        .filter(|_| captures.name("label").is_some() || next != Some(':'))
+                        .map(|definition| {
+                            (
+                                LinkSyntax::Reference,
+                                link_text,
+                                definition.url(),
+                                definition.title,
+                            )
+                        })
+                };
+                if let Some((syntax, link_text, destination, title)) = found {
+                    line_links.push((
+                        start,
+                        Link {
+                            line: i + 1,
+                            syntax,
+                            text: link_text.to_owned(),
+                            destination: destination.to_owned(),
+                            title: title.map(unquote_title),
+                        },
+                    ));
+                }
+            }
+            // Bare URLs are only autolinks in GFM.
+            let bare_urls = match markdown::dialect().has_gfm_extensions() {
+                true => bare_urls(text),
+                false => Vec::new(),
+            };
+            for url in bare_urls {
+                let url_text = &text[url.clone()];
+                line_links.push((
+                    url.start,
+                    Link {
+                        line: i + 1,
+                        syntax: LinkSyntax::Bare,
+                        text: url_text.to_owned(),
+                        destination: url_text.to_owned(),
+                        title: None,
+                    },
+                ));
+            }
+            text.to_owned()
+        });
+        line_links.sort_by_key(|(start, _)| *start);
+        links.extend(line_links.into_iter().map(|(_, link)| link));
+    }
+    links
+}
+
+/// All of a document's links, as a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkInventory(pub Vec<Link>);
+
+impl LinkInventory {
+    pub fn of(text: &str) -> Self {
+        Self(links(text))
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        self.0.iter().map(Link::to_json).collect()
+    }
+
+    /// CSV rows of `path,line,kind,text,destination,title`, without the header.
+    pub fn to_csv(&self, path: &Path) -> String {
+        let path = path.display().to_string();
+        self.0
+            .iter()
+            .map(|link| {
+                let line = link.line.to_string();
+                let title = link.title.as_deref().unwrap_or_default();
+                [
+                    path.as_str(),
+                    &line,
+                    link.syntax.as_str(),
+                    &link.text,
+                    &link.destination,
+                    title,
+                ]
+                .map(csv_field)
+                .join(",")
+            })
+            .join("\n")
+    }
+}
+
+/// Formatted as `{line}: {kind}: {text} -> {destination}`, one link per line.
+impl Display for LinkInventory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let links = self.0.iter().map(|link| {
+            let Link {
+                line,
+                syntax,
+                text,
+                destination,
+                ..
+            } = link;
+            format!("{line}: {}: {text} -> {destination}", syntax.as_str())
+        });
+        write!(f, "{}", links.format("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use itertools::Itertools;
+
+    use crate::diagnostic::Diagnostic;
+    use crate::links::canonicalize_urls;
+    use crate::links::check_file_links;
+    use crate::links::link_bare_urls;
+    use crate::links::BareUrlStyle;
+    use crate::links::LinkInventory;
+
+    #[test]
+    fn test_link_bare_urls() {
+        let before = "See https://example.com/a_(b). Or (https://example.org), not <https://a.com>,
+[https://b.com](https://b.com), <a href=\"https://c.com\">, or `https://d.com`.
+[e]: https://e.com";
+        let after =
+            "See <https://example.com/a_(b)>. Or (<https://example.org>), not <https://a.com>,
+[https://b.com](https://b.com), <a href=\"https://c.com\">, or `https://d.com`.
+[e]: https://e.com";
+        assert_eq!(link_bare_urls(before.into(), BareUrlStyle::Autolink), after);
+        let before = "See https://example.com/a?b.";
+        let after = "See [example.com](https://example.com/a?b).";
+        assert_eq!(link_bare_urls(before.into(), BareUrlStyle::Host), after);
+    }
+
+    #[test]
+    fn test_check_file_links() {
+        let dir = env::temp_dir().join("style-markdown-test-check-file-links");
+        fs_err::create_dir_all(dir.join("docs")).unwrap();
+        fs_err::write(dir.join("docs/Guide.md"), "# Setup\n").unwrap();
+        fs_err::write(dir.join("docs/new.md"), "").unwrap();
+        let path = dir.join("README.md");
+        let before =
+            "See [the guide](./docs/guide.md#setup), [old](docs/old.md), [x](docs/Guide.md#x),
+[missing](missing.md), [site](https://example.com), and [anchor](#a).
+
+[old]: docs/old.md
+";
+        let renames = [(dir.join("docs/old.md"), dir.join("docs/new.md"))];
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_file_links(before.into(), &path, &renames, false, &mut diagnostics),
+            before
+        );
+        let guide = dir.join("docs/Guide.md");
+        let guide = guide.display();
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::new(
+                    1,
+                    "`./docs/guide.md` doesn't exist; did you mean `./docs/Guide.md`?"
+                ),
+                Diagnostic::new(
+                    1,
+                    "`docs/old.md` doesn't exist; did you mean `docs/new.md`?"
+                ),
+                Diagnostic::new(1, format!("`{guide}` has no heading for `#x`")),
+                Diagnostic::new(2, "`missing.md` doesn't exist"),
+                Diagnostic::new(
+                    4,
+                    "`docs/old.md` doesn't exist; did you mean `docs/new.md`?"
+                ),
+            ]
+        );
+        let after =
+            "See [the guide](./docs/Guide.md#setup), [old](docs/new.md), [x](docs/Guide.md#x),
+[missing](missing.md), [site](https://example.com), and [anchor](#a).
+
+[old]: docs/new.md
+";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            check_file_links(before.into(), &path, &renames, true, &mut diagnostics),
+            after
+        );
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_canonicalize_urls() {
+        let before = "See [a](HTTPS://Example.COM:443/Path?utm_source=x&id=1&fbclid=y#Top),
+<http://user@Example.com:80/?ref=z&gclid=w>, and `https://EXAMPLE.com:443`.
+
+[b]: https://example.com:8443/a?utm_medium=email \"title\"
+";
+        let after = "See [a](https://example.com/Path?id=1#Top),
+<http://user@example.com/>, and `https://EXAMPLE.com:443`.
+
+[b]: https://example.com:8443/a \"title\"
+";
+        assert_eq!(canonicalize_urls(before.into(), &["ref".into()]), after);
+        let before = "[M](https://en.wikipedia.org/wiki/Mercury_(planet)?utm_source=x) ((https://a.com/?utm_source=x))";
+        let after =
+            "[M](https://en.wikipedia.org/wiki/Mercury_(planet)) ((https://a.com/?utm_source=x))";
+        assert_eq!(canonicalize_urls(before.into(), &[]), after);
+    }
+
+    #[test]
+    fn test_links() {
+        let text = r#"See[^1] [the report](https://example.com/a "A, \"B\""), [the map][map], [Map],
+![image](b.png), <https://example.org>, and https://example.net.
+[Mercury](https://en.wikipedia.org/wiki/Mercury_(planet) (Planet))
+`[code](c)`
+
+[map]: <https://example.com/map> 'Map'
+[^1]: Note.
+"#;
+        let inventory = LinkInventory::of(text);
+        assert_eq!(
+            inventory.to_string(),
+            "1: inline: the report -> https://example.com/a
+1: reference: the map -> https://example.com/map
+1: reference: Map -> https://example.com/map
+2: autolink: https://example.org -> https://example.org
+2: bare: https://example.net -> https://example.net
+3: inline: Mercury -> https://en.wikipedia.org/wiki/Mercury_(planet)"
+        );
+        assert_eq!(
+            inventory.to_csv("a.md".as_ref()).lines().take(2).join("\n"),
+            r#"a.md,1,inline,the report,https://example.com/a,"A, ""B"""
+a.md,1,reference,the map,https://example.com/map,Map"#
+        );
+    }
+}
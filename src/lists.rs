@@ -0,0 +1,446 @@
+//! Lists, i.e. `- item` and `1. item`.
+
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use regex::Regex;
+
+use crate::markdown;
+use crate::markdown::Line;
+use crate::markdown::LineKind;
+
+/// A list item marker, like `-` or `1.`, and the spaces after it.
+pub fn parse_item(line: &str) -> Option<(&str, &str)> {
+    static ITEM: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(?<marker>[-*+]|\d{1,9}[.)])(?<spaces>[ \t]+|$)").unwrap());
+    let captures = ITEM.captures(line)?;
+    let marker = captures.name("marker").unwrap().as_str();
+    let spaces = captures.name("spaces").unwrap().as_str();
+    Some((marker, spaces))
+}
+
+/// An item in the stack of currently open list items.
+struct OpenItem {
+    old_indent: usize,
+    old_content_indent: usize,
+    new_indent: usize,
+    new_content_indent: usize,
+}
+
+/// Re-indent nested lists so each level is indented by `width` spaces
+/// (or more, if the parent item's marker is wider),
+/// converting tabs to spaces.
+///
+/// Continuation lines are shifted along with their items.
+pub fn normalize_list_indentation(before: String, width: usize) -> String {
+    let mut open = Vec::<OpenItem>::new();
+    // How much to shift the lines of a fenced code block in a list item.
+    let mut code_shift = None;
+    let after = markdown::lines(&before)
+        .map(|line| {
+            let (old_indent, rest) = markdown::expand_indent(line.text);
+            if line.kind == LineKind::Code {
+                return match code_shift {
+                    Some(shift) => shift_line(old_indent, rest, shift),
+                    None => line.text.to_owned(),
+                };
+            }
+            if rest.is_empty() {
+                return line.text.to_owned();
+            }
+            if line.kind == LineKind::Text && !markdown::is_thematic_break(rest) {
+                if let Some((marker, spaces)) = parse_item(rest) {
+                    while open
+                        .last()
+                        .is_some_and(|item| item.old_indent >= old_indent)
+                    {
+                        open.pop();
+                    }
+                    // Only items indented past their parent's content are nested.
+                    while open
+                        .last()
+                        .is_some_and(|item| item.old_content_indent > old_indent)
+                    {
+                        open.pop();
+                    }
+                    let new_indent = match open.last() {
+                        None => 0,
+                        Some(parent) => {
+                            parent.new_indent
+                                + width.max(parent.new_content_indent - parent.new_indent)
+                        }
+                    };
+                    let spaces_len = spaces.len().max(1);
+                    open.push(OpenItem {
+                        old_indent,
+                        old_content_indent: old_indent + marker.len() + spaces_len,
+                        new_indent,
+                        new_content_indent: new_indent + marker.len() + spaces_len,
+                    });
+                    return format!("{}{rest}", " ".repeat(new_indent));
+                }
+            }
+            if old_indent == 0 && line.kind == LineKind::Text {
+                // Not indented, so the list is over (lazy continuation lines aside).
+                open.clear();
+            }
+            let Some(item) = open
+                .iter()
+                .rev()
+                .find(|item| item.old_content_indent <= old_indent)
+            else {
+                if line.kind == LineKind::Fence {
+                    code_shift = None;
+                }
+                return line.text.to_owned();
+            };
+            let shift = item.new_content_indent as isize - item.old_content_indent as isize;
+            if line.kind == LineKind::Fence {
+                code_shift = match code_shift {
+                    None => Some(shift),
+                    Some(_) => None,
+                };
+            }
+            shift_line(old_indent, rest, shift)
+        })
+        .join("\n");
+    after
+}
+
+/// Which character to write bullet list markers with.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulletMarker {
+    /// `- item`
+    Dash,
+
+    /// `* item`
+    Asterisk,
+
+    /// `+ item`
+    Plus,
+}
+
+impl BulletMarker {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dash => "-",
+            Self::Asterisk => "*",
+            Self::Plus => "+",
+        }
+    }
+}
+
+/// Write the markers of bullet list items with `marker`.
+///
+/// Ordered lists, thematic breaks like `* * *`, and empty items are left alone,
+/// since a lone `-` after a paragraph is a setext heading underline.
+/// Note that adjacent lists with different markers become one list.
+pub fn normalize_list_markers(before: String, marker: BulletMarker) -> String {
+    let after = markdown::lines(&before)
+        .map(|line| {
+            let rest = line.text.trim_start();
+            let indent = &line.text[..line.text.len() - rest.len()];
+            let is_bullet = line.kind == LineKind::Text
+                && !markdown::is_thematic_break(rest)
+                && parse_item(rest).is_some_and(|(old, _)| "-*+".contains(old))
+                && !rest[1..].trim().is_empty();
+            match is_bullet {
+                true => format!("{indent}{}{}", marker.as_str(), &rest[1..]),
+                false => line.text.to_owned(),
+            }
+        })
+        .join("\n");
+    after
+}
+
+fn shift_line(indent: usize, rest: &str, shift: isize) -> String {
+    let indent = indent.saturating_add_signed(shift);
+    format!("{}{rest}", " ".repeat(indent))
+}
+
+/// A task list item, i.e. `- [ ] task` or `- [x] done`, with its parts.
+struct TaskItem<'a> {
+    indent: &'a str,
+    marker: &'a str,
+    done: bool,
+    text: &'a str,
+}
+
+impl<'a> TaskItem<'a> {
+    /// Parse a task list item, including malformed ones like `* [X]task` and `-[]`.
+    fn parse(line: &'a str) -> Option<Self> {
+        // Task lists are a GFM extension.
+        if !markdown::dialect().has_gfm_extensions() {
+            return None;
+        }
+        static TASK: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^(?<indent>[ \t]*)(?<marker>[-*+]|\d{1,9}[.)])[ \t]*\[(?<check>[ xX]?)\][ \t]*(?<text>.*)$").unwrap()
+        });
+        let captures = TASK.captures(line)?;
+        let text = captures.name("text").unwrap().as_str();
+        // Not a link like `- [ ](url)`.
+        if text.starts_with(['(', '[', ':']) {
+            return None;
+        }
+        Some(Self {
+            indent: captures.name("indent").unwrap().as_str(),
+            marker: captures.name("marker").unwrap().as_str(),
+            done: &captures["check"] != " " && !captures["check"].is_empty(),
+            text,
+        })
+    }
+}
+
+/// Split a list into its items at the shallowest indentation,
+/// after any lines before the first item.
+fn split_items(lines: &[String]) -> (&[String], Vec<&[String]>) {
+    let indent_of = |line: &String| markdown::expand_indent(line).0;
+    let is_item = |line: &String| parse_item(line.trim_start()).is_some();
+    let Some(indent) = lines
+        .iter()
+        .filter(|line| is_item(line))
+        .map(indent_of)
+        .min()
+    else {
+        return (lines, Vec::new());
+    };
+    let starts = (0..lines.len())
+        .filter(|&i| is_item(&lines[i]) && indent_of(&lines[i]) == indent)
+        .collect::<Vec<_>>();
+    let items = starts
+        .iter()
+        .enumerate()
+        .map(|(j, &start)| &lines[start..starts.get(j + 1).copied().unwrap_or(lines.len())])
+        .collect();
+    (&lines[..starts[0]], items)
+}
+
+/// Move completed task items after the other items at each level of a list.
+fn sort_completed(lines: &[String]) -> Vec<String> {
+    let (prefix, items) = split_items(lines);
+    if items.is_empty() {
+        return lines.to_vec();
+    }
+    let is_blank = |line: &String| line.trim().is_empty();
+    // Items are separated by blank lines in loose lists, which shouldn't move with the items.
+    let separator = items[0]
+        .iter()
+        .rev()
+        .take_while(|line| is_blank(line))
+        .count();
+    let last_trailing = items
+        .last()
+        .unwrap()
+        .iter()
+        .rev()
+        .take_while(|line| is_blank(line))
+        .count();
+    let mut items = items
+        .into_iter()
+        .map(|item| {
+            let item =
+                &item[..item.len() - item.iter().rev().take_while(|line| is_blank(line)).count()];
+            let done = TaskItem::parse(&item[0]).is_some_and(|task| task.done);
+            let mut sorted = vec![item[0].clone()];
+            sorted.extend(sort_completed(&item[1..]));
+            (done, sorted)
+        })
+        .collect::<Vec<_>>();
+    items.sort_by_key(|(done, _)| *done);
+    let mut sorted = prefix.to_vec();
+    let count = items.len();
+    for (j, (_, item)) in items.into_iter().enumerate() {
+        sorted.extend(item);
+        let blanks = match j + 1 == count {
+            true => last_trailing,
+            false => separator,
+        };
+        sorted.extend(std::iter::repeat_n(String::new(), blanks));
+    }
+    sorted
+}
+
+/// Normalize task list items to `- [ ] task` and `- [x] done`,
+/// fixing uppercase `X`s, missing spaces, and `*` and `+` markers.
+///
+/// If `sort`, also move completed items after the other items in their list.
+pub fn normalize_task_lists(before: String, sort: bool) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let mut after = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let is_item = |line: &Line| {
+            line.kind == LineKind::Text && parse_item(line.text.trim_start()).is_some()
+        };
+        if !is_item(&lines[i]) {
+            after.push(lines[i].text.to_owned());
+            i += 1;
+            continue;
+        }
+        // Find the end of the list: a non-blank, non-indented line that isn't an item.
+        let mut end = i + 1;
+        let mut list_end = end;
+        while end < lines.len() {
+            let line = lines[end];
+            let is_blank = line.text.trim().is_empty();
+            if !(is_blank || is_item(&line) || markdown::expand_indent(line.text).0 > 0) {
+                break;
+            }
+            end += 1;
+            if !is_blank {
+                list_end = end;
+            }
+        }
+        let list = &lines[i..list_end];
+        // Bullets at the same indentation as a task need the same marker to stay in the same list.
+        let task_indents = list
+            .iter()
+            .filter(|line| line.kind == LineKind::Text)
+            .filter_map(|line| TaskItem::parse(line.text))
+            .map(|task| task.indent.to_owned())
+            .collect::<Vec<_>>();
+        let mut normalized = list
+            .iter()
+            .map(|line| {
+                if line.kind != LineKind::Text {
+                    return line.text.to_owned();
+                }
+                if let Some(task) = TaskItem::parse(line.text) {
+                    let marker = match task.marker {
+                        "*" | "+" => "-",
+                        marker => marker,
+                    };
+                    let check = if task.done { 'x' } else { ' ' };
+                    let text = task.text;
+                    let item = format!("{}{marker} [{check}] {text}", task.indent);
+                    return item.trim_end().to_owned();
+                }
+                let unindented = line.text.trim_start();
+                let indent = &line.text[..line.text.len() - unindented.len()];
+                match parse_item(unindented) {
+                    Some(("*" | "+", _)) if task_indents.iter().any(|task| task == indent) => {
+                        format!("{indent}-{}", &unindented[1..])
+                    }
+                    _ => line.text.to_owned(),
+                }
+            })
+            .collect::<Vec<_>>();
+        if sort {
+            normalized = sort_completed(&normalized);
+        }
+        after.extend(normalized);
+        i = list_end;
+    }
+    after.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lists::normalize_list_indentation;
+    use crate::lists::normalize_list_markers;
+    use crate::lists::normalize_task_lists;
+    use crate::lists::BulletMarker;
+
+    #[test]
+    fn test_normalize_list_indentation() {
+        let before = "- a
+    - b
+\t- c
+
+      continued c
+
+      ```
+      code
+      ```
+- d
+  1. e
+     - f
+
+g
+  - not nested";
+        let after = "- a
+  - b
+  - c
+
+    continued c
+
+    ```
+    code
+    ```
+- d
+  1. e
+     - f
+
+g
+- not nested";
+        assert_eq!(normalize_list_indentation(before.into(), 2), after);
+    }
+
+    #[test]
+    fn test_normalize_list_markers() {
+        let before = "* a
+  + b
+1. c
+* * *
+Setext
+-
+
+```
+* code
+```";
+        let after = "- a
+  - b
+1. c
+* * *
+Setext
+-
+
+```
+* code
+```";
+        assert_eq!(
+            normalize_list_markers(before.into(), BulletMarker::Dash),
+            after
+        );
+    }
+
+    #[test]
+    fn test_normalize_task_lists() {
+        let before = "* [X] done
+* [ ]todo
+* not a task
+  + [] nested
+  +  [x] nested done
+  + [ ](https://example.com) a link
++ [ ] also todo
+
+Not a list.
+- [x] first
+- [ ] second";
+        let after = "- [x] done
+- [ ] todo
+- not a task
+  - [ ] nested
+  - [x] nested done
+  - [ ](https://example.com) a link
+- [ ] also todo
+
+Not a list.
+- [x] first
+- [ ] second";
+        assert_eq!(normalize_task_lists(before.into(), false), after);
+        let after = "- [ ] todo
+- not a task
+  - [ ] nested
+  - [ ](https://example.com) a link
+  - [x] nested done
+- [ ] also todo
+- [x] done
+
+Not a list.
+- [ ] second
+- [x] first";
+        assert_eq!(normalize_task_lists(before.into(), true), after);
+    }
+}
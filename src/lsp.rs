@@ -0,0 +1,403 @@
+//! A language server over stdio, so editors can run a command while editing.
+//!
+//! Formatting applies the command, diagnostics are its diagnostics,
+//! and a code action applies it as a fix.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::slice;
+
+use color_eyre::eyre;
+use lsp_server::Connection;
+use lsp_server::ErrorCode;
+use lsp_server::Message;
+use lsp_server::Notification;
+use lsp_server::Request;
+use lsp_server::Response;
+use serde_json::json;
+
+use style_markdown::changes;
+use style_markdown::changes::LineEdit;
+use style_markdown::diagnostic::Diagnostic;
+use style_markdown::diagnostic::Severity;
+use style_markdown::markdown::Dialect;
+use style_markdown::Command;
+use style_markdown::FinalNewline;
+
+/// Serve `command` to an editor over stdio until it shuts the server down,
+/// parsing documents as `dialect` (or guessing it from their paths),
+/// and ending formatted documents like `final_newline`.
+pub fn serve(
+    command: &Command,
+    dialect: Option<Dialect>,
+    final_newline: FinalNewline,
+) -> eyre::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    connection.initialize(json!({
+        // Full documents, not incremental changes.
+        "textDocumentSync": 1,
+        "documentFormattingProvider": true,
+        "documentRangeFormattingProvider": true,
+        "codeActionProvider": true,
+    }))?;
+    let mut server = Server::new(command, dialect, final_newline);
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                connection
+                    .sender
+                    .send(Message::Response(server.respond(request)))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(notification) = server.notify(notification) {
+                    connection
+                        .sender
+                        .send(Message::Notification(notification))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// An LSP range of whole lines.
+fn line_range(lines: Range<usize>) -> serde_json::Value {
+    json!({
+        "start": {"line": lines.start, "character": 0},
+        "end": {"line": lines.end, "character": 0},
+    })
+}
+
+fn text_edit(edit: &LineEdit) -> serde_json::Value {
+    json!({
+        "range": line_range(edit.lines.clone()),
+        "newText": edit.text,
+    })
+}
+
+fn lsp_diagnostic(diagnostic: &Diagnostic, rule: &str) -> serde_json::Value {
+    let line = diagnostic.line - 1;
+    let severity = match diagnostic.severity {
+        Severity::Warning => 2,
+        Severity::Note => 3,
+    };
+    json!({
+        "range": line_range(line..line + 1),
+        "severity": severity,
+        "source": "style-markdown",
+        "code": rule,
+        "message": diagnostic.message,
+    })
+}
+
+/// The path of a `file://` URI, with its percent-encoding decoded, like `%20` for a space.
+fn uri_path(uri: &str) -> PathBuf {
+    let encoded = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let byte = encoded
+            .get(i + 1..i + 3)
+            .filter(|_| encoded[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned().into()
+}
+
+/// The open documents and what to do with them.
+pub struct Server<'a> {
+    command: &'a Command,
+
+    /// The dialect to parse documents as, instead of guessing it from their paths.
+    dialect: Option<Dialect>,
+
+    final_newline: FinalNewline,
+
+    /// The text of each open document, by URI.
+    documents: HashMap<String, String>,
+}
+
+impl<'a> Server<'a> {
+    pub fn new(
+        command: &'a Command,
+        dialect: Option<Dialect>,
+        final_newline: FinalNewline,
+    ) -> Self {
+        Self {
+            command,
+            dialect,
+            final_newline,
+            documents: HashMap::new(),
+        }
+    }
+
+    /// Run the command on an open document like the CLI does, returning its edits and diagnostics,
+    /// or `None` if it isn't open.
+    fn rewrite(&self, uri: &str) -> Option<eyre::Result<(Vec<LineEdit>, Vec<Diagnostic>)>> {
+        let before = self.documents.get(uri)?;
+        let path = uri_path(uri);
+        let dialect = self
+            .dialect
+            .or_else(|| Dialect::from_path(&path))
+            .unwrap_or_default();
+        let commands = slice::from_ref(self.command);
+        let rewritten = style_markdown::rewrite_as(
+            &path,
+            before.clone(),
+            commands,
+            dialect,
+            self.final_newline,
+        );
+        Some(rewritten.map(|rewritten| {
+            let diagnostics = rewritten
+                .diagnostics
+                .into_iter()
+                .map(|(_, diagnostic)| diagnostic)
+                .collect();
+            (changes::line_edits(before, &rewritten.text), diagnostics)
+        }))
+    }
+
+    /// Respond to a request from the editor.
+    pub fn respond(&self, request: Request) -> Response {
+        let Request { id, method, params } = request;
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let methods = [
+            "textDocument/formatting",
+            "textDocument/rangeFormatting",
+            "textDocument/codeAction",
+        ];
+        if !methods.contains(&method.as_str()) {
+            return Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported method `{method}`"),
+            );
+        }
+        let edits = match self.rewrite(uri) {
+            Some(Ok((edits, _))) => edits,
+            Some(Err(error)) => {
+                return Response::new_err(id, ErrorCode::RequestFailed as i32, error.to_string());
+            }
+            None => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::InvalidParams as i32,
+                    format!("unknown document `{uri}`"),
+                );
+            }
+        };
+        let result = match method.as_str() {
+            "textDocument/rangeFormatting" => {
+                let position = |position: &str| {
+                    let position = &params["range"][position];
+                    let line = position["line"].as_u64().unwrap_or(0) as usize;
+                    (line, position["character"].as_u64().unwrap_or(0))
+                };
+                let (start, _) = position("start");
+                // A selection of whole lines ends at the start of the next line.
+                let end = match position("end") {
+                    (end, 0) if end > start => end,
+                    (end, _) => end + 1,
+                };
+                edits
+                    .iter()
+                    .filter(|edit| edit.is_within(&(start..end)))
+                    .map(text_edit)
+                    .collect::<Vec<_>>()
+                    .into()
+            }
+            "textDocument/codeAction" if edits.is_empty() => json!([]),
+            "textDocument/codeAction" => {
+                let edits = edits.iter().map(text_edit).collect::<Vec<_>>();
+                json!([{
+                    "title": format!("Apply `{}`", self.command.name()),
+                    "kind": "quickfix",
+                    "diagnostics": params["context"]["diagnostics"],
+                    "edit": {"changes": {uri: edits}},
+                }])
+            }
+            _ => edits.iter().map(text_edit).collect::<Vec<_>>().into(),
+        };
+        Response::new_ok(id, result)
+    }
+
+    /// Handle a notification from the editor about a document,
+    /// returning the document's diagnostics to publish, if any changed.
+    pub fn notify(&mut self, notification: Notification) -> Option<Notification> {
+        let Notification { method, params } = notification;
+        let uri = params["textDocument"]["uri"].as_str()?.to_owned();
+        let text = match method.as_str() {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()?
+                .last()
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return Some(publish_diagnostics(&uri, Vec::new()));
+            }
+            _ => None,
+        }?;
+        self.documents.insert(uri.clone(), text.to_owned());
+        let rule = self.command.name();
+        let diagnostics = match self.rewrite(&uri)? {
+            Ok((_, diagnostics)) => diagnostics
+                .iter()
+                .map(|diagnostic| lsp_diagnostic(diagnostic, &rule))
+                .collect(),
+            // Like an invalid output, which the CLI would refuse to write.
+            Err(error) => vec![json!({
+                "range": line_range(0..1),
+                "severity": 1,
+                "source": "style-markdown",
+                "code": rule,
+                "message": error.to_string(),
+            })],
+        };
+        Some(publish_diagnostics(&uri, diagnostics))
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<serde_json::Value>) -> Notification {
+    Notification::new(
+        "textDocument/publishDiagnostics".into(),
+        json!({"uri": uri, "diagnostics": diagnostics}),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use lsp_server::Notification;
+    use lsp_server::Request;
+    use lsp_server::RequestId;
+    use serde_json::json;
+
+    use crate::lsp::uri_path;
+    use crate::lsp::Server;
+    use style_markdown::markdown::Dialect;
+    use style_markdown::Command;
+    use style_markdown::FinalNewline;
+
+    #[test]
+    fn test_server() {
+        let command = Command::DoubledWords {
+            allowed: Vec::new(),
+            report_only: false,
+        };
+        let mut server = Server::new(&command, None, FinalNewline::One);
+        let uri = "file:///a.md";
+        let text = "One.\nThe the train.\nTwo.\nA a bus.\n";
+        let open = Notification::new(
+            "textDocument/didOpen".into(),
+            json!({"textDocument": {"uri": uri, "text": text}}),
+        );
+        let published = server.notify(open).unwrap();
+        assert_eq!(published.method, "textDocument/publishDiagnostics");
+        assert_eq!(
+            published.params["diagnostics"][1],
+            json!({
+                "range": {
+                    "start": {"line": 3, "character": 0},
+                    "end": {"line": 4, "character": 0},
+                },
+                "severity": 3,
+                "source": "style-markdown",
+                "code": "doubled-words",
+                "message": "removed doubled word `a`",
+            })
+        );
+
+        let request =
+            |method: &str, params| Request::new(RequestId::from(1), method.into(), params);
+        let edit = |line: usize, text: &str| {
+            json!({
+                "range": {
+                    "start": {"line": line, "character": 0},
+                    "end": {"line": line + 1, "character": 0},
+                },
+                "newText": text,
+            })
+        };
+        let formatting = server.respond(request(
+            "textDocument/formatting",
+            json!({"textDocument": {"uri": uri}}),
+        ));
+        assert_eq!(
+            formatting.response_result.unwrap(),
+            json!([edit(1, "The train.\n"), edit(3, "A bus.\n")])
+        );
+        let range_formatting = server.respond(request(
+            "textDocument/rangeFormatting",
+            json!({
+                "textDocument": {"uri": uri},
+                "range": {
+                    "start": {"line": 2, "character": 0},
+                    "end": {"line": 3, "character": 2},
+                },
+            }),
+        ));
+        assert_eq!(
+            range_formatting.response_result.unwrap(),
+            json!([edit(3, "A bus.\n")])
+        );
+        let code_action = server.respond(request(
+            "textDocument/codeAction",
+            json!({"textDocument": {"uri": uri}, "context": {"diagnostics": []}}),
+        ));
+        let action = &code_action.response_result.unwrap()[0];
+        assert_eq!(action["title"], "Apply `doubled-words`");
+        assert_eq!(action["edit"]["changes"][uri].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_server_dialect() {
+        let command = Command::from_rule("quotes").unwrap();
+        let uri = "file:///my%20docs/doc.mdx";
+        assert_eq!(uri_path(uri), Path::new("/my docs/doc.mdx"));
+        let text = "<Chart data={[“a”]} />\n\n“b”\n";
+        let open = Notification::new(
+            "textDocument/didOpen".into(),
+            json!({"textDocument": {"uri": uri, "text": text}}),
+        );
+        let formatting = Request::new(
+            RequestId::from(1),
+            "textDocument/formatting".into(),
+            json!({"textDocument": {"uri": uri}}),
+        );
+        let edited_lines = |dialect| {
+            let mut server = Server::new(&command, dialect, FinalNewline::One);
+            server.notify(open.clone());
+            let edits = server.respond(formatting.clone()).response_result.unwrap();
+            edits
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|edit| edit["range"]["start"]["line"].clone())
+                .collect::<Vec<_>>()
+        };
+        // JSX is left alone in MDX, guessed from the path.
+        assert_eq!(edited_lines(None), [2]);
+        // But not when it's parsed as another dialect.
+        assert_eq!(edited_lines(Some(Dialect::Gfm)), [0, 2]);
+    }
+}
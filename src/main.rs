@@ -1,41 +1,421 @@
+#![allow(clippy::let_and_return)]
+
 use std::borrow::Cow;
 use std::env;
+use std::fmt::Display;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
 use std::process;
 use std::process::Output;
+use std::slice;
 
+use clap::CommandFactory;
 use clap::Parser;
 use clap::Subcommand;
 use color_eyre::eyre;
+use color_eyre::eyre::bail;
 use color_eyre::eyre::ensure;
+use color_eyre::eyre::eyre;
 use color_eyre::eyre::Context;
 use itertools::Itertools;
-use regex::Captures;
-use regex::Regex;
+use miette::GraphicalReportHandler;
+
+use style_markdown::bench;
+use style_markdown::bench::Benchmark;
+use style_markdown::cache;
+use style_markdown::cache::Cache;
+use style_markdown::capabilities;
+use style_markdown::changes;
+use style_markdown::changes::Change;
+use style_markdown::changes::RangeUnit;
+use style_markdown::changes::Summary;
+use style_markdown::config;
+use style_markdown::config::Config;
+use style_markdown::diagnostic::Diagnostic;
+use style_markdown::diagnostic::Severity;
+use style_markdown::error::Error;
+use style_markdown::frequency::WordFrequency;
+use style_markdown::images::EmbeddedImages;
+use style_markdown::input::Input;
+use style_markdown::journal::Journal;
+use style_markdown::journal::Run;
+use style_markdown::links::LinkInventory;
+use style_markdown::markdown;
+use style_markdown::markdown::Dialect;
+use style_markdown::markdownlint;
+use style_markdown::markdownlint::Equivalents;
+use style_markdown::mdbook;
+use style_markdown::ordering;
+use style_markdown::pandoc;
+use style_markdown::presets;
+use style_markdown::readability::Readability;
+use style_markdown::remote;
+use style_markdown::review;
+use style_markdown::review::Reviewer;
+use style_markdown::stats::Outline;
+use style_markdown::stats::Stats;
+use style_markdown::stream;
+use style_markdown::stream::Streamed;
+use style_markdown::terms::Terminology;
+use style_markdown::triggers::Triggers;
+use style_markdown::walk;
+use style_markdown::walk::SymlinkWrite;
+use style_markdown::walk::Walk;
+use style_markdown::Command;
+use style_markdown::FinalNewline;
+use style_markdown::RuleInfo;
+
+mod lsp;
 
 fn main() -> eyre::Result<()> {
     let args = Args::parse();
-    println!("{args:?}");
-    args.run()?;
+    if let Err(error) = args.run() {
+        // Errors pointing into a file are clearer with a snippet of it than as a chain.
+        if let Some(error) = error.downcast_ref::<Error>() {
+            eprint!("{}", render_error(error)?);
+            process::exit(1);
+        }
+        return Err(error);
+    }
     Ok(())
 }
 
+/// `error` with an annotated snippet of the file it points into.
+fn render_error(error: &Error) -> eyre::Result<String> {
+    let mut report = String::new();
+    GraphicalReportHandler::new().render_report(&mut report, error)?;
+    Ok(report)
+}
+
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+// An explicit `about`, since clap would otherwise use the doc comment of a flattened subcommand enum.
+#[command(
+    version,
+    about = "Style Markdown files by rewriting them with rules, or report on them",
+    long_about = None,
+    subcommand_precedence_over_arg = true,
+    after_help = "Options can also be set with the `STYLE_MARKDOWN_*` environment variables shown, \
+        which flags take precedence over. \
+        `STYLE_MARKDOWN_CONFIG` is the path of the config to use instead of `style-markdown.toml`, \
+        and `STYLE_MARKDOWN_RULES` is the rules to run without a command or `--preset`, \
+        separated by `;`, like `quotes; dashes --style unicode`."
+)]
 struct Args {
-    /// Path of the Markdown `*.md` file to style.
-    path: PathBuf,
+    /// Paths of the Markdown `*.md` files to style, which `lsp` gets from the editor instead.
+    ///
+    /// Directories are styled recursively, skipping files that git ignores and symlinks.
+    /// Anything else that isn't a regular file, like a FIFO or device, is an error.
+    ///
+    /// An `https://` URL, like a raw GitHub file or a gist, is downloaded and styled,
+    /// and written to `--output` or stdout.
+    paths: Vec<PathBuf>,
+
+    /// Style the files in directories that git ignores (like in `.gitignore`) and hidden ones too.
+    #[arg(long, env = "STYLE_MARKDOWN_NO_IGNORE")]
+    no_ignore: bool,
+
+    /// Follow symlinks to files and directories in directories, instead of skipping them.
+    #[arg(long, env = "STYLE_MARKDOWN_FOLLOW_SYMLINKS")]
+    follow_symlinks: bool,
+
+    /// Whether to write a styled symlink to the file it links to,
+    /// or replace it with a regular file, leaving the file it linked to unchanged.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "STYLE_MARKDOWN_SYMLINK_WRITES"
+    )]
+    symlink_writes: SymlinkWrite,
+
+    /// Skip files bigger than this, like `100K`, `64M`, or `1G`, or `0` for no limit,
+    /// instead of loading them into memory.
+    ///
+    /// With `--stream`, files of any size are styled, since they're never loaded wholesale.
+    #[arg(long, value_parser = walk::parse_size, default_value = walk::DEFAULT_MAX_FILE_SIZE, env = "STYLE_MARKDOWN_MAX_FILE_SIZE")]
+    max_file_size: u64,
+
+    /// Where to write a document styled from a URL, instead of stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
 
     /// `git commit` the changes.
     #[arg(long)]
     commit: bool,
 
+    /// Print JSON output, like the changes and diagnostics, or reports.
+    #[arg(long, env = "STYLE_MARKDOWN_JSON")]
+    json: bool,
+
+    /// Print JSON describing the rules and their options, the other options,
+    /// the dialects, and the reports and their formats, for tools that wrap this.
+    #[arg(long)]
+    capabilities: bool,
+
+    /// Only change this range of each file, as `START:END`, like an editor's selection.
+    ///
+    /// Edits that would change anything outside of it are left out.
+    /// With a path of `-`, stdin is styled and written to stdout,
+    /// and diagnostics are printed to stderr.
+    #[arg(long, value_parser = changes::parse_range)]
+    range: Option<(usize, usize)>,
+
+    /// What `--range` counts.
+    #[arg(long, value_enum, default_value_t = RangeUnit::Lines)]
+    range_unit: RangeUnit,
+
+    /// The Markdown dialect to parse files as, instead of guessing it from their extensions.
+    ///
+    /// `commonmark` doesn't recognize GFM's tables, task lists, footnotes, strikethrough, or autolinks.
+    #[arg(long, value_enum, env = "STYLE_MARKDOWN_DIALECT")]
+    dialect: Option<Dialect>,
+
+    /// How to end each file: with exactly one newline, with whatever newlines it already ends with,
+    /// or with none.
+    ///
+    /// A final newline is `\r\n` if the file's lines end with `\r\n`.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "STYLE_MARKDOWN_FINAL_NEWLINE"
+    )]
+    final_newline: FinalNewline,
+
+    /// Run the rules of a preset instead of a command,
+    /// either built-in, like `gdocs-cleanup`, or from `[preset.NAME]` in `style-markdown.toml`.
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// Skip files that are unchanged since they were last styled the same way,
+    /// which is remembered in `.style-markdown/cache.json` in the current directory.
+    ///
+    /// Files aren't skipped with rules that read other files or the network, like `dead-links`.
+    #[arg(long, env = "STYLE_MARKDOWN_CACHE")]
+    cache: bool,
+
+    /// Read and write each file one block at a time, so that huge files fit in memory,
+    /// which only works with block-local rules, like `quotes` and `trailing-whitespace`.
+    #[arg(long, conflicts_with_all = ["range", "cache"])]
+    stream: bool,
+
+    /// The path of the file that stdin (a path of `-`) is the contents of, like in an editor,
+    /// for finding the config, guessing the dialect, and attributing diagnostics.
+    #[arg(long)]
+    stdin_filename: Option<PathBuf>,
+
+    /// Ask whether to make each change, showing it in context,
+    /// and whether to make the rest of its rule's changes, like `git add -p`.
+    #[arg(long, conflicts_with = "stream")]
+    interactive: bool,
+
+    /// Don't change anything, but show what would change as word diffs,
+    /// and fail if anything would.
+    #[arg(long, conflicts_with_all = ["commit", "output", "stream", "interactive"])]
+    check: bool,
+
+    /// Stop at the first file that can't be styled,
+    /// instead of styling the rest and reporting the failures at the end.
+    #[arg(long, env = "STYLE_MARKDOWN_FAIL_FAST")]
+    fail_fast: bool,
+
     #[command(subcommand)]
-    command: Command,
+    action: Option<Action>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    #[command(flatten)]
+    Rewrite(Command),
+
+    #[command(flatten)]
+    Report(Report),
+
+    /// Run the rules equivalent to those in a markdownlint config,
+    /// like `.markdownlint.json` or `.markdownlint.yaml`.
+    Markdownlint {
+        /// The markdownlint config, instead of the one in the current directory.
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+
+    /// Run a language server over stdio for editors, which formats files with the command,
+    /// reports its diagnostics, and offers applying it as a code action.
+    Lsp {
+        #[command(subcommand)]
+        command: Command,
+    },
+
+    /// Run as an mdBook preprocessor, styling every chapter with the `rules`
+    /// in the `[preprocessor.style-markdown]` table of `book.toml`.
+    MdbookPreprocessor {
+        #[command(subcommand)]
+        supports: Option<Supports>,
+    },
+
+    /// Run as a Pandoc JSON filter from stdin to stdout,
+    /// applying the rules that work on Pandoc's AST, like `quotes` and `capitalization`.
+    PandocFilter {
+        /// A rule to apply, like `capitalization --term GitHub`, in order.
+        #[arg(long = "rule", required = true)]
+        rules: Vec<String>,
+    },
+
+    /// Time each rule over the Markdown files in a directory, and report their throughput.
+    Bench {
+        corpus: PathBuf,
+
+        /// A rule to time, like `dashes --style unicode`,
+        /// instead of every rule that works with its default options.
+        #[arg(long = "rule")]
+        rules: Vec<String>,
+    },
+
+    /// Revert the files that the last run changed,
+    /// from the journal of runs in `.style-markdown/` in the current directory.
+    Undo,
+
+    /// List every rule, with its aliases and what it does.
+    ListRules,
+}
+
+#[derive(Subcommand, Debug)]
+enum Supports {
+    /// Check if a renderer is supported, which they all are.
+    Supports { renderer: String },
 }
 
 impl Args {
     fn run(&self) -> eyre::Result<()> {
+        if self.capabilities {
+            return print_capabilities();
+        }
+        match &self.action {
+            Some(Action::Lsp { command }) => {
+                ensure!(
+                    !matches!(
+                        command,
+                        Command::Spell {
+                            interactive: true,
+                            ..
+                        }
+                    ),
+                    "`--interactive` needs stdin, which the language server uses"
+                );
+                return lsp::serve(command, self.dialect, self.final_newline);
+            }
+            Some(Action::MdbookPreprocessor { supports }) => {
+                return match supports {
+                    Some(Supports::Supports { .. }) => Ok(()),
+                    None => run_mdbook_preprocessor(),
+                };
+            }
+            Some(Action::PandocFilter { rules }) => {
+                let dialect = self.dialect.unwrap_or_default();
+                return markdown::with_dialect(dialect, || run_pandoc_filter(rules));
+            }
+            Some(Action::Bench { corpus, rules }) => return run_bench(corpus, rules, self.json),
+            Some(Action::Undo) => return run_undo(),
+            Some(Action::ListRules) => return run_list_rules(self.json),
+            Some(Action::Rewrite(_) | Action::Report(_) | Action::Markdownlint { .. }) | None => {}
+        }
+        ensure!(!self.paths.is_empty(), "no paths given");
+        let walk = Walk {
+            no_ignore: self.no_ignore,
+            follow_symlinks: self.follow_symlinks,
+        };
+        let paths = walk::expand(&self.paths, walk)?;
+        let owned_commands;
+        let commands = match (&self.action, &self.preset) {
+            (Some(_), Some(_)) => bail!("`--preset` can't be combined with a command"),
+            (None, None) => {
+                // A misspelled rule is taken as a path instead.
+                let suggestion = self
+                    .paths
+                    .iter()
+                    .filter(|path| !path.exists())
+                    .find_map(|path| Command::suggest(path.to_str()?));
+                match (env::var(config::RULES_VAR), suggestion) {
+                    (Ok(rules), _) => {
+                        owned_commands = presets::parse_rule_list(&rules)
+                            .wrap_err_with(|| format!("invalid `{}`", config::RULES_VAR))?;
+                        &owned_commands
+                    }
+                    (Err(_), Some(rule)) => {
+                        bail!("expected a command or a `--preset`; did you mean `{rule}`?")
+                    }
+                    (Err(_), None) => bail!("expected a command or a `--preset`"),
+                }
+            }
+            (None, Some(preset)) => {
+                owned_commands = Config::find(self.config_dir())?.preset(preset)?;
+                &owned_commands
+            }
+            (Some(Action::Rewrite(command)), None) => slice::from_ref(command),
+            (Some(Action::Report(report)), None) => {
+                let dialect = self.dialect.unwrap_or_default();
+                return markdown::with_dialect(dialect, || report.run(&paths, self.json));
+            }
+            (Some(Action::Markdownlint { config }), None) => {
+                owned_commands = markdownlint_equivalents(config.as_deref(), self.config_dir())?;
+                &owned_commands
+            }
+            (
+                Some(
+                    Action::Lsp { .. }
+                    | Action::MdbookPreprocessor { .. }
+                    | Action::PandocFilter { .. }
+                    | Action::Bench { .. }
+                    | Action::Undo
+                    | Action::ListRules,
+                ),
+                None,
+            ) => unreachable!(),
+        };
+        let uses_stdin = paths.iter().any(|path| path == Path::new("-"));
+        ensure!(
+            !(uses_stdin && self.commit),
+            "can't `--commit` changes to stdin"
+        );
+        ensure!(
+            !(uses_stdin && self.interactive),
+            "`--interactive` needs stdin for answers, not a document"
+        );
+        ensure!(
+            self.stdin_filename.is_none() || uses_stdin,
+            "`--stdin-filename` needs a path of `-` for stdin"
+        );
+        let urls = self
+            .paths
+            .iter()
+            .filter(|path| remote::url(path).is_some())
+            .count();
+        ensure!(
+            !(urls > 0 && self.commit),
+            "can't `--commit` changes to a URL"
+        );
+        if self.output.is_some() {
+            ensure!(
+                urls == 1 && self.paths.len() == 1,
+                "`--output` needs exactly one URL"
+            );
+        }
+        if self.stream {
+            ensure!(urls == 0, "can't `--stream` a URL");
+            if let Some(command) = commands.iter().find(|command| !command.is_block_local()) {
+                bail!(
+                    "`{}` needs the whole document, so it can't be `--stream`ed",
+                    command.name()
+                );
+            }
+        }
+        // Keep stdout for the styled stdin or URL.
+        let uses_stdout = !self.check && (uses_stdin || (urls > 0 && self.output.is_none()));
         let git = || process::Command::new("git");
         if self.commit {
             // `git status --porcelain` should be empty; no current changes
@@ -48,37 +428,464 @@ impl Args {
                 output.status.success() && output.stdout.is_empty()
             });
         }
-        let before = fs_err::read_to_string(&self.path)?;
-        let mut after = self.command.rewrite(before);
-        if !after.ends_with("\n") {
-            after.push_str("\n");
+        let mut cache = match self.cache {
+            true => Some(Cache::load(Path::new("."))),
+            false => None,
+        };
+        let mut journal = Journal::load(Path::new("."))?;
+        let mut run = Run::new(command_line());
+        let mut changes = Vec::new();
+        let mut all_diagnostics = Vec::new();
+        let mut failures = Vec::new();
+        let mut ask = review::ask;
+        let mut reviewer = self.interactive.then(|| Reviewer::new(&mut ask));
+        for path in &paths {
+            match self.style(path, commands, cache.as_mut(), reviewer.as_mut(), &mut run) {
+                Ok((path_changes, diagnostics)) => {
+                    changes.extend(path_changes);
+                    all_diagnostics.push((self.name(path), diagnostics));
+                }
+                Err(error) if self.fail_fast => return Err(error),
+                // Keep styling the other files, and report this at the end.
+                Err(error) => failures.push((self.name(path), error)),
+            }
+        }
+        if let Some(cache) = &cache {
+            cache.save(Path::new("."))?;
         }
-        fs_err::write(&self.path, after)?;
-        if self.commit {
-            // `git add {self.path}`
-            run_command(git().arg("add").arg(&self.path), &[&check_status])?;
-            let cmd = env::args()
-                .map(|arg| {
-                    if arg.contains(' ') {
-                        format!("'{}'", arg.replace('\'', r"\'"))
-                    } else {
-                        arg
-                    }
-                })
-                .join(" ");
-            let msg = format!("run `{cmd}`");
+        if !run.files.is_empty() {
+            journal.push(run);
+            journal.save(Path::new("."))?;
+        }
+        let changed_files = changes
+            .iter()
+            .filter(|change| change.edits > 0)
+            .map(|change| &change.path)
+            .unique()
+            .count();
+        if self.commit && !failures.is_empty() {
+            eprintln!("not committing, since some files failed");
+        }
+        if self.commit && failures.is_empty() {
+            // `git add {paths}`
+            run_command(git().arg("add").args(&paths), &[&check_status])?;
+            let msg = format!("run `{}`", command_line());
             // `git commit -m "run `{cmd}`"`
             run_command(git().args(["commit", "-m", &msg]), &[&check_status])?;
         }
+        let mut out: Box<dyn Write> = match uses_stdout {
+            true => Box::new(io::stderr()),
+            false => Box::new(io::stdout()),
+        };
+        if self.json {
+            let files = changes
+                .iter()
+                .zip(&all_diagnostics)
+                .map(|(change, (_, diagnostics))| {
+                    let mut json = change.to_json();
+                    json["diagnostics"] = diagnostics.iter().map(Diagnostic::to_json).collect();
+                    json
+                })
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", serde_json::to_string_pretty(&files)?)?;
+        } else {
+            for (path, diagnostics) in &all_diagnostics {
+                for diagnostic in diagnostics {
+                    writeln!(out, "{}:{diagnostic}", path.display())?;
+                }
+            }
+            writeln!(out, "{}", Summary(changes))?;
+        }
+        for (path, error) in &failures {
+            match error.downcast_ref::<Error>() {
+                Some(error) => eprint!("{}", render_error(error)?),
+                None => eprintln!("failed to style `{}`: {error:#}", path.display()),
+            }
+        }
+        ensure!(
+            failures.is_empty(),
+            "failed to style {} file(s)",
+            failures.len()
+        );
+        ensure!(
+            !self.check || changed_files == 0,
+            "{changed_files} file(s) would change"
+        );
+        let problems = all_diagnostics
+            .iter()
+            .flat_map(|(_, diagnostics)| diagnostics)
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+            .count();
+        if problems > 0 {
+            bail!("found {problems} problem(s)");
+        }
         Ok(())
     }
+
+    /// Where to find configs: next to the file that stdin is, if it's given,
+    /// or else the current directory.
+    fn config_dir(&self) -> &Path {
+        self.stdin_filename
+            .as_deref()
+            .and_then(Path::parent)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    }
+
+    /// What the file at `path` is called in diagnostics,
+    /// which for stdin is `--stdin-filename` if it's given.
+    fn name<'a>(&'a self, path: &'a Path) -> &'a Path {
+        match path == Path::new("-") {
+            true => self.stdin_filename.as_deref().unwrap_or(path),
+            false => path,
+        }
+    }
+
+    /// Style the file at `path` with `commands`,
+    /// returning the changes each command made and the diagnostics they found.
+    fn style(
+        &self,
+        path: &Path,
+        commands: &[Command],
+        cache: Option<&mut Cache>,
+        mut reviewer: Option<&mut Reviewer>,
+        run: &mut Run,
+    ) -> eyre::Result<(Vec<Change>, Vec<Diagnostic>)> {
+        let mut changes = Vec::new();
+        let cache = cache.filter(|_| cache::is_cacheable(commands));
+        let is_stdin = path == Path::new("-");
+        let name = self.name(path);
+        let url = remote::url(path);
+        if !is_stdin && url.is_none() {
+            // Reading a FIFO or device could block or never end.
+            let size = walk::check_regular(path)?.len();
+            let max = self.max_file_size;
+            if !self.stream && max != 0 && size > max {
+                eprintln!(
+                    "skipping `{}`, which is {size} bytes, over `--max-file-size` of {max} bytes; use `--stream` to style it in bounded memory",
+                    path.display()
+                );
+                return Ok((changes, Vec::new()));
+            }
+        }
+        if self.stream {
+            let dialect = self
+                .dialect
+                .or_else(|| Dialect::from_path(name))
+                .unwrap_or_default();
+            let streamed = markdown::with_dialect(dialect, || match is_stdin {
+                true => stream::stream(
+                    name,
+                    io::stdin().lock(),
+                    io::stdout().lock(),
+                    commands,
+                    self.final_newline,
+                ),
+                false => stream_path(path, commands, self.symlink_writes, self.final_newline),
+            })?;
+            changes.extend(
+                commands
+                    .iter()
+                    .zip(streamed.edits)
+                    .map(|(command, edits)| Change {
+                        path: name.to_owned(),
+                        rule: command.name(),
+                        edits,
+                    }),
+            );
+            return Ok((changes, streamed.diagnostics));
+        }
+        let input = match (is_stdin, url) {
+            (true, _) => Input::stdin(name)?,
+            (false, Some(url)) => Input::Text(remote::fetch(url)?),
+            (false, None) => Input::map(path)?,
+        };
+        let dialect = self
+            .dialect
+            .or_else(|| Dialect::from_path(name))
+            .unwrap_or_default();
+        let before = input.as_str()?;
+        let is_file = !is_stdin && url.is_none();
+        let is_cached = cache.as_deref().is_some_and(|cache| {
+            is_file
+                && cache.is_styled(
+                    path,
+                    &cache::key(commands, dialect, self.final_newline, before),
+                )
+        });
+        if is_cached {
+            changes.extend(commands.iter().map(|command| Change {
+                path: name.to_owned(),
+                rule: command.name(),
+                edits: 0,
+            }));
+            return Ok((changes, Vec::new()));
+        }
+        // Only copied once a rule changes it.
+        let mut after = Cow::Borrowed(before);
+        let mut diagnostics = Vec::new();
+        let mut triggers = None;
+        for command in commands {
+            let before = after;
+            let mut command_diagnostics = Vec::new();
+            let is_skipped =
+                command.is_skipped(triggers.get_or_insert_with(|| Triggers::scan(&before)));
+            let rewritten = match is_skipped {
+                true => None,
+                false => markdown::with_dialect(dialect, || {
+                    match command.rewrite_str(name, &before, &mut command_diagnostics) {
+                        Cow::Owned(after) => Some(after),
+                        Cow::Borrowed(_) => None,
+                    }
+                }),
+            };
+            let rewritten = match rewritten {
+                None if !self.final_newline.is_ended(&before) => {
+                    let mut after = before.to_string();
+                    self.final_newline.end(&mut after);
+                    Some(after)
+                }
+                rewritten => rewritten,
+            };
+            let lines = self
+                .range
+                .map(|range| changes::covered_lines(&before, range, self.range_unit));
+            if let Some(lines) = &lines {
+                command_diagnostics.retain(|diagnostic| lines.contains(&(diagnostic.line - 1)));
+            }
+            diagnostics.extend(command_diagnostics);
+            // Unchanged, so it's still borrowed and there's nothing to count.
+            let Some(mut rewritten) = rewritten else {
+                changes.push(Change {
+                    path: name.to_owned(),
+                    rule: command.name(),
+                    edits: 0,
+                });
+                after = before;
+                continue;
+            };
+            self.final_newline.end(&mut rewritten);
+            Error::check_output(name, &command.name(), &before, &rewritten)?;
+            if let Some(lines) = &lines {
+                let edits = changes::line_edits(&before, &rewritten)
+                    .into_iter()
+                    .filter(|edit| edit.is_within(lines))
+                    .collect::<Vec<_>>();
+                rewritten = changes::apply_line_edits(&before, &edits);
+            }
+            if let Some(reviewer) = reviewer.as_mut() {
+                rewritten = reviewer.review(name, &command.name(), &before, &rewritten);
+            }
+            // Every change was left out, so it's unchanged after all.
+            if rewritten == *before {
+                changes.push(Change {
+                    path: name.to_owned(),
+                    rule: command.name(),
+                    edits: 0,
+                });
+                after = before;
+                continue;
+            }
+            changes.push(Change {
+                path: name.to_owned(),
+                rule: command.name(),
+                edits: changes::count_edits(&before, &rewritten),
+            });
+            // The text changed, so scan it again for the next commands.
+            triggers = None;
+            after = Cow::Owned(rewritten);
+        }
+        // Partly styled or with problems left to report, a file isn't done.
+        if let Some(cache) = cache {
+            if is_file && self.range.is_none() && diagnostics.is_empty() {
+                cache.insert(
+                    path,
+                    cache::key(commands, dialect, self.final_newline, &after),
+                );
+            }
+        }
+        if self.check {
+            // Color the words only for people, not for other tools reading the output.
+            let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+            for (line, marked) in changes::word_diff(before, &after, color) {
+                println!("{}:{line}: {marked}", name.display());
+            }
+            return Ok((changes, diagnostics));
+        }
+        match (is_stdin, url, &self.output) {
+            (false, Some(_), Some(output)) => fs_err::write(output, &*after)?,
+            (true, _, _) | (false, Some(_), None) => print!("{after}"),
+            // Unchanged, so there's nothing to write.
+            (false, None, _) if matches!(after, Cow::Borrowed(_)) => {}
+            (false, None, _) => {
+                run.record(path, before, &after)?;
+                // Unmap the file before overwriting it.
+                let after = after.into_owned();
+                drop(input);
+                walk::write(path, &after, self.symlink_writes)?;
+            }
+        }
+        Ok((changes, diagnostics))
+    }
 }
 
-fn run_command(
-    cmd: &mut process::Command,
-    checks: &[&dyn Fn(&mut Output) -> eyre::Result<()>],
-) -> eyre::Result<()> {
-    println!("> {cmd:?}");
+/// The command line this was run with, quoting arguments with spaces.
+fn command_line() -> String {
+    env::args()
+        .map(|arg| {
+            if arg.contains(' ') {
+                format!("'{}'", arg.replace('\'', r"\'"))
+            } else {
+                arg
+            }
+        })
+        .join(" ")
+}
+
+/// Undo the last run that changed files, from the journal in the current directory.
+fn run_undo() -> eyre::Result<()> {
+    let dir = Path::new(".");
+    let mut journal = Journal::load(dir)?;
+    let run = journal.undo()?;
+    journal.save(dir)?;
+    for file in &run.files {
+        eprintln!("reverted `{}`", file.path.display());
+    }
+    eprintln!("undid `{}`", run.command);
+    Ok(())
+}
+
+/// Style the file at `path` with `commands` one block at a time, ending it like `final_newline`,
+/// writing it to a temporary file next to it that replaces it if anything changed.
+fn stream_path(
+    path: &Path,
+    commands: &[Command],
+    symlinks: SymlinkWrite,
+    final_newline: FinalNewline,
+) -> eyre::Result<Streamed> {
+    let target = match symlinks {
+        SymlinkWrite::Follow => fs_err::canonicalize(path)?,
+        SymlinkWrite::Replace => path.to_owned(),
+    };
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| eyre!("`{}` isn't a file", path.display()))?;
+    let temp = target.with_file_name(format!(".{}.style-markdown", file_name.to_string_lossy()));
+    let reader = BufReader::new(fs_err::File::open(path)?);
+    let writer = BufWriter::new(fs_err::File::create(&temp)?);
+    let streamed = stream::stream(path, reader, writer, commands, final_newline);
+    match &streamed {
+        Ok(streamed) if streamed.is_changed => {
+            fs_err::set_permissions(&temp, fs_err::metadata(path)?.permissions())?;
+            fs_err::rename(&temp, &target)?;
+        }
+        _ => fs_err::remove_file(&temp)?,
+    }
+    streamed
+}
+
+/// The rules equivalent to the markdownlint config at `path`,
+/// or else in `dir`, warning about rules without equivalents.
+fn markdownlint_equivalents(path: Option<&Path>, dir: &Path) -> eyre::Result<Vec<Command>> {
+    let path = match path {
+        Some(path) => path.to_owned(),
+        None => markdownlint::find_config(dir).ok_or_else(|| {
+            eyre!(
+                "no markdownlint config found; expected one of {}",
+                markdownlint::CONFIG_FILE_NAMES.join(", ")
+            )
+        })?,
+    };
+    let config = fs_err::read_to_string(&path)?;
+    let equivalents = Equivalents::parse(&config)
+        .wrap_err_with(|| format!("invalid markdownlint config `{}`", path.display()))?;
+    for rule in &equivalents.unsupported {
+        eprintln!("skipping markdownlint rule {rule}, which has no equivalent");
+    }
+    ordering::order(equivalents.commands)
+}
+
+/// Read a book from mdBook on stdin, and write it back styled to stdout,
+/// with diagnostics on stderr.
+fn run_mdbook_preprocessor() -> eyre::Result<()> {
+    let input = io::read_to_string(io::stdin())?;
+    let (book, chapter_diagnostics) = mdbook::preprocess(&input)?;
+    for (path, diagnostics) in &chapter_diagnostics {
+        for (rule, diagnostic) in diagnostics {
+            eprintln!("{}:{diagnostic} [{rule}]", path.display());
+        }
+    }
+    println!("{}", serde_json::to_string(&book)?);
+    Ok(())
+}
+
+/// Read Pandoc's JSON AST on stdin, and write it back styled with `rules` to stdout.
+fn run_pandoc_filter(rules: &[String]) -> eyre::Result<()> {
+    let commands = presets::parse_rules(rules)?;
+    let input = io::read_to_string(io::stdin())?;
+    let document = pandoc::filter(&input, &commands)?;
+    println!("{}", serde_json::to_string(&document)?);
+    Ok(())
+}
+
+/// Time each of `rules`, or every rule by default, over the Markdown files in `corpus`.
+fn run_bench(corpus: &Path, rules: &[String], json: bool) -> eyre::Result<()> {
+    let files = bench::corpus(corpus)?;
+    ensure!(
+        !files.is_empty(),
+        "no Markdown files in `{}`",
+        corpus.display()
+    );
+    let commands = match rules.is_empty() {
+        true => bench::default_rules(),
+        false => presets::parse_rules(rules)?,
+    };
+    let benchmark = Benchmark(bench::bench(&files, &commands));
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&benchmark.to_json())?),
+        false => println!("{benchmark}"),
+    }
+    Ok(())
+}
+
+/// Print the capabilities of the CLI as JSON.
+fn print_capabilities() -> eyre::Result<()> {
+    let reports = Report::augment_subcommands(clap::Command::new("reports"));
+    let reports = reports
+        .get_subcommands()
+        .map(|report| report.get_name())
+        .collect::<Vec<_>>();
+    let capabilities = capabilities::capabilities(&Args::command(), &reports);
+    println!("{}", serde_json::to_string_pretty(&capabilities)?);
+    Ok(())
+}
+
+/// Print every rule, with its aliases and what it does.
+fn run_list_rules(json: bool) -> eyre::Result<()> {
+    let rules = Command::rules();
+    if json {
+        let rules = rules.iter().map(RuleInfo::to_json).collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&rules)?);
+        return Ok(());
+    }
+    let names = rules
+        .iter()
+        .map(|rule| match rule.aliases.is_empty() {
+            true => rule.name.clone(),
+            false => format!("{} ({})", rule.name, rule.aliases.join(", ")),
+        })
+        .collect::<Vec<_>>();
+    let width = names.iter().map(String::len).max().unwrap_or_default();
+    for (name, rule) in names.iter().zip(&rules) {
+        println!("{name:width$}  {}", rule.about);
+    }
+    Ok(())
+}
+
+type Check = dyn Fn(&mut Output) -> eyre::Result<()>;
+
+fn run_command(cmd: &mut process::Command, checks: &[&Check]) -> eyre::Result<()> {
+    eprintln!("> {cmd:?}");
     cmd.output()
         .map_err(eyre::Error::from) // into eyre
         .and_then(|mut output| {
@@ -110,271 +917,110 @@ fn check_empty_stdout(output: &mut Output) -> eyre::Result<()> {
     Ok(())
 }
 
+/// Commands that report on files without changing them.
 #[derive(Subcommand, Debug)]
-enum Command {
-    /// Replace fancy (`‘’`, `“”`) quotes with simple (`'`, `"`) quotes.
-    Quotes,
-
-    /// Delete large embedded images (i.e. `<data:image/[^>]*>` HTML elements).
-    EmbeddedImages,
+enum Report {
+    /// Print word, sentence, footnote, link, and image counts, reading time, and the outline.
+    Stats,
 
-    /// Delete extra spaces after `\[[^\]]: `, such as footnotes.
-    ExtraRefSpaces,
+    /// Print the images embedded as `data:` URIs, their sizes and types, and their total size.
+    EmbeddedImageSizes,
 
-    /// Simplify `[URL](URL)`s as `<URL>`.
-    SimplifyUrls,
+    /// Print every link's line, kind, text, destination, and title.
+    Links {
+        /// Print CSV instead, with a header and a row per link.
+        #[arg(long)]
+        csv: bool,
+    },
 
-    /// Add semantic line breaks as best as possible.
-    SemanticLineBreaks,
+    /// Print the heading tree with line numbers and the word count of each section.
+    Outline,
 
-    /// Canonicalize "through-running" words, always hyphenating and always putting "through" before "run".
-    ThroughRunning,
+    /// Print the Flesch–Kincaid grade level and average sentence length of each section,
+    /// and the longest sentences.
+    Readability {
+        /// How many of the longest sentences to print.
+        #[arg(long, default_value_t = 5)]
+        longest: usize,
+    },
 
-    /// Move footnotes to always after punctuation.
-    FootnotesAfterPunctuation,
-}
-
-impl Command {
-    fn rewrite(&self, before: String) -> String {
-        let rewrite = match *self {
-            Self::Quotes => canonicalize_quotes,
-            Self::EmbeddedImages => remove_embedded_images,
-            Self::ExtraRefSpaces => remove_extra_ref_spaces,
-            Self::SimplifyUrls => simplify_urls,
-            Self::SemanticLineBreaks => add_semantic_line_breaks,
-            Self::ThroughRunning => canonicalize_through_running,
-            Self::FootnotesAfterPunctuation => move_footnotes_after_punctuation,
-        };
-        rewrite(before)
-    }
-}
-
-fn canonicalize_quotes(before: String) -> String {
-    let after = before
-        .replace(|c| "‘’".contains(c), "'")
-        .replace(|c| "“”".contains(c), "\"");
-    after
-}
+    /// Print terms spelled inconsistently, like `third rail` and `third-rail`, with their counts,
+    /// to pick a canonical spelling for `prose-lint`'s preferred terms.
+    Terminology,
 
-fn remove_embedded_images(before: String) -> String {
-    let data_image = Regex::new(r"<data:image/[^>]*>").unwrap();
-    let after = data_image.split(&before).join("TODO");
-    after
+    /// Print the most frequent words and pairs of words in each section, besides common words.
+    WordFrequency {
+        /// How many words and pairs of words to print per section.
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
 }
 
-fn remove_extra_ref_spaces(before: String) -> String {
-    let ref_with_spaces = Regex::new(r"(\[[^\]]*\]: ) *").unwrap();
-    let after = ref_with_spaces
-        .replace_all(&before, |captures: &Captures| captures[1].to_string())
-        .into_owned();
-    after
-}
-
-fn simplify_urls(before: String) -> String {
-    let link = Regex::new(r"\[(?<text>[^\]]*)\]\((?<link>[^)]*)\)").unwrap();
-    let after = link
-        .replace_all(&before, |captures: &Captures| {
-            let (full, [text, link]) = captures.extract();
-            if text.replace('\\', "") == link {
-                format!("<{link}>")
-            } else {
-                full.to_string()
+impl Report {
+    fn run(&self, paths: &[PathBuf], json: bool) -> eyre::Result<()> {
+        match *self {
+            Self::Stats => print_reports(paths, json, Stats::of, Stats::to_json),
+            Self::EmbeddedImageSizes => {
+                print_reports(paths, json, EmbeddedImages::of, EmbeddedImages::to_json)
             }
-        })
-        .into_owned();
-    after
-}
-
-fn add_semantic_line_breaks(before: String) -> String {
-    let max_line_length = 100;
-
-    /// First, split each original line at the given punctuation regex.
-    /// Then rejoin lines before it gets longer than the line length.
-    ///
-    /// `separator_regex` should have either a `before` or `after` capture name
-    /// depending on if it should go before or after the line break.
-    fn add_line_breaks<'a>(
-        separator_regex: &str,
-        line: &'a str,
-        max_line_length: usize,
-    ) -> Cow<'a, str> {
-        let punctuation = Regex::new(separator_regex).unwrap();
-        // Don't break headings.
-        let is_heading = || line.trim_ascii_start().starts_with('#');
-        // Early optimization.
-        if line.len() < max_line_length || is_heading() {
-            return Cow::Borrowed(line);
-        }
-        let with_all_line_breaks = punctuation
-            // Replace punctuation plus space with punctuation plus newline,
-            // thus adding line breaks at all punctuation.
-            .replace_all(line, |captures: &Captures| {
-                if let Some(before) = captures.name("before") {
-                    format!("{}\n", before.as_str())
-                } else if let Some(after) = captures.name("after") {
-                    format!("\n{}", after.as_str())
-                } else {
-                    panic!("captures supposed to have either `before` xor `after` group, but is {captures:?}");
+            Self::Links { csv: true } => {
+                println!("path,line,kind,text,destination,title");
+                for path in paths {
+                    let text = fs_err::read_to_string(path)?;
+                    let csv = LinkInventory::of(&text).to_csv(path);
+                    if !csv.is_empty() {
+                        println!("{csv}");
+                    }
                 }
-            });
-        // For simplicity, the above is implemented by
-        // replacing the spaces after punctuation with a newline,
-        // so now split again to get the lines.
-        let fully_split_lines = with_all_line_breaks.split('\n');
-        // Newlines are manually added here.
-        let mut rejoined_lines = Vec::new();
-        let mut current_line_length = 0;
-        for line in fully_split_lines {
-            if current_line_length == 0 {
-                // It could be too long, but we can't split it anymore by punctuation.
-                rejoined_lines.push(line);
-                current_line_length = line.len();
-            } else if current_line_length + line.len() < max_line_length {
-                // There's room to join a line, so join it with a space.
-                rejoined_lines.push(" ");
-                rejoined_lines.push(line);
-                current_line_length += line.len();
-            } else {
-                // The line is too long, so keep it split.
-                rejoined_lines.push("\n");
-                rejoined_lines.push(line);
-                current_line_length = line.len();
+                Ok(())
             }
+            Self::Links { csv: false } => {
+                print_reports(paths, json, LinkInventory::of, LinkInventory::to_json)
+            }
+            Self::Outline => print_reports(paths, json, Outline::of, Outline::to_json),
+            Self::Readability { longest } => print_reports(
+                paths,
+                json,
+                |text| Readability::of(text, longest),
+                Readability::to_json,
+            ),
+            Self::Terminology => print_reports(paths, json, Terminology::of, Terminology::to_json),
+            Self::WordFrequency { top } => print_reports(
+                paths,
+                json,
+                |text| WordFrequency::of(text, top),
+                WordFrequency::to_json,
+            ),
         }
-        Cow::Owned(rejoined_lines.concat())
     }
-
-    // These are chosen somewhat subjectively.
-    // Usually they should be coordinating and subordinating conjunctions.
-    let line_starting_words = ["because", "that", "rather than", "of how", "in order to"];
-    let line_starting_words_regex = line_starting_words
-        .iter()
-        // Sort by more words first, so that they take priority in the regex.
-        .map(|conjunction| conjunction.split(' ').collect::<Vec<_>>())
-        .sorted_by(|a, b| a.len().cmp(&b.len()).reverse())
-        .map(|words| words.join(" "))
-        .join("|");
-
-    let outer_separators_regex = r"(?<before>[.!?;:]) +";
-    let inner_separators_regex =
-        &format!(r"(?<before>[,)\]]) +| +(?<after>\(|\[|{line_starting_words_regex})");
-
-    let after = before
-        .split_terminator('\n')
-        .map(|line| {
-            add_line_breaks(&outer_separators_regex, line, max_line_length)
-                .split_terminator('\n')
-                .map(|line| add_line_breaks(inner_separators_regex, line, max_line_length))
-                .join("\n")
-        })
-        .join("\n");
-    after
-}
-
-fn canonicalize_through_running(before: String) -> String {
-    let after = before
-        .replace("through running", "through-running")
-        .replace("running through", "through-running")
-        .replace("through run", "through-run")
-        .replace("run through", "through-run");
-    after
 }
 
-fn move_footnotes_after_punctuation(before: String) -> String {
-    let regex = Regex::new(r"(?<footnote>\[\^[^\]]*\])(?<punctuation>[.!?;,])").unwrap();
-    let after = regex.replace_all(&before, |captures: &Captures| {
-        let (_, [footnote, punctuation]) = captures.extract();
-        format!("{punctuation}{footnote}")
-    });
-    after.into_owned()
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::add_semantic_line_breaks;
-    use crate::canonicalize_quotes;
-    use crate::canonicalize_through_running;
-    use crate::move_footnotes_after_punctuation;
-    use crate::remove_embedded_images;
-    use crate::remove_extra_ref_spaces;
-    use crate::simplify_urls;
-
-    #[test]
-    fn test_canonicalize_quotes() {
-        let before = "‘’, “”";
-        let after = "'', \"\"";
-        assert_eq!(canonicalize_quotes(before.into()), after);
-    }
-
-    #[test]
-    fn test_remove_embedded_images() {
-        let before = "[image1]: <data:image/png;base64,iVBORw0KGgoAAAAN>
-
-[image2]: <data:image/png;base64,iVBORw0KGgoAAAANS>";
-        let after = "[image1]: TODO
-
-[image2]: TODO";
-        assert_eq!(remove_embedded_images(before.into()), after);
-    }
-
-    #[test]
-    fn test_remove_extra_ref_spaces() {
-        let before = "[^2]:    hello";
-        let after = "[^2]: hello";
-        assert_eq!(remove_extra_ref_spaces(before.into()), after);
-    }
-
-    #[test]
-    fn test_simplify_urls() {
-        let before = r"[URL](URL), [URL\_2](URL_2)";
-        let after = "<URL>, <URL_2>";
-        assert_eq!(simplify_urls(before.into()), after);
-    }
-
-    #[test]
-    fn test_add_semantic_line_breaks() {
-        let before = "
-# A Not-So-Capital Plan Part 2: The Future is Electric
-
-Metro-North's M8 can run on catenary power (left[^M8-catenary-pantograph-citation]) or on either over- or under-running third rails (shoe seen at right[^M8-third-rail-shoe-citation]).
-
-## Introduction
-
-In major cities all across the globe, electric trains form the backbone of urban transportation. The benefits of electrification are simply too great to ignore. Electric trains accelerate faster, reduce overall journey times, and provide a higher-quality passenger experience than their diesel-powered counterparts, all while being cheaper to run and maintain. Electric trains are also a powerful tool for decarbonization: they can easily run on non-carbon fuel sources and produce no local pollution. It is rare that a single technology can reduce both pollution and costs while also actually improving service, but electric rail can accomplish just that. That is why the future of rail is electric around both the country and the world.
-        ";
-        let after = "
-# A Not-So-Capital Plan Part 2: The Future is Electric
-
-Metro-North's M8 can run on catenary power (left[^M8-catenary-pantograph-citation])
-or on either over- or under-running third rails (shoe seen at right[^M8-third-rail-shoe-citation]).
-
-## Introduction
-
-In major cities all across the globe, electric trains form the backbone of urban transportation.
-The benefits of electrification are simply too great to ignore.
-Electric trains accelerate faster, reduce overall journey times,
-and provide a higher-quality passenger experience than their diesel-powered counterparts,
-all while being cheaper to run and maintain.
-Electric trains are also a powerful tool for decarbonization:
-they can easily run on non-carbon fuel sources and produce no local pollution.
-It is rare that a single technology can reduce both pollution and costs while also actually improving service,
-but electric rail can accomplish just that.
-That is why the future of rail is electric around both the country and the world.
-        ";
-        assert_eq!(add_semantic_line_breaks(before.into()), after);
-    }
-
-    #[test]
-    fn test_canonicalize_through_running() {
-        let before = "through-running, through running, running through, through-run, through run, run through";
-        let after = "through-running, through-running, through-running, through-run, through-run, through-run";
-        assert_eq!(canonicalize_through_running(before.into()), after);
+/// Print a report on each file, or all of them as a JSON array.
+fn print_reports<R: Display>(
+    paths: &[PathBuf],
+    json: bool,
+    report: impl Fn(&str) -> R,
+    to_json: impl Fn(&R) -> serde_json::Value,
+) -> eyre::Result<()> {
+    let mut reports = Vec::new();
+    for path in paths {
+        let text = fs_err::read_to_string(path)?;
+        reports.push((path, report(&text)));
     }
-
-    #[test]
-    fn test_move_footnotes_after_punctuation() {
-        let before = "[^1].";
-        let after = ".[^1]";
-        assert_eq!(move_footnotes_after_punctuation(before.into()), after);
+    if json {
+        let json = reports
+            .iter()
+            .map(|(path, report)| {
+                let mut json = to_json(report);
+                json["path"] = path.display().to_string().into();
+                json
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        for (path, report) in &reports {
+            println!("{}:\n{report}", path.display());
+        }
     }
+    Ok(())
 }
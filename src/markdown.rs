@@ -0,0 +1,828 @@
+//! Just enough Markdown structure for rules to know what they shouldn't touch.
+
+use std::cell::Cell;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::links::BALANCED_DESTINATION;
+use crate::references::Definition;
+
+/// Which flavor of Markdown a file is written in.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// Plain CommonMark, without GFM's extensions.
+    #[value(name = "commonmark")]
+    CommonMark,
+
+    /// GitHub Flavored Markdown.
+    #[default]
+    Gfm,
+
+    /// Markdown with JSX, `import`/`export` statements, and `{expressions}`, like in `*.mdx`.
+    Mdx,
+
+    /// Quarto or R Markdown, with executable code chunks, like ```` ```{r} ````,
+    /// and inline code, like `` `r 1 + 1` ``, like in `*.qmd` and `*.Rmd`.
+    Quarto,
+}
+
+impl Dialect {
+    /// The dialect of a file by its extension, if it's not just Markdown.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "mdx" => Some(Self::Mdx),
+            "qmd" | "Rmd" | "rmd" => Some(Self::Quarto),
+            _ => None,
+        }
+    }
+
+    /// Whether GFM's extensions to CommonMark are recognized:
+    /// tables, task lists, footnotes, strikethrough, and bare URLs as autolinks.
+    ///
+    /// Quarto and most MDX sites support them too.
+    pub fn has_gfm_extensions(self) -> bool {
+        self != Self::CommonMark
+    }
+}
+
+thread_local! {
+    static DIALECT: Cell<Dialect> = const { Cell::new(Dialect::Gfm) };
+}
+
+/// The dialect that the Markdown is currently parsed as.
+pub fn dialect() -> Dialect {
+    DIALECT.get()
+}
+
+/// Run `f` parsing Markdown as `dialect`, since rules don't pass it around.
+pub fn with_dialect<T>(dialect: Dialect, f: impl FnOnce() -> T) -> T {
+    let outer = DIALECT.replace(dialect);
+    let result = f();
+    DIALECT.set(outer);
+    result
+}
+
+/// The kind of block a line is in, as far as rules need to know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// Regular Markdown outside of any code block.
+    Text,
+
+    /// An opening or closing code fence.
+    Fence,
+
+    /// A line inside a fenced code block.
+    Code,
+
+    /// A line of YAML (`---`) or TOML (`+++`) front matter, including its delimiters.
+    FrontMatter,
+
+    /// A line that isn't Markdown, which rules shouldn't change:
+    /// in MDX, a JSX tag or an `import` or `export` statement,
+    /// and in Quarto, an executable code chunk, including its fences.
+    Opaque,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line<'a> {
+    /// The line without its `\n`.
+    pub text: &'a str,
+
+    pub kind: LineKind,
+}
+
+/// An opening code fence, i.e. ```` ``` ```` or `~~~`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fence {
+    pub char: char,
+    pub len: usize,
+}
+
+impl Display for Fence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for _ in 0..self.len {
+            f.write_char(self.char)?;
+        }
+        Ok(())
+    }
+}
+
+impl Fence {
+    /// Parse an opening fence per CommonMark:
+    /// up to 3 spaces of indentation and then at least 3 backticks or tildes.
+    pub fn parse_opening(line: &str) -> Option<Self> {
+        let unindented = line.trim_start_matches(' ');
+        if line.len() - unindented.len() > 3 {
+            return None;
+        }
+        let char = unindented.chars().next().filter(|c| "`~".contains(*c))?;
+        let len = unindented.len() - unindented.trim_start_matches(char).len();
+        if len < 3 {
+            return None;
+        }
+        // The info string of a backtick fence can't contain backticks.
+        if char == '`' && unindented[len..].contains('`') {
+            return None;
+        }
+        Some(Self { char, len })
+    }
+
+    /// Whether `line` closes this fence:
+    /// a fence of the same character at least as long, with nothing after it.
+    pub fn is_closed_by(&self, line: &str) -> bool {
+        let unindented = line.trim_start_matches(' ');
+        if line.len() - unindented.len() > 3 {
+            return false;
+        }
+        let rest = unindented.trim_start_matches(self.char);
+        unindented.len() - rest.len() >= self.len && rest.trim().is_empty()
+    }
+}
+
+/// Split `text` into `\n`-separated lines, classifying each.
+///
+/// Joining the [`Line::text`]s back with `\n` gives back `text` exactly.
+pub fn lines(text: &str) -> impl Iterator<Item = Line<'_>> {
+    let front_matter_len = front_matter_len(text);
+    let is_mdx = dialect() == Dialect::Mdx;
+    let is_quarto = dialect() == Dialect::Quarto;
+    let mut fence = None::<Fence>;
+    let mut fence_is_chunk = false;
+    // In MDX, whether the previous line continues into this one,
+    // as an `import`/`export` statement until a blank line, or a JSX tag until its `>`.
+    let mut in_statement = false;
+    let mut in_tag = false;
+    let mut previous_is_blank = true;
+    text.split('\n').enumerate().map(move |(i, text)| {
+        let is_blank = text.trim().is_empty();
+        let kind = match fence {
+            _ if i < front_matter_len => LineKind::FrontMatter,
+            None if is_mdx && (in_tag || in_statement && !is_blank) => {
+                in_tag = in_tag && !text.trim_end().ends_with('>');
+                LineKind::Opaque
+            }
+            None if is_mdx
+                && previous_is_blank
+                && (text.starts_with("import ") || text.starts_with("export ")) =>
+            {
+                in_statement = true;
+                LineKind::Opaque
+            }
+            None if is_mdx && is_jsx_tag(text) => {
+                in_tag = text.rfind('<') > text.rfind('>');
+                LineKind::Opaque
+            }
+            None => match Fence::parse_opening(text) {
+                None => LineKind::Text,
+                Some(opening) => {
+                    fence = Some(opening);
+                    fence_is_chunk = is_quarto && is_chunk_opening(text, opening);
+                    match fence_is_chunk {
+                        true => LineKind::Opaque,
+                        false => LineKind::Fence,
+                    }
+                }
+            },
+            Some(opening) => {
+                let kind = if opening.is_closed_by(text) {
+                    fence = None;
+                    LineKind::Fence
+                } else {
+                    LineKind::Code
+                };
+                match fence_is_chunk {
+                    true => LineKind::Opaque,
+                    false => kind,
+                }
+            }
+        };
+        in_statement = in_statement && !is_blank;
+        previous_is_blank = is_blank;
+        Line { text, kind }
+    })
+}
+
+/// Whether the opening fence `line` starts an executable code chunk,
+/// like ```` ```{r} ```` or ```` ```{python echo=false} ````,
+/// not just a code block with attributes, like ```` ```{.python} ````.
+fn is_chunk_opening(line: &str, opening: Fence) -> bool {
+    let info = line
+        .trim_start()
+        .trim_start_matches(opening.char)
+        .trim_start();
+    static CHUNK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\{[A-Za-z]").unwrap());
+    CHUNK.is_match(info)
+}
+
+/// Whether `line` is only JSX tags, like `<Tabs>`, `</Tabs>`, or `<>`,
+/// or starts a tag that continues on the next line.
+///
+/// Tags followed by text, like `<b>bold</b> text`, are inline in a paragraph instead.
+fn is_jsx_tag(line: &str) -> bool {
+    static TAG: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^ {0,3}<(?:/?[A-Za-z]|>)").unwrap());
+    TAG.is_match(line) && (line.trim_end().ends_with('>') || line.rfind('<') > line.rfind('>'))
+}
+
+/// The number of lines of front matter at the start of `text`, including the delimiters.
+///
+/// Front matter is only recognized if it's closed.
+fn front_matter_len(text: &str) -> usize {
+    let mut lines = text.split('\n').map(|line| line.trim_end());
+    let closing: &[&str] = match lines.next() {
+        Some("---") => &["---", "..."],
+        Some("+++") => &["+++"],
+        _ => return 0,
+    };
+    match lines.position(|line| closing.contains(&line)) {
+        Some(i) => i + 2,
+        None => 0,
+    }
+}
+
+/// Whether `line` is a thematic break, like `---`, `***`, or `_ _ _`.
+///
+/// Note that a `---` line directly after a paragraph is a setext heading underline instead.
+pub fn is_thematic_break(line: &str) -> bool {
+    let unindented = line.trim_start_matches(' ');
+    let Some(char) = unindented.chars().next().filter(|c| "-*_".contains(*c)) else {
+        return false;
+    };
+    line.len() - unindented.len() <= 3
+        && unindented
+            .trim_end()
+            .chars()
+            .all(|c| c == char || c == ' ' || c == '\t')
+        && unindented.matches(char).count() >= 3
+}
+
+/// The column of the first non-whitespace character of `line`, and the rest of the line.
+///
+/// Tabs are expanded with tab stops every 4 columns.
+pub fn expand_indent(line: &str) -> (usize, &str) {
+    let mut column = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            ' ' => column += 1,
+            '\t' => column += 4 - column % 4,
+            _ => return (column, &line[i..]),
+        }
+    }
+    (column, "")
+}
+
+/// Whether `line` is an ATX heading, i.e. `# Heading`.
+pub fn is_heading(line: &str) -> bool {
+    let unindented = line.trim_start_matches(' ');
+    let hashes = unindented.len() - unindented.trim_start_matches('#').len();
+    line.len() - unindented.len() <= 3
+        && (1..=6).contains(&hashes)
+        && unindented[hashes..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+}
+
+/// The byte ranges of the inline code spans in `line`, including their backticks.
+///
+/// A code span starts with a run of backticks and ends with the next run of the same length.
+pub fn code_spans(line: &str) -> Vec<Range<usize>> {
+    let backtick_run_len = |i: usize| line[i..].len() - line[i..].trim_start_matches('`').len();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while let Some(start) = line[i..].find('`').map(|start| i + start) {
+        let len = backtick_run_len(start);
+        i = start + len;
+        let mut end = None;
+        let mut j = i;
+        while let Some(next) = line[j..].find('`').map(|next| j + next) {
+            let next_len = backtick_run_len(next);
+            if next_len == len {
+                end = Some(next + next_len);
+                break;
+            }
+            j = next + next_len;
+        }
+        if let Some(end) = end {
+            spans.push(start..end);
+            i = end;
+        }
+    }
+    spans
+}
+
+/// The byte ranges of the shortcodes in `text`, including their delimiters,
+/// like Hugo's `{{< figure src="a.png" >}}` and `{{% note %}}`,
+/// or Zola's `{{ youtube(id="a") }}` and `{% quote() %}`.
+///
+/// Shortcodes can span lines, but not paragraphs.
+pub fn shortcodes(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while let Some(start) = text[i..].find('{').map(|start| i + start) {
+        i = start + 1;
+        let closing = match text[i..].chars().next() {
+            Some('{') => "}}",
+            Some('%') => "%}",
+            _ => continue,
+        };
+        let Some(end) = text[i + 1..]
+            .find(closing)
+            .map(|end| i + 1 + end + closing.len())
+        else {
+            continue;
+        };
+        if text[start..end]
+            .split('\n')
+            .any(|line| line.trim().is_empty())
+        {
+            continue;
+        }
+        ranges.push(start..end);
+        i = end;
+    }
+    ranges
+}
+
+/// The byte ranges of the `{expressions}` in a line of MDX, including their braces.
+pub fn expressions(line: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    let mut depth = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '{' => {
+                start = start.or(Some(i));
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    ranges.extend(start.take().map(|start| start..i + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// The byte ranges of the inline code in a line of Quarto that's run, like `` `r 1 + 1` ``
+/// or `` `{python} x` ``, including the backticks.
+pub fn inline_chunks(line: &str) -> Vec<Range<usize>> {
+    static CHUNK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^`+(?:r |\{[A-Za-z])").unwrap());
+    code_spans(line)
+        .into_iter()
+        .filter(|span| CHUNK.is_match(&line[span.clone()]))
+        .collect()
+}
+
+/// The sorted, non-overlapping byte ranges of `text` that aren't Markdown, so rules shouldn't change them:
+/// [`shortcodes`], [`LineKind::Opaque`] lines, and in MDX, [`expressions`],
+/// and in Quarto, [`inline_chunks`].
+pub fn opaque_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = shortcodes(text);
+    let dialect = dialect();
+    if matches!(dialect, Dialect::Mdx | Dialect::Quarto) {
+        let mut start = 0;
+        for line in lines(text) {
+            let end = start + line.text.len();
+            let line_ranges = match (line.kind, dialect) {
+                (LineKind::Opaque, _) => {
+                    ranges.push(start..end);
+                    Vec::new()
+                }
+                (LineKind::Text, Dialect::Mdx) => expressions(line.text),
+                (LineKind::Text, Dialect::Quarto) => inline_chunks(line.text),
+                _ => Vec::new(),
+            };
+            ranges.extend(
+                line_ranges
+                    .into_iter()
+                    .map(|range| start + range.start..start + range.end),
+            );
+            start = end + 1;
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+    let mut merged = Vec::<Range<usize>>::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Apply `rewrite` to each part of `text` that's not in an [opaque range](opaque_ranges).
+///
+/// Unlike in [`rewrite_outside_code`], the parts can span lines.
+pub fn rewrite_outside_opaque(text: &str, mut rewrite: impl FnMut(&str) -> String) -> String {
+    let mut rewritten = String::new();
+    let mut last = 0;
+    for range in opaque_ranges(text) {
+        rewritten.push_str(&rewrite(&text[last..range.start]));
+        rewritten.push_str(&text[range.clone()]);
+        last = range.end;
+    }
+    rewritten.push_str(&rewrite(&text[last..]));
+    rewritten
+}
+
+/// Apply `rewrite` to each line of `text`, without the `\n`,
+/// in parallel across lines for line-oriented rules that are slow on large documents.
+pub fn rewrite_lines_in_parallel(text: &str, rewrite: impl Fn(&str) -> String + Sync) -> String {
+    // The dialect is thread-local, so pass it along to the other threads.
+    let dialect = dialect();
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let after = lines
+        .into_par_iter()
+        .map(|line| with_dialect(dialect, || rewrite(line)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    after
+}
+
+/// Apply `rewrite` to each part of `line`, which starts at byte `start` of the whole text,
+/// that's not in a code span or in one of the `opaque_ranges` of the whole text.
+fn rewrite_line_outside_code(
+    line: &Line,
+    start: usize,
+    opaque_ranges: &[Range<usize>],
+    mut rewrite: impl FnMut(&str) -> String,
+) -> String {
+    let end = start + line.text.len();
+    if line.kind != LineKind::Text {
+        return line.text.to_owned();
+    }
+    let mut opaque = code_spans(line.text);
+    opaque.extend(
+        opaque_ranges
+            .iter()
+            .filter(|range| range.start < end && range.end > start)
+            .map(|range| range.start.max(start) - start..range.end.min(end) - start),
+    );
+    opaque.sort_by_key(|span| span.start);
+    let mut rewritten = String::new();
+    let mut last = 0;
+    for span in opaque {
+        // Code spans in opaque ranges, or the reverse.
+        if span.end <= last {
+            continue;
+        }
+        let span = span.start.max(last)..span.end;
+        rewritten.push_str(&rewrite(&line.text[last..span.start]));
+        rewritten.push_str(&line.text[span.clone()]);
+        last = span.end;
+    }
+    rewritten.push_str(&rewrite(&line.text[last..]));
+    rewritten
+}
+
+/// The lines of `text`, with the byte each starts at.
+fn lines_with_starts(text: &str) -> Vec<(usize, Line<'_>)> {
+    let mut start = 0;
+    lines(text)
+        .map(|line| {
+            let line_start = start;
+            start += line.text.len() + 1;
+            (line_start, line)
+        })
+        .collect()
+}
+
+/// The range of the opening fence of a code block in `text` that's never closed,
+/// which turns the rest of the document into code.
+pub fn unclosed_fence(text: &str) -> Option<Range<usize>> {
+    let mut opening = None;
+    for (start, line) in lines_with_starts(text) {
+        if line.kind == LineKind::Fence {
+            opening = match opening {
+                None => Some(start..start + line.text.len()),
+                Some(_) => None,
+            };
+        }
+    }
+    opening
+}
+
+/// Apply `rewrite` to each part of `text` that's not in a code block, code span,
+/// or [opaque range](opaque_ranges).
+///
+/// `rewrite` is called on parts of single lines, without the `\n`.
+pub fn rewrite_outside_code(text: &str, mut rewrite: impl FnMut(&str) -> String) -> String {
+    let opaque_ranges = opaque_ranges(text);
+    let after = lines_with_starts(text)
+        .iter()
+        .map(|(start, line)| rewrite_line_outside_code(line, *start, &opaque_ranges, &mut rewrite))
+        .join("\n");
+    after
+}
+
+/// [`rewrite_outside_code`], but in parallel across lines.
+pub fn rewrite_outside_code_in_parallel(
+    text: &str,
+    rewrite: impl Fn(&str) -> String + Sync,
+) -> String {
+    let dialect = dialect();
+    let opaque_ranges = opaque_ranges(text);
+    let after = lines_with_starts(text)
+        .par_iter()
+        .map(|(start, line)| {
+            with_dialect(dialect, || {
+                rewrite_line_outside_code(line, *start, &opaque_ranges, &rewrite)
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    after
+}
+
+/// Apply `rewrite` to each part of `text`, part of a single line, that's prose.
+fn rewrite_prose_parts(text: &str, mut rewrite: impl FnMut(&str) -> String) -> String {
+    static NOT_PROSE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r"\]\((?:<[^>]*>|{BALANCED_DESTINATION})[^)]*\)|<[^>]*>|\bhttps?://[^\s<>]+"
+        ))
+        .unwrap()
+    });
+    if Definition::parse(text).is_some() {
+        return text.to_owned();
+    }
+    let mut rewritten = String::new();
+    let mut last = 0;
+    for m in NOT_PROSE.find_iter(text) {
+        rewritten.push_str(&rewrite(&text[last..m.start()]));
+        rewritten.push_str(m.as_str());
+        last = m.end();
+    }
+    rewritten.push_str(&rewrite(&text[last..]));
+    rewritten
+}
+
+/// Apply `rewrite` to each part of `text` that's prose,
+/// i.e. not code, front matter, HTML tags, URLs, link destinations, or reference definitions.
+///
+/// `rewrite` is called on parts of single lines, without the `\n`.
+pub fn rewrite_prose(text: &str, mut rewrite: impl FnMut(&str) -> String) -> String {
+    rewrite_outside_code(text, |text| rewrite_prose_parts(text, &mut rewrite))
+}
+
+/// [`rewrite_prose`], but in parallel across lines.
+pub fn rewrite_prose_in_parallel(text: &str, rewrite: impl Fn(&str) -> String + Sync) -> String {
+    rewrite_outside_code_in_parallel(text, |text| rewrite_prose_parts(text, &rewrite))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markdown::code_spans;
+    use crate::markdown::dialect;
+    use crate::markdown::expressions;
+    use crate::markdown::inline_chunks;
+    use crate::markdown::is_heading;
+    use crate::markdown::is_thematic_break;
+    use crate::markdown::lines;
+    use crate::markdown::rewrite_lines_in_parallel;
+    use crate::markdown::rewrite_outside_code;
+    use crate::markdown::rewrite_outside_code_in_parallel;
+    use crate::markdown::rewrite_outside_opaque;
+    use crate::markdown::rewrite_prose;
+    use crate::markdown::rewrite_prose_in_parallel;
+    use crate::markdown::shortcodes;
+    use crate::markdown::unclosed_fence;
+    use crate::markdown::with_dialect;
+    use crate::markdown::Dialect;
+    use crate::markdown::LineKind;
+
+    #[test]
+    fn test_lines() {
+        let text = "
+a
+```rust
+b
+~~~
+```
+  ~~~~
+c
+~~~
+d
+````
+";
+        let kinds = lines(text).map(|line| line.kind).collect::<Vec<_>>();
+        use LineKind::*;
+        assert_eq!(
+            kinds,
+            [Text, Text, Fence, Code, Code, Fence, Fence, Code, Code, Code, Code, Code]
+        );
+    }
+
+    #[test]
+    fn test_front_matter() {
+        let text = "---
+title: a
+---
+b
+---";
+        let kinds = lines(text).map(|line| line.kind).collect::<Vec<_>>();
+        use LineKind::*;
+        assert_eq!(kinds, [FrontMatter, FrontMatter, FrontMatter, Text, Text]);
+        let text = "---
+unclosed";
+        let kinds = lines(text).map(|line| line.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [Text, Text]);
+    }
+
+    #[test]
+    fn test_mdx_lines() {
+        let text = "import {Tabs} from './tabs'
+export const meta = {
+  title: 'a',
+}
+
+<Tabs
+  items={['a', 'b']}
+>
+
+Important, but not an import.
+<b>bold</b> text
+</Tabs>";
+        let kinds = with_dialect(Dialect::Mdx, || {
+            lines(text).map(|line| line.kind).collect::<Vec<_>>()
+        });
+        use LineKind::*;
+        assert_eq!(
+            kinds,
+            [
+                Opaque, Opaque, Opaque, Opaque, Text, Opaque, Opaque, Opaque, Text, Text, Text,
+                Opaque
+            ]
+        );
+        let kinds = lines(text).map(|line| line.kind).collect::<Vec<_>>();
+        assert!(kinds.iter().all(|kind| *kind == Text));
+    }
+
+    #[test]
+    fn test_quarto_lines() {
+        let text = "```{r}
+x <- 1
+```
+```{.python}
+x = 1
+```";
+        let kinds = with_dialect(Dialect::Quarto, || {
+            lines(text).map(|line| line.kind).collect::<Vec<_>>()
+        });
+        use LineKind::*;
+        assert_eq!(kinds, [Opaque, Opaque, Opaque, Fence, Code, Fence]);
+    }
+
+    #[test]
+    fn test_inline_chunks() {
+        let line = "`r 1 + 1` `{python} x` `code` ``r 2``";
+        let chunks = inline_chunks(line)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect::<Vec<_>>();
+        assert_eq!(chunks, ["`r 1 + 1`", "`{python} x`", "``r 2``"]);
+    }
+
+    #[test]
+    fn test_is_thematic_break() {
+        assert!(is_thematic_break("---"));
+        assert!(is_thematic_break(" * * *"));
+        assert!(is_thematic_break("_____ "));
+        assert!(!is_thematic_break("--"));
+        assert!(!is_thematic_break("-*-"));
+        assert!(!is_thematic_break("    ---"));
+        assert!(!is_thematic_break("- a"));
+    }
+
+    #[test]
+    fn test_is_heading() {
+        assert!(is_heading("# a"));
+        assert!(is_heading("   ###### a"));
+        assert!(is_heading("##"));
+        assert!(!is_heading("#hashtag"));
+        assert!(!is_heading("####### a"));
+        assert!(!is_heading("    # a"));
+    }
+
+    #[test]
+    fn test_code_spans() {
+        let line = "a `b` ``c`d`` ` e";
+        let spans = code_spans(line)
+            .into_iter()
+            .map(|span| &line[span])
+            .collect::<Vec<_>>();
+        assert_eq!(spans, ["`b`", "``c`d``"]);
+    }
+
+    #[test]
+    fn test_shortcodes() {
+        let text = r#"a {{< figure src="a" >}} {{% note
+title="a" %}} {% quote() %} { {{ b }}
+
+{{< unclosed
+
+>}}"#;
+        let shortcodes = shortcodes(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shortcodes,
+            [
+                r#"{{< figure src="a" >}}"#,
+                "{{% note\ntitle=\"a\" %}}",
+                "{% quote() %}",
+                "{{ b }}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expressions() {
+        let line = "a {b} {{c: 'd'}} {/* e */} {";
+        let expressions = expressions(line)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect::<Vec<_>>();
+        assert_eq!(expressions, ["{b}", "{{c: 'd'}}", "{/* e */}"]);
+    }
+
+    #[test]
+    fn test_unclosed_fence() {
+        assert_eq!(unclosed_fence("```\ncode\n```\n"), None);
+        assert_eq!(unclosed_fence("a\n\n~~~ rust\ncode\n```\n"), Some(3..11));
+    }
+
+    #[test]
+    fn test_rewrite_outside_opaque() {
+        let before = "a {{< a\na >}}\na";
+        let after = "b {{< a\na >}}\nb";
+        assert_eq!(
+            rewrite_outside_opaque(before, |text| text.replace('a', "b")),
+            after
+        );
+        let before = "a {a}\n<A a='a' />\na";
+        let after = "b {a}\n<A a='a' />\nb";
+        let rewritten = with_dialect(Dialect::Mdx, || {
+            rewrite_outside_opaque(before, |text| text.replace('a', "b"))
+        });
+        assert_eq!(rewritten, after);
+    }
+
+    #[test]
+    fn test_rewrite_outside_code() {
+        let before = "a `a` a {{< a
+a >}} a
+```
+a
+```";
+        let after = "b `a` b {{< a
+a >}} b
+```
+a
+```";
+        assert_eq!(
+            rewrite_outside_code(before, |text| text.replace('a', "b")),
+            after
+        );
+        assert_eq!(
+            rewrite_outside_code_in_parallel(before, |text| text.replace('a', "b")),
+            after
+        );
+    }
+
+    #[test]
+    fn test_rewrite_prose() {
+        let before = "a [a](a) <a href=\"a\"> https://a.com/a `a`
+[a]: a";
+        let after = "b [b](a) <a href=\"a\"> https://a.com/a `a`
+[a]: a";
+        assert_eq!(rewrite_prose(before, |text| text.replace('a', "b")), after);
+        assert_eq!(
+            rewrite_prose_in_parallel(before, |text| text.replace('a', "b")),
+            after
+        );
+    }
+
+    #[test]
+    fn test_rewrite_lines_in_parallel() {
+        let after = with_dialect(Dialect::Mdx, || {
+            rewrite_lines_in_parallel("a\n\nb", |line| format!("{line}{:?}", dialect()))
+        });
+        assert_eq!(after, "aMdx\nMdx\nbMdx");
+    }
+}
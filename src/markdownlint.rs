@@ -0,0 +1,231 @@
+//! The equivalents of the rules in a markdownlint config, like `.markdownlint.json`,
+//! so that projects already using markdownlint don't need to configure the rules twice.
+//!
+//! markdownlint's rules are enabled by default, unless the config sets `"default": false`,
+//! and each is configured by its ID, like `MD013`, or its alias, like `line-length`.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use yaml_rust2::yaml::Hash;
+use yaml_rust2::Yaml;
+use yaml_rust2::YamlLoader;
+
+use crate::code_blocks::FenceStyle;
+use crate::links::BareUrlStyle;
+use crate::lists::BulletMarker;
+use crate::markdown;
+use crate::Command;
+use crate::HardBreak;
+
+/// The names of markdownlint configs, in the order markdownlint looks for them.
+pub const CONFIG_FILE_NAMES: &[&str] = &[
+    ".markdownlint.json",
+    ".markdownlint.yaml",
+    ".markdownlint.yml",
+];
+
+/// The markdownlint rules with equivalents, by their ID and alias.
+const RULES: &[(&str, &str)] = &[
+    ("MD003", "heading-style"),
+    ("MD004", "ul-style"),
+    ("MD007", "ul-indent"),
+    ("MD009", "no-trailing-spaces"),
+    ("MD012", "no-multiple-blanks"),
+    ("MD013", "line-length"),
+    ("MD024", "no-duplicate-heading"),
+    ("MD034", "no-bare-urls"),
+    ("MD035", "hr-style"),
+    ("MD040", "fenced-code-language"),
+    ("MD046", "code-block-style"),
+    ("MD048", "code-fence-style"),
+];
+
+/// Keys of a markdownlint config that aren't rules.
+const NON_RULE_KEYS: &[&str] = &["default", "extends", "$schema"];
+
+/// Find a markdownlint config in `dir`.
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// The rules equivalent to a markdownlint config's.
+#[derive(Debug)]
+pub struct Equivalents {
+    /// The equivalent rules, in the order of the markdownlint rules.
+    pub commands: Vec<Command>,
+
+    /// The markdownlint rules the config enables that have no equivalent,
+    /// like `MD033` or `MD004 (style: consistent)`.
+    pub unsupported: Vec<String>,
+}
+
+/// The equivalent of the markdownlint rule `id` with `options`,
+/// or [`None`] if its options have no equivalent.
+fn equivalent(id: &str, options: &Yaml) -> Option<Command> {
+    let int = |key: &str, default: usize| {
+        options[key]
+            .as_i64()
+            .map_or(default, |int| int.max(0) as usize)
+    };
+    let bool = |key: &str, default: bool| options[key].as_bool().unwrap_or(default);
+    let style = options["style"].as_str().unwrap_or("consistent");
+    match id {
+        "MD003" => (style == "atx").then_some(Command::AtxHeadings),
+        "MD004" => {
+            let marker = match style {
+                "dash" => BulletMarker::Dash,
+                "asterisk" => BulletMarker::Asterisk,
+                "plus" => BulletMarker::Plus,
+                _ => return None,
+            };
+            Some(Command::ListMarkers { marker })
+        }
+        "MD007" => Some(Command::ListIndentation {
+            width: int("indent", 2),
+        }),
+        "MD009" => Some(Command::TrailingWhitespace {
+            hard_breaks: HardBreak::Spaces,
+        }),
+        "MD012" => Some(Command::BlankLines {
+            max: int("maximum", 1),
+        }),
+        "MD013" => Some(Command::LineLength {
+            max: int("line_length", 80),
+            include_tables: bool("tables", true),
+            include_code: bool("code_blocks", true),
+            include_urls: bool("strict", false),
+        }),
+        "MD024" => Some(Command::DuplicateHeadings { fix: false }),
+        "MD034" => Some(Command::BareUrls {
+            style: BareUrlStyle::Autolink,
+        }),
+        "MD035" => markdown::is_thematic_break(style).then(|| Command::ThematicBreaks {
+            style: style.to_owned(),
+        }),
+        "MD040" => Some(Command::CodeLanguages {
+            fix: false,
+            default: None,
+        }),
+        "MD046" => (style == "fenced").then_some(Command::FenceIndentedCode { language: None }),
+        "MD048" => {
+            let style = match style {
+                "backtick" => FenceStyle::Backticks,
+                "tilde" => FenceStyle::Tildes,
+                _ => return None,
+            };
+            Some(Command::CodeFences { style })
+        }
+        _ => None,
+    }
+}
+
+impl Equivalents {
+    /// Map a markdownlint config, in JSON or YAML, to the equivalent rules.
+    pub fn parse(config: &str) -> eyre::Result<Self> {
+        let documents = YamlLoader::load_from_str(config)?;
+        let empty = Hash::new();
+        let config = match documents.first() {
+            None => &empty,
+            Some(config) => config
+                .as_hash()
+                .ok_or_else(|| eyre!("a markdownlint config must be an object"))?,
+        };
+        let rule = |key: &Yaml| key.as_str().unwrap_or_default().to_owned();
+        let is_enabled = |options: &Yaml| !matches!(options, Yaml::Boolean(false));
+        let default = config
+            .get(&Yaml::String("default".into()))
+            .is_none_or(is_enabled);
+        let mut unsupported = config
+            .iter()
+            .filter(|(key, options)| {
+                let key = rule(key);
+                is_enabled(options)
+                    && !NON_RULE_KEYS.contains(&key.as_str())
+                    && !RULES.iter().any(|(id, alias)| {
+                        key.eq_ignore_ascii_case(id) || key.eq_ignore_ascii_case(alias)
+                    })
+            })
+            .map(|(key, _)| rule(key))
+            .collect::<Vec<_>>();
+        let mut commands = Vec::new();
+        for &(id, alias) in RULES {
+            let options = config.iter().find_map(|(key, options)| {
+                let key = rule(key);
+                (key.eq_ignore_ascii_case(id) || key.eq_ignore_ascii_case(alias)).then_some(options)
+            });
+            if !options.map_or(default, is_enabled) {
+                continue;
+            }
+            match equivalent(id, options.unwrap_or(&Yaml::BadValue)) {
+                Some(command) => commands.push(command),
+                // Only report explicitly configured rules, not ones enabled by default.
+                None if options.is_some() => {
+                    let style = options.unwrap()["style"].as_str().unwrap_or("consistent");
+                    unsupported.push(format!("{id} (style: {style})"));
+                }
+                None => {}
+            }
+        }
+        Ok(Self {
+            commands,
+            unsupported,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markdownlint::Equivalents;
+    use crate::Command;
+
+    #[test]
+    fn test_equivalents() {
+        let json = r#"{
+            "default": false,
+            "MD013": {"line_length": 100, "tables": false},
+            "ul-style": {"style": "asterisk"},
+            "heading-style": {"style": "setext"},
+            "no-multiple-blanks": true,
+            "MD033": true,
+            "MD041": false
+        }"#;
+        let equivalents = Equivalents::parse(json).unwrap();
+        assert_eq!(
+            equivalents
+                .commands
+                .iter()
+                .map(Command::name)
+                .collect::<Vec<_>>(),
+            ["list-markers", "blank-lines", "line-length"]
+        );
+        assert_eq!(
+            format!("{:?}", equivalents.commands[2]),
+            "LineLength { max: 100, include_tables: false, include_code: true, include_urls: false }"
+        );
+        assert_eq!(equivalents.unsupported, ["MD033", "MD003 (style: setext)"]);
+
+        let yaml = "MD004: false
+code-fence-style:
+  style: tilde
+";
+        let equivalents = Equivalents::parse(yaml).unwrap();
+        let names = equivalents
+            .commands
+            .iter()
+            .map(Command::name)
+            .collect::<Vec<_>>();
+        assert!(!names.contains(&"list-markers".into()));
+        assert!(names.contains(&"line-length".into()));
+        assert_eq!(
+            format!("{:?}", equivalents.commands.last().unwrap()),
+            "CodeFences { style: Tildes }"
+        );
+        assert!(equivalents.unsupported.is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+//! An mdBook preprocessor, which styles every chapter of a book when it's built,
+//! without changing its sources.
+//!
+//! It runs the rules configured in `book.toml`, like:
+//!
+//! ```toml
+//! [preprocessor.style-markdown]
+//! command = "style-markdown mdbook-preprocessor"
+//! rules = ["quotes", "dashes --style unicode"]
+//! ```
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use serde_json::Value;
+
+use crate::diagnostic::Diagnostic;
+use crate::presets;
+use crate::Command;
+use crate::FinalNewline;
+
+/// The name of the preprocessor's table in `book.toml`.
+pub const NAME: &str = "style-markdown";
+
+/// The diagnostics of each chapter, by its path, with the name of the rule that found each.
+pub type ChapterDiagnostics = Vec<(PathBuf, Vec<(String, Diagnostic)>)>;
+
+/// Rewrite the chapters in `items`, and their sub-chapters, with `commands`.
+fn rewrite_chapters(
+    items: Option<&mut Value>,
+    src: &Path,
+    commands: &[Command],
+    diagnostics: &mut ChapterDiagnostics,
+) -> eyre::Result<()> {
+    let Some(items) = items.and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    for item in items {
+        // Separators and part titles aren't chapters.
+        let Some(chapter) = item.get_mut("Chapter") else {
+            continue;
+        };
+        // Draft chapters have no file.
+        if let Some(path) = chapter["source_path"].as_str().or(chapter["path"].as_str()) {
+            let path = src.join(path);
+            let content = chapter["content"].as_str().unwrap_or_default().to_owned();
+            let rewritten = crate::rewrite(&path, content, commands, FinalNewline::default())?;
+            chapter["content"] = rewritten.text.into();
+            diagnostics.push((path, rewritten.diagnostics));
+        }
+        rewrite_chapters(chapter.get_mut("sub_items"), src, commands, diagnostics)?;
+    }
+    Ok(())
+}
+
+/// Preprocess the `[context, book]` JSON that mdBook sends,
+/// returning the book with every chapter styled, and the diagnostics of each chapter.
+pub fn preprocess(input: &str) -> eyre::Result<(Value, ChapterDiagnostics)> {
+    let (context, mut book) = serde_json::from_str::<(Value, Value)>(input)
+        .wrap_err("expected mdBook's `[context, book]` JSON")?;
+    let config = &context["config"];
+    let commands = config["preprocessor"][NAME]["rules"]
+        .as_array()
+        .ok_or_else(|| eyre!("`preprocessor.{NAME}.rules` in `book.toml` isn't a list"))?
+        .iter()
+        .map(|rule| {
+            rule.as_str()
+                .ok_or_else(|| eyre!("`preprocessor.{NAME}.rules` has a non-string"))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let commands = presets::parse_rules(&commands)?;
+    let root = Path::new(context["root"].as_str().unwrap_or_default());
+    let src = root.join(config["book"]["src"].as_str().unwrap_or("src"));
+    let mut diagnostics = Vec::new();
+    // Older versions of mdBook call the items `sections`.
+    for key in ["items", "sections"] {
+        rewrite_chapters(book.get_mut(key), &src, &commands, &mut diagnostics)?;
+    }
+    Ok((book, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use crate::diagnostic::Diagnostic;
+    use crate::mdbook::preprocess;
+
+    #[test]
+    fn test_preprocess() {
+        let chapter = |path: &str, content: &str, sub_items| {
+            json!({"Chapter": {
+                "name": path,
+                "content": content,
+                "path": path,
+                "source_path": path,
+                "sub_items": sub_items,
+            }})
+        };
+        let context = json!({
+            "root": "/book",
+            "renderer": "html",
+            "config": {
+                "book": {},
+                "preprocessor": {"style-markdown": {"rules": ["quotes", "doubled-words --report-only"]}},
+            },
+        });
+        let book = json!({"sections": [
+            chapter("a.md", "# ‘A’", json!([chapter("b.md", "The the end", json!([]))])),
+            "Separator",
+        ]});
+        let (book, diagnostics) = preprocess(&json!([context, book]).to_string()).unwrap();
+        assert_eq!(
+            book,
+            json!({"sections": [
+                chapter("a.md", "# 'A'\n", json!([chapter("b.md", "The the end\n", json!([]))])),
+                "Separator",
+            ]})
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (PathBuf::from("/book/src/a.md"), vec![]),
+                (
+                    PathBuf::from("/book/src/b.md"),
+                    vec![(
+                        "doubled-words".into(),
+                        Diagnostic::new(1, "doubled word `the`")
+                    )]
+                ),
+            ]
+        );
+    }
+}
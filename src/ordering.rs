@@ -0,0 +1,159 @@
+//! The order that rules run in, which is the same whatever order they're given in,
+//! so that the output doesn't depend on how a preset happens to list its rules.
+
+use color_eyre::eyre;
+use color_eyre::eyre::bail;
+use color_eyre::eyre::eyre;
+
+use crate::Command;
+
+/// Pairs of rules where the first runs before the second when both are run,
+/// since it changes what the second sees.
+pub const RUNS_BEFORE: &[(&str, &str)] = &[
+    // `[URL\_2](URL_2)` is only a simple URL once its text is unescaped.
+    ("escapes", "simplify-urls"),
+    // Newly linked URLs are canonicalized too.
+    ("bare-urls", "canonical-urls"),
+    // Images written in HTML are only checked once they're Markdown.
+    ("html-images", "alt-text"),
+    // A zero-width space between a letter and a combining accent keeps them from composing.
+    ("invisible-chars", "nfc"),
+    // Indented code becomes fenced code, which is then styled and checked like the rest.
+    ("fence-indented-code", "code-fences"),
+    ("fence-indented-code", "code-languages"),
+    ("fence-indented-code", "block-spacing"),
+    // The table of contents and anchors use the numbered headings.
+    ("atx-headings", "number-headings"),
+    // Setext headings are spaced as ATX headings.
+    ("atx-headings", "heading-spacing"),
+    ("number-headings", "toc"),
+    ("number-headings", "duplicate-headings"),
+    // Terms that are fixed aren't reported.
+    ("capitalization", "spell"),
+    ("capitalization", "prose-lint"),
+    // Lines are checked once they're broken.
+    ("semantic-line-breaks", "line-length"),
+    // New footnotes are placed, numbered, and checked like the rest.
+    ("inline-footnotes", "footnotes-after-punctuation"),
+    ("inline-footnotes", "renumber-footnotes"),
+    ("inline-footnotes", "sort-footnote-definitions"),
+    ("inline-footnotes", "footnotes-to-end"),
+    ("inline-footnotes", "footnote-check"),
+    // HTML images are converted too, and new definitions are merged and sorted like the rest.
+    ("html-images", "reference-images"),
+    ("reference-images", "ref-definitions"),
+    ("reference-images", "sort-ref-definitions"),
+    // Definitions are merged before they're sorted.
+    ("ref-definitions", "sort-ref-definitions"),
+    // Removing and moving lines can leave runs of blank lines,
+    // and lines of only whitespace become blank.
+    ("html-comments", "blank-lines"),
+    ("ref-definitions", "blank-lines"),
+    ("sort-ref-definitions", "blank-lines"),
+    ("footnotes-to-end", "blank-lines"),
+    ("trailing-whitespace", "blank-lines"),
+];
+
+/// The rules that `rule` runs after, if they're also run.
+pub fn runs_after(rule: &str) -> Vec<&'static str> {
+    RUNS_BEFORE
+        .iter()
+        .filter(|&&(_, after)| after == rule)
+        .map(|&(before, _)| before)
+        .collect()
+}
+
+/// Order `commands` so that each runs after the ones it [runs after](RUNS_BEFORE),
+/// and otherwise in the order they're defined in [`Command`].
+///
+/// A rule given twice is only run once,
+/// unless they have different options, like `quotes` and `quotes --to smart`,
+/// which is an error, since the output would depend on which ran last.
+pub fn order(commands: Vec<Command>) -> eyre::Result<Vec<Command>> {
+    let mut unique = Vec::<Command>::new();
+    for command in commands {
+        let name = command.name();
+        match unique.iter().find(|other| other.name() == name) {
+            Some(other) if format!("{other:?}") == format!("{command:?}") => {}
+            Some(_) => bail!("`{name}` is given twice with different options"),
+            None => unique.push(command),
+        }
+    }
+    let rules = Command::rules();
+    unique.sort_by_key(|command| {
+        let name = command.name();
+        rules.iter().position(|rule| rule.name == name)
+    });
+    let mut ordered = Vec::with_capacity(unique.len());
+    while !unique.is_empty() {
+        let names = unique.iter().map(Command::name).collect::<Vec<_>>();
+        let next = names
+            .iter()
+            .position(|name| {
+                runs_after(name)
+                    .iter()
+                    .all(|before| !names.iter().any(|name| name == before))
+            })
+            .ok_or_else(|| eyre!("the order of {} is circular", names.join(", ")))?;
+        ordered.push(unique.remove(next));
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ordering::order;
+    use crate::ordering::runs_after;
+    use crate::ordering::RUNS_BEFORE;
+    use crate::presets;
+    use crate::Command;
+
+    fn names(rules: &[&str]) -> Vec<String> {
+        let commands = rules
+            .iter()
+            .map(|rule| Command::from_rule(rule).unwrap())
+            .collect();
+        order(commands).unwrap().iter().map(Command::name).collect()
+    }
+
+    #[test]
+    fn test_order() {
+        let rules = ["simplify-urls", "blank-lines", "escapes", "quotes"];
+        let ordered = ["quotes", "blank-lines", "escapes", "simplify-urls"];
+        assert_eq!(names(&rules), ordered);
+        let mut reversed = rules;
+        reversed.reverse();
+        assert_eq!(names(&reversed), ordered);
+        assert_eq!(names(&["quotes", "dequote"]), ["quotes"]);
+        let commands = presets::parse_rules(&["quotes", "quotes --to smart"]);
+        assert_eq!(
+            commands.unwrap_err().to_string(),
+            "`quotes` is given twice with different options"
+        );
+        assert_eq!(runs_after("toc"), ["number-headings"]);
+    }
+
+    #[test]
+    fn test_runs_before() {
+        let rules = Command::rules();
+        for (before, after) in RUNS_BEFORE {
+            for rule in [before, after] {
+                assert!(rules.iter().any(|other| other.name == *rule), "{rule}");
+            }
+        }
+        // Every rule can be ordered, so there are no cycles.
+        let all = RUNS_BEFORE
+            .iter()
+            .flat_map(|(before, after)| [*before, *after])
+            .filter_map(|rule| Command::from_rule(rule).ok())
+            .collect::<Vec<_>>();
+        let names = all.iter().map(Command::name).collect::<Vec<_>>();
+        let ordered = order(all).unwrap();
+        let position = |rule: &str| ordered.iter().position(|command| command.name() == rule);
+        for (before, after) in RUNS_BEFORE {
+            if names.iter().any(|name| name == before) && names.iter().any(|name| name == after) {
+                assert!(position(before) < position(after), "{before} {after}");
+            }
+        }
+    }
+}
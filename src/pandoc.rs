@@ -0,0 +1,193 @@
+//! A Pandoc JSON filter, which applies the rules that make sense on Pandoc's AST,
+//! so that Pandoc pipelines can use them without going through Markdown, like:
+//!
+//! ```sh
+//! pandoc -t json post.md | style-markdown pandoc-filter --rule quotes | pandoc -f json -o post.html
+//! ```
+//!
+//! Only the text of `Str`, `Space`, and `SoftBreak` inlines is rewritten,
+//! so code, math, raw blocks, and URLs are left alone.
+
+use std::path::Path;
+use std::slice;
+use std::sync::LazyLock;
+
+use color_eyre::eyre;
+use color_eyre::eyre::ensure;
+use itertools::Itertools;
+use regex::Regex;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::Command;
+use crate::FinalNewline;
+
+/// Whether `command` can rewrite Pandoc's AST.
+pub fn supports(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Quotes { .. }
+            | Command::ThroughRunning
+            | Command::Capitalization { .. }
+            | Command::FootnotesAfterPunctuation
+    )
+}
+
+fn is_text(inline: &Value) -> bool {
+    matches!(inline["t"].as_str(), Some("Str" | "Space" | "SoftBreak"))
+}
+
+/// Rewrite a run of text inlines with `command` as if it were a line of prose.
+fn rewrite_text(run: Vec<Value>, command: &Command) -> eyre::Result<Vec<Value>> {
+    let before = run
+        .iter()
+        .map(|inline| match inline["t"].as_str() {
+            Some("Str") => inline["c"].as_str().unwrap_or_default(),
+            Some("Space") => " ",
+            _ => "\n",
+        })
+        .collect::<String>();
+    // There's no path to guess the dialect from, so it's the current one.
+    let commands = slice::from_ref(command);
+    let after = crate::rewrite(Path::new(""), before.clone(), commands, FinalNewline::Keep)?.text;
+    // Keep the original inlines if nothing changed.
+    if after == before {
+        return Ok(run);
+    }
+    static TOKEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[^ \n]+|[ \n]").unwrap());
+    let inlines = TOKEN
+        .find_iter(&after)
+        .map(|token| match token.as_str() {
+            " " => json!({"t": "Space"}),
+            "\n" => json!({"t": "SoftBreak"}),
+            text => json!({"t": "Str", "c": text}),
+        })
+        .collect();
+    Ok(inlines)
+}
+
+/// Move punctuation right after a footnote to before it, like in [`Command::FootnotesAfterPunctuation`].
+fn move_footnotes_after_punctuation(inlines: &mut Vec<Value>) {
+    let mut i = 0;
+    while i + 1 < inlines.len() {
+        let next = &inlines[i + 1];
+        let punctuation = match next["c"].as_str() {
+            Some(text) if inlines[i]["t"] == "Note" && next["t"] == "Str" => {
+                text.chars().next().filter(|&c| ".!?;,".contains(c))
+            }
+            _ => None,
+        };
+        if let Some(punctuation) = punctuation {
+            let rest = &inlines[i + 1]["c"].as_str().unwrap()[punctuation.len_utf8()..];
+            if rest.is_empty() {
+                inlines.remove(i + 1);
+            } else {
+                inlines[i + 1]["c"] = rest.to_owned().into();
+            }
+            match i.checked_sub(1).filter(|&j| inlines[j]["t"] == "Str") {
+                Some(j) => {
+                    let text = format!("{}{punctuation}", inlines[j]["c"].as_str().unwrap());
+                    inlines[j]["c"] = text.into();
+                }
+                None => {
+                    inlines.insert(i, json!({"t": "Str", "c": punctuation.to_string()}));
+                    i += 1;
+                }
+            }
+        }
+        i += 1;
+    }
+}
+
+/// Rewrite every list of inlines in `value`, recursively.
+fn rewrite_inlines(value: &mut Value, command: &Command) -> eyre::Result<()> {
+    match value {
+        Value::Array(values) => {
+            if let Command::FootnotesAfterPunctuation = command {
+                move_footnotes_after_punctuation(values);
+            } else {
+                let mut rewritten = Vec::new();
+                for (is_text, run) in &std::mem::take(values).into_iter().chunk_by(is_text) {
+                    match is_text {
+                        true => rewritten.extend(rewrite_text(run.collect(), command)?),
+                        false => rewritten.extend(run),
+                    }
+                }
+                *values = rewritten;
+            }
+            for value in values {
+                rewrite_inlines(value, command)?;
+            }
+        }
+        Value::Object(object) => {
+            for value in object.values_mut() {
+                rewrite_inlines(value, command)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Rewrite the Pandoc AST JSON `input`, including its metadata, with each of `commands` in order.
+pub fn filter(input: &str, commands: &[Command]) -> eyre::Result<Value> {
+    for command in commands {
+        ensure!(
+            supports(command),
+            "`{}` only works on Markdown, not Pandoc's AST",
+            command.name()
+        );
+    }
+    let mut document = serde_json::from_str::<Value>(input)?;
+    ensure!(
+        document.get("blocks").is_some(),
+        "expected Pandoc's JSON, like from `pandoc -t json`"
+    );
+    for command in commands {
+        rewrite_inlines(&mut document, command)?;
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::pandoc::filter;
+    use crate::Command;
+
+    #[test]
+    fn test_filter() {
+        let str = |text: &str| json!({"t": "Str", "c": text});
+        let space = json!({"t": "Space"});
+        let note = json!({"t": "Note", "c": [{"t": "Para", "c": [str("Source")]}]});
+        let document = json!({
+            "pandoc-api-version": [1, 23, 1],
+            "meta": {},
+            "blocks": [
+                {"t": "Para", "c": [
+                    str("“Github"), space, str("is"), {"t": "SoftBreak"}, str("great”"),
+                    note, str("."), space, {"t": "Code", "c": [["", [], []], "“github”"]},
+                ]},
+            ],
+        });
+        let commands = [
+            Command::from_rule("quotes").unwrap(),
+            Command::Capitalization { terms: Vec::new() },
+            Command::FootnotesAfterPunctuation,
+        ];
+        let filtered = filter(&document.to_string(), &commands).unwrap();
+        assert_eq!(
+            filtered["blocks"][0]["c"],
+            json!([
+                str("\"GitHub"), space, str("is"), {"t": "SoftBreak"}, str("great\"."),
+                note, space, {"t": "Code", "c": [["", [], []], "“github”"]},
+            ])
+        );
+        let error = filter(&document.to_string(), &[Command::Nfc]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`nfc` only works on Markdown, not Pandoc's AST"
+        );
+    }
+}
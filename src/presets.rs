@@ -0,0 +1,115 @@
+//! Presets, i.e. named lists of rules run in order for a common workflow.
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use itertools::Itertools;
+
+use crate::ordering;
+use crate::Command;
+
+/// Clean up Markdown exported from Google Docs, which is what most of the original rules were for.
+///
+/// Embedded images are removed first, so the later rules don't have to scan their base64.
+pub const GDOCS_CLEANUP: &[&str] = &[
+    "embedded-images",
+    "escapes",
+    "quotes",
+    "extra-ref-spaces",
+    "simplify-urls",
+    "blank-lines",
+];
+
+/// The built-in presets by name.
+pub const BUILTIN: &[(&str, &[&str])] = &[("gdocs-cleanup", GDOCS_CLEANUP)];
+
+/// Parse each of `rules`, like `dashes --style unicode`, and [order](ordering::order) them.
+pub fn parse_rules(rules: &[impl AsRef<str>]) -> eyre::Result<Vec<Command>> {
+    let commands = rules
+        .iter()
+        .map(|rule| {
+            let rule = rule.as_ref();
+            Command::from_rule(rule).wrap_err_with(|| format!("invalid rule `{rule}`"))
+        })
+        .collect::<eyre::Result<_>>()?;
+    ordering::order(commands)
+}
+
+/// Parse a list of rules separated by `;` or newlines, like `quotes; dashes --style unicode`,
+/// since rules' options can have spaces and commas.
+pub fn parse_rule_list(list: &str) -> eyre::Result<Vec<Command>> {
+    let rules = list
+        .split([';', '\n'])
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .collect::<Vec<_>>();
+    parse_rules(&rules)
+}
+
+/// The rules of the built-in preset `name`.
+pub fn builtin(name: &str) -> eyre::Result<Vec<Command>> {
+    let (_, rules) = BUILTIN
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .ok_or_else(|| {
+            let names = BUILTIN
+                .iter()
+                .map(|(name, _)| format!("`{name}`"))
+                .join(", ");
+            eyre!("unknown preset `{name}`; expected one of {names}")
+        })?;
+    parse_rules(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::presets::builtin;
+    use crate::presets::parse_rule_list;
+    use crate::presets::BUILTIN;
+    use crate::Command;
+    use crate::FinalNewline;
+
+    #[test]
+    fn test_builtin() {
+        for (name, rules) in BUILTIN {
+            assert_eq!(builtin(name).unwrap().len(), rules.len());
+        }
+        assert_eq!(
+            builtin("blog").unwrap_err().to_string(),
+            "unknown preset `blog`; expected one of `gdocs-cleanup`"
+        );
+        let before = "“Hi”, see [example.com/a\\_b](example.com/a_b)\\.
+
+
+
+![][image1]
+
+[image1]:    <data:image/png;base64,iVBORw0KGgo>";
+        let after = "\"Hi\", see <example.com/a_b>.
+
+![][image1]
+
+[image1]: TODO
+";
+        let commands = builtin("gdocs-cleanup").unwrap();
+        let rewritten = crate::rewrite(
+            Path::new("a.md"),
+            before.into(),
+            &commands,
+            FinalNewline::One,
+        )
+        .unwrap();
+        assert_eq!(rewritten.text, after);
+    }
+
+    #[test]
+    fn test_parse_rule_list() {
+        let commands =
+            parse_rule_list("quotes; front-matter --order title,date\n\nsembr;").unwrap();
+        let names = commands.iter().map(Command::name).collect::<Vec<_>>();
+        assert_eq!(names, ["quotes", "semantic-line-breaks", "front-matter"]);
+        assert!(parse_rule_list("quotes; quotse").is_err());
+    }
+}
@@ -0,0 +1,460 @@
+//! Word-level rules for prose, like doubled words and compound modifiers.
+
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use regex::Regex;
+
+use crate::diagnostic::Diagnostic;
+use crate::lists;
+use crate::markdown;
+use crate::markdown::LineKind;
+
+/// Words that are often correctly doubled, like `had had`.
+pub const DOUBLED_WORDS: &[&str] = &[
+    "had", "that", "bye", "no", "so", "very", "really", "ha", "knock", "tsk", "yada", "chop", "wow",
+];
+
+/// A word in prose, by its byte range in the line.
+struct Word<'a> {
+    start: usize,
+    end: usize,
+    text: &'a str,
+}
+
+/// Remove accidentally doubled words, like `the the`, except for [`DOUBLED_WORDS`] and `allowed`.
+///
+/// This includes words doubled across a line break within a paragraph,
+/// but not across code, links, or other non-prose.
+/// If `report_only`, report them instead.
+pub fn remove_doubled_words(
+    before: String,
+    allowed: &[String],
+    report_only: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\p{L}[\p{L}'’]*").unwrap());
+    let is_allowed = |word: &str| {
+        let word = word.to_lowercase();
+        DOUBLED_WORDS.contains(&word.as_str())
+            || allowed.iter().any(|allowed| allowed.to_lowercase() == word)
+    };
+    let report = |line: usize, word: &str, can_fix: bool| match can_fix && !report_only {
+        true => Diagnostic::note(line, format!("removed doubled word `{word}`")),
+        false => Diagnostic::new(line, format!("doubled word `{word}`")),
+    };
+    let mut after = Vec::<String>::new();
+    // The last word of the previous line, if nothing came after it.
+    let mut trailing_word = None::<String>;
+    for (i, line) in markdown::lines(&before).enumerate() {
+        let trimmed = line.text.trim();
+        let starts_block = trimmed.is_empty()
+            || markdown::is_heading(line.text)
+            || markdown::is_thematic_break(trimmed)
+            || lists::parse_item(trimmed).is_some();
+        if line.kind != LineKind::Text || markdown::is_heading(line.text) {
+            after.push(line.text.to_owned());
+            trailing_word = None;
+            continue;
+        }
+        if starts_block {
+            trailing_word = None;
+        }
+        let mut is_line_start = true;
+        let mut line_trailing_word = None;
+        let rewritten = markdown::rewrite_prose(line.text, |text| {
+            let words = WORD
+                .find_iter(text)
+                .map(|m| Word {
+                    start: m.start(),
+                    end: m.end(),
+                    text: m.as_str(),
+                })
+                .collect::<Vec<_>>();
+            let mut removed = Vec::new();
+            if is_line_start {
+                if let (Some(previous), Some(first)) = (&trailing_word, words.first()) {
+                    let is_at_start = text[..first.start].trim().is_empty();
+                    if is_at_start
+                        && previous.to_lowercase() == first.text.to_lowercase()
+                        && !is_allowed(first.text)
+                    {
+                        // Don't leave an empty line, which would split the paragraph.
+                        let can_fix = words.len() > 1 || !text[first.end..].trim().is_empty();
+                        diagnostics.push(report(i + 1, first.text, can_fix));
+                        if can_fix {
+                            let end = first.end + text[first.end..].len()
+                                - text[first.end..].trim_start().len();
+                            removed.push(first.start..end);
+                        }
+                    }
+                }
+            }
+            is_line_start = false;
+            for pair in words.windows(2) {
+                let [first, second] = pair else {
+                    unreachable!();
+                };
+                let is_adjacent = text[first.end..second.start]
+                    .chars()
+                    .all(char::is_whitespace);
+                if is_adjacent
+                    && first.text.to_lowercase() == second.text.to_lowercase()
+                    && !is_allowed(second.text)
+                {
+                    diagnostics.push(report(i + 1, second.text, true));
+                    removed.push(first.end..second.end);
+                }
+            }
+            line_trailing_word = words
+                .last()
+                .filter(|word| text[word.end..].trim().is_empty())
+                .map(|word| word.text.to_owned());
+            if report_only {
+                return text.to_owned();
+            }
+            let mut rewritten = String::new();
+            let mut last = 0;
+            for range in removed {
+                rewritten.push_str(&text[last..range.start]);
+                last = range.end;
+            }
+            rewritten.push_str(&text[last..]);
+            rewritten
+        });
+        trailing_word = line_trailing_word;
+        after.push(rewritten);
+    }
+    if report_only {
+        return before;
+    }
+    after.join("\n")
+}
+
+/// Phrases that are hyphenated when they modify a noun, like `high-speed rail`.
+pub const COMPOUND_MODIFIERS: &[&str] = &[
+    "high speed",
+    "third rail",
+    "long distance",
+    "short distance",
+    "real time",
+    "open source",
+    "full time",
+    "part time",
+    "low income",
+    "well known",
+    "single track",
+    "double track",
+    "grade separated",
+    "at grade",
+    "cross platform",
+    "last mile",
+    "first class",
+    "state of the art",
+    "up to date",
+    "one way",
+    "two way",
+];
+
+/// Words that can follow a compound modifier that isn't modifying them,
+/// like in `trains run at high speed in the tunnel`.
+const NOT_NOUNS: &[&str] = &[
+    "a", "an", "the", "and", "or", "but", "nor", "so", "yet", "of", "in", "on", "at", "to", "for",
+    "by", "with", "from", "into", "onto", "over", "under", "as", "than", "is", "are", "was",
+    "were", "be", "been", "being", "has", "have", "had", "do", "does", "did", "will", "would",
+    "can", "could", "should", "may", "might", "must", "that", "which", "who", "when", "where",
+    "while", "if", "because", "it", "its", "this", "these", "those", "there", "then", "now", "too",
+    "also", "only", "just", "not",
+];
+
+/// Hyphenate [`COMPOUND_MODIFIERS`] and `extra_phrases` when they're followed by (probably) a noun.
+///
+/// If `report_only`, report them instead, since it's a guess without understanding the sentence.
+pub fn hyphenate_compound_modifiers(
+    before: String,
+    extra_phrases: &[String],
+    report_only: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let phrases = COMPOUND_MODIFIERS
+        .iter()
+        .copied()
+        .chain(extra_phrases.iter().map(String::as_str))
+        .map(|phrase| {
+            phrase
+                .split_whitespace()
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(r"[ \t]+")
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+    let modifier = Regex::new(&format!(
+        r"(?i)\b(?<phrase>{phrases})[ \t]+(?<next>\p{{L}}+)"
+    ))
+    .unwrap();
+    let after = markdown::lines(&before)
+        .enumerate()
+        .map(|(i, line)| {
+            if line.kind != LineKind::Text || markdown::is_heading(line.text) {
+                return line.text.to_owned();
+            }
+            markdown::rewrite_prose(line.text, |text| {
+                let mut rewritten = String::new();
+                let mut last = 0;
+                // Matches consume the next word, so find them one at a time to allow `high speed third rail trains`.
+                let mut start = 0;
+                while let Some(captures) = modifier.captures_at(text, start) {
+                    let phrase = captures.name("phrase").unwrap();
+                    let next = &captures["next"];
+                    start = phrase.end();
+                    let is_start_of_word = !text[..phrase.start()].ends_with(['-', '\'']);
+                    if NOT_NOUNS.contains(&next.to_lowercase().as_str()) || !is_start_of_word {
+                        continue;
+                    }
+                    let hyphenated = phrase
+                        .as_str()
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join("-");
+                    match report_only {
+                        true => diagnostics.push(Diagnostic::new(
+                            i + 1,
+                            format!(
+                                "`{}` modifies `{next}`, so it should be `{hyphenated}`",
+                                phrase.as_str()
+                            ),
+                        )),
+                        false => diagnostics.push(Diagnostic::note(
+                            i + 1,
+                            format!("hyphenated `{hyphenated}` before `{next}`"),
+                        )),
+                    }
+                    rewritten.push_str(&text[last..phrase.start()]);
+                    rewritten.push_str(&hyphenated);
+                    last = phrase.end();
+                }
+                rewritten.push_str(&text[last..]);
+                match report_only {
+                    true => text.to_owned(),
+                    false => rewritten,
+                }
+            })
+        })
+        .join("\n");
+    after
+}
+
+/// Hedging and filler words and phrases that can usually be removed.
+pub const WEASEL_WORDS: &[&str] = &[
+    "very",
+    "really",
+    "simply",
+    "just",
+    "quite",
+    "basically",
+    "actually",
+    "obviously",
+    "clearly",
+    "extremely",
+    "fairly",
+    "somewhat",
+    "arguably",
+    "of course",
+    "needless to say",
+    "it goes without saying that",
+    "it is worth noting that",
+    "it should be noted that",
+    "it is important to note that",
+];
+
+/// Report [`WEASEL_WORDS`] and `extra_phrases` in prose, except `allowed` ones.
+///
+/// A `<!-- style-markdown-allow: very, simply -->` comment allows phrases
+/// from its line to the end of its paragraph.
+pub fn report_weasel_words(
+    before: String,
+    extra_phrases: &[String],
+    allowed: &[String],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    static ALLOW_COMMENT: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"<!--\s*style-markdown-allow:(?<phrases>.*?)-->").unwrap());
+    let phrases = WEASEL_WORDS
+        .iter()
+        .copied()
+        .chain(extra_phrases.iter().map(String::as_str))
+        .map(|phrase| {
+            phrase
+                .split_whitespace()
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(r"\s+")
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+    let weasel = Regex::new(&format!(r"(?i)\b(?:{phrases})\b")).unwrap();
+    let normalize = |phrase: &str| phrase.split_whitespace().join(" ").to_lowercase();
+    let allowed = allowed
+        .iter()
+        .map(|phrase| normalize(phrase))
+        .collect::<Vec<_>>();
+    let mut allowed_in_paragraph = Vec::<String>::new();
+    for (i, line) in markdown::lines(&before).enumerate() {
+        if line.kind != LineKind::Text || line.text.trim().is_empty() {
+            allowed_in_paragraph.clear();
+            continue;
+        }
+        for captures in ALLOW_COMMENT.captures_iter(line.text) {
+            allowed_in_paragraph.extend(captures["phrases"].split(',').map(normalize));
+        }
+        markdown::rewrite_prose(line.text, |text| {
+            for m in weasel.find_iter(text) {
+                let phrase = normalize(m.as_str());
+                if allowed.contains(&phrase) || allowed_in_paragraph.contains(&phrase) {
+                    continue;
+                }
+                diagnostics.push(Diagnostic::new(
+                    i + 1,
+                    format!(
+                        "`{}` is filler; remove it or allow it with `<!-- style-markdown-allow: {phrase} -->`",
+                        m.as_str()
+                    ),
+                ));
+            }
+            text.to_owned()
+        });
+    }
+    before
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::prose::hyphenate_compound_modifiers;
+    use crate::prose::remove_doubled_words;
+    use crate::prose::report_weasel_words;
+
+    #[test]
+    fn test_remove_doubled_words() {
+        let before = "The the train is is late, but it had had delays.
+It stopped at the
+the station. `a a` and [b](c) [b](c).
+the
+
+## The
+The end";
+        let after = "The train is late, but it had had delays.
+It stopped at the
+station. `a a` and [b](c) [b](c).
+the
+
+## The
+The end";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            remove_doubled_words(before.into(), &[], false, &mut diagnostics),
+            after
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::note(1, "removed doubled word `the`"),
+                Diagnostic::note(1, "removed doubled word `is`"),
+                Diagnostic::note(3, "removed doubled word `the`"),
+            ]
+        );
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            remove_doubled_words(before.into(), &["is".into()], true, &mut diagnostics),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::new(1, "doubled word `the`"),
+                Diagnostic::new(3, "doubled word `the`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hyphenate_compound_modifiers() {
+        let before = "High speed rail uses a third rail shoe, not the third rail.
+It runs at high speed in a state of the art tunnel, unlike non-high speed trains.
+Unlike `high speed rail` or [long distance trains](https://example.com/long distance).";
+        let after = "High-speed rail uses a third-rail shoe, not the third rail.
+It runs at high speed in a state-of-the-art tunnel, unlike non-high speed trains.
+Unlike `high speed rail` or [long-distance trains](https://example.com/long distance).";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            hyphenate_compound_modifiers(before.into(), &[], false, &mut diagnostics),
+            after
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::note(1, "hyphenated `High-speed` before `rail`"),
+                Diagnostic::note(1, "hyphenated `third-rail` before `shoe`"),
+                Diagnostic::note(2, "hyphenated `state-of-the-art` before `tunnel`"),
+                Diagnostic::note(3, "hyphenated `long-distance` before `trains`"),
+            ]
+        );
+        let before = "Stop the bus stop sign.";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            hyphenate_compound_modifiers(
+                before.into(),
+                &["bus stop".into()],
+                true,
+                &mut diagnostics
+            ),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [Diagnostic::new(
+                1,
+                "`bus stop` modifies `sign`, so it should be `bus-stop`"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_report_weasel_words() {
+        let before = "It is worth noting that the train is very
+fast. `simply` [simply](https://example.com/very)
+
+<!-- style-markdown-allow: very, of course -->
+Of course, it's very, very fast,
+and simply lovely.
+
+Very nice.";
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            report_weasel_words(
+                before.into(),
+                &["lovely".into()],
+                &["nice".into(), "simply".into()],
+                &mut diagnostics
+            ),
+            before
+        );
+        let filler = |line, phrase: &str| {
+            let allow = phrase.to_lowercase();
+            Diagnostic::new(
+                line,
+                format!("`{phrase}` is filler; remove it or allow it with `<!-- style-markdown-allow: {allow} -->`"),
+            )
+        };
+        assert_eq!(
+            diagnostics,
+            [
+                filler(1, "It is worth noting that"),
+                filler(1, "very"),
+                filler(6, "lovely"),
+                filler(8, "Very"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Editorial style checks from rule files, like Vale's,
+//! for banned phrases, preferred terms, and passive voice.
+
+use std::path::Path;
+use std::sync::LazyLock;
+
+use color_eyre::eyre;
+use color_eyre::eyre::bail;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use itertools::Itertools;
+use regex::Regex;
+use yaml_rust2::Yaml;
+use yaml_rust2::YamlLoader;
+
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Severity;
+use crate::markdown;
+use crate::markdown::LineKind;
+
+/// Forms of "to be" that start a passive verb phrase.
+const TO_BE: &[&str] = &["am", "is", "are", "was", "were", "be", "been", "being"];
+
+/// Common irregular past participles, since most end in `-ed`.
+const IRREGULAR_PARTICIPLES: &[&str] = &[
+    "built",
+    "begun",
+    "bought",
+    "brought",
+    "chosen",
+    "done",
+    "driven",
+    "found",
+    "given",
+    "known",
+    "made",
+    "paid",
+    "put",
+    "run",
+    "seen",
+    "sent",
+    "shown",
+    "sold",
+    "spent",
+    "taken",
+    "told",
+    "thought",
+    "understood",
+    "written",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProseRuleKind {
+    /// Phrases to avoid.
+    Banned(Vec<String>),
+
+    /// Terms to replace with a preferred term, as `(term, preferred)`.
+    Preferred(Vec<(String, String)>),
+
+    /// Verbs in the passive voice, like `was built`.
+    Passive,
+}
+
+/// A prose rule, parsed from a YAML file like:
+///
+/// ```yaml
+/// kind: preferred  # or `banned` or `passive`
+/// severity: note  # or `warning`, the default
+/// message: "use `%s` instead"  # optional, with `%s` replaced by the suggestion or match
+/// swap:  # for `preferred`
+///   e-mail: email
+/// phrases:  # for `banned`
+///   - in order to
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProseRule {
+    /// The file name without its extension.
+    pub name: String,
+
+    pub kind: ProseRuleKind,
+
+    pub severity: Severity,
+
+    pub message: Option<String>,
+}
+
+impl ProseRule {
+    /// Parse a rule named `name` from its YAML.
+    pub fn parse(name: &str, yaml: &str) -> eyre::Result<Self> {
+        let documents = YamlLoader::load_from_str(yaml)?;
+        let Some(rule) = documents.first() else {
+            bail!("empty rule");
+        };
+        let strings = |key: &str| -> eyre::Result<Vec<String>> {
+            rule[key]
+                .as_vec()
+                .ok_or_else(|| eyre!("`{key}` must be a list"))?
+                .iter()
+                .map(|phrase| {
+                    phrase
+                        .as_str()
+                        .map(str::to_owned)
+                        .ok_or_else(|| eyre!("`{key}` must be a list of strings"))
+                })
+                .collect()
+        };
+        let kind = match rule["kind"].as_str() {
+            Some("banned") => ProseRuleKind::Banned(strings("phrases")?),
+            Some("preferred") => {
+                let swap = rule["swap"]
+                    .as_hash()
+                    .ok_or_else(|| eyre!("`swap` must be a mapping"))?
+                    .iter()
+                    .map(|(term, preferred)| match (term, preferred) {
+                        (Yaml::String(term), Yaml::String(preferred)) => {
+                            Ok((term.clone(), preferred.clone()))
+                        }
+                        _ => Err(eyre!("`swap` must map strings to strings")),
+                    })
+                    .collect::<eyre::Result<_>>()?;
+                ProseRuleKind::Preferred(swap)
+            }
+            Some("passive") => ProseRuleKind::Passive,
+            kind => bail!("unknown kind {kind:?}; expected `banned`, `preferred`, or `passive`"),
+        };
+        let severity = match rule["severity"].as_str() {
+            None | Some("warning" | "error") => Severity::Warning,
+            Some("note" | "suggestion") => Severity::Note,
+            Some(severity) => bail!("unknown severity `{severity}`"),
+        };
+        Ok(Self {
+            name: name.to_owned(),
+            kind,
+            severity,
+            message: rule["message"].as_str().map(str::to_owned),
+        })
+    }
+
+    /// The regexes for what the rule matches, with the suggestion for each, if any.
+    fn patterns(&self) -> Vec<(Regex, Option<&str>)> {
+        let phrase = |phrase: &str| {
+            let words = phrase.split_whitespace().map(regex::escape).join(r"\s+");
+            Regex::new(&format!(r"(?i)\b{words}\b")).unwrap()
+        };
+        match &self.kind {
+            ProseRuleKind::Banned(phrases) => phrases
+                .iter()
+                .map(|banned| (phrase(banned), None))
+                .collect(),
+            ProseRuleKind::Preferred(swap) => swap
+                .iter()
+                .map(|(term, preferred)| (phrase(term), Some(preferred.as_str())))
+                .collect(),
+            ProseRuleKind::Passive => {
+                static PASSIVE: LazyLock<Regex> = LazyLock::new(|| {
+                    let to_be = TO_BE.join("|");
+                    let irregular = IRREGULAR_PARTICIPLES.join("|");
+                    Regex::new(&format!(
+                        r"(?i)\b(?:{to_be})\s+(?:\w+ly\s+)?(?:\w+ed|{irregular})\b"
+                    ))
+                    .unwrap()
+                });
+                vec![(PASSIVE.clone(), None)]
+            }
+        }
+    }
+
+    fn message(&self, matched: &str, suggestion: Option<&str>) -> String {
+        let default = match (&self.kind, suggestion) {
+            (ProseRuleKind::Preferred(_), Some(suggestion)) => {
+                format!("use `{suggestion}` instead of `{matched}`")
+            }
+            (ProseRuleKind::Passive, _) => format!("`{matched}` may be passive voice"),
+            _ => format!("avoid `{matched}`"),
+        };
+        let message = match &self.message {
+            Some(message) => message.replace("%s", suggestion.unwrap_or(matched)),
+            None => default,
+        };
+        format!("[{}] {message}", self.name)
+    }
+}
+
+/// Parse a prose rule from a YAML file, named after the file.
+pub fn parse_rule_file(path: &str) -> eyre::Result<ProseRule> {
+    let yaml = fs_err::read_to_string(path)?;
+    let name = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    ProseRule::parse(&name, &yaml).wrap_err_with(|| format!("invalid prose rule `{path}`"))
+}
+
+/// Report prose that breaks any of `rules`, with each rule's severity.
+pub fn lint_prose(
+    before: String,
+    rules: &[ProseRule],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let patterns = rules
+        .iter()
+        .map(|rule| (rule, rule.patterns()))
+        .collect::<Vec<_>>();
+    for (i, line) in markdown::lines(&before).enumerate() {
+        if line.kind != LineKind::Text {
+            continue;
+        }
+        markdown::rewrite_prose(line.text, |text| {
+            for (rule, patterns) in &patterns {
+                for (regex, suggestion) in patterns {
+                    for m in regex.find_iter(text) {
+                        diagnostics.push(Diagnostic {
+                            line: i + 1,
+                            severity: rule.severity,
+                            message: rule.message(m.as_str(), *suggestion),
+                        });
+                    }
+                }
+            }
+            text.to_owned()
+        });
+    }
+    before
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::diagnostic::Severity;
+    use crate::prose_lint::lint_prose;
+    use crate::prose_lint::ProseRule;
+    use crate::prose_lint::ProseRuleKind;
+
+    #[test]
+    fn test_parse() {
+        let yaml = "kind: preferred
+severity: note
+swap:
+  e-mail: email
+  train set: trainset
+";
+        assert_eq!(
+            ProseRule::parse("terms", yaml).unwrap(),
+            ProseRule {
+                name: "terms".into(),
+                kind: ProseRuleKind::Preferred(vec![
+                    ("e-mail".into(), "email".into()),
+                    ("train set".into(), "trainset".into()),
+                ]),
+                severity: Severity::Note,
+                message: None,
+            }
+        );
+        assert!(ProseRule::parse("bad", "kind: other").is_err());
+        assert!(ProseRule::parse("bad", "kind: banned\nphrases: a").is_err());
+    }
+
+    #[test]
+    fn test_lint_prose() {
+        let rules = [
+            ProseRule::parse(
+                "wordy",
+                "kind: banned\nmessage: \"`%s` is wordy\"\nphrases: [in order to]",
+            )
+            .unwrap(),
+            ProseRule::parse(
+                "terms",
+                "kind: preferred\nseverity: note\nswap: {E-mail: email}",
+            )
+            .unwrap(),
+            ProseRule::parse("passive", "kind: passive").unwrap(),
+        ];
+        let before = "The line was quickly built in order to
+serve riders. Send an e-mail, `in order to` test.
+In order to ride, trains are run hourly.";
+        let mut diagnostics = Vec::new();
+        assert_eq!(lint_prose(before.into(), &rules, &mut diagnostics), before);
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::new(1, "[wordy] `in order to` is wordy"),
+                Diagnostic::new(1, "[passive] `was quickly built` may be passive voice"),
+                Diagnostic::note(2, "[terms] use `email` instead of `e-mail`"),
+                Diagnostic::new(3, "[wordy] `In order to` is wordy"),
+                Diagnostic::new(3, "[passive] `are run` may be passive voice"),
+            ]
+        );
+    }
+}
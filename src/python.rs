@@ -0,0 +1,60 @@
+//! Python bindings, as the `style_markdown` module built with `maturin`.
+
+use std::path::Path;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::presets;
+use crate::FinalNewline;
+use crate::Rewritten;
+
+fn rewrite_with_rules(text: String, rules: &[String], path: &str) -> PyResult<Rewritten> {
+    let rewritten = presets::parse_rules(rules).and_then(|commands| {
+        crate::rewrite(Path::new(path), text, &commands, FinalNewline::default())
+    });
+    rewritten.map_err(|e| PyValueError::new_err(format!("{e:#}")))
+}
+
+/// Rewrite Markdown `text` with `rules`, like `["quotes", "dashes --style unicode"]`,
+/// in the same order whatever order they're given in.
+///
+/// `path` is the file name the rules see.
+#[pyfunction]
+#[pyo3(signature = (text, rules, path = "input.md"))]
+fn rewrite(text: String, rules: Vec<String>, path: &str) -> PyResult<String> {
+    Ok(rewrite_with_rules(text, &rules, path)?.text)
+}
+
+/// The diagnostics from rewriting Markdown `text` with `rules`, like [`rewrite`],
+/// as dicts with the `rule`, `line`, `severity`, and `message`.
+#[pyfunction]
+#[pyo3(signature = (text, rules, path = "input.md"))]
+fn check<'py>(
+    py: Python<'py>,
+    text: String,
+    rules: Vec<String>,
+    path: &str,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let rewritten = rewrite_with_rules(text, &rules, path)?;
+    rewritten
+        .diagnostics
+        .iter()
+        .map(|(rule, diagnostic)| {
+            let dict = PyDict::new(py);
+            dict.set_item("rule", rule)?;
+            dict.set_item("line", diagnostic.line)?;
+            dict.set_item("severity", diagnostic.severity.to_string())?;
+            dict.set_item("message", &diagnostic.message)?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+#[pymodule]
+fn style_markdown(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(rewrite, module)?)?;
+    module.add_function(wrap_pyfunction!(check, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! Readability metrics, like the Flesch–Kincaid grade level, per section.
+
+use std::cmp::Reverse;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use serde_json::json;
+
+use crate::stats;
+
+/// Estimate the syllables in `word` by counting groups of vowels.
+fn syllables(word: &str) -> usize {
+    let word = word
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .collect::<String>()
+        .to_lowercase();
+    let is_vowel = |c: char| "aeiouy".contains(c);
+    let mut count = 0;
+    let mut previous_is_vowel = false;
+    for c in word.chars() {
+        let is_vowel = is_vowel(c);
+        if is_vowel && !previous_is_vowel {
+            count += 1;
+        }
+        previous_is_vowel = is_vowel;
+    }
+    // A silent `e`, like in `make`, but not `table`.
+    if word.ends_with('e') && !word.ends_with("le") && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+/// The readability of a section, i.e. the text under a heading until the next heading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// The 1-based line number of the heading, if the section has one.
+    pub line: Option<usize>,
+
+    pub heading: Option<String>,
+
+    pub words: usize,
+    pub sentences: usize,
+    pub syllables: usize,
+}
+
+impl Section {
+    pub fn words_per_sentence(&self) -> f64 {
+        self.words as f64 / self.sentences.max(1) as f64
+    }
+
+    fn syllables_per_word(&self) -> f64 {
+        self.syllables as f64 / self.words.max(1) as f64
+    }
+
+    /// The Flesch–Kincaid grade level, i.e. about how many years of school are needed.
+    pub fn grade_level(&self) -> f64 {
+        0.39 * self.words_per_sentence() + 11.8 * self.syllables_per_word() - 15.59
+    }
+
+    /// The Flesch reading ease, from 0 (hardest) to 100 (easiest).
+    pub fn reading_ease(&self) -> f64 {
+        206.835 - 1.015 * self.words_per_sentence() - 84.6 * self.syllables_per_word()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "line": self.line,
+            "heading": self.heading,
+            "words": self.words,
+            "sentences": self.sentences,
+            "words_per_sentence": self.words_per_sentence(),
+            "grade_level": self.grade_level(),
+            "reading_ease": self.reading_ease(),
+        })
+    }
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.heading, self.line) {
+            (Some(heading), Some(line)) => write!(f, "{line}: {heading}: ")?,
+            _ => write!(f, "(before any heading): ")?,
+        }
+        write!(
+            f,
+            "{} words, {} sentences, {:.1} words/sentence, grade {:.1}, ease {:.1}",
+            self.words,
+            self.sentences,
+            self.words_per_sentence(),
+            self.grade_level(),
+            self.reading_ease(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sentence {
+    /// The 1-based line number of the paragraph the sentence is in.
+    pub line: usize,
+
+    pub words: usize,
+
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Readability {
+    pub sections: Vec<Section>,
+
+    /// The longest sentences, longest first.
+    pub longest_sentences: Vec<Sentence>,
+}
+
+impl Readability {
+    /// The readability of each section of `text`, and its `longest` longest sentences.
+    pub fn of(text: &str, longest: usize) -> Self {
+        let mut sections = Vec::<Section>::new();
+        let mut sentences = Vec::new();
+        for block in stats::prose_blocks(text) {
+            if block.heading_level.is_some() || sections.is_empty() {
+                sections.push(Section {
+                    line: block.heading_level.map(|_| block.line + 1),
+                    heading: block.heading_level.map(|_| block.text.clone()),
+                    words: 0,
+                    sentences: 0,
+                    syllables: 0,
+                });
+            }
+            if block.heading_level.is_some() {
+                continue;
+            }
+            let section = sections.last_mut().unwrap();
+            for sentence in stats::sentences(&block.text) {
+                let words = stats::words(sentence).collect::<Vec<_>>();
+                section.words += words.len();
+                section.sentences += 1;
+                section.syllables += words.iter().map(|word| syllables(word)).sum::<usize>();
+                sentences.push(Sentence {
+                    line: block.line + 1,
+                    words: words.len(),
+                    text: sentence.to_owned(),
+                });
+            }
+        }
+        sections.retain(|section| section.heading.is_some() || section.words > 0);
+        // Stable, so ties stay in document order.
+        sentences.sort_by_key(|sentence| Reverse(sentence.words));
+        sentences.truncate(longest);
+        Self {
+            sections,
+            longest_sentences: sentences,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let sections = self
+            .sections
+            .iter()
+            .map(Section::to_json)
+            .collect::<Vec<_>>();
+        let longest_sentences = self
+            .longest_sentences
+            .iter()
+            .map(|sentence| {
+                json!({
+                    "line": sentence.line,
+                    "words": sentence.words,
+                    "text": sentence.text,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "sections": sections,
+            "longest_sentences": longest_sentences,
+        })
+    }
+}
+
+impl Display for Readability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "sections:")?;
+        for section in &self.sections {
+            write!(f, "\n  {section}")?;
+        }
+        write!(f, "\nlongest sentences:")?;
+        for sentence in &self.longest_sentences {
+            write!(
+                f,
+                "\n  {}: {} words: {}",
+                sentence.line, sentence.words, sentence.text
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::readability::syllables;
+    use crate::readability::Readability;
+
+    #[test]
+    fn test_syllables() {
+        let counts = [
+            ("train", 1),
+            ("make", 1),
+            ("table", 2),
+            ("railway", 2),
+            ("electrification", 6),
+            ("the", 1),
+            ("42", 1),
+        ];
+        for (word, count) in counts {
+            assert_eq!(syllables(word), count, "{word}");
+        }
+    }
+
+    #[test]
+    fn test_readability() {
+        let text = "Intro text here.
+
+## Short
+
+The train is fast. It is red.
+
+## Long
+
+Electrification of commuter railways substantially improves acceleration,
+reliability, and maintenance costs compared to diesel operation.
+";
+        let readability = Readability::of(text, 2);
+        let sections = readability
+            .sections
+            .iter()
+            .map(|section| {
+                (
+                    section.heading.as_deref(),
+                    section.words,
+                    section.sentences,
+                    section.grade_level().round(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sections,
+            [
+                (None, 3, 1, 1.0),
+                (Some("Short"), 7, 2, -2.0),
+                (Some("Long"), 15, 1, 25.0),
+            ]
+        );
+        let longest = readability
+            .longest_sentences
+            .iter()
+            .map(|sentence| (sentence.line, sentence.words))
+            .collect::<Vec<_>>();
+        assert_eq!(longest, [(9, 15), (5, 4)]);
+    }
+}
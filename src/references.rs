@@ -0,0 +1,490 @@
+//! Link reference definitions, i.e. `[label]: destination "title"`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use regex::Regex;
+
+use crate::markdown;
+use crate::markdown::LineKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Definition<'a> {
+    pub label: &'a str,
+    pub destination: &'a str,
+    pub title: Option<&'a str>,
+}
+
+impl<'a> Definition<'a> {
+    /// Parse a reference definition, which is all of `text`.
+    ///
+    /// Like CommonMark, the destination can be on the line after the label,
+    /// and the title can be on the line after the destination, or span several lines.
+    ///
+    /// Footnote definitions (`[^label]: ...`) are not reference definitions.
+    pub fn parse(text: &'a str) -> Option<Self> {
+        static DEFINITION: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r#"^ {0,3}\[(?<label>[^\]^\n][^\]\n]*)\]:[ \t]*(?:\r?\n[ \t]*)?(?<destination><[^>\n]*>|\S+)(?:(?:[ \t]+|[ \t]*\r?\n[ \t]*)(?<title>"[^"]*"|'[^']*'|\([^)]*\)))?[ \t]*\r?$"#).unwrap()
+        });
+        let captures = DEFINITION.captures(text)?;
+        Some(Self {
+            label: captures.name("label").unwrap().as_str(),
+            destination: captures.name("destination").unwrap().as_str(),
+            title: captures.name("title").map(|title| title.as_str()),
+        })
+    }
+
+    /// The destination without any `<>`.
+    pub fn url(&self) -> &'a str {
+        self.destination
+            .strip_prefix('<')
+            .and_then(|destination| destination.strip_suffix('>'))
+            .unwrap_or(self.destination)
+    }
+}
+
+/// The reference definitions in `text`, with the lines each spans.
+///
+/// Like CommonMark, a definition can't interrupt a paragraph,
+/// so it has to follow a blank line, another definition, or a line that isn't paragraph text.
+pub fn definitions(text: &str) -> Vec<(Range<usize>, Definition<'_>)> {
+    // The label, destination, and title can each be on their own line,
+    // and the title can wrap, so try the most lines first.
+    const MAX_LINES: usize = 4;
+    let lines = markdown::lines(text).collect::<Vec<_>>();
+    let starts = lines
+        .iter()
+        .scan(0, |start, line| {
+            let line_start = *start;
+            *start += line.text.len() + 1;
+            Some(line_start)
+        })
+        .collect::<Vec<_>>();
+    let mut definitions = Vec::<(Range<usize>, Definition)>::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let follows_paragraph = i > 0 && {
+            let previous = lines[i - 1];
+            previous.kind == LineKind::Text
+                && !previous.text.trim().is_empty()
+                && !markdown::is_heading(previous.text)
+                && !markdown::is_thematic_break(previous.text)
+                && definitions
+                    .last()
+                    .is_none_or(|(definition_lines, _)| definition_lines.end != i)
+        };
+        if follows_paragraph || !lines[i].text.trim_start().starts_with('[') {
+            i += 1;
+            continue;
+        }
+        let found = (1..=MAX_LINES).rev().find_map(|len| {
+            let definition_lines = lines.get(i..i + len)?;
+            if definition_lines
+                .iter()
+                .any(|line| line.kind != LineKind::Text || line.text.trim().is_empty())
+            {
+                return None;
+            }
+            let end = starts[i + len - 1] + lines[i + len - 1].text.len();
+            Definition::parse(&text[starts[i]..end]).map(|definition| (len, definition))
+        });
+        match found {
+            Some((len, definition)) => {
+                definitions.push((i..i + len, definition));
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+    definitions
+}
+
+/// The reference definition that each line of `text` is part of, if any.
+pub fn line_definitions(text: &str) -> Vec<Option<Definition<'_>>> {
+    let mut line_definitions = vec![None; markdown::lines(text).count()];
+    for (lines, definition) in definitions(text) {
+        line_definitions[lines].fill(Some(definition));
+    }
+    line_definitions
+}
+
+/// Labels match case-insensitively and with whitespace collapsed.
+pub fn normalize_label(label: &str) -> String {
+    label.split_whitespace().join(" ").to_lowercase()
+}
+
+/// Merge definitions with the same destination and title into the first one,
+/// rewriting usages of the others to it,
+/// and delete definitions that are never used (unless `keep_unused`).
+pub fn dedup_definitions(before: String, keep_unused: bool) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let definitions = definitions(&before);
+    // The index in `definitions` of the definition that each line is part of, if any.
+    let mut line_definitions = vec![None; lines.len()];
+    for (i, (definition_lines, _)) in definitions.iter().enumerate() {
+        line_definitions[definition_lines.clone()].fill(Some(i));
+    }
+
+    // Maps each label to the canonical label it should be written as.
+    // Only the first definition of a label counts.
+    let mut canonical_labels = HashMap::new();
+    let mut canonical_by_target = HashMap::new();
+    let mut canonical_definitions = HashSet::new();
+    for (i, (_, definition)) in definitions.iter().enumerate() {
+        let label = normalize_label(definition.label);
+        if canonical_labels.contains_key(&label) {
+            continue;
+        }
+        let canonical = canonical_by_target
+            .entry((definition.url(), definition.title))
+            .or_insert_with(|| {
+                canonical_definitions.insert(i);
+                definition.label
+            });
+        canonical_labels.insert(label, *canonical);
+    }
+
+    let mut used = HashSet::new();
+    let rewritten = lines
+        .iter()
+        .zip(&line_definitions)
+        .map(|(line, definition)| {
+            if line.kind != LineKind::Text || definition.is_some() {
+                return Cow::Borrowed(line.text);
+            }
+            rewrite_usages(line.text, &canonical_labels, &mut used)
+        })
+        .collect::<Vec<_>>();
+    let after = rewritten
+        .iter()
+        .zip(&line_definitions)
+        .filter(|(_, definition)| match definition {
+            None => true,
+            Some(i) => {
+                let (_, definition) = definitions[*i];
+                canonical_definitions.contains(i)
+                    && (keep_unused || used.contains(&normalize_label(definition.label)))
+            }
+        })
+        .map(|(line, _)| line)
+        .join("\n");
+    after
+}
+
+/// Rewrite usages of labels in `line` to their canonical labels,
+/// recording which canonical labels are `used`.
+///
+/// Labels in code spans aren't usages, and are left alone.
+fn rewrite_usages<'a>(
+    line: &'a str,
+    canonical_labels: &HashMap<String, &str>,
+    used: &mut HashSet<String>,
+) -> Cow<'a, str> {
+    static LABEL_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\[(?<label>[^\[\]]+)\]").unwrap());
+    let code_spans = markdown::code_spans(line);
+    let mut rewritten = String::new();
+    let mut last = 0;
+    for captures in LABEL_REGEX.captures_iter(line) {
+        let whole = captures.get(0).unwrap();
+        if code_spans.iter().any(|span| span.contains(&whole.start())) {
+            continue;
+        }
+        let label = &captures["label"];
+        let Some(&canonical) = canonical_labels.get(&normalize_label(label)) else {
+            continue;
+        };
+        // Anything that looks like it could be a usage counts,
+        // so that we never delete a definition that's actually used.
+        used.insert(normalize_label(canonical));
+        if normalize_label(canonical) == normalize_label(label) {
+            continue;
+        }
+        let rest = &line[whole.end()..];
+        let (replacement, end) = if line[..whole.start()].ends_with(']') {
+            // `[text][label]`
+            (format!("[{canonical}]"), whole.end())
+        } else if rest.starts_with("[]") {
+            // `[label][]`
+            (format!("[{label}][{canonical}]"), whole.end() + "[]".len())
+        } else if rest.starts_with(['[', '(']) {
+            // The text of a full reference or inline link.
+            continue;
+        } else {
+            // `[label]`
+            (format!("[{label}][{canonical}]"), whole.end())
+        };
+        rewritten.push_str(&line[last..whole.start()]);
+        rewritten.push_str(&replacement);
+        last = end;
+    }
+    if last == 0 {
+        return Cow::Borrowed(line);
+    }
+    rewritten.push_str(&line[last..]);
+    Cow::Owned(rewritten)
+}
+
+/// How to order sorted reference definitions.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionOrder {
+    /// Alphabetically by label.
+    Label,
+
+    /// By where each label is first used, with unused ones last.
+    FirstUse,
+}
+
+/// Move all reference definitions to the end of the document
+/// (or of their section, if `per_section`) and sort them.
+pub fn sort_definitions(before: String, order: DefinitionOrder, per_section: bool) -> String {
+    let lines = markdown::lines(&before).collect::<Vec<_>>();
+    let definitions = definitions(&before)
+        .into_iter()
+        .map(|(definition_lines, definition)| {
+            (definition_lines.start, (definition_lines, definition))
+        })
+        .collect::<HashMap<_, _>>();
+    let mut definition_end = 0;
+    static LABEL_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\[(?<label>[^\[\]]+)\]").unwrap());
+
+    let mut first_uses = HashMap::new();
+    let mut sections = vec![(Vec::new(), Vec::new())];
+    let mut removed_definition = false;
+    for (i, line) in lines.iter().enumerate() {
+        if line.kind != LineKind::Text {
+            sections.last_mut().unwrap().0.push(line.text);
+            continue;
+        }
+        if let Some((definition_lines, definition)) = definitions.get(&i) {
+            let definition_lines = &lines[definition_lines.clone()];
+            sections
+                .last_mut()
+                .unwrap()
+                .1
+                .push((*definition, definition_lines));
+            definition_end = i + definition_lines.len();
+            removed_definition = true;
+            continue;
+        }
+        // The rest of a definition that spans several lines.
+        if i < definition_end {
+            continue;
+        }
+        for captures in LABEL_REGEX.captures_iter(line.text) {
+            first_uses
+                .entry(normalize_label(&captures["label"]))
+                .or_insert((i, captures.get(0).unwrap().start()));
+        }
+        let is_blank = line.text.trim().is_empty();
+        if per_section && markdown::is_heading(line.text) {
+            sections.push((Vec::new(), Vec::new()));
+        }
+        let (text, _) = sections.last_mut().unwrap();
+        // Don't leave a double blank line where definitions were removed.
+        let last_is_blank = text.last().is_none_or(|line: &&str| line.trim().is_empty());
+        if !(removed_definition && is_blank && last_is_blank) {
+            text.push(line.text);
+        }
+        removed_definition = false;
+    }
+
+    let ends_with_newline = before.ends_with('\n');
+    let section_count = sections.len();
+    let after = sections
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, (mut text, mut definitions))| {
+            if definitions.is_empty() {
+                return text;
+            }
+            let key = |(definition, _): &(Definition, &[markdown::Line])| {
+                let label = normalize_label(definition.label);
+                let first_use = match order {
+                    DefinitionOrder::Label => None,
+                    DefinitionOrder::FirstUse => {
+                        Some(first_uses.get(&label).copied().unwrap_or((usize::MAX, 0)))
+                    }
+                };
+                (first_use, label)
+            };
+            definitions.sort_by_cached_key(key);
+            while text.last().is_some_and(|line| line.trim().is_empty()) {
+                text.pop();
+            }
+            if !text.is_empty() {
+                text.push("");
+            }
+            text.extend(
+                definitions
+                    .iter()
+                    .flat_map(|(_, lines)| lines.iter().map(|line| line.text)),
+            );
+            if i + 1 < section_count || ends_with_newline {
+                text.push("");
+            }
+            text
+        })
+        .join("\n");
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::references::dedup_definitions;
+    use crate::references::definitions;
+    use crate::references::line_definitions;
+    use crate::references::sort_definitions;
+    use crate::references::Definition;
+    use crate::references::DefinitionOrder;
+
+    #[test]
+    fn test_parse_definition() {
+        assert_eq!(
+            Definition::parse(r#"[a b]: <https://example.com> "title""#),
+            Some(Definition {
+                label: "a b",
+                destination: "<https://example.com>",
+                title: Some(r#""title""#),
+            })
+        );
+        assert_eq!(Definition::parse("[^1]: footnote"), None);
+        assert_eq!(
+            Definition::parse("[a]:\n  /url\n  'the\r\n  title'\r"),
+            Some(Definition {
+                label: "a",
+                destination: "/url",
+                title: Some("'the\r\n  title'"),
+            })
+        );
+        assert_eq!(Definition::parse("[a]: /url\n\"title\" and more"), None);
+    }
+
+    #[test]
+    fn test_definitions() {
+        let text = "[a]: /a
+\"A\"
+[b]:
+/b
+\"B\" and more
+
+```
+[c]: /c
+```
+text
+[d]: /d
+# Heading
+[e]: /e";
+        let definitions = definitions(text);
+        let lines = definitions
+            .iter()
+            .map(|(lines, definition)| (lines.clone(), definition.label))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [(0..2, "a"), (2..4, "b"), (12..13, "e")]);
+        assert_eq!(definitions[0].1.title, Some("\"A\""));
+        let line_definitions = line_definitions(text);
+        assert_eq!(
+            line_definitions
+                .iter()
+                .map(Option::is_some)
+                .collect::<Vec<_>>(),
+            [
+                true, true, true, true, false, false, false, false, false, false, false, false,
+                true
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dedup_definitions() {
+        let before = "[a], [b][], [text][B], ![][c], [b](inline), `x[b]`
+
+[a]: https://example.com
+[b]: <https://example.com>
+[c]: https://example.org
+[d]:
+  https://example.net
+  \"title\"";
+        let after = "[a], [b][a], [text][a], ![][c], [b](inline), `x[b]`
+
+[a]: https://example.com
+[c]: https://example.org";
+        assert_eq!(dedup_definitions(before.into(), false), after);
+    }
+
+    #[test]
+    fn test_sort_definitions() {
+        let before = "# A
+
+[c] [b]
+
+[b]: https://b.com
+
+[a]:
+  https://a.com
+  \"A\"
+[c]: https://c.com
+
+# B
+
+[d]
+
+[d]: https://d.com
+";
+        let after = "# A
+
+[c] [b]
+
+# B
+
+[d]
+
+[a]:
+  https://a.com
+  \"A\"
+[b]: https://b.com
+[c]: https://c.com
+[d]: https://d.com
+";
+        assert_eq!(
+            sort_definitions(before.into(), DefinitionOrder::Label, false),
+            after
+        );
+        let after = "# A
+
+[c] [b]
+
+[c]: https://c.com
+[b]: https://b.com
+[a]:
+  https://a.com
+  \"A\"
+
+# B
+
+[d]
+
+[d]: https://d.com
+";
+        assert_eq!(
+            sort_definitions(before.into(), DefinitionOrder::FirstUse, true),
+            after
+        );
+        // A definition can't interrupt a paragraph, so it's just text.
+        let before = "Some text
+[a]: /x
+
+[b]: /b
+";
+        assert_eq!(
+            sort_definitions(before.into(), DefinitionOrder::Label, false),
+            before
+        );
+    }
+}
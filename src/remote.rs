@@ -0,0 +1,90 @@
+//! Styling documents from a URL, like a raw GitHub file or a gist, instead of a local path.
+
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use color_eyre::eyre;
+use color_eyre::eyre::WrapErr;
+use regex::Regex;
+use ureq::Agent;
+
+/// The URL in `path`, if it's an `http://` or `https://` URL instead of a local path.
+pub fn url(path: &Path) -> Option<&str> {
+    let path = path.to_str()?;
+    let scheme = path.split_once("://")?.0;
+    ["http", "https"]
+        .iter()
+        .any(|http| scheme.eq_ignore_ascii_case(http))
+        .then_some(path)
+}
+
+/// The URL of the raw file for a GitHub file page or a gist page, or else `url` itself.
+pub fn raw_url(url: &str) -> String {
+    static BLOB: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^https://github\.com/([^/]+/[^/]+)/blob/(.+)$").unwrap());
+    static GIST: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^https://gist\.github\.com/([^/]+/[0-9a-f]+)/?$").unwrap());
+    if let Some(captures) = BLOB.captures(url) {
+        return format!(
+            "https://raw.githubusercontent.com/{}/{}",
+            &captures[1], &captures[2]
+        );
+    }
+    if let Some(captures) = GIST.captures(url) {
+        return format!("https://gist.githubusercontent.com/{}/raw", &captures[1]);
+    }
+    url.to_owned()
+}
+
+/// Download the document at `url`.
+pub fn fetch(url: &str) -> eyre::Result<String> {
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(20)))
+        .build()
+        .into();
+    let text = agent
+        .get(raw_url(url))
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .wrap_err_with(|| format!("couldn't fetch `{url}`"))?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::remote::raw_url;
+    use crate::remote::url;
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            url(Path::new("https://example.com/a.md")),
+            Some("https://example.com/a.md")
+        );
+        assert_eq!(
+            url(Path::new("HTTP://example.com/a.md")),
+            Some("HTTP://example.com/a.md")
+        );
+        assert_eq!(url(Path::new("docs/a.md")), None);
+        assert_eq!(url(Path::new("ftp://example.com/a.md")), None);
+    }
+
+    #[test]
+    fn test_raw_url() {
+        assert_eq!(
+            raw_url("https://github.com/owner/repo/blob/main/docs/README.md"),
+            "https://raw.githubusercontent.com/owner/repo/main/docs/README.md"
+        );
+        assert_eq!(
+            raw_url("https://gist.github.com/someone/0123abcd"),
+            "https://gist.githubusercontent.com/someone/0123abcd/raw"
+        );
+        assert_eq!(
+            raw_url("https://example.com/notes.md"),
+            "https://example.com/notes.md"
+        );
+    }
+}
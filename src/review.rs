@@ -0,0 +1,211 @@
+//! Reviewing each change a rule proposes before it's made, like `git add -p`,
+//! for rules whose changes are judgment calls, like hyphenation and terminology.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+
+use crate::changes;
+use crate::changes::LineEdit;
+
+/// The number of unchanged lines shown around a change.
+const CONTEXT: usize = 3;
+
+/// What to do with a proposed change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Accept,
+    Reject,
+
+    /// Accept this and the rest of the rule's changes, in every file.
+    AcceptRule,
+
+    /// Reject this and every change left.
+    Quit,
+}
+
+impl Answer {
+    pub fn parse(answer: &str) -> Option<Self> {
+        let answer = match answer.trim() {
+            "y" => Self::Accept,
+            "n" => Self::Reject,
+            "a" => Self::AcceptRule,
+            "q" => Self::Quit,
+            _ => return None,
+        };
+        Some(answer)
+    }
+}
+
+/// A change that `rule` proposes to the file at `path`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Proposal<'a> {
+    pub path: &'a Path,
+    pub rule: &'a str,
+
+    /// The whole text before the change.
+    pub before: &'a str,
+
+    pub edit: &'a LineEdit,
+}
+
+impl Proposal<'_> {
+    /// The change as a diff hunk, with a few unchanged lines around it.
+    pub fn hunk(&self) -> String {
+        let lines = self.before.split_inclusive('\n').collect::<Vec<_>>();
+        let range = &self.edit.lines;
+        let start = range.start.saturating_sub(CONTEXT);
+        let end = (range.end + CONTEXT).min(lines.len());
+        let mut hunk = format!(
+            "{}:{} [{}]\n",
+            self.path.display(),
+            range.start + 1,
+            self.rule
+        );
+        let mut push = |prefix: char, line: &str| {
+            writeln!(hunk, "{prefix} {}", line.trim_end_matches('\n')).unwrap();
+        };
+        lines[start..range.start]
+            .iter()
+            .for_each(|line| push(' ', line));
+        lines[range.clone()].iter().for_each(|line| push('-', line));
+        self.edit
+            .text
+            .split_inclusive('\n')
+            .for_each(|line| push('+', line));
+        lines[range.end..end]
+            .iter()
+            .for_each(|line| push(' ', line));
+        hunk
+    }
+}
+
+/// Decides what to do with a proposed change.
+pub type Decide<'a> = dyn FnMut(&Proposal) -> Answer + 'a;
+
+/// Ask on the terminal what to do with `proposal`, asking again until the answer is valid.
+pub fn ask(proposal: &Proposal) -> Answer {
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "{}", proposal.hunk());
+    loop {
+        let _ = write!(
+            stderr,
+            "accept this change [y,n,a,q]? (a: all of this rule's, q: none left) "
+        );
+        let _ = stderr.flush();
+        let mut answer = String::new();
+        // Without any more answers, leave the rest unchanged.
+        match io::stdin().lock().read_line(&mut answer) {
+            Ok(0) | Err(_) => return Answer::Quit,
+            Ok(_) => {}
+        }
+        if let Some(answer) = Answer::parse(&answer) {
+            return answer;
+        }
+    }
+}
+
+/// The answers so far, which carry over between rules and files.
+pub struct Reviewer<'a> {
+    decide: &'a mut Decide<'a>,
+
+    /// The rules whose changes are all accepted.
+    accepted_rules: HashSet<String>,
+
+    is_done: bool,
+}
+
+impl<'a> Reviewer<'a> {
+    pub fn new(decide: &'a mut Decide<'a>) -> Self {
+        Self {
+            decide,
+            accepted_rules: HashSet::new(),
+            is_done: false,
+        }
+    }
+
+    /// Review each change `rule` made from `before` to `after` in the file at `path`,
+    /// returning `before` with only the accepted ones.
+    pub fn review(&mut self, path: &Path, rule: &str, before: &str, after: &str) -> String {
+        let edits = changes::line_edits(before, after)
+            .into_iter()
+            .filter(|edit| {
+                if self.is_done {
+                    return false;
+                }
+                if self.accepted_rules.contains(rule) {
+                    return true;
+                }
+                let proposal = Proposal {
+                    path,
+                    rule,
+                    before,
+                    edit,
+                };
+                match (self.decide)(&proposal) {
+                    Answer::Accept => true,
+                    Answer::Reject => false,
+                    Answer::AcceptRule => {
+                        self.accepted_rules.insert(rule.to_owned());
+                        true
+                    }
+                    Answer::Quit => {
+                        self.is_done = true;
+                        false
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        changes::apply_line_edits(before, &edits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::changes::LineEdit;
+    use crate::review::Answer;
+    use crate::review::Proposal;
+    use crate::review::Reviewer;
+
+    #[test]
+    fn test_hunk() {
+        let before = "a\nb\nc\nd\ne\nf\n";
+        let edit = LineEdit {
+            lines: 4..5,
+            text: "E\n".into(),
+        };
+        let proposal = Proposal {
+            path: Path::new("a.md"),
+            rule: "rule",
+            before,
+            edit: &edit,
+        };
+        assert_eq!(
+            proposal.hunk(),
+            "a.md:5 [rule]\n  b\n  c\n  d\n- e\n+ E\n  f\n"
+        );
+    }
+
+    #[test]
+    fn test_review() {
+        let path = Path::new("a.md");
+        let mut answers = [Answer::Reject, Answer::AcceptRule, Answer::Quit].into_iter();
+        let mut decide = |_: &Proposal| answers.next().unwrap();
+        let mut reviewer = Reviewer::new(&mut decide);
+        let before = "a\nb\nc\n";
+        let after = reviewer.review(path, "upper", before, "A\nB\nC\n");
+        assert_eq!(after, "a\nB\nC\n");
+        // Still accepted, without asking.
+        let after = reviewer.review(path, "upper", "d\n", "D\n");
+        assert_eq!(after, "D\n");
+        let after = reviewer.review(path, "other", "e\nf\n", "E\nF\n");
+        assert_eq!(after, "e\nf\n");
+        assert_eq!(Answer::parse("a\n"), Some(Answer::AcceptRule));
+        assert_eq!(Answer::parse("yes"), None);
+    }
+}
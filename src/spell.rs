@@ -0,0 +1,295 @@
+//! Spell checking with a Hunspell dictionary and project word lists.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::LazyLock;
+
+use color_eyre::eyre;
+use color_eyre::eyre::eyre;
+use color_eyre::eyre::Context;
+use itertools::Itertools;
+use regex::Captures;
+use regex::Regex;
+use spellbook::Dictionary;
+
+use crate::diagnostic::Diagnostic;
+use crate::graphemes;
+use crate::markdown;
+use crate::markdown::LineKind;
+
+/// A Hunspell dictionary, loaded from its `.aff` and `.dic` files.
+#[derive(Clone)]
+pub struct SpellDictionary(Arc<Dictionary>);
+
+impl Debug for SpellDictionary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("SpellDictionary")
+    }
+}
+
+impl SpellDictionary {
+    pub fn new(aff: &str, dic: &str) -> eyre::Result<Self> {
+        let dictionary = Dictionary::new(aff, dic).map_err(|e| eyre!("{e}"))?;
+        Ok(Self(Arc::new(dictionary)))
+    }
+}
+
+/// Parse a Hunspell dictionary from `path`, like `/usr/share/hunspell/en_US`,
+/// which is `path.aff` and `path.dic`.
+pub fn parse_dictionary(path: &str) -> eyre::Result<SpellDictionary> {
+    let read = |extension| {
+        fs_err::read_to_string(format!("{path}.{extension}"))
+            .wrap_err_with(|| format!("dictionary `{path}` wasn't found"))
+    };
+    let aff = read("aff")?;
+    let dic = read("dic")?;
+    SpellDictionary::new(&aff, &dic).wrap_err_with(|| format!("invalid dictionary `{path}`"))
+}
+
+/// Parse a project word list, with a word per line and `#` comments.
+pub fn parse_word_list(path: &str) -> eyre::Result<BTreeSet<String>> {
+    let words = fs_err::read_to_string(path)?
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect();
+    Ok(words)
+}
+
+/// Parse a JSON file mapping misspellings to their corrections.
+pub fn parse_corrections(path: &str) -> eyre::Result<BTreeMap<String, String>> {
+    let json = fs_err::read_to_string(path)?;
+    serde_json::from_str(&json)
+        .wrap_err_with(|| format!("`{path}` is not a JSON object of strings"))
+}
+
+/// A misspelled word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling<'a> {
+    /// 1-based line number.
+    pub line: usize,
+
+    /// 1-based column, in characters.
+    pub column: usize,
+
+    pub word: &'a str,
+
+    pub suggestions: Vec<String>,
+}
+
+/// Decides how to fix a misspelling, returning the replacement, if any.
+pub type Choose = dyn FnMut(&Misspelling) -> Option<String>;
+
+/// Ask on the terminal how to fix `misspelling`, returning the replacement, if any.
+pub fn ask(misspelling: &Misspelling) -> Option<String> {
+    let Misspelling {
+        line,
+        column,
+        word,
+        suggestions,
+    } = misspelling;
+    let mut stderr = io::stderr();
+    let choices = suggestions
+        .iter()
+        .enumerate()
+        .map(|(i, suggestion)| format!("{}) {suggestion}", i + 1))
+        .join(", ");
+    writeln!(stderr, "{line}:{column}: `{word}`: {choices}").ok()?;
+    write!(stderr, "number, replacement, or nothing to skip: ").ok()?;
+    stderr.flush().ok()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).ok()?;
+    let answer = answer.trim();
+    match answer.parse::<usize>() {
+        _ if answer.is_empty() => None,
+        Ok(n) => suggestions.get(n.wrapping_sub(1)).cloned(),
+        Err(_) => Some(answer.to_owned()),
+    }
+}
+
+/// Report misspelled words in prose, i.e. not in code, URLs, or footnote labels,
+/// with suggested corrections.
+///
+/// Words in `project_words` are spelled correctly, and `corrections` are applied.
+/// If `choose` is given, it's asked how to fix each remaining misspelling.
+pub fn check_spelling(
+    before: String,
+    dictionary: &SpellDictionary,
+    project_words: &[String],
+    corrections: Option<&BTreeMap<String, String>>,
+    mut choose: Option<&mut Choose>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let SpellDictionary(dictionary) = dictionary;
+    let project_words = project_words
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<HashSet<_>>();
+    static WORD: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"\[\^[^\]]*\]|(?<word>[\p{L}\p{N}](?:[\p{L}\p{N}\p{M}]|['’][\p{L}])*)").unwrap()
+    });
+    let after = markdown::lines(&before)
+        .enumerate()
+        .map(|(i, line)| {
+            if line.kind != LineKind::Text {
+                return line.text.to_owned();
+            }
+            let mut start = 0;
+            markdown::rewrite_prose(line.text, |text| {
+                // `rewrite_prose` calls this with consecutive parts of the line.
+                let offset = line.text[start..]
+                    .find(text)
+                    .map_or(start, |offset| start + offset);
+                start = offset + text.len();
+                let rewritten = WORD.replace_all(text, |captures: &Captures| {
+                    let whole = captures.get(0).unwrap();
+                    let Some(word) = captures.name("word").map(|word| word.as_str()) else {
+                        return whole.as_str().to_owned();
+                    };
+                    let is_correct = word.chars().all(|c| c.is_numeric())
+                        || project_words.contains(&word.to_lowercase())
+                        || dictionary.check(word);
+                    if is_correct {
+                        return word.to_owned();
+                    }
+                    if let Some(correction) = corrections.and_then(|map| map.get(word)) {
+                        diagnostics.push(Diagnostic::note(
+                            i + 1,
+                            format!("corrected `{word}` to `{correction}`"),
+                        ));
+                        return correction.clone();
+                    }
+                    let mut suggestions = Vec::new();
+                    dictionary.suggest(word, &mut suggestions);
+                    suggestions.truncate(3);
+                    let column = graphemes::count(&line.text[..offset + whole.start()]) + 1;
+                    let misspelling = Misspelling {
+                        line: i + 1,
+                        column,
+                        word,
+                        suggestions,
+                    };
+                    if let Some(choose) = choose.as_mut() {
+                        if let Some(replacement) = choose(&misspelling) {
+                            diagnostics.push(Diagnostic::note(
+                                i + 1,
+                                format!("corrected `{word}` to `{replacement}`"),
+                            ));
+                            return replacement;
+                        }
+                    }
+                    let suggestions = match misspelling.suggestions.as_slice() {
+                        [] => String::new(),
+                        suggestions => {
+                            let suggestions = suggestions
+                                .iter()
+                                .map(|suggestion| format!("`{suggestion}`"))
+                                .join(", ");
+                            format!("; did you mean {suggestions}?")
+                        }
+                    };
+                    diagnostics.push(Diagnostic::new(
+                        i + 1,
+                        format!("`{word}` at column {column} is misspelled{suggestions}"),
+                    ));
+                    word.to_owned()
+                });
+                rewritten.into_owned()
+            })
+        })
+        .join("\n");
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::diagnostic::Diagnostic;
+    use crate::spell::check_spelling;
+    use crate::spell::parse_dictionary;
+    use crate::spell::SpellDictionary;
+    use crate::Command;
+
+    fn dictionary() -> SpellDictionary {
+        let aff = "SET UTF-8\nTRY esianrtolcdugmphbyfvkwzESIANRTOLCDUGMPHBYFVKWZ\n";
+        let dic = "9\nthe\ntrain\nruns\nunder\ncatenary\nsee\nand\nit\nis\n";
+        SpellDictionary::new(aff, dic).unwrap()
+    }
+
+    #[test]
+    fn test_parse_dictionary() {
+        let error = parse_dictionary("/nonexistent/en_US").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "dictionary `/nonexistent/en_US` wasn't found"
+        );
+        assert!(Command::from_rule("spell").is_err());
+    }
+
+    #[test]
+    fn test_check_spelling() {
+        let before = "The M8 trian runs under catenery,[^labell] see `codde`
+and https://exampel.com, it's 2024.";
+        let mut diagnostics = Vec::new();
+        let project_words = ["M8".into(), "it's".into()];
+        assert_eq!(
+            check_spelling(
+                before.into(),
+                &dictionary(),
+                &project_words,
+                None,
+                None,
+                &mut diagnostics
+            ),
+            before
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::new(
+                    1,
+                    "`trian` at column 8 is misspelled; did you mean `train`?"
+                ),
+                Diagnostic::new(
+                    1,
+                    "`catenery` at column 25 is misspelled; did you mean `catenary`?"
+                ),
+            ]
+        );
+
+        let corrections = BTreeMap::from([("trian".into(), "train".into())]);
+        let mut choose =
+            |misspelling: &super::Misspelling| misspelling.suggestions.first().cloned();
+        let mut diagnostics = Vec::new();
+        let after = "The M8 train runs under catenary,[^labell] see `codde`
+and https://exampel.com, it's 2024.";
+        assert_eq!(
+            check_spelling(
+                before.into(),
+                &dictionary(),
+                &project_words,
+                Some(&corrections),
+                Some(&mut choose),
+                &mut diagnostics
+            ),
+            after
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::note(1, "corrected `trian` to `train`"),
+                Diagnostic::note(1, "corrected `catenery` to `catenary`"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,444 @@
+//! Statistics about a document, like its word count and reading time.
+
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use regex::Regex;
+use serde_json::json;
+
+use crate::blockquotes;
+use crate::footnotes;
+use crate::graphemes;
+use crate::headings;
+use crate::lists;
+use crate::markdown;
+use crate::markdown::LineKind;
+use crate::references;
+use crate::references::Definition;
+
+/// The average silent reading speed of adults, in words per minute.
+pub const WORDS_PER_MINUTE: usize = 238;
+
+/// Abbreviations whose `.` doesn't end a sentence.
+const ABBREVIATIONS: &[&str] = &[
+    "e.g.", "i.e.", "etc.", "vs.", "cf.", "Mr.", "Mrs.", "Ms.", "Dr.", "St.", "Jr.", "Sr.", "No.",
+];
+
+/// A block of prose, i.e. a paragraph, list item, or heading, as plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// 0-based index of the block's first line.
+    pub line: usize,
+
+    /// The level if the block is a heading.
+    pub heading_level: Option<usize>,
+
+    /// The text without Markdown syntax, code blocks, or URLs, with lines joined by spaces.
+    pub text: String,
+}
+
+/// The blocks of prose in `text`.
+pub fn prose_blocks(text: &str) -> Vec<Block> {
+    static SETEXT_UNDERLINE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^ {0,3}(?:=+|-+)[ \t]*$").unwrap());
+    static TABLE_DELIMITER: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^[ \t]*\|?[ \t]*:?-+:?[ \t]*(?:\|[ \t]*:?-+:?[ \t]*)*\|?[ \t]*$").unwrap()
+    });
+    static FOOTNOTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[\^[^\]]*\]:?").unwrap());
+    static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bhttps?://[^\s<>]+").unwrap());
+    let headings = headings::headings(text);
+    let line_definitions = references::line_definitions(text);
+    let mut blocks = Vec::<Block>::new();
+    let mut is_continuation = false;
+    for (i, line) in markdown::lines(text).enumerate() {
+        let (_, content) = blockquotes::parse_markers(line.text);
+        let content = content.trim();
+        if line.kind != LineKind::Text
+            || content.is_empty()
+            || line_definitions[i].is_some()
+            || Definition::parse(content).is_some()
+            || markdown::is_thematic_break(content)
+            || SETEXT_UNDERLINE.is_match(content)
+            || TABLE_DELIMITER.is_match(content)
+        {
+            is_continuation = false;
+            continue;
+        }
+        let heading = headings.iter().find(|heading| heading.line == i);
+        let (content, is_item) = match lists::parse_item(content) {
+            Some((marker, spaces)) => (&content[marker.len() + spaces.len()..], true),
+            None => (content, false),
+        };
+        let content = content
+            .strip_prefix("[ ] ")
+            .or_else(|| content.strip_prefix("[x] "))
+            .unwrap_or(content);
+        let content = match heading {
+            Some(heading) => heading.text,
+            None => content,
+        };
+        let content = FOOTNOTE.replace_all(content, "");
+        let plain = headings::plain_text(&content).replace('|', " ");
+        let plain = URL.replace_all(&plain, "");
+        let plain = plain.split_whitespace().collect::<Vec<_>>().join(" ");
+        match blocks.last_mut() {
+            Some(block) if is_continuation && heading.is_none() && !is_item => {
+                block.text.push(' ');
+                block.text.push_str(&plain);
+            }
+            _ => blocks.push(Block {
+                line: i,
+                heading_level: heading.map(|heading| heading.level),
+                text: plain,
+            }),
+        }
+        is_continuation = heading.is_none();
+    }
+    blocks.retain(|block| !block.text.is_empty());
+    blocks
+}
+
+/// The words in `text`, i.e. runs of non-whitespace with a letter or digit.
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+}
+
+/// Split `text` into sentences at `.`, `!`, and `?`, except after common abbreviations.
+pub fn sentences(text: &str) -> Vec<&str> {
+    static END: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"[.!?]+["'”’)\]]*(?:\s+|$)"#).unwrap());
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for m in END.find_iter(text) {
+        let last_word = text[start..m.start() + 1]
+            .split_whitespace()
+            .next_back()
+            .unwrap_or_default();
+        if ABBREVIATIONS.contains(&last_word) {
+            continue;
+        }
+        sentences.push(text[start..m.end()].trim());
+        start = m.end();
+    }
+    sentences.push(text[start..].trim());
+    sentences.retain(|sentence| words(sentence).next().is_some());
+    sentences
+}
+
+/// A heading in a document's outline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineHeading {
+    /// 1-based line number.
+    pub line: usize,
+
+    pub level: usize,
+
+    pub text: String,
+
+    /// The words in the section, including its subsections.
+    pub words: usize,
+}
+
+impl OutlineHeading {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "line": self.line,
+            "level": self.level,
+            "text": self.text,
+            "words": self.words,
+        })
+    }
+}
+
+/// Formatted as an indented ATX heading, followed by its line and word count.
+impl Display for OutlineHeading {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let indent = "  ".repeat(self.level - 1);
+        let hashes = "#".repeat(self.level);
+        let Self {
+            line, text, words, ..
+        } = self;
+        write!(f, "{indent}{hashes} {text} (line {line}, {words} words)")
+    }
+}
+
+/// The headings of `text`, with the number of words in each section.
+pub fn outline(text: &str) -> Vec<OutlineHeading> {
+    let headings = headings::headings(text);
+    let blocks = prose_blocks(text);
+    headings
+        .iter()
+        .enumerate()
+        .map(|(i, heading)| {
+            let end = headings[i + 1..]
+                .iter()
+                .find(|next| next.level <= heading.level)
+                .map_or(usize::MAX, |next| next.line);
+            let words = blocks
+                .iter()
+                .filter(|block| block.heading_level.is_none())
+                .filter(|block| (heading.line + 1..end).contains(&block.line))
+                .map(|block| words(&block.text).count())
+                .sum();
+            OutlineHeading {
+                line: heading.line + 1,
+                level: heading.level,
+                text: headings::plain_text(heading.text),
+                words,
+            }
+        })
+        .collect()
+}
+
+/// A document's outline, as a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outline(pub Vec<OutlineHeading>);
+
+impl Outline {
+    pub fn of(text: &str) -> Self {
+        Self(outline(text))
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        self.0.iter().map(OutlineHeading::to_json).collect()
+    }
+}
+
+impl Display for Outline {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.iter().join("\n"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    pub words: usize,
+    pub characters: usize,
+    pub sentences: usize,
+    pub reading_minutes: usize,
+    pub footnotes: usize,
+    pub links: usize,
+    pub images: usize,
+    pub outline: Vec<OutlineHeading>,
+}
+
+impl Stats {
+    pub fn of(text: &str) -> Self {
+        let blocks = prose_blocks(text);
+        let words = blocks
+            .iter()
+            .map(|block| words(&block.text).count())
+            .sum::<usize>();
+        let characters = blocks
+            .iter()
+            .map(|block| graphemes::count(&block.text))
+            .sum();
+        let sentences = blocks
+            .iter()
+            .filter(|block| block.heading_level.is_none())
+            .map(|block| sentences(&block.text).len())
+            .sum();
+        static LINK: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"(?<bang>!?)(?:\[[^\]]*\]\([^)]*\)|\[[^\]]+\]\[[^\]]*\])|<(?i:https?)://[^\s<>]*>|(?i)<img\b").unwrap()
+        });
+        let (mut links, mut images) = (0, 0);
+        let line_definitions = references::line_definitions(text);
+        for (line, definition) in markdown::lines(text).zip(line_definitions) {
+            if line.kind != LineKind::Text || definition.is_some() {
+                continue;
+            }
+            markdown::rewrite_outside_code(line.text, |text| {
+                for captures in LINK.captures_iter(text) {
+                    let is_image = captures
+                        .name("bang")
+                        .is_some_and(|bang| bang.as_str() == "!")
+                        || captures[0].to_lowercase() == "<img";
+                    match is_image {
+                        true => images += 1,
+                        false => links += 1,
+                    }
+                }
+                if markdown::dialect().has_gfm_extensions() {
+                    links += crate::links::bare_urls(text).len();
+                }
+                text.to_owned()
+            });
+        }
+        Self {
+            words,
+            characters,
+            sentences,
+            reading_minutes: words.div_ceil(WORDS_PER_MINUTE),
+            footnotes: footnotes::labels_by_first_use(text).len(),
+            links,
+            images,
+            outline: outline(text),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "words": self.words,
+            "characters": self.characters,
+            "sentences": self.sentences,
+            "reading_minutes": self.reading_minutes,
+            "footnotes": self.footnotes,
+            "links": self.links,
+            "images": self.images,
+            "outline": Outline(self.outline.clone()).to_json(),
+        })
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "words: {}", self.words)?;
+        writeln!(f, "characters: {}", self.characters)?;
+        writeln!(f, "sentences: {}", self.sentences)?;
+        writeln!(f, "reading time: {} min", self.reading_minutes)?;
+        writeln!(f, "footnotes: {}", self.footnotes)?;
+        writeln!(f, "links: {}", self.links)?;
+        writeln!(f, "images: {}", self.images)?;
+        write!(f, "outline:")?;
+        for heading in &self.outline {
+            let indent = "  ".repeat(heading.level);
+            write!(
+                f,
+                "\n{indent}{} {}",
+                "#".repeat(heading.level),
+                heading.text
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::outline;
+    use crate::stats::prose_blocks;
+    use crate::stats::sentences;
+    use crate::stats::Block;
+    use crate::stats::OutlineHeading;
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_prose_blocks() {
+        let text = "# The *Title*
+
+A paragraph with [a link](https://example.com)[^1]
+across two lines.
+
+- An item
+- [x] Done
+
+> Quoted.
+
+```
+code
+```
+
+[^1]: A footnote.
+";
+        let block = |line, heading_level, text: &str| Block {
+            line,
+            heading_level,
+            text: text.into(),
+        };
+        assert_eq!(
+            prose_blocks(text),
+            [
+                block(0, Some(1), "The Title"),
+                block(2, None, "A paragraph with a link across two lines."),
+                block(5, None, "An item"),
+                block(6, None, "Done"),
+                block(8, None, "Quoted."),
+                block(14, None, "A footnote."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentences() {
+        assert_eq!(
+            sentences("Trains run, e.g. hourly. Really?! \"Yes.\" Ok"),
+            ["Trains run, e.g. hourly.", "Really?!", "\"Yes.\"", "Ok"]
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let text = "# Title
+
+Some text.[^1] See [a](b), <https://example.com>, and https://example.org.
+![image](c.png) <img src=\"d.png\">
+
+## Section
+
+More text.
+
+[^1]: Note.
+";
+        assert_eq!(
+            Stats::of(text),
+            Stats {
+                words: 11,
+                characters: 56,
+                sentences: 4,
+                reading_minutes: 1,
+                footnotes: 1,
+                links: 3,
+                images: 2,
+                outline: vec![
+                    OutlineHeading {
+                        line: 1,
+                        level: 1,
+                        text: "Title".into(),
+                        words: 9,
+                    },
+                    OutlineHeading {
+                        line: 6,
+                        level: 2,
+                        text: "Section".into(),
+                        words: 3,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_outline() {
+        let text = "# Title
+
+One two.
+
+## A
+
+Three.
+
+### A.1
+
+Four five.
+
+## B
+";
+        let outline = outline(text)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outline,
+            [
+                "# Title (line 1, 5 words)",
+                "  ## A (line 5, 3 words)",
+                "    ### A.1 (line 9, 2 words)",
+                "  ## B (line 13, 0 words)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! Styling a document one block at a time, reading and writing it incrementally,
+//! so that huge files can be styled in bounded memory by rules that only look within a block.
+
+use std::borrow::Cow;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+
+use color_eyre::eyre;
+use color_eyre::eyre::ensure;
+
+use crate::changes;
+use crate::diagnostic::Diagnostic;
+use crate::markdown::Fence;
+use crate::triggers::Triggers;
+use crate::Command;
+use crate::FinalNewline;
+
+/// The blocks of a document, read a line at a time.
+///
+/// A block is a run of lines up to and including the blank lines after it,
+/// except that fenced code blocks and front matter are never split,
+/// even if they contain blank lines.
+pub struct Blocks<R> {
+    reader: R,
+
+    /// The first line of the next block, which was read to find the end of the last one.
+    next_line: Option<String>,
+
+    is_start: bool,
+}
+
+impl<R: BufRead> Blocks<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            next_line: None,
+            is_start: true,
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        if let Some(line) = self.next_line.take() {
+            return Ok(Some(line));
+        }
+        let mut line = String::new();
+        Ok(match self.reader.read_line(&mut line)? {
+            0 => None,
+            _ => Some(line),
+        })
+    }
+
+    fn read_block(&mut self) -> io::Result<Option<String>> {
+        let mut block = String::new();
+        // The closing delimiters of front matter, if the block is front matter.
+        let mut front_matter: &[&str] = &[];
+        let mut fence = None::<Fence>;
+        let mut ends_with_blank = false;
+        while let Some(line) = self.read_line()? {
+            let text = line.strip_suffix('\n').unwrap_or(&line);
+            if self.is_start {
+                self.is_start = false;
+                front_matter = match text.trim_end() {
+                    "---" => &["---", "..."],
+                    "+++" => &["+++"],
+                    _ => &[],
+                };
+                if !front_matter.is_empty() {
+                    block.push_str(&line);
+                    continue;
+                }
+            }
+            if !front_matter.is_empty() {
+                if front_matter.contains(&text.trim_end()) {
+                    front_matter = &[];
+                }
+                block.push_str(&line);
+                continue;
+            }
+            if let Some(opening) = fence {
+                if opening.is_closed_by(text) {
+                    fence = None;
+                }
+                block.push_str(&line);
+                continue;
+            }
+            let is_blank = text.trim().is_empty();
+            if !is_blank && ends_with_blank && !block.is_empty() {
+                self.next_line = Some(line);
+                break;
+            }
+            fence = Fence::parse_opening(text);
+            ends_with_blank = is_blank;
+            block.push_str(&line);
+        }
+        Ok(Some(block).filter(|block| !block.is_empty()))
+    }
+}
+
+impl<R: BufRead> Iterator for Blocks<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_block().transpose()
+    }
+}
+
+/// What styling a document one block at a time did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Streamed {
+    /// The number of edits each command made, in the same order as the commands.
+    pub edits: Vec<usize>,
+
+    pub diagnostics: Vec<Diagnostic>,
+
+    /// Whether anything changed, including adding a final newline.
+    pub is_changed: bool,
+}
+
+/// Rewrite the document at `path` from `reader` to `writer` one [`Blocks`] block at a time
+/// with each of `commands`, which must all be [block-local](Command::is_block_local),
+/// ending it like `final_newline`.
+pub fn stream(
+    path: &Path,
+    reader: impl BufRead,
+    mut writer: impl Write,
+    commands: &[Command],
+    final_newline: FinalNewline,
+) -> eyre::Result<Streamed> {
+    for command in commands {
+        ensure!(
+            command.is_block_local(),
+            "`{}` needs the whole document, so it can't be streamed",
+            command.name()
+        );
+    }
+    let mut streamed = Streamed {
+        edits: vec![0; commands.len()],
+        ..Default::default()
+    };
+    let mut line = 0;
+    let mut blocks = Blocks::new(reader).peekable();
+    while let Some(block) = blocks.next() {
+        let block = block?;
+        let mut text = Cow::Borrowed(block.as_str());
+        let mut triggers = None;
+        for (command, edits) in commands.iter().zip(&mut streamed.edits) {
+            if command.is_skipped(triggers.get_or_insert_with(|| Triggers::scan(&text))) {
+                continue;
+            }
+            let mut diagnostics = Vec::new();
+            let rewritten = match command.rewrite_str(path, &text, &mut diagnostics) {
+                Cow::Owned(after) => Some(after),
+                Cow::Borrowed(_) => None,
+            };
+            streamed
+                .diagnostics
+                .extend(diagnostics.into_iter().map(|diagnostic| Diagnostic {
+                    line: line + diagnostic.line,
+                    ..diagnostic
+                }));
+            if let Some(after) = rewritten {
+                *edits += changes::count_edits(&text, &after);
+                text = Cow::Owned(after);
+                triggers = None;
+            }
+        }
+        // The last block has the end of the document, including any blank lines at the end.
+        if blocks.peek().is_none() && !final_newline.is_ended(&text) {
+            final_newline.end(text.to_mut());
+        }
+        streamed.is_changed |= text != block;
+        writer.write_all(text.as_bytes())?;
+        line += block.matches('\n').count();
+    }
+    writer.flush()?;
+    Ok(streamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::diagnostic::Diagnostic;
+    use crate::stream::stream;
+    use crate::stream::Blocks;
+    use crate::Command;
+    use crate::FinalNewline;
+
+    #[test]
+    fn test_blocks() {
+        let text = "\
+---
+title: A
+
+---
+
+A
+paragraph.
+
+
+```
+code
+
+```
+Still in the same block.
+
+";
+        let blocks = Blocks::new(text.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            blocks,
+            [
+                "---\ntitle: A\n\n---\n\n",
+                "A\nparagraph.\n\n\n",
+                "```\ncode\n\n```\nStill in the same block.\n\n",
+            ]
+        );
+        assert_eq!(blocks.concat(), text);
+        let blocks = Blocks::new("\n\na\n\nb".as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(blocks, ["\n\n", "a\n\n", "b"]);
+    }
+
+    #[test]
+    fn test_stream() {
+        let commands = [
+            Command::from_rule("quotes").unwrap(),
+            Command::from_rule("nfc").unwrap(),
+            Command::from_rule("trailing-whitespace").unwrap(),
+        ];
+        let path = Path::new("a.md");
+        let before = "“A”  \n\n```\ncode  \n\n```\n\nCafe\u{301}";
+        let mut after = Vec::new();
+        let streamed = stream(
+            path,
+            before.as_bytes(),
+            &mut after,
+            &commands,
+            FinalNewline::One,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(after).unwrap(),
+            "\"A\"\n\n```\ncode  \n\n```\n\nCafé\n"
+        );
+        assert_eq!(streamed.edits, [1, 1, 1]);
+        assert!(streamed
+            .diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.line == 8));
+        assert!(!streamed.diagnostics.is_empty());
+        assert!(streamed.is_changed);
+
+        let mut after = Vec::new();
+        let streamed = stream(
+            path,
+            "Clean.\n".as_bytes(),
+            &mut after,
+            &commands,
+            FinalNewline::One,
+        )
+        .unwrap();
+        assert!(!streamed.is_changed);
+        assert_eq!(streamed.diagnostics, Vec::<Diagnostic>::new());
+
+        let mut after = Vec::new();
+        let before = "A\r\n\r\nB\r\n\r\n";
+        let streamed = stream(
+            path,
+            before.as_bytes(),
+            &mut after,
+            &commands,
+            FinalNewline::Strip,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(after).unwrap(), "A\r\n\r\nB");
+        assert!(streamed.is_changed);
+
+        let commands = [Command::from_rule("renumber-footnotes").unwrap()];
+        assert!(stream(
+            path,
+            "".as_bytes(),
+            Vec::new(),
+            &commands,
+            FinalNewline::One
+        )
+        .is_err());
+    }
+}
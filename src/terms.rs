@@ -0,0 +1,252 @@
+//! Terms with a canonical spelling, like `GitHub` and `Metro-North`.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use regex::Regex;
+use serde_json::json;
+
+use crate::markdown;
+use crate::stats;
+
+/// Proper nouns and acronyms whose capitalization is often wrong.
+///
+/// These should be unambiguous, i.e. not also common words in other capitalizations.
+pub const TERMS: &[&str] = &[
+    "GitHub",
+    "GitLab",
+    "JavaScript",
+    "TypeScript",
+    "WebAssembly",
+    "PostgreSQL",
+    "MySQL",
+    "SQLite",
+    "macOS",
+    "iOS",
+    "iPhone",
+    "YouTube",
+    "LinkedIn",
+    "PowerPoint",
+    "OpenStreetMap",
+    "GTFS",
+    "Amtrak",
+    "Metro-North",
+    "MBTA",
+    "SEPTA",
+    "WMATA",
+    "BART",
+    "LIRR",
+];
+
+/// Fix the capitalization of [`TERMS`] and `extra_terms` in prose,
+/// where `extra_terms` take precedence.
+///
+/// Words that are part of a domain or path, like `github.com`, are left alone.
+pub fn fix_capitalization(before: String, extra_terms: &[String]) -> String {
+    let terms = TERMS
+        .iter()
+        .copied()
+        .chain(extra_terms.iter().map(String::as_str))
+        .map(|term| (term.to_lowercase(), term))
+        .collect::<HashMap<_, _>>();
+    let alternation = terms
+        .keys()
+        .map(|term| regex::escape(term))
+        .collect::<Vec<_>>()
+        .join("|");
+    let term = Regex::new(&format!(r"(?i)\b(?:{alternation})\b")).unwrap();
+    let after = markdown::rewrite_prose_in_parallel(&before, |text| {
+        let mut rewritten = String::new();
+        let mut last = 0;
+        for m in term.find_iter(text) {
+            let word = m.as_str();
+            let canonical = terms[&word.to_lowercase()];
+            let before_word = text[..m.start()].chars().next_back();
+            let after_word = text[m.end()..].chars().take(2).collect::<String>();
+            let is_in_name = matches!(before_word, Some('.' | '/' | '@' | '_' | '-'))
+                || after_word.starts_with(['/', '_', '@'])
+                || (after_word.starts_with(['.', '-'])
+                    && after_word.chars().nth(1).is_some_and(char::is_alphanumeric));
+            // Terms that are all caps are only fixed when they're written in lowercase,
+            // since e.g. `Bart` is a name.
+            let is_other_word = canonical.chars().all(|c| !c.is_lowercase())
+                && word.chars().any(char::is_uppercase);
+            if is_in_name || is_other_word {
+                continue;
+            }
+            rewritten.push_str(&text[last..m.start()]);
+            rewritten.push_str(canonical);
+            last = m.end();
+        }
+        rewritten.push_str(&text[last..]);
+        rewritten
+    });
+    after
+}
+
+/// A term and how many times it's used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermUse {
+    pub term: String,
+    pub count: usize,
+}
+
+/// Spellings of the same term that differ by spaces or hyphens,
+/// like `third rail` and `third-rail`, most used first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermVariants(pub Vec<TermUse>);
+
+impl TermVariants {
+    pub fn to_json(&self) -> serde_json::Value {
+        self.0
+            .iter()
+            .map(|TermUse { term, count }| json!({"term": term, "count": count}))
+            .collect()
+    }
+}
+
+impl Display for TermVariants {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let variants = self
+            .0
+            .iter()
+            .map(|TermUse { term, count }| format!("{term} ({count})"))
+            .join(", ");
+        f.write_str(&variants)
+    }
+}
+
+/// Find terms spelled inconsistently in prose, as words or pairs of words
+/// that are the same without spaces or hyphens, like `trainset` and `train set`.
+///
+/// Spellings that only differ in case are counted together as the most common one,
+/// since fixing capitalization is `capitalization`'s job.
+pub fn inconsistent_terms(text: &str) -> Vec<TermVariants> {
+    static WORD: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"[\p{L}\p{N}]+(?:[-‐][\p{L}\p{N}]+)*").unwrap());
+    // Spellings, by key without spaces or hyphens, then by lowercase spelling.
+    let mut spellings = HashMap::<String, HashMap<String, HashMap<String, usize>>>::new();
+    let mut add = |term: &str| {
+        let lowercase = term.to_lowercase();
+        let key = lowercase.replace([' ', '-', '‐'], "");
+        *spellings
+            .entry(key)
+            .or_default()
+            .entry(lowercase)
+            .or_default()
+            .entry(term.to_owned())
+            .or_default() += 1;
+    };
+    for block in stats::prose_blocks(text) {
+        let text = block.text.as_str();
+        let words = WORD
+            .find_iter(text)
+            .filter(|m| m.as_str().chars().any(char::is_alphabetic))
+            .collect::<Vec<_>>();
+        for m in &words {
+            add(m.as_str());
+        }
+        for (a, b) in words.iter().tuple_windows() {
+            if &text[a.end()..b.start()] == " " {
+                add(&text[a.start()..b.end()]);
+            }
+        }
+    }
+    let mut inconsistent = spellings
+        .into_values()
+        .filter(|variants| variants.len() > 1)
+        .map(|variants| {
+            let mut variants = variants
+                .into_iter()
+                .map(|(lowercase, cases)| {
+                    let count = cases.values().sum();
+                    // Ties go to lowercase, since capitalized words often start sentences.
+                    let (term, _) = cases
+                        .into_iter()
+                        .max_by_key(|(term, count)| {
+                            (*count, *term == lowercase, Reverse(term.clone()))
+                        })
+                        .unwrap();
+                    TermUse { term, count }
+                })
+                .collect::<Vec<_>>();
+            variants.sort_by_cached_key(|variant| (Reverse(variant.count), variant.term.clone()));
+            TermVariants(variants)
+        })
+        .collect::<Vec<_>>();
+    inconsistent.sort_by_cached_key(|TermVariants(variants)| {
+        let total = variants.iter().map(|variant| variant.count).sum::<usize>();
+        (Reverse(total), variants[0].term.clone())
+    });
+    inconsistent
+}
+
+/// The terms spelled inconsistently in a document, as a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terminology(pub Vec<TermVariants>);
+
+impl Terminology {
+    pub fn of(text: &str) -> Self {
+        Self(inconsistent_terms(text))
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "inconsistent": self.0.iter().map(TermVariants::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Formatted as a line of variants per term.
+impl Display for Terminology {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.iter().join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terms::fix_capitalization;
+    use crate::terms::inconsistent_terms;
+
+    #[test]
+    fn test_fix_capitalization() {
+        let before = "Push to github or Gitlab, then take metro-north and septa.
+Ask Bart. See github.com, [github](https://github.com/kkysen), and `github`.
+Ask the mta.";
+        let after = "Push to GitHub or GitLab, then take Metro-North and SEPTA.
+Ask Bart. See github.com, [GitHub](https://github.com/kkysen), and `github`.
+Ask the MTA.";
+        assert_eq!(fix_capitalization(before.into(), &["MTA".into()]), after);
+    }
+
+    #[test]
+    fn test_inconsistent_terms() {
+        let text = "# Third rail
+
+The third-rail shoe touches the third rail. E-mail the crew about the trainset,
+or email them. Each train set has a Third-rail shoe.
+
+```
+train set
+```
+";
+        let variants = inconsistent_terms(text)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            variants,
+            [
+                "third rail (2), third-rail (2)",
+                "E-mail (1), email (1)",
+                "train set (1), trainset (1)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! A prefilter finding which rules could change a document in one pass over it,
+//! so that the rest can be skipped instead of each scanning the whole document.
+//!
+//! The rules that could change it still each rewrite the whole document in turn,
+//! since most need more context than a match, like which block it's in.
+
+use std::sync::LazyLock;
+
+use regex::RegexSet;
+use regex::SetMatches;
+
+/// Something that all text a rule could change contains.
+///
+/// These are deliberately loose, since skipping a rule that would've changed something is a bug,
+/// but running one that doesn't is only slower.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    CurlyQuote,
+    DataImage,
+    RefSpaces,
+    InlineLink,
+    ThroughRun,
+    FootnoteBeforePunctuation,
+    TrailingWhitespace,
+    Html,
+    Image,
+    HtmlComment,
+    Ellipsis,
+    NonAscii,
+    Footnote,
+    Backslash,
+    Blockquote,
+    Colon,
+    Url,
+    Digit,
+    Fence,
+    StraightQuote,
+    InlineFootnote,
+}
+
+impl Trigger {
+    /// Every trigger, in the order of their discriminants.
+    const ALL: &[Self] = &[
+        Self::CurlyQuote,
+        Self::DataImage,
+        Self::RefSpaces,
+        Self::InlineLink,
+        Self::ThroughRun,
+        Self::FootnoteBeforePunctuation,
+        Self::TrailingWhitespace,
+        Self::Html,
+        Self::Image,
+        Self::HtmlComment,
+        Self::Ellipsis,
+        Self::NonAscii,
+        Self::Footnote,
+        Self::Backslash,
+        Self::Blockquote,
+        Self::Colon,
+        Self::Url,
+        Self::Digit,
+        Self::Fence,
+        Self::StraightQuote,
+        Self::InlineFootnote,
+    ];
+
+    fn pattern(self) -> &'static str {
+        match self {
+            Self::CurlyQuote => "[‘’“”‚„‹›«»『』「」]",
+            Self::DataImage => "data:image/",
+            Self::RefSpaces => r"\]:[ \t]",
+            Self::InlineLink => r"\]\(",
+            Self::ThroughRun => "through run|run(?:ning)? through",
+            Self::FootnoteBeforePunctuation => r"\[\^[^\]]*\][.!?;,]",
+            Self::TrailingWhitespace => r"(?m)[ \t]\r?$",
+            Self::Html => "<",
+            Self::Image => "(?i)<img",
+            Self::HtmlComment => "<!--",
+            Self::Ellipsis => r"\.\.\.|\. \. \.|…",
+            Self::NonAscii => r"[^\x00-\x7F]",
+            Self::Footnote => r"\[\^",
+            Self::Backslash => r"\\",
+            Self::Blockquote => ">",
+            Self::Colon => ":",
+            Self::Url => "(?i)https?://",
+            Self::Digit => "[0-9]",
+            Self::Fence => "```|~~~",
+            Self::StraightQuote => "['\"]",
+            Self::InlineFootnote => r"\^\[",
+        }
+    }
+}
+
+/// The [`Trigger`]s found in a text.
+#[derive(Debug, Clone)]
+pub struct Triggers(SetMatches);
+
+impl Triggers {
+    /// Find all the triggers in `text` at once.
+    pub fn scan(text: &str) -> Self {
+        static SET: LazyLock<RegexSet> = LazyLock::new(|| {
+            RegexSet::new(Trigger::ALL.iter().map(|trigger| trigger.pattern())).unwrap()
+        });
+        Self(SET.matches(text))
+    }
+
+    pub fn contains(&self, trigger: Trigger) -> bool {
+        self.0.matched(trigger as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::triggers::Trigger;
+    use crate::triggers::Triggers;
+
+    #[test]
+    fn test_triggers() {
+        for (i, &trigger) in Trigger::ALL.iter().enumerate() {
+            assert_eq!(trigger as usize, i);
+        }
+        let triggers = Triggers::scan("A “quote” with a [^1].\nAnd 2 trailing spaces.  \n");
+        let found = Trigger::ALL
+            .iter()
+            .copied()
+            .filter(|&trigger| triggers.contains(trigger))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                Trigger::CurlyQuote,
+                Trigger::FootnoteBeforePunctuation,
+                Trigger::TrailingWhitespace,
+                Trigger::NonAscii,
+                Trigger::Footnote,
+                Trigger::Digit,
+            ]
+        );
+    }
+}
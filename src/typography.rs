@@ -0,0 +1,818 @@
+//! Typographic characters, like dashes and ellipses.
+
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use aho_corasick::AhoCorasick;
+use aho_corasick::MatchKind;
+use clap::ValueEnum;
+use itertools::Itertools;
+use regex::Captures;
+use regex::Regex;
+use unicode_normalization::is_nfc;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::diagnostic::Diagnostic;
+use crate::graphemes;
+use crate::markdown;
+
+/// Whether to write quotes as ASCII or typographic quotes.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// `'` and `"`.
+    #[default]
+    Ascii,
+
+    /// The opening and closing quotes of the locale, like `‘’` and `“”` in English.
+    Smart,
+}
+
+/// Whose typographic quotes to use.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuoteLocale {
+    /// `‘’` and `“”`.
+    #[default]
+    En,
+
+    /// German low quotes, `‚‘` and `„“`.
+    De,
+
+    /// French guillemets, `‹ ›` and `« »`, with non-breaking spaces inside.
+    Fr,
+
+    /// CJK corner brackets, `『』` and `「」`.
+    Cjk,
+}
+
+impl QuoteLocale {
+    /// The opening and closing single quotes and then double quotes,
+    /// and the space inside of them.
+    fn quotes(self) -> ([&'static str; 4], &'static str) {
+        match self {
+            Self::En => (["‘", "’", "“", "”"], ""),
+            Self::De => (["‚", "‘", "„", "“"], ""),
+            Self::Fr => (["‹", "›", "«", "»"], "\u{a0}"),
+            Self::Cjk => (["『", "』", "「", "」"], ""),
+        }
+    }
+}
+
+/// Convert the typographic quotes of `locale` to ASCII quotes, or the reverse.
+pub fn convert_quotes(before: &str, to: QuoteStyle, locale: QuoteLocale) -> Cow<'_, str> {
+    match to {
+        QuoteStyle::Ascii => to_ascii_quotes(before, locale),
+        QuoteStyle::Smart => to_smart_quotes(before, locale),
+    }
+}
+
+/// Replace the typographic quotes of `locale`, and English's, which are used everywhere,
+/// with ASCII quotes, removing any space inside of them.
+///
+/// Unlike smart quotes, they're replaced in code too, except in opaque ranges like shortcodes.
+fn to_ascii_quotes(before: &str, locale: QuoteLocale) -> Cow<'_, str> {
+    static QUOTES: LazyLock<Vec<(AhoCorasick, Vec<&str>)>> = LazyLock::new(|| {
+        QuoteLocale::value_variants()
+            .iter()
+            .map(|&locale| {
+                let mut replacements = Vec::new();
+                for locale in [QuoteLocale::En, locale] {
+                    let (quotes, space) = locale.quotes();
+                    for (i, quote) in quotes.into_iter().enumerate() {
+                        let ascii = if i < 2 { "'" } else { "\"" };
+                        replacements.push((quote.to_owned(), ascii));
+                        if !space.is_empty() {
+                            let spaced = match i % 2 {
+                                0 => format!("{quote}{space}"),
+                                _ => format!("{space}{quote}"),
+                            };
+                            replacements.push((spaced, ascii));
+                        }
+                    }
+                }
+                replacements.sort();
+                replacements.dedup();
+                let (patterns, replacements) = replacements.into_iter().unzip::<_, _, Vec<_>, _>();
+                let quotes = AhoCorasick::builder()
+                    .match_kind(MatchKind::LeftmostLongest)
+                    .build(patterns)
+                    .unwrap();
+                (quotes, replacements)
+            })
+            .collect()
+    });
+    let (quotes, replacements) = &QUOTES[locale as usize];
+    if !quotes.is_match(before) {
+        return Cow::Borrowed(before);
+    }
+    let after =
+        markdown::rewrite_outside_opaque(before, |text| quotes.replace_all(text, replacements));
+    Cow::Owned(after)
+}
+
+/// Replace ASCII quotes in prose with the typographic quotes of `locale`,
+/// opening ones before a word and closing ones after it,
+/// and `'` within a word with an apostrophe, like in `don’t`.
+fn to_smart_quotes(before: &str, locale: QuoteLocale) -> Cow<'_, str> {
+    if !before.contains(['\'', '"']) {
+        return Cow::Borrowed(before);
+    }
+    let ([open_single, close_single, open_double, close_double], space) = locale.quotes();
+    let is_word = |grapheme: Option<&str>| grapheme.is_some_and(graphemes::is_alphanumeric);
+    let is_spaced =
+        |grapheme: Option<&str>| grapheme.is_none_or(|grapheme| grapheme.trim().is_empty());
+    let after = markdown::rewrite_prose_in_parallel(before, |text| {
+        let mut rewritten = String::new();
+        for (i, c) in text.char_indices() {
+            let (open, close) = match c {
+                '\'' => (open_single, close_single),
+                '"' => (open_double, close_double),
+                _ => {
+                    rewritten.push(c);
+                    continue;
+                }
+            };
+            // Graphemes, so that a letter with a combining accent is still a letter.
+            let previous = graphemes::last(&text[..i]);
+            let next = graphemes::first(&text[i + 1..]);
+            if c == '\'' && is_word(previous) && is_word(next) {
+                rewritten.push('’');
+                continue;
+            }
+            let is_opening = match previous {
+                None => true,
+                // Nested quotes, like `"'`.
+                Some("'" | "\"") => is_word(next),
+                Some(previous) => previous
+                    .chars()
+                    .all(|c| c.is_whitespace() || "([{<-–—/".contains(c)),
+            };
+            match is_opening {
+                true => {
+                    rewritten.push_str(open);
+                    if !is_spaced(next) {
+                        rewritten.push_str(space);
+                    }
+                }
+                false => {
+                    if !is_spaced(previous) {
+                        rewritten.push_str(space);
+                    }
+                    rewritten.push_str(close);
+                }
+            }
+        }
+        rewritten
+    });
+    match after == before {
+        true => Cow::Borrowed(before),
+        false => Cow::Owned(after),
+    }
+}
+
+/// Whether to write dashes as Unicode characters or ASCII hyphens.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DashStyle {
+    /// `–` (en dash) and `—` (em dash).
+    Unicode,
+
+    /// `--` (en dash) and `---` (em dash).
+    Ascii,
+}
+
+/// Convert ` - `, `--`, and `---` to en and em dashes, or the reverse.
+///
+/// A spaced ` - ` or ` -- ` is used parenthetically, so it becomes an em dash,
+/// while an unspaced `--` is a range, so it becomes an en dash.
+/// Em dashes are then spaced or not depending on `spaced_em_dashes`.
+pub fn convert_dashes(before: String, style: DashStyle, spaced_em_dashes: bool) -> String {
+    static DASH: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?<before> *)(?<dash>-{1,3}|—|–)(?<after> *)").unwrap());
+    let (en_dash, em_dash) = match style {
+        DashStyle::Unicode => ("–", "—"),
+        DashStyle::Ascii => ("--", "---"),
+    };
+    let em_dash = match spaced_em_dashes {
+        true => format!(" {em_dash} "),
+        false => em_dash.to_owned(),
+    };
+    let after = markdown::rewrite_prose_in_parallel(&before, |text| {
+        // Skip tables, thematic breaks, and setext heading underlines.
+        if text.contains('|') || markdown::is_thematic_break(text) {
+            return text.to_owned();
+        }
+        let mut rewritten = String::new();
+        let mut last = 0;
+        for captures in DASH.captures_iter(text) {
+            let whole = captures.get(0).unwrap();
+            // Only dashes between words, so not list markers or longer runs of hyphens.
+            let prev = text[..whole.start()].chars().next_back();
+            let next = text[whole.end()..].chars().next();
+            let (Some(prev), Some(next)) = (prev, next) else {
+                continue;
+            };
+            if prev.is_whitespace() || next.is_whitespace() || prev == '-' || next == '-' {
+                continue;
+            }
+            let is_spaced = !captures["before"].is_empty() && !captures["after"].is_empty();
+            let replacement = match &captures["dash"] {
+                "---" | "—" => &em_dash,
+                "--" if is_spaced => &em_dash,
+                "--" | "–" => en_dash,
+                // Subtraction.
+                "-" if prev.is_ascii_digit() && next.is_ascii_digit() => continue,
+                "-" if is_spaced => &em_dash,
+                _ => continue,
+            };
+            let replacement = match &captures["dash"] {
+                "–" if is_spaced => format!(" {en_dash} "),
+                _ => replacement.to_owned(),
+            };
+            rewritten.push_str(&text[last..whole.start()]);
+            rewritten.push_str(&replacement);
+            last = whole.end();
+        }
+        rewritten.push_str(&text[last..]);
+        rewritten
+    });
+    after
+}
+
+/// Whether to write ellipses as Unicode characters or ASCII periods.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EllipsisStyle {
+    /// `…`
+    Unicode,
+
+    /// `...`
+    Ascii,
+}
+
+/// Convert `...` and `. . .` to `…`, or the reverse.
+pub fn convert_ellipses(before: String, style: EllipsisStyle) -> String {
+    static ELLIPSIS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\.\.\.|\. \. \.|…").unwrap());
+    let replacement = match style {
+        EllipsisStyle::Unicode => "…",
+        EllipsisStyle::Ascii => "...",
+    };
+    let after = markdown::rewrite_prose_in_parallel(&before, |text| {
+        ELLIPSIS.replace_all(text, replacement).into_owned()
+    });
+    after
+}
+
+/// Units that should stay on the same line as the number before them.
+pub const UNITS: &[&str] = &[
+    "kV", "V", "A", "kW", "MW", "GW", "kWh", "MWh", "Hz", "km", "m", "cm", "mm", "mi", "ft",
+    "km/h", "mph", "kg", "t", "lb", "s", "min", "h", "%",
+];
+
+/// Abbreviations that should stay on the same line as the word after them.
+const PREFIXES: &[&str] = &[
+    "No.", "Nos.", "Mr.", "Mrs.", "Ms.", "Dr.", "St.", "p.", "pp.",
+];
+
+/// Abbreviations that should stay on the same line as the word before them.
+const SUFFIXES: &[&str] = &["Jr.", "Sr."];
+
+/// A regex matching any of `words` literally.
+fn alternation<'a>(words: impl IntoIterator<Item = &'a str>) -> String {
+    words.into_iter().map(regex::escape).join("|")
+}
+
+/// Use non-breaking spaces between numbers and units (`25 kV`)
+/// and around abbreviations (`No. 7`, `Smith Jr.`).
+///
+/// If `strip_other`, non-breaking spaces anywhere else are replaced with normal spaces.
+pub fn add_non_breaking_spaces(
+    before: String,
+    extra_units: &[String],
+    strip_other: bool,
+) -> String {
+    let units = alternation(
+        UNITS
+            .iter()
+            .copied()
+            .chain(extra_units.iter().map(String::as_str)),
+    );
+    let prefixes = alternation(PREFIXES.iter().copied());
+    let suffixes = alternation(SUFFIXES.iter().copied());
+    let contexts = Regex::new(&format!(
+        r"(?<number>\d)[ \u{{a0}}](?<unit>{units})(?<unit_end>[^\w/]|$)|\b(?<prefix>{prefixes})[ \u{{a0}}](?<word>\w)|(?<word_before>\w),?[ \u{{a0}}](?<suffix>{suffixes})"
+    ))
+    .unwrap();
+    let after = markdown::rewrite_prose_in_parallel(&before, |text| {
+        let mut text = match strip_other {
+            true => text.replace('\u{a0}', " "),
+            false => text.to_owned(),
+        };
+        // Matches can overlap by a character (like `No. 7 kV`), so repeat until nothing changes.
+        loop {
+            let rewritten = contexts.replace_all(&text, |captures: &Captures| {
+                let whole = &captures[0];
+                let separator = whole.find([' ', '\u{a0}']).unwrap();
+                let separator_len = whole[separator..].chars().next().unwrap().len_utf8();
+                format!(
+                    "{}\u{a0}{}",
+                    &whole[..separator],
+                    &whole[separator + separator_len..]
+                )
+            });
+            if rewritten == text {
+                break text;
+            }
+            text = rewritten.into_owned();
+        }
+    });
+    after
+}
+
+/// Normalize `before` to NFC, noting each word that changed.
+pub fn normalize_nfc(before: String, diagnostics: &mut Vec<Diagnostic>) -> String {
+    if is_nfc(&before) {
+        return before;
+    }
+    let code_points = |s: &str| s.chars().map(|c| format!("U+{:04X}", c as u32)).join(" ");
+    for (i, line) in before.split('\n').enumerate() {
+        for word in line.split_whitespace() {
+            let normalized = word.nfc().collect::<String>();
+            if normalized == word {
+                continue;
+            }
+            // Only show the code points that actually changed.
+            let prefix_len = word
+                .chars()
+                .zip(normalized.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(c, _)| c.len_utf8())
+                .sum::<usize>();
+            let suffix_len = word[prefix_len..]
+                .chars()
+                .rev()
+                .zip(normalized[prefix_len..].chars().rev())
+                .take_while(|(a, b)| a == b)
+                .map(|(c, _)| c.len_utf8())
+                .sum::<usize>();
+            let old = &word[prefix_len..word.len() - suffix_len];
+            let new = &normalized[prefix_len..normalized.len() - suffix_len];
+            diagnostics.push(Diagnostic::note(
+                i + 1,
+                format!(
+                    "normalized `{word}` to NFC: {} -> {}",
+                    code_points(old),
+                    code_points(new)
+                ),
+            ));
+        }
+    }
+    before.nfc().collect()
+}
+
+/// Invisible characters that are usually junk pasted from web pages.
+const INVISIBLE_CHARS: &[char] = &[
+    '\u{ad}',   // soft hyphen
+    '\u{61c}',  // Arabic letter mark
+    '\u{200b}', // zero-width space
+    '\u{200c}', // zero-width non-joiner
+    '\u{200d}', // zero-width joiner
+    '\u{200e}', // left-to-right mark
+    '\u{200f}', // right-to-left mark
+    '\u{202a}', // left-to-right embedding
+    '\u{202b}', // right-to-left embedding
+    '\u{202c}', // pop directional formatting
+    '\u{202d}', // left-to-right override
+    '\u{202e}', // right-to-left override
+    '\u{2060}', // word joiner
+    '\u{2066}', // left-to-right isolate
+    '\u{2067}', // right-to-left isolate
+    '\u{2068}', // first strong isolate
+    '\u{2069}', // pop directional isolate
+    '\u{feff}', // zero-width no-break space (byte order mark)
+];
+
+/// Parse a character as either itself or as a `U+XXXX` code point.
+pub fn parse_char(s: &str) -> Result<char, String> {
+    let mut chars = s.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(c);
+    }
+    s.strip_prefix("U+")
+        .or_else(|| s.strip_prefix("u+"))
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .and_then(char::from_u32)
+        .ok_or_else(|| format!("`{s}` is not a character or `U+XXXX` code point"))
+}
+
+/// Whether `c` is probably part of an emoji, which zero-width joiners are used to combine.
+fn is_emoji(c: char) -> bool {
+    matches!(c, '\u{2600}'..='\u{27bf}' | '\u{1f000}'..)
+}
+
+/// Remove zero-width and other invisible characters outside of code,
+/// except for the `allowed` ones and zero-width joiners in emoji sequences.
+pub fn remove_invisible_chars(before: String, allowed: &[char]) -> String {
+    let is_removed = |c: char| INVISIBLE_CHARS.contains(&c) && !allowed.contains(&c);
+    if !before.contains(is_removed) {
+        return before;
+    }
+    let after = markdown::rewrite_outside_code(&before, |text| {
+        let mut prev = None;
+        let mut rewritten = String::new();
+        for c in text.chars() {
+            let is_emoji_joiner = c == '\u{200d}' && prev.is_some_and(is_emoji);
+            if !is_removed(c) || is_emoji_joiner {
+                rewritten.push(c);
+            }
+            prev = Some(c);
+        }
+        rewritten
+    });
+    after
+}
+
+/// Which thousands separator to use in large numbers.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThousandsSeparator {
+    /// `120,000` (English).
+    Comma,
+
+    /// `120.000` (much of continental Europe).
+    Period,
+
+    /// `120 000` with a narrow non-breaking space (SI).
+    Space,
+}
+
+impl ThousandsSeparator {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Comma => ",",
+            Self::Period => ".",
+            Self::Space => "\u{202f}",
+        }
+    }
+}
+
+/// Insert thousands separators into integers in prose with at least `min_digits` digits.
+///
+/// Numbers that are part of something else, like `v1.10000`, `#12345`, or `555-12345`, are left alone,
+/// as are 4-digit numbers that could be years.
+/// Decimals are only changed with [`ThousandsSeparator::Comma`],
+/// since otherwise the decimal separator is ambiguous.
+pub fn add_thousands_separators(
+    before: String,
+    separator: ThousandsSeparator,
+    min_digits: usize,
+) -> String {
+    static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d+").unwrap());
+    static ALIGNMENT_ROW: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^\s*\|?\s*:?-+:?\s*(?:\|\s*:?-+:?\s*)*\|?\s*$").unwrap());
+    let after = markdown::rewrite_prose_in_parallel(&before, |text| {
+        if ALIGNMENT_ROW.is_match(text) {
+            return text.to_owned();
+        }
+        NUMBER
+            .replace_all(text, |captures: &Captures| {
+                let m = captures.get(0).unwrap();
+                let digits = m.as_str();
+                let before_number = text[..m.start()].chars().rev().take(2).collect::<Vec<_>>();
+                let after_number = text[m.end()..].chars().take(2).collect::<Vec<_>>();
+                let is_part_of_other = match before_number.as_slice() {
+                    [c, ..] if c.is_alphanumeric() || ".,#/:_^\\'".contains(*c) => true,
+                    ['-', c, ..] => c.is_alphanumeric(),
+                    _ => false,
+                } || match after_number.as_slice() {
+                    [c, ..] if c.is_alphanumeric() || "_/:".contains(*c) => true,
+                    ['.' | ',', c, ..] if c.is_ascii_digit() => {
+                        separator != ThousandsSeparator::Comma || after_number[0] == ','
+                    }
+                    ['.' | '-', c, ..] => c.is_alphanumeric(),
+                    _ => false,
+                };
+                let is_year =
+                    digits.len() == 4 && (1000..2100).contains(&digits.parse::<u32>().unwrap());
+                if digits.len() < min_digits.max(4)
+                    || digits.starts_with('0')
+                    || is_year
+                    || is_part_of_other
+                {
+                    return digits.to_owned();
+                }
+                let groups = digits
+                    .as_bytes()
+                    .rchunks(3)
+                    .rev()
+                    .map(|group| std::str::from_utf8(group).unwrap())
+                    .join(separator.as_str());
+                groups
+            })
+            .into_owned()
+    });
+    after
+}
+
+/// Units and their common misspellings, which are only recognized after a number.
+///
+/// Single-letter units aren't included, since e.g. `4A` is more likely a name than 4 amps.
+const UNIT_ALIASES: &[(&str, &[&str])] = &[
+    (
+        "km/h",
+        &["kph", "kmh", "kmph", "km/hr", "Km/h", "KPH", "KMH"],
+    ),
+    ("mph", &["MPH", "mi/h"]),
+    ("kV", &["kv", "KV", "Kv"]),
+    ("kW", &["kw", "KW", "Kw"]),
+    ("MW", &["Mw"]),
+    ("GW", &["gw", "Gw"]),
+    ("kWh", &["kwh", "KWh", "KWH", "kWH"]),
+    ("MWh", &["MWH", "Mwh"]),
+    ("Hz", &["hz", "HZ"]),
+    ("kHz", &["khz", "KHz", "KHZ"]),
+    ("km", &["Km", "KM"]),
+    ("cm", &["CM"]),
+    ("mm", &["MM"]),
+    ("kg", &["Kg", "KG", "kgs"]),
+    ("lb", &["lbs", "Lb", "Lbs", "LB", "LBS"]),
+    ("ft", &["Ft", "FT"]),
+    ("mi", &["Mi"]),
+];
+
+/// Parse a `--alias alias=unit` mapping.
+pub fn parse_unit_alias(alias: &str) -> Result<(String, String), String> {
+    let (alias, unit) = alias
+        .split_once('=')
+        .ok_or_else(|| format!("expected `alias=unit`, not `{alias}`"))?;
+    Ok((alias.into(), unit.into()))
+}
+
+/// Canonicalize units after numbers (see [`UNIT_ALIASES`] and `extra_aliases`),
+/// like `kph` to `km/h` and `ft.` to `ft`,
+/// and put a space between the number and the unit, a non-breaking one if `non_breaking`.
+pub fn canonicalize_units(
+    before: String,
+    extra_aliases: &[(String, String)],
+    non_breaking: bool,
+) -> String {
+    let mut canonical = UNIT_ALIASES
+        .iter()
+        .flat_map(|&(unit, aliases)| {
+            aliases
+                .iter()
+                .copied()
+                .chain([unit])
+                .map(move |alias| (alias, unit))
+        })
+        .collect::<Vec<_>>();
+    canonical.extend(
+        extra_aliases
+            .iter()
+            .map(|(alias, unit)| (alias.as_str(), unit.as_str())),
+    );
+    // Try longer aliases first, like `km/hr` before `km`.
+    canonical.sort_by_key(|(alias, _)| std::cmp::Reverse(alias.len()));
+    let units = alternation(canonical.iter().map(|&(alias, _)| alias));
+    let unit = Regex::new(&format!(
+        r"(?<number>\d)(?<space>[ \u{{a0}}]?)(?<unit>{units})(?<period>\.)?(?<end>[^\w/]|$)"
+    ))
+    .unwrap();
+    let space = match non_breaking {
+        true => "\u{a0}",
+        false => " ",
+    };
+    let after = markdown::rewrite_prose_in_parallel(&before, |text| {
+        let rewritten = unit.replace_all(text, |captures: &Captures| {
+            let whole = captures.get(0).unwrap();
+            let number = &captures["number"];
+            let alias = &captures["unit"];
+            let end = &captures["end"];
+            let unit = canonical
+                .iter()
+                .find(|&&(other, _)| other == alias)
+                .unwrap()
+                .1;
+            // A period at the end of a sentence isn't part of the unit.
+            let next = text[whole.end()..].trim_start().chars().next();
+            let is_sentence_end = captures.name("period").is_some()
+                && (end.is_empty() || end.trim().is_empty())
+                && next.is_none_or(|c| c.is_uppercase());
+            let period = match is_sentence_end {
+                true => ".",
+                false => "",
+            };
+            format!("{number}{space}{unit}{period}{end}")
+        });
+        rewritten.into_owned()
+    });
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::diagnostic::Diagnostic;
+    use crate::typography::add_non_breaking_spaces;
+    use crate::typography::add_thousands_separators;
+    use crate::typography::canonicalize_units;
+    use crate::typography::convert_dashes;
+    use crate::typography::convert_ellipses;
+    use crate::typography::convert_quotes;
+    use crate::typography::normalize_nfc;
+    use crate::typography::parse_char;
+    use crate::typography::remove_invisible_chars;
+    use crate::typography::DashStyle;
+    use crate::typography::EllipsisStyle;
+    use crate::typography::QuoteLocale;
+    use crate::typography::QuoteStyle;
+    use crate::typography::ThousandsSeparator;
+
+    #[test]
+    fn test_convert_quotes() {
+        let ascii = |before| convert_quotes(before, QuoteStyle::Ascii, QuoteLocale::En);
+        let before = "‘’, “” {{< quote text=\"“a”\" >}}";
+        let after = "'', \"\" {{< quote text=\"“a”\" >}}";
+        assert_eq!(ascii(before), after);
+        // Other locales' quotes are only replaced for them.
+        assert_eq!(ascii("«a» 「b」"), "«a» 「b」");
+
+        let before = "\"Don't,\" she said, \"say 'cafe\u{301}'.\" `\"code\"` [a](b \"c\")
+(\"a\") <span title=\"a\">it's</span>
+";
+        for (locale, after) in [
+            (
+                QuoteLocale::En,
+                "“Don’t,” she said, “say ‘cafe\u{301}’.” `\"code\"` [a](b \"c\")
+(“a”) <span title=\"a\">it’s</span>
+",
+            ),
+            (
+                QuoteLocale::De,
+                "„Don’t,“ she said, „say ‚cafe\u{301}‘.“ `\"code\"` [a](b \"c\")
+(„a“) <span title=\"a\">it’s</span>
+",
+            ),
+            (
+                QuoteLocale::Fr,
+                "«\u{a0}Don’t,\u{a0}» she said, «\u{a0}say ‹\u{a0}cafe\u{301}\u{a0}›.\u{a0}» `\"code\"` [a](b \"c\")
+(«\u{a0}a\u{a0}») <span title=\"a\">it’s</span>
+",
+            ),
+            (
+                QuoteLocale::Cjk,
+                "「Don’t,」 she said, 「say 『cafe\u{301}』.」 `\"code\"` [a](b \"c\")
+(「a」) <span title=\"a\">it’s</span>
+",
+            ),
+        ] {
+            assert_eq!(
+                convert_quotes(before, QuoteStyle::Smart, locale),
+                after,
+                "{locale:?}"
+            );
+            let ascii = convert_quotes(after, QuoteStyle::Ascii, locale);
+            assert_eq!(ascii, before.replace('’', "'"), "{locale:?}");
+        }
+        assert!(matches!(
+            convert_quotes("No quotes.", QuoteStyle::Smart, QuoteLocale::En),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_convert_dashes() {
+        let before = "---
+date: 2024-01-01
+---
+
+- A well-known fact - or so -- they say --- is pages 1--2, 5 - 3, and a—b.
+    - nested
+
+| a - b |
+|-------|
+
+`a -- b`, [a - b](a--b), <!-- c -->";
+        let after = "---
+date: 2024-01-01
+---
+
+- A well-known fact—or so—they say—is pages 1–2, 5 - 3, and a—b.
+    - nested
+
+| a - b |
+|-------|
+
+`a -- b`, [a—b](a--b), <!-- c -->";
+        assert_eq!(
+            convert_dashes(before.into(), DashStyle::Unicode, false),
+            after
+        );
+        let spaced = after
+            .replace("a—b", "a — b")
+            .replace("fact—or so—they say—is", "fact — or so — they say — is");
+        assert_eq!(
+            convert_dashes(after.into(), DashStyle::Unicode, true),
+            spaced
+        );
+        let ascii = after.replace('—', "---").replace('–', "--");
+        assert_eq!(convert_dashes(after.into(), DashStyle::Ascii, false), ascii);
+    }
+
+    #[test]
+    fn test_convert_ellipses() {
+        let before = "Wait... what. . . `a...b` [c...](d...)";
+        let after = "Wait… what… `a...b` [c…](d...)";
+        assert_eq!(
+            convert_ellipses(before.into(), EllipsisStyle::Unicode),
+            after
+        );
+        let after = "Wait... what... `a...b` [c...](d...)";
+        assert_eq!(convert_ellipses(before.into(), EllipsisStyle::Ascii), after);
+    }
+
+    #[test]
+    fn test_add_non_breaking_spaces() {
+        let before = "25 kV and 100 km/h on No. 7, by John Smith, Jr. with 3 kids.\u{a0}Hi 5 Mm";
+        let after = "25\u{a0}kV and 100\u{a0}km/h on No.\u{a0}7, by John Smith,\u{a0}Jr. with 3 kids. Hi 5\u{a0}Mm";
+        assert_eq!(
+            add_non_breaking_spaces(before.into(), &["Mm".into()], true),
+            after
+        );
+    }
+
+    #[test]
+    fn test_normalize_nfc() {
+        let before = "cafe\u{301} and cafe\u{301}s
+naïve";
+        let after = "caf\u{e9} and caf\u{e9}s
+naïve";
+        let mut diagnostics = Vec::new();
+        assert_eq!(normalize_nfc(before.into(), &mut diagnostics), after);
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic::note(
+                    1,
+                    "normalized `cafe\u{301}` to NFC: U+0065 U+0301 -> U+00E9"
+                ),
+                Diagnostic::note(
+                    1,
+                    "normalized `cafe\u{301}s` to NFC: U+0065 U+0301 -> U+00E9"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_char() {
+        assert_eq!(parse_char("a"), Ok('a'));
+        assert_eq!(parse_char("U+200B"), Ok('\u{200b}'));
+        assert!(parse_char("ab").is_err());
+    }
+
+    #[test]
+    fn test_remove_invisible_chars() {
+        let before =
+            "[a\u{200b}b](#a\u{200e}b) co\u{ad}op \u{1f468}\u{200d}\u{1f469} \u{2060} `\u{200b}`";
+        let after = "[ab](#ab) coop \u{1f468}\u{200d}\u{1f469} \u{2060} `\u{200b}`";
+        assert_eq!(remove_invisible_chars(before.into(), &['\u{2060}']), after);
+    }
+
+    #[test]
+    fn test_add_thousands_separators() {
+        let before = "In 2019, 120000 riders paid $1500000.50 (about 12345 per day, up from 9999).
+See #12345, v1.100000, 555-123456, `1000000`, and [1000000](https://example.com/1000000).
+| 1000000 |
+|--------:|";
+        let after =
+            "In 2019, 120,000 riders paid $1,500,000.50 (about 12,345 per day, up from 9999).
+See #12345, v1.100000, 555-123456, `1000000`, and [1,000,000](https://example.com/1000000).
+| 1,000,000 |
+|--------:|";
+        assert_eq!(
+            add_thousands_separators(before.into(), ThousandsSeparator::Comma, 5),
+            after
+        );
+        let before = "120000 riders paid 1500000,50 in 1999 and 4500 in 2500.";
+        let after =
+            "120\u{202f}000 riders paid 1500000,50 in 1999 and 4\u{202f}500 in 2\u{202f}500.";
+        assert_eq!(
+            add_thousands_separators(before.into(), ThousandsSeparator::Space, 4),
+            after
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_units() {
+        let before = "Trains run at 125 mph (200kph) on 25kv lines, 10 ft. from a 3KW, 60 hz motor.
+The bridge is 40 ft. It weighs 5 tons and 2 lbs, on Route 4A, with `25kv`.";
+        let after =
+            "Trains run at 125 mph (200 km/h) on 25 kV lines, 10 ft from a 3 kW, 60 Hz motor.
+The bridge is 40 ft. It weighs 5 tons and 2 lb, on Route 4A, with `25kv`.";
+        assert_eq!(canonicalize_units(before.into(), &[], false), after);
+        let before = "It weighs 5 tons.";
+        let after = "It weighs 5\u{a0}t.";
+        assert_eq!(
+            canonicalize_units(before.into(), &[("tons".into(), "t".into())], true),
+            after
+        );
+    }
+}
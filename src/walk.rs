@@ -0,0 +1,229 @@
+//! Finding the Markdown files in directories,
+//! skipping those ignored by git, like `node_modules` and build output.
+
+use std::fs::Metadata;
+use std::path::Path;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use color_eyre::eyre;
+use color_eyre::eyre::ensure;
+use color_eyre::eyre::eyre;
+use ignore::WalkBuilder;
+
+/// The extensions of Markdown files.
+pub const EXTENSIONS: &[&str] = &["md", "markdown", "mdx", "qmd", "Rmd", "rmd"];
+
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| EXTENSIONS.contains(&extension))
+}
+
+/// How to find files in directories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Walk {
+    /// Include files ignored by git (like in `.gitignore`) and hidden files.
+    pub no_ignore: bool,
+
+    /// Follow symlinks to files and directories, instead of skipping them.
+    pub follow_symlinks: bool,
+}
+
+/// How to write a styled file that's a symlink.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkWrite {
+    /// Write to the file it links to.
+    #[default]
+    Follow,
+
+    /// Replace the symlink with a regular file, leaving the file it linked to unchanged.
+    Replace,
+}
+
+/// The default largest file that's styled, which is far bigger than any handwritten document,
+/// but small enough that loading a few of them at once won't run out of memory.
+pub const DEFAULT_MAX_FILE_SIZE: &str = "64M";
+
+/// Parse a number of bytes, like `512`, `100K`, `64M`, or `1G`, in powers of 1024.
+pub fn parse_size(size: &str) -> eyre::Result<u64> {
+    let (number, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => size.split_at(i),
+        None => (size, ""),
+    };
+    let shift = match unit.to_ascii_uppercase().trim_end_matches(['B', 'I']) {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        _ => return Err(eyre!("unknown unit `{unit}`; expected K, M, or G")),
+    };
+    let number = number.parse::<u64>()?;
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| eyre!("`{size}` is too big"))
+}
+
+/// Check that `path` is a regular file (or a symlink to one), not something like a FIFO or device,
+/// which can block or never end when read, returning its metadata.
+pub fn check_regular(path: &Path) -> eyre::Result<Metadata> {
+    let metadata = fs_err::metadata(path)?;
+    ensure!(
+        metadata.is_file(),
+        "`{}` isn't a regular file",
+        path.display()
+    );
+    Ok(metadata)
+}
+
+/// Write `text` to the file at `path`, either through or replacing a symlink.
+pub fn write(path: &Path, text: &str, symlinks: SymlinkWrite) -> eyre::Result<()> {
+    if symlinks == SymlinkWrite::Replace && path.is_symlink() {
+        fs_err::remove_file(path)?;
+    }
+    fs_err::write(path, text)?;
+    Ok(())
+}
+
+/// The Markdown files in `dir` and its subdirectories, sorted by path.
+pub fn markdown_files(dir: &Path, walk: Walk) -> eyre::Result<Vec<PathBuf>> {
+    let Walk {
+        no_ignore,
+        follow_symlinks,
+    } = walk;
+    let mut files = Vec::new();
+    let walker = WalkBuilder::new(dir)
+        .standard_filters(!no_ignore)
+        .follow_links(follow_symlinks)
+        .build();
+    for entry in walker {
+        let entry = entry?;
+        let is_file = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file());
+        if is_file && is_markdown(entry.path()) {
+            files.push(entry.into_path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `paths` with each directory replaced by its [`markdown_files`].
+pub fn expand(paths: &[PathBuf], walk: Walk) -> eyre::Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        match path.is_dir() {
+            true => expanded.extend(markdown_files(path, walk)?),
+            false => expanded.push(path.clone()),
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+
+    use crate::walk::check_regular;
+    use crate::walk::expand;
+    use crate::walk::markdown_files;
+    use crate::walk::parse_size;
+    use crate::walk::write;
+    use crate::walk::SymlinkWrite;
+    use crate::walk::Walk;
+
+    #[test]
+    fn test_markdown_files() {
+        let dir = env::temp_dir().join("style-markdown-test-walk");
+        let _ = fs_err::remove_dir_all(&dir);
+        fs_err::create_dir_all(dir.join("node_modules/a")).unwrap();
+        fs_err::create_dir_all(dir.join("docs")).unwrap();
+        for path in [
+            "README.md",
+            "docs/b.mdx",
+            "docs/c.txt",
+            "node_modules/a/README.md",
+        ] {
+            fs_err::write(dir.join(path), "").unwrap();
+        }
+        fs_err::write(dir.join(".gitignore"), "node_modules/\n").unwrap();
+        // `.gitignore` only applies in a git repo.
+        let status = process::Command::new("git")
+            .arg("init")
+            .arg("--quiet")
+            .arg(&dir)
+            .status();
+        if !status.is_ok_and(|status| status.success()) {
+            return;
+        }
+        let relative = |files: Vec<PathBuf>| {
+            files
+                .iter()
+                .map(|file| file.strip_prefix(&dir).unwrap().display().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            relative(markdown_files(&dir, Walk::default()).unwrap()),
+            ["README.md", "docs/b.mdx"]
+        );
+        assert_eq!(
+            relative(
+                markdown_files(
+                    &dir,
+                    Walk {
+                        no_ignore: true,
+                        ..Default::default()
+                    }
+                )
+                .unwrap()
+            ),
+            ["README.md", "docs/b.mdx", "node_modules/a/README.md"]
+        );
+        let paths = [PathBuf::from("-"), dir.join("docs")];
+        assert_eq!(
+            expand(&paths, Walk::default()).unwrap(),
+            [PathBuf::from("-"), dir.join("docs/b.mdx")]
+        );
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("100K").unwrap(), 100 << 10);
+        assert_eq!(parse_size("64m").unwrap(), 64 << 20);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("99999999999999G").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks() {
+        let dir = env::temp_dir().join("style-markdown-test-symlinks");
+        let _ = fs_err::remove_dir_all(&dir);
+        fs_err::create_dir_all(dir.join("docs")).unwrap();
+        let (target, link) = (dir.join("target.md"), dir.join("docs/link.md"));
+        fs_err::write(&target, "a\n").unwrap();
+        fs_err::os::unix::fs::symlink(&target, &link).unwrap();
+        let docs = dir.join("docs");
+        assert!(markdown_files(&docs, Walk::default()).unwrap().is_empty());
+        let walk = Walk {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        assert_eq!(markdown_files(&docs, walk).unwrap(), [link.as_path()]);
+
+        check_regular(&link).unwrap();
+        assert!(check_regular(&docs).is_err());
+        write(&link, "b\n", SymlinkWrite::Follow).unwrap();
+        assert_eq!(fs_err::read_to_string(&target).unwrap(), "b\n");
+        write(&link, "c\n", SymlinkWrite::Replace).unwrap();
+        assert!(!link.is_symlink());
+        assert_eq!(fs_err::read_to_string(&target).unwrap(), "b\n");
+        assert_eq!(fs_err::read_to_string(&link).unwrap(), "c\n");
+    }
+}
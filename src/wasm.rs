@@ -0,0 +1,32 @@
+//! A WebAssembly binding for JavaScript, wrapped by `js/style-markdown.js`.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde_json::Value;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsError;
+
+use crate::presets;
+use crate::FinalNewline;
+
+/// Rewrite Markdown `input` with `rules`, like `["quotes", "dashes --style unicode"]`,
+/// returning the JSON of [`Rewritten`](crate::Rewritten).
+///
+/// `options` is a JSON object, where `path` is the file name used by the rules,
+/// and `finalNewline` is how to end the output, `one`, `keep`, or `none`, like `--final-newline`.
+#[wasm_bindgen(js_name = rewriteJson)]
+pub fn rewrite_json(input: String, rules: Vec<String>, options: &str) -> Result<String, JsError> {
+    let options = serde_json::from_str::<Value>(options)?;
+    let path = options["path"].as_str().unwrap_or("input.md");
+    let final_newline = match options["finalNewline"].as_str() {
+        Some(final_newline) => {
+            FinalNewline::from_str(final_newline, false).map_err(|e| JsError::new(&e))?
+        }
+        None => FinalNewline::default(),
+    };
+    let commands = presets::parse_rules(&rules).map_err(|e| JsError::new(&format!("{e:#}")))?;
+    let rewritten = crate::rewrite(Path::new(path), input, &commands, final_newline)
+        .map_err(|e| JsError::new(&format!("{e:#}")))?;
+    Ok(rewritten.to_json().to_string())
+}
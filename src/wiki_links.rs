@@ -0,0 +1,167 @@
+//! Wiki links, i.e. `[[Page Name]]` and `[[page#heading|text]]`, like Obsidian uses.
+
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use regex::Captures;
+use regex::Regex;
+
+use crate::headings;
+use crate::markdown;
+
+/// Which kind of links to convert to.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// `[text](page-name.md#heading)`
+    Markdown,
+
+    /// `[[Page Name#heading|text]]`
+    Wiki,
+}
+
+/// How to turn page names into file names.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlugStyle {
+    /// `Page Name` to `page-name`.
+    Kebab,
+
+    /// `Page Name` to `page_name`.
+    Snake,
+
+    /// `Page Name` to `Page%20Name`.
+    Preserve,
+}
+
+impl SlugStyle {
+    fn slugify(&self, name: &str) -> String {
+        let words = name.split_whitespace();
+        match self {
+            Self::Kebab => words.collect::<Vec<_>>().join("-").to_lowercase(),
+            Self::Snake => words.collect::<Vec<_>>().join("_").to_lowercase(),
+            Self::Preserve => name.replace(' ', "%20"),
+        }
+    }
+}
+
+/// Whether the last part of `path` has an extension, like `map.png`.
+fn has_extension(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .is_some_and(|name| name.contains('.'))
+}
+
+/// Convert wiki links to Markdown links (or the reverse),
+/// where pages are files ending in `extension`.
+///
+/// Page names are turned into file names with `slug` and headings into anchors like GitHub does.
+/// Converting to wiki links only converts relative links to pages and embedded images,
+/// and can't recover page names and headings from file names and anchors.
+pub fn convert_wiki_links(
+    before: String,
+    to: LinkKind,
+    slug: SlugStyle,
+    extension: &str,
+) -> String {
+    static WIKI_LINK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"(?<embed>!?)\[\[(?<page>[^\]|#]*)(?:#(?<heading>[^\]|]*))?(?:\|(?<text>[^\]]*))?\]\]",
+        )
+        .unwrap()
+    });
+    static LINK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?<embed>!?)\[(?<text>[^\]]*)\]\((?<path>[^)\s#]*)(?:#(?<anchor>[^)\s]*))?\)")
+            .unwrap()
+    });
+    static SCHEME: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap());
+    let after = markdown::rewrite_outside_code(&before, |text| {
+        let rewritten = match to {
+            LinkKind::Markdown => WIKI_LINK.replace_all(text, |captures: &Captures| {
+                let embed = &captures["embed"];
+                let page = captures["page"].trim();
+                let path = match (page.is_empty(), has_extension(page)) {
+                    (true, _) => String::new(),
+                    (false, true) => page.replace(' ', "%20"),
+                    (false, false) => format!("{}{extension}", slug.slugify(page)),
+                };
+                let heading = captures
+                    .name("heading")
+                    .map(|heading| heading.as_str().trim());
+                let anchor = heading
+                    .map(|heading| format!("#{}", headings::slugify(heading)))
+                    .unwrap_or_default();
+                let text = match (captures.name("text"), embed, heading) {
+                    (Some(text), _, _) => text.as_str().to_owned(),
+                    (None, "!", _) => String::new(),
+                    (None, _, Some(heading)) if page.is_empty() => heading.to_owned(),
+                    (None, _, Some(heading)) => format!("{page} > {heading}"),
+                    (None, _, None) => page.to_owned(),
+                };
+                format!("{embed}[{text}]({path}{anchor})")
+            }),
+            LinkKind::Wiki => LINK.replace_all(text, |captures: &Captures| {
+                let whole = captures[0].to_owned();
+                let embed = &captures["embed"];
+                let path = &captures["path"];
+                let is_page = !extension.is_empty() && path.ends_with(extension);
+                let is_local = !path.is_empty() && !path.starts_with('/') && !SCHEME.is_match(path);
+                if !is_local || !(is_page || embed == "!") {
+                    return whole;
+                }
+                let page = match is_page {
+                    true => &path[..path.len() - extension.len()],
+                    false => path,
+                }
+                .replace("%20", " ");
+                let anchor = captures
+                    .name("anchor")
+                    .map(|anchor| format!("#{}", anchor.as_str()))
+                    .unwrap_or_default();
+                let text = &captures["text"];
+                let text = match text.is_empty() || text.eq_ignore_ascii_case(&page) || embed == "!"
+                {
+                    true => String::new(),
+                    false => format!("|{text}"),
+                };
+                format!("{embed}[[{page}{anchor}{text}]]")
+            }),
+        };
+        rewritten.into_owned()
+    });
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wiki_links::convert_wiki_links;
+    use crate::wiki_links::LinkKind;
+    use crate::wiki_links::SlugStyle;
+
+    #[test]
+    fn test_convert_wiki_links() {
+        let before = "See [[Penn Station]], [[penn station#Track Layout|the tracks]], [[#Summary]],
+![[Bus Map.png]], and `[[Not a Link]]`.";
+        let after = "See [Penn Station](penn-station.md), [the tracks](penn-station.md#track-layout), [Summary](#summary),
+![](Bus%20Map.png), and `[[Not a Link]]`.";
+        assert_eq!(
+            convert_wiki_links(before.into(), LinkKind::Markdown, SlugStyle::Kebab, ".md"),
+            after
+        );
+        let after = "See [Penn Station](Penn%20Station), [the tracks](penn%20station#track-layout), [Summary](#summary),
+![](Bus%20Map.png), and `[[Not a Link]]`.";
+        assert_eq!(
+            convert_wiki_links(before.into(), LinkKind::Markdown, SlugStyle::Preserve, ""),
+            after
+        );
+
+        let before =
+            "See [penn-station](penn-station.md), [the tracks](penn_station.md#track-layout),
+![map](Bus%20Map.png), [site](https://example.com/a.md), and [summary](#summary).";
+        let after = "See [[penn-station]], [[penn_station#track-layout|the tracks]],
+![[Bus Map.png]], [site](https://example.com/a.md), and [summary](#summary).";
+        assert_eq!(
+            convert_wiki_links(before.into(), LinkKind::Wiki, SlugStyle::Kebab, ".md"),
+            after
+        );
+    }
+}